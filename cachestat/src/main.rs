@@ -0,0 +1,185 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::mem::{page_size, query_residency};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::fs::File;
+use std::os::raw::c_long;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Number of cachestat(2), which is the same on all architectures
+const SYS_CACHESTAT: c_long = 451;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    query: QueryInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct QueryInfo {
+    /// File to print the page cache statistics of
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Start of the range to query (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Length of the range to query [default: up to the end of FILE]
+    #[clap(short, long, value_parser = parse_size)]
+    length: Option<i64>,
+    /// Repeat the query every number of seconds until interrupted
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "SECONDS"
+    )]
+    watch: Option<u64>,
+}
+
+#[repr(C)]
+struct CachestatRange {
+    off: u64,
+    len: u64,
+}
+
+/// Statistics in pages, from linux/mman.h
+#[repr(C)]
+#[derive(Default)]
+struct Cachestat {
+    nr_cache: u64,
+    nr_dirty: u64,
+    nr_writeback: u64,
+    nr_evicted: u64,
+    nr_recently_evicted: u64,
+}
+
+fn cachestat(file: &File, offset: i64, length: Option<i64>) -> nix::Result<Cachestat> {
+    // A length of 0 means up to the end of the file
+    let range = CachestatRange {
+        off: offset as u64,
+        len: length.unwrap_or(0) as u64,
+    };
+    let mut stat = Cachestat::default();
+    // SAFETY: range and stat are valid structs for the kernel to read and write.
+    let ret = unsafe {
+        libc::syscall(
+            SYS_CACHESTAT,
+            file.as_raw_fd(),
+            &range as *const CachestatRange,
+            &mut stat as *mut Cachestat,
+            0u32,
+        )
+    };
+    Errno::result(ret).map(|_| stat)
+}
+
+/// Counts the cached pages of the range with mincore, which knows nothing about the other statistics.
+fn estimate_with_mincore(file: &File, offset: i64, length: Option<i64>) -> anyhow::Result<u64> {
+    let file_len = file
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?
+        .len() as i64;
+    let offset = offset.min(file_len);
+    let end = match length {
+        Some(length) => offset.saturating_add(length).min(file_len),
+        None => file_len,
+    };
+    let page_size = page_size()?;
+    // mmap requires the offset to be page aligned
+    let start = offset / page_size * page_size;
+    let residency = query_residency(file, start, end, page_size)?;
+
+    Ok(residency.iter().filter(|&&resident| resident).count() as u64)
+}
+
+/// Prints the statistics, and returns whether cachestat(2) is supported.
+fn print_stat(
+    filename: &Path,
+    file: &File,
+    info: &QueryInfo,
+    supported: bool,
+) -> anyhow::Result<bool> {
+    if supported {
+        match cachestat(file, info.offset, info.length) {
+            Ok(stat) => {
+                println!("filename: {}", filename.display());
+                println!("nr_cache: {}", stat.nr_cache);
+                println!("nr_dirty: {}", stat.nr_dirty);
+                println!("nr_writeback: {}", stat.nr_writeback);
+                println!("nr_evicted: {}", stat.nr_evicted);
+                println!("nr_recently_evicted: {}", stat.nr_recently_evicted);
+                return Ok(true);
+            }
+            Err(Errno::ENOSYS) => {
+                eprintln!(
+                    "warning: cachestat(2) requires Linux 6.5 or later; falling back to mincore, \
+                    which only counts the cached pages"
+                );
+            }
+            Err(Errno::EBADF) => {
+                return Err(anyhow!(
+                    "'{}' is not a file with a page cache",
+                    filename.display()
+                ))
+            }
+            Err(errno) => return Err(errno).context("Failed to call cachestat"),
+        }
+    }
+
+    let nr_cache = estimate_with_mincore(file, info.offset, info.length)?;
+    println!("filename: {}", filename.display());
+    println!("nr_cache: {}", nr_cache);
+
+    Ok(false)
+}
+
+fn handle_query(info: QueryInfo) -> anyhow::Result<()> {
+    // clap requires FILE unless a subcommand is given
+    let filename = info.filename.clone().unwrap();
+    // cachestat takes a length of 0 as up to the end of the file
+    if info.length == Some(0) {
+        return Err(anyhow!("--length must be larger than 0"));
+    }
+    let file = File::open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+
+    let mut supported = print_stat(&filename, &file, &info, true)?;
+    if let Some(watch) = info.watch {
+        loop {
+            std::thread::sleep(Duration::from_secs(watch));
+            println!();
+            supported = print_stat(&filename, &file, &info, supported)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_query(cli.query),
+    }
+}
@@ -0,0 +1,372 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::ffi::OsString;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// Names of the capabilities indexed by their numbers
+const CAP_NAMES: &[&str] = &[
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print all capability sets of a process
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// Target process [default: this process]
+        #[clap(value_parser)]
+        pid: Option<i32>,
+    },
+    /// Drop capabilities from the effective, permitted and inheritable sets
+    #[clap(display_order = 2, name = "drop")]
+    Drop {
+        /// Capabilities to drop, separated by commas (e.g. CAP_NET_RAW or net_raw)
+        #[clap(
+            required = true,
+            value_parser = parse_cap,
+            use_value_delimiter = true,
+            value_name = "CAP_NAME"
+        )]
+        caps: Vec<Cap>,
+        /// Also drop the capabilities from the bounding set, so that COMMAND cannot regain them
+        #[clap(short, long)]
+        bounding: bool,
+        #[clap(flatten)]
+        command: CommandInfo,
+    },
+    /// Clear the ambient set
+    #[clap(display_order = 3, name = "clear-ambient")]
+    ClearAmbient {
+        #[clap(flatten)]
+        command: CommandInfo,
+    },
+    /// Raise capabilities in the ambient set, which is kept across execve(2)
+    #[clap(display_order = 4, name = "set-ambient")]
+    SetAmbient {
+        /// Capabilities to raise, separated by commas (e.g. CAP_NET_BIND_SERVICE)
+        #[clap(
+            required = true,
+            value_parser = parse_cap,
+            use_value_delimiter = true,
+            value_name = "CAP_NAME"
+        )]
+        caps: Vec<Cap>,
+        #[clap(flatten)]
+        command: CommandInfo,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 5, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CommandInfo {
+    /// Command to execute with the capabilities, after --
+    #[clap(value_parser, value_name = "COMMAND", last = true)]
+    command: Vec<OsString>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Cap(u32);
+
+impl std::fmt::Display for Cap {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match CAP_NAMES.get(self.0 as usize) {
+            Some(name) => write!(f, "{}", name),
+            // A capability newer than this command
+            None => write!(f, "CAP_{}", self.0),
+        }
+    }
+}
+
+impl Cap {
+    fn bit(self) -> u64 {
+        1 << self.0
+    }
+}
+
+/// Parses a capability name such as `CAP_NET_RAW` or `net_raw`, case-insensitively.
+fn parse_cap(s: &str) -> Result<Cap, String> {
+    let upper = s.to_ascii_uppercase();
+    let name = format!("CAP_{}", upper.strip_prefix("CAP_").unwrap_or(&upper));
+    CAP_NAMES
+        .iter()
+        .position(|&cap| cap == name)
+        .map(|cap| Cap(cap as u32))
+        .ok_or_else(|| format!("unknown capability '{}'", s))
+}
+
+fn format_caps(caps: u64) -> String {
+    let names: Vec<String> = (0..64)
+        .map(Cap)
+        .filter(|cap| caps & cap.bit() != 0)
+        .map(|cap| cap.to_string())
+        .collect();
+    if names.is_empty() {
+        "none".to_owned()
+    } else {
+        names.join(", ")
+    }
+}
+
+#[repr(C)]
+struct CapUserHeader {
+    version: u32,
+    pid: i32,
+}
+
+#[repr(C)]
+#[derive(Default, Copy, Clone)]
+struct CapUserData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+/// The effective, permitted and inheritable sets of a process
+struct CapSets {
+    effective: u64,
+    permitted: u64,
+    inheritable: u64,
+}
+
+fn capget(pid: i32) -> nix::Result<CapSets> {
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid,
+    };
+    // Version 3 splits each 64-bit set into two 32-bit halves
+    let mut data = [CapUserData::default(); 2];
+    // SAFETY: header and data are valid for version 3.
+    let ret = unsafe { libc::syscall(libc::SYS_capget, &mut header, data.as_mut_ptr()) };
+    Errno::result(ret)?;
+
+    let join = |low: u32, high: u32| u64::from(low) | (u64::from(high) << 32);
+    Ok(CapSets {
+        effective: join(data[0].effective, data[1].effective),
+        permitted: join(data[0].permitted, data[1].permitted),
+        inheritable: join(data[0].inheritable, data[1].inheritable),
+    })
+}
+
+fn capset(sets: &CapSets) -> nix::Result<()> {
+    let mut header = CapUserHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let split = |set: u64| (set as u32, (set >> 32) as u32);
+    let (effective, permitted, inheritable) = (
+        split(sets.effective),
+        split(sets.permitted),
+        split(sets.inheritable),
+    );
+    let data = [
+        CapUserData {
+            effective: effective.0,
+            permitted: permitted.0,
+            inheritable: inheritable.0,
+        },
+        CapUserData {
+            effective: effective.1,
+            permitted: permitted.1,
+            inheritable: inheritable.1,
+        },
+    ];
+    // SAFETY: header and data are valid for version 3.
+    let ret = unsafe { libc::syscall(libc::SYS_capset, &mut header, data.as_ptr()) };
+    Errno::result(ret).map(drop)
+}
+
+fn prctl(option: i32, arg2: u64, arg3: u64) -> nix::Result<i32> {
+    // SAFETY: the options used in this file only take integer arguments.
+    let ret = unsafe { libc::prctl(option, arg2, arg3, 0u64, 0u64) };
+    Errno::result(ret)
+}
+
+/// Reads a capability set such as CapBnd from /proc/PID/status.
+fn read_status_caps(pid: Option<i32>, key: &str) -> anyhow::Result<u64> {
+    let path = match pid {
+        Some(pid) => format!("/proc/{}/status", pid),
+        None => "/proc/self/status".to_owned(),
+    };
+    let status =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+    let value = status
+        .lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+        .ok_or_else(|| anyhow!("{} is not found in {}", key, path))?;
+
+    u64::from_str_radix(value.trim(), 16)
+        .with_context(|| format!("Unexpected {} '{}' in {}", key, value.trim(), path))
+}
+
+/// Replaces this process with COMMAND so that it runs with the capabilities.
+fn exec_command(command: &CommandInfo) -> anyhow::Result<()> {
+    let command = &command.command;
+    if command.is_empty() {
+        return Ok(());
+    }
+
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn handle_get(pid: Option<i32>) -> anyhow::Result<()> {
+    // 0 means the calling process
+    let sets = capget(pid.unwrap_or(0)).context("Failed to get capabilities")?;
+
+    println!("effective: {}", format_caps(sets.effective));
+    println!("permitted: {}", format_caps(sets.permitted));
+    println!("inheritable: {}", format_caps(sets.inheritable));
+    // capget does not return the bounding and ambient sets
+    println!(
+        "bounding: {}",
+        format_caps(read_status_caps(pid, "CapBnd")?)
+    );
+    println!("ambient: {}", format_caps(read_status_caps(pid, "CapAmb")?));
+
+    Ok(())
+}
+
+fn handle_drop(caps: Vec<Cap>, bounding: bool, command: CommandInfo) -> anyhow::Result<()> {
+    let mask = caps.iter().fold(0, |mask, cap| mask | cap.bit());
+
+    if bounding {
+        // Dropping from the bounding set requires CAP_SETPCAP, which may be dropped below
+        for cap in &caps {
+            prctl(libc::PR_CAPBSET_DROP, u64::from(cap.0), 0)
+                .with_context(|| format!("Failed to drop {} from the bounding set", cap))?;
+        }
+    }
+
+    let sets = capget(0).context("Failed to get capabilities")?;
+    let sets = CapSets {
+        effective: sets.effective & !mask,
+        permitted: sets.permitted & !mask,
+        inheritable: sets.inheritable & !mask,
+    };
+    capset(&sets).context("Failed to drop capabilities")?;
+
+    exec_command(&command)
+}
+
+fn handle_clear_ambient(command: CommandInfo) -> anyhow::Result<()> {
+    prctl(
+        libc::PR_CAP_AMBIENT,
+        libc::PR_CAP_AMBIENT_CLEAR_ALL as u64,
+        0,
+    )
+    .context("Failed to clear the ambient set")?;
+
+    exec_command(&command)
+}
+
+fn handle_set_ambient(caps: Vec<Cap>, command: CommandInfo) -> anyhow::Result<()> {
+    let sets = capget(0).context("Failed to get capabilities")?;
+    let missing: Vec<Cap> = caps
+        .iter()
+        .copied()
+        .filter(|cap| sets.permitted & cap.bit() == 0)
+        .collect();
+    if !missing.is_empty() {
+        return Err(anyhow!(
+            "{} is not in the permitted set; only capabilities held by this process can be raised",
+            missing
+                .iter()
+                .map(|cap| cap.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ));
+    }
+
+    // An ambient capability has to be both permitted and inheritable
+    let mask = caps.iter().fold(0, |mask, cap| mask | cap.bit());
+    let sets = CapSets {
+        inheritable: sets.inheritable | mask,
+        ..sets
+    };
+    capset(&sets).context("Failed to add capabilities to the inheritable set")?;
+    for cap in &caps {
+        prctl(
+            libc::PR_CAP_AMBIENT,
+            libc::PR_CAP_AMBIENT_RAISE as u64,
+            u64::from(cap.0),
+        )
+        .with_context(|| format!("Failed to raise {} in the ambient set", cap))?;
+    }
+
+    exec_command(&command)
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { pid } => handle_get(pid),
+        Commands::Drop {
+            caps,
+            bounding,
+            command,
+        } => handle_drop(caps, bounding, command),
+        Commands::ClearAmbient { command } => handle_clear_ambient(command),
+        Commands::SetAmbient { caps, command } => handle_set_ambient(caps, command),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
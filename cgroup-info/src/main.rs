@@ -0,0 +1,349 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::cgroup::{cgroup_dir, read_mounts, Membership, Mount};
+use linux_tools_core::print_completer;
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    cgroup: CgroupInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CgroupInfo {
+    /// Process to print the cgroup of
+    #[clap(required = true, value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// Only print the statistics of these controllers [default: all of them]
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        value_delimiter = ',',
+        value_name = "CONTROLLER,..."
+    )]
+    controller: Vec<Controller>,
+    /// Print the statistics every number of seconds with the changes of the counters until the
+    /// process exits
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "SECONDS"
+    )]
+    watch: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Controller {
+    Memory,
+    Cpu,
+    Io,
+    Pids,
+}
+
+/// How the content of a controller file is laid out.
+#[derive(Copy, Clone)]
+enum Format {
+    /// A single value such as `memory.current`
+    Single,
+    /// A key and a value in each line such as `cpu.stat`
+    Flat,
+    /// A device and its `key=value` pairs in each line such as `io.stat`
+    Nested,
+    /// A device, an operation and a value in each line such as `blkio.throttle.io_serviced`
+    Blkio,
+}
+
+/// A file of a controller and whether its values are counters that only increase.
+struct StatFile {
+    /// Name of the cgroup v1 hierarchy the file is in, which is empty for cgroup v2
+    hierarchy: &'static str,
+    name: &'static str,
+    format: Format,
+    cumulative: bool,
+}
+
+const fn stat_file(
+    hierarchy: &'static str,
+    name: &'static str,
+    format: Format,
+    cumulative: bool,
+) -> StatFile {
+    StatFile {
+        hierarchy,
+        name,
+        format,
+        cumulative,
+    }
+}
+
+impl Controller {
+    /// Name of the controller in cgroup v2 and of its main hierarchy in cgroup v1.
+    fn name(self) -> &'static str {
+        match self {
+            Controller::Memory => "memory",
+            Controller::Cpu => "cpu",
+            Controller::Io => "io",
+            Controller::Pids => "pids",
+        }
+    }
+
+    fn v1_name(self) -> &'static str {
+        match self {
+            Controller::Io => "blkio",
+            controller => controller.name(),
+        }
+    }
+
+    fn v2_files(self) -> &'static [StatFile] {
+        const MEMORY: &[StatFile] = &[
+            stat_file("", "memory.current", Format::Single, false),
+            stat_file("", "memory.peak", Format::Single, false),
+        ];
+        const CPU: &[StatFile] = &[stat_file("", "cpu.stat", Format::Flat, true)];
+        const IO: &[StatFile] = &[stat_file("", "io.stat", Format::Nested, true)];
+        const PIDS: &[StatFile] = &[stat_file("", "pids.current", Format::Single, false)];
+        match self {
+            Controller::Memory => MEMORY,
+            Controller::Cpu => CPU,
+            Controller::Io => IO,
+            Controller::Pids => PIDS,
+        }
+    }
+
+    /// The files of cgroup v1, which differ from cgroup v2 in the names and the hierarchies.
+    fn v1_files(self) -> &'static [StatFile] {
+        const MEMORY: &[StatFile] = &[
+            stat_file("memory", "memory.usage_in_bytes", Format::Single, false),
+            stat_file("memory", "memory.max_usage_in_bytes", Format::Single, false),
+        ];
+        // CPU time is accounted by cpuacct, which is often mounted together with cpu
+        const CPU: &[StatFile] = &[
+            stat_file("cpu", "cpu.stat", Format::Flat, true),
+            stat_file("cpuacct", "cpuacct.usage", Format::Single, true),
+            stat_file("cpuacct", "cpuacct.stat", Format::Flat, true),
+        ];
+        const IO: &[StatFile] = &[
+            stat_file(
+                "blkio",
+                "blkio.throttle.io_service_bytes",
+                Format::Blkio,
+                true,
+            ),
+            stat_file("blkio", "blkio.throttle.io_serviced", Format::Blkio, true),
+        ];
+        const PIDS: &[StatFile] = &[stat_file("pids", "pids.current", Format::Single, false)];
+        match self {
+            Controller::Memory => MEMORY,
+            Controller::Cpu => CPU,
+            Controller::Io => IO,
+            Controller::Pids => PIDS,
+        }
+    }
+}
+
+/// A value of a controller file.
+struct Field {
+    key: String,
+    value: u64,
+    cumulative: bool,
+}
+
+fn parse_stat_file(path: &Path, file: &StatFile, fields: &mut Vec<Field>) -> anyhow::Result<bool> {
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        // For example, memory.peak requires Linux 5.19 and the root cgroup has no pids.current
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    };
+    let unexpected = |line: &str| anyhow!("Unexpected line in {}: {}", path.display(), line);
+    let parse = |value: &str, line: &str| value.parse::<u64>().map_err(|_| unexpected(line));
+    let mut push = |key: String, value: u64| {
+        fields.push(Field {
+            key,
+            value,
+            cumulative: file.cumulative,
+        })
+    };
+
+    for line in content.lines().filter(|line| !line.is_empty()) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match (file.format, words.as_slice()) {
+            (Format::Single, [value]) => push(file.name.to_owned(), parse(value, line)?),
+            (Format::Flat, [key, value]) => {
+                push(format!("{}.{}", file.name, key), parse(value, line)?)
+            }
+            (Format::Nested, [device, pairs @ ..]) => {
+                for pair in pairs {
+                    let (key, value) = pair.split_once('=').ok_or_else(|| unexpected(line))?;
+                    push(
+                        format!("{}.{}.{}", file.name, device, key),
+                        parse(value, line)?,
+                    );
+                }
+            }
+            (Format::Blkio, [device, operation, value]) => push(
+                format!("{}.{}.{}", file.name, device, operation),
+                parse(value, line)?,
+            ),
+            // The line of the total of all devices
+            (Format::Blkio, [total, value]) => {
+                push(format!("{}.{}", file.name, total), parse(value, line)?)
+            }
+            _ => return Err(unexpected(line)),
+        }
+    }
+
+    Ok(true)
+}
+
+/// Reads the files of the controllers from the hierarchies of the process, warning about the
+/// controllers that are not available.
+fn read_stats(
+    membership: &Membership,
+    mounts: &[Mount],
+    controllers: &[Controller],
+    warn: bool,
+) -> anyhow::Result<Vec<Field>> {
+    let mut fields = Vec::new();
+    for &controller in controllers {
+        // A controller belongs to a cgroup v1 hierarchy if any, and to cgroup v2 otherwise
+        let sources: Vec<(&StatFile, Option<PathBuf>)> =
+            if membership.hierarchy(controller.v1_name()).is_some() {
+                controller
+                    .v1_files()
+                    .iter()
+                    .map(|file| {
+                        let dir = membership
+                            .hierarchy(file.hierarchy)
+                            .and_then(|cgroup| cgroup_dir(mounts, Some(file.hierarchy), cgroup));
+                        (file, dir)
+                    })
+                    .collect()
+            } else {
+                let dir = membership
+                    .unified
+                    .as_deref()
+                    .and_then(|cgroup| cgroup_dir(mounts, None, cgroup));
+                controller
+                    .v2_files()
+                    .iter()
+                    .map(|file| (file, dir.clone()))
+                    .collect()
+            };
+
+        let mut found = false;
+        for (file, dir) in sources {
+            if let Some(dir) = dir {
+                found |= parse_stat_file(&dir.join(file.name), file, &mut fields)?;
+            }
+        }
+        if !found && warn {
+            eprintln!(
+                "warning: no statistics of the {} controller are available for the cgroup",
+                controller.name()
+            );
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Prints the fields, with the changes of the counters since `previous` if given.
+fn print_fields(fields: &[Field], previous: Option<&HashMap<String, u64>>) {
+    for field in fields {
+        match previous {
+            Some(previous) if field.cumulative => {
+                let before = previous.get(&field.key).copied().unwrap_or(0);
+                println!(
+                    "{}: {} (+{})",
+                    field.key,
+                    field.value,
+                    field.value.saturating_sub(before)
+                );
+            }
+            _ => println!("{}: {}", field.key, field.value),
+        }
+    }
+}
+
+fn handle_cgroup(info: CgroupInfo) -> anyhow::Result<()> {
+    // clap requires PID unless a subcommand is given
+    let pid = info.pid.unwrap();
+    let controllers = if info.controller.is_empty() {
+        Controller::value_variants().to_vec()
+    } else {
+        info.controller
+    };
+
+    let membership = Membership::read(pid)?;
+    let mounts = read_mounts()?;
+    println!("pid: {}", pid);
+    println!("version: {}", membership.version());
+    if let Some(cgroup) = &membership.unified {
+        println!("cgroup: {}", cgroup);
+    }
+    for (names, cgroup) in &membership.hierarchies {
+        println!("cgroup.{}: {}", names.join(","), cgroup);
+    }
+
+    let fields = read_stats(&membership, &mounts, &controllers, true)?;
+    print_fields(&fields, None);
+
+    let watch = match info.watch {
+        Some(watch) => watch,
+        None => return Ok(()),
+    };
+    let mut previous: HashMap<String, u64> = fields
+        .into_iter()
+        .map(|field| (field.key, field.value))
+        .collect();
+    loop {
+        std::thread::sleep(Duration::from_secs(watch));
+        // The process may have moved to another cgroup since the last sample
+        let membership = match Membership::read(pid) {
+            Ok(membership) => membership,
+            Err(err) => {
+                eprintln!("{:#}", err);
+                return Ok(());
+            }
+        };
+        let fields = read_stats(&membership, &mounts, &controllers, false)?;
+        println!();
+        print_fields(&fields, Some(&previous));
+        previous = fields
+            .into_iter()
+            .map(|field| (field.key, field.value))
+            .collect();
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_cgroup(cli.cgroup),
+    }
+}
@@ -0,0 +1,310 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::cgroup::Membership;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::signal::Signal;
+use std::ffi::{CString, OsString};
+use std::fs::File;
+use std::io::ErrorKind;
+use std::os::raw::c_char;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Flags of clone3 that libc may not provide.
+const CLONE_PIDFD: u64 = 0x1000;
+const CLONE_CLEAR_SIGHAND: u64 = 0x1_0000_0000;
+const CLONE_INTO_CGROUP: u64 = 0x2_0000_0000;
+
+/// Sizes of the versions of struct clone_args, which older kernels accept.
+const CLONE_ARGS_SIZE_VER0: usize = 64;
+const CLONE_ARGS_SIZE_VER1: usize = 80;
+const CLONE_ARGS_SIZE_VER2: usize = 88;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    clone: CloneInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CloneInfo {
+    /// Directory of a cgroup v2 to start the child in with CLONE_INTO_CGROUP
+    #[clap(short, long, value_parser, value_name = "DIR")]
+    cgroup: Option<PathBuf>,
+    /// PID to give the child with set_tid, which requires CAP_SYS_ADMIN
+    #[clap(short = 't', long, value_parser = clap::value_parser!(i32).range(1..), value_name = "TID")]
+    set_tid: Option<i32>,
+    /// Additional flags of clone3 (can be specified multiple times)
+    #[clap(short, long, arg_enum, use_value_delimiter = true)]
+    flags: Vec<CloneFlag>,
+    /// Command to execute in the child
+    #[clap(required = true, value_parser, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum CloneFlag {
+    /// CLONE_NEWPID: start the child as PID 1 of a new PID namespace
+    NewPid,
+    /// CLONE_NEWNS: give the child a new mount namespace
+    NewNs,
+    /// CLONE_NEWUTS: give the child a new UTS namespace
+    NewUts,
+    /// CLONE_NEWIPC: give the child a new IPC namespace
+    NewIpc,
+    /// CLONE_NEWNET: give the child a new network namespace
+    NewNet,
+    /// CLONE_NEWUSER: give the child a new user namespace
+    NewUser,
+    /// CLONE_NEWCGROUP: give the child a new cgroup namespace
+    NewCgroup,
+    /// CLONE_CLEAR_SIGHAND: reset the signal handlers of the child to the defaults
+    ClearSighand,
+}
+
+impl std::fmt::Display for CloneFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CloneFlag::NewPid => write!(f, "CLONE_NEWPID"),
+            CloneFlag::NewNs => write!(f, "CLONE_NEWNS"),
+            CloneFlag::NewUts => write!(f, "CLONE_NEWUTS"),
+            CloneFlag::NewIpc => write!(f, "CLONE_NEWIPC"),
+            CloneFlag::NewNet => write!(f, "CLONE_NEWNET"),
+            CloneFlag::NewUser => write!(f, "CLONE_NEWUSER"),
+            CloneFlag::NewCgroup => write!(f, "CLONE_NEWCGROUP"),
+            CloneFlag::ClearSighand => write!(f, "CLONE_CLEAR_SIGHAND"),
+        }
+    }
+}
+
+impl CloneFlag {
+    fn bits(self) -> u64 {
+        match self {
+            CloneFlag::NewPid => libc::CLONE_NEWPID as u64,
+            CloneFlag::NewNs => libc::CLONE_NEWNS as u64,
+            CloneFlag::NewUts => libc::CLONE_NEWUTS as u64,
+            CloneFlag::NewIpc => libc::CLONE_NEWIPC as u64,
+            CloneFlag::NewNet => libc::CLONE_NEWNET as u64,
+            CloneFlag::NewUser => libc::CLONE_NEWUSER as u64,
+            CloneFlag::NewCgroup => libc::CLONE_NEWCGROUP as u64,
+            CloneFlag::ClearSighand => CLONE_CLEAR_SIGHAND,
+        }
+    }
+}
+
+/// struct clone_args of linux/sched.h, whose fields are all 64 bits wide.
+#[repr(C)]
+#[derive(Default)]
+struct CloneArgs {
+    flags: u64,
+    pidfd: u64,
+    child_tid: u64,
+    parent_tid: u64,
+    exit_signal: u64,
+    stack: u64,
+    stack_size: u64,
+    tls: u64,
+    set_tid: u64,
+    set_tid_size: u64,
+    cgroup: u64,
+}
+
+/// Follows the shell convention for a command killed by a signal.
+fn exit_code(code: i32, signaled: bool) -> ExitCode {
+    let code = if signaled { 128 + code } else { code };
+    ExitCode::from(code as u8)
+}
+
+/// Replaces the child with the command, exiting with 127 like a shell if it cannot.
+///
+/// Only async-signal-safe functions are called, since the child is a copy of this process.
+fn exec_child(argv: &[*const c_char], error: &[u8]) -> ! {
+    // SAFETY: argv is a null-terminated array of C strings, and error is a valid buffer.
+    unsafe {
+        libc::execvp(argv[0], argv.as_ptr());
+        libc::write(2, error.as_ptr().cast(), error.len());
+        libc::_exit(127);
+    }
+}
+
+fn open_cgroup(path: &Path) -> anyhow::Result<File> {
+    let dir = File::open(path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Cgroup '{}' does not exist", path.display()),
+        _ => anyhow!(err).context(format!("Failed to open '{}'", path.display())),
+    })?;
+    if !path.join("cgroup.procs").exists() || path.join("tasks").exists() {
+        return Err(anyhow!(
+            "'{}' is not a directory of a cgroup v2",
+            path.display()
+        ));
+    }
+
+    Ok(dir)
+}
+
+fn clone_error(errno: Errno, info: &CloneInfo) -> anyhow::Error {
+    match errno {
+        Errno::ENOSYS => anyhow!("clone3(2) requires Linux 5.3 or later"),
+        // The kernel rejects a struct larger than it knows
+        Errno::E2BIG if info.cgroup.is_some() => {
+            anyhow!("CLONE_INTO_CGROUP requires Linux 5.7 or later")
+        }
+        Errno::E2BIG => anyhow!("set_tid requires Linux 5.5 or later"),
+        Errno::EEXIST => anyhow!("PID {} is already in use", info.set_tid.unwrap_or_default()),
+        Errno::EPERM if info.set_tid.is_some() => {
+            anyhow!("set_tid requires CAP_SYS_ADMIN in the PID namespace")
+        }
+        Errno::EPERM => {
+            anyhow!("Creating namespaces other than a user namespace requires CAP_SYS_ADMIN")
+        }
+        Errno::EBUSY => anyhow!(
+            "The cgroup has controllers enabled for its children, where processes cannot be placed"
+        ),
+        Errno::EOPNOTSUPP => {
+            anyhow!("The cgroup cannot take a process, such as a threaded cgroup")
+        }
+        Errno::EACCES => anyhow!("Permission denied to write cgroup.procs of the cgroup"),
+        Errno::EINVAL if info.set_tid.is_some() && info.flags.contains(&CloneFlag::NewPid) => {
+            anyhow!("With new-pid, set_tid must be 1, which is the PID of the child in the new namespace")
+        }
+        Errno::EINVAL if info.set_tid.is_some() => {
+            anyhow!("set_tid is invalid, such as a PID over /proc/sys/kernel/pid_max")
+        }
+        errno => anyhow!(errno).context("Failed to call clone3"),
+    }
+}
+
+fn handle_clone(info: CloneInfo) -> anyhow::Result<ExitCode> {
+    let cgroup = info.cgroup.as_deref().map(open_cgroup).transpose()?;
+    let command: Vec<CString> = info
+        .command
+        .iter()
+        .map(|arg| CString::new(arg.as_bytes()))
+        .collect::<Result<_, _>>()
+        .context("COMMAND must not contain a null byte")?;
+    let mut argv: Vec<*const c_char> = command.iter().map(|arg| arg.as_ptr()).collect();
+    argv.push(std::ptr::null());
+    // Formatted before cloning, since the child cannot allocate safely
+    let error = format!(
+        "Error: Failed to execute '{}'\n",
+        info.command[0].to_string_lossy()
+    );
+
+    let flags = info
+        .flags
+        .iter()
+        .fold(CLONE_PIDFD, |flags, flag| flags | flag.bits());
+    let mut pidfd: RawFd = -1;
+    let set_tid = info.set_tid.unwrap_or_default();
+    let mut args = CloneArgs {
+        flags,
+        pidfd: &mut pidfd as *mut RawFd as u64,
+        exit_signal: libc::SIGCHLD as u64,
+        ..Default::default()
+    };
+    // The smallest version of the struct works on the oldest kernels
+    let mut size = CLONE_ARGS_SIZE_VER0;
+    if info.set_tid.is_some() {
+        args.set_tid = &set_tid as *const i32 as u64;
+        args.set_tid_size = 1;
+        size = CLONE_ARGS_SIZE_VER1;
+    }
+    if let Some(cgroup) = &cgroup {
+        args.flags |= CLONE_INTO_CGROUP;
+        args.cgroup = cgroup.as_raw_fd() as u64;
+        size = CLONE_ARGS_SIZE_VER2;
+    }
+
+    // SAFETY: args is a valid clone_args of the given size. Without CLONE_VM, the child has a
+    // copy of the memory, and only calls async-signal-safe functions before exec.
+    let ret = unsafe { libc::syscall(libc::SYS_clone3, &mut args as *mut CloneArgs, size) };
+    let pid = Errno::result(ret).map_err(|errno| clone_error(errno, &info))? as i32;
+    if pid == 0 {
+        exec_child(&argv, error.as_bytes());
+    }
+    // SAFETY: clone3 returns a new pidfd owned by nobody else.
+    let pidfd = unsafe { File::from_raw_fd(pidfd) };
+    drop(cgroup);
+
+    // Keep stdout for the command
+    eprintln!("pid: {}", pid);
+    eprintln!(
+        "pidfd: /proc/{}/fd/{}",
+        std::process::id(),
+        pidfd.as_raw_fd()
+    );
+    let names: Vec<String> = info.flags.iter().map(|flag| flag.to_string()).collect();
+    if !names.is_empty() {
+        eprintln!("flags: {}", names.join(" | "));
+    }
+    if let Some(cgroup) = Membership::read(pid)
+        .ok()
+        .and_then(|membership| membership.unified)
+    {
+        eprintln!("cgroup: {}", cgroup);
+    }
+
+    // SAFETY: siginfo_t is a plain C struct, for which all zeros is valid.
+    let mut siginfo: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: siginfo is valid for the kernel to write a siginfo_t.
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PIDFD,
+                pidfd.as_raw_fd() as libc::id_t,
+                &mut siginfo,
+                libc::WEXITED,
+            )
+        };
+        match Errno::result(ret) {
+            Ok(_) => break,
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to wait for the child"),
+        }
+    }
+
+    // SAFETY: waitid filled siginfo with the status of an exited child.
+    let status = unsafe { siginfo.si_status() };
+    let signaled = siginfo.si_code != libc::CLD_EXITED;
+    if signaled {
+        match Signal::try_from(status) {
+            Ok(signal) => eprintln!("signal: {}", signal),
+            Err(_) => eprintln!("signal: {}", status),
+        }
+    } else {
+        eprintln!("status: {}", status);
+    }
+
+    Ok(exit_code(status, signaled))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_clone(cli.clone),
+    }
+}
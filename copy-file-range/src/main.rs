@@ -0,0 +1,336 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::copy::{copy_with_sendfile, throughput};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::copy_file_range;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    copy: CopyInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CopyInfo {
+    /// File to copy from
+    #[clap(required = true, value_parser, value_name = "SOURCE")]
+    source: Option<PathBuf>,
+    /// File to copy to
+    #[clap(required = true, value_parser, value_name = "DEST")]
+    destination: Option<PathBuf>,
+    /// Offset in SOURCE to start copying from (accepts suffixes such as K, MiB, GB)
+    #[clap(short = 's', long, default_value_t = 0, value_parser = parse_size)]
+    src_offset: i64,
+    /// Offset in DEST to start copying to (accepts suffixes such as K, MiB, GB)
+    #[clap(short = 'd', long, default_value_t = 0, value_parser = parse_size)]
+    dst_offset: i64,
+    /// Number of bytes to copy [default: up to the end of SOURCE]
+    #[clap(short = 'n', long, value_parser = parse_size)]
+    count: Option<i64>,
+    /// Create DEST if it does not exist
+    #[clap(short, long)]
+    create: bool,
+}
+
+/// Offsets in SOURCE and DEST up to which the data has been copied.
+///
+/// Every method advances them so that a fallback can resume from them.
+struct Progress {
+    src_offset: i64,
+    dst_offset: i64,
+    end: i64,
+}
+
+impl Progress {
+    fn advance(&mut self, copied: usize) {
+        self.src_offset += copied as i64;
+        self.dst_offset += copied as i64;
+    }
+
+    fn remaining(&self) -> usize {
+        usize::try_from(self.end - self.src_offset).unwrap_or(usize::MAX)
+    }
+}
+
+fn copy_with_copy_file_range(
+    source: &File,
+    destination: &File,
+    progress: &mut Progress,
+) -> nix::Result<()> {
+    while progress.src_offset < progress.end {
+        let (mut src_offset, mut dst_offset) = (progress.src_offset, progress.dst_offset);
+        let copied = copy_file_range(
+            source.as_raw_fd(),
+            Some(&mut src_offset),
+            destination.as_raw_fd(),
+            Some(&mut dst_offset),
+            progress.remaining(),
+        )?;
+        if copied == 0 {
+            // SOURCE is shorter than expected
+            break;
+        }
+        progress.advance(copied);
+    }
+
+    Ok(())
+}
+
+fn copy_with_buffer(
+    mut source: &File,
+    mut destination: &File,
+    progress: &mut Progress,
+) -> std::io::Result<()> {
+    source.seek(SeekFrom::Start(progress.src_offset as u64))?;
+    destination.seek(SeekFrom::Start(progress.dst_offset as u64))?;
+    let copied = std::io::copy(
+        &mut source.take((progress.end - progress.src_offset) as u64),
+        &mut destination,
+    )?;
+    progress.advance(copied as usize);
+
+    Ok(())
+}
+
+/// copy_file_range fails with these errors when the files do not support it,
+/// e.g. they are on different file systems before Linux 5.3.
+fn is_unsupported(errno: Errno) -> bool {
+    matches!(errno, Errno::EXDEV | Errno::EOPNOTSUPP | Errno::ENOSYS)
+}
+
+/// Copies the range of progress with `copy`, and with sendfile or a buffer from where it stopped
+/// when it is unsupported, returning the name of the method that finished the copy.
+fn copy_with_fallback<F>(
+    source: &File,
+    mut destination: &File,
+    progress: &mut Progress,
+    copy: F,
+) -> anyhow::Result<&'static str>
+where
+    F: FnOnce(&File, &File, &mut Progress) -> nix::Result<()>,
+{
+    match copy(source, destination, progress) {
+        Ok(()) => return Ok("copy_file_range"),
+        Err(errno) if is_unsupported(errno) => {
+            eprintln!(
+                "warning: copy_file_range failed with {}; falling back to sendfile",
+                errno
+            );
+        }
+        Err(errno) => return Err(errno).context("Failed to call copy_file_range"),
+    }
+
+    // sendfile writes at the file offset of DEST
+    destination
+        .seek(SeekFrom::Start(progress.dst_offset as u64))
+        .context("Failed to seek the destination")?;
+    let mut src_offset = progress.src_offset;
+    let result = copy_with_sendfile(
+        source,
+        destination,
+        &mut src_offset,
+        progress.end - progress.src_offset,
+    );
+    progress.advance((src_offset - progress.src_offset) as usize);
+    match result {
+        Ok(_) => Ok("sendfile"),
+        Err(Errno::EINVAL) => {
+            eprintln!("warning: sendfile failed with EINVAL; falling back to buffered copy");
+            copy_with_buffer(source, destination, progress).context("Failed to copy the file")?;
+            Ok("buffered")
+        }
+        Err(errno) => Err(errno).context("Failed to call sendfile"),
+    }
+}
+
+fn handle_copy(info: CopyInfo) -> anyhow::Result<()> {
+    // clap requires SOURCE and DEST unless a subcommand is given
+    let source_path = info.source.unwrap();
+    let destination_path = info.destination.unwrap();
+
+    let source = File::open(&source_path)
+        .with_context(|| format!("Failed to open '{}'", source_path.display()))?;
+    let source_len = source
+        .metadata()
+        .context("Failed to retrieve metadata of the source")?
+        .len() as i64;
+    let count = info
+        .count
+        .unwrap_or(source_len - info.src_offset.min(source_len));
+    let end = info.src_offset.checked_add(count).ok_or_else(|| {
+        anyhow!(
+            "The range of offset {} and count {} exceeds the maximum file offset",
+            info.src_offset,
+            count
+        )
+    })?;
+    if info.dst_offset.checked_add(count).is_none() {
+        return Err(anyhow!(
+            "The range of offset {} and count {} exceeds the maximum file offset",
+            info.dst_offset,
+            count
+        ));
+    }
+    // DEST is not truncated since only the range is overwritten
+    let destination = OpenOptions::new()
+        .write(true)
+        .create(info.create)
+        .truncate(false)
+        .open(&destination_path)
+        .with_context(|| format!("Failed to open '{}'", destination_path.display()))?;
+
+    eprintln!("source: {}", source_path.display());
+    eprintln!("destination: {}", destination_path.display());
+    eprintln!("src_offset: {}", info.src_offset);
+    eprintln!("dst_offset: {}", info.dst_offset);
+    eprintln!("count: {}", count);
+
+    let start = Instant::now();
+    let mut progress = Progress {
+        src_offset: info.src_offset,
+        dst_offset: info.dst_offset,
+        end,
+    };
+    let method = copy_with_fallback(
+        &source,
+        &destination,
+        &mut progress,
+        copy_with_copy_file_range,
+    )?;
+    eprintln!("method: {}", method);
+    let elapsed = start.elapsed();
+    let copied = progress.src_offset - info.src_offset;
+
+    eprintln!("copied: {}", copied);
+    eprintln!("elapsed: {:.3}s", elapsed.as_secs_f64());
+    eprintln!("throughput: {:.0} bytes/sec", throughput(copied, elapsed));
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_copy(cli.copy),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const CONTENT: &[u8] = b"0123456789abcdefghijklmnopqrstuv";
+
+    fn files() -> (tempfile::NamedTempFile, tempfile::NamedTempFile) {
+        let mut source = tempfile::NamedTempFile::new().unwrap();
+        source.write_all(CONTENT).unwrap();
+        let destination = tempfile::NamedTempFile::new().unwrap();
+        (source, destination)
+    }
+
+    #[test]
+    fn cross_device_copy_falls_back_to_sendfile() {
+        let (source, destination) = files();
+        let mut progress = Progress {
+            src_offset: 2,
+            dst_offset: 4,
+            end: CONTENT.len() as i64,
+        };
+
+        let method = copy_with_fallback(
+            source.as_file(),
+            destination.as_file(),
+            &mut progress,
+            |_, _, _| Err(Errno::EXDEV),
+        )
+        .unwrap();
+
+        assert_eq!(method, "sendfile");
+        assert_eq!(progress.src_offset, CONTENT.len() as i64);
+        assert_eq!(progress.dst_offset, CONTENT.len() as i64 + 2);
+        let copied = std::fs::read(destination.path()).unwrap();
+        assert_eq!(&copied[..4], &[0; 4]);
+        assert_eq!(&copied[4..], &CONTENT[2..]);
+    }
+
+    #[test]
+    fn fallback_resumes_where_copy_file_range_stopped() {
+        let (source, destination) = files();
+        let mut progress = Progress {
+            src_offset: 0,
+            dst_offset: 0,
+            end: CONTENT.len() as i64,
+        };
+
+        // The first half is copied before copy_file_range fails
+        let method = copy_with_fallback(
+            source.as_file(),
+            destination.as_file(),
+            &mut progress,
+            |source, destination, progress| {
+                let mut half = Progress {
+                    end: progress.end / 2,
+                    ..*progress
+                };
+                copy_with_buffer(source, destination, &mut half).unwrap();
+                *progress = Progress {
+                    end: progress.end,
+                    ..half
+                };
+                Err(Errno::EOPNOTSUPP)
+            },
+        )
+        .unwrap();
+
+        assert_eq!(method, "sendfile");
+        assert_eq!(progress.src_offset, CONTENT.len() as i64);
+        assert_eq!(std::fs::read(destination.path()).unwrap(), CONTENT);
+    }
+
+    #[test]
+    fn other_errors_of_copy_file_range_do_not_fall_back() {
+        let (source, destination) = files();
+        let mut progress = Progress {
+            src_offset: 0,
+            dst_offset: 0,
+            end: CONTENT.len() as i64,
+        };
+
+        let err = copy_with_fallback(
+            source.as_file(),
+            destination.as_file(),
+            &mut progress,
+            |_, _, _| Err(Errno::EBADF),
+        )
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Failed to call copy_file_range");
+        assert!(std::fs::read(destination.path()).unwrap().is_empty());
+    }
+}
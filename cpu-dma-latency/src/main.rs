@@ -0,0 +1,121 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::exit_code;
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, ExitCode};
+
+const CPU_DMA_LATENCY: &str = "/dev/cpu_dma_latency";
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    latency: LatencyInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct LatencyInfo {
+    /// Maximum wakeup latency of CPUs in microseconds, where 0 keeps them out of deep idle states
+    #[clap(
+        short,
+        long,
+        required = true,
+        value_parser = clap::value_parser!(i32).range(0..),
+        value_name = "MICROSECONDS"
+    )]
+    latency_us: Option<i32>,
+    /// Command to run while holding the constraint [default: hold it until interrupted]
+    #[clap(
+        short,
+        long,
+        multiple_values = true,
+        allow_hyphen_values = true,
+        value_parser,
+        value_name = "COMMAND"
+    )]
+    exec: Vec<OsString>,
+}
+
+/// Reads the latency in effect, which is the smallest one of all the constraints.
+fn read_latency(file: &mut File) -> anyhow::Result<i32> {
+    let mut buf = [0u8; 4];
+    file.read_exact(&mut buf)
+        .with_context(|| format!("Failed to read {}", CPU_DMA_LATENCY))?;
+
+    Ok(i32::from_ne_bytes(buf))
+}
+
+/// Opens the device and adds the constraint, which lasts until the file is closed.
+fn request_latency(latency_us: i32) -> anyhow::Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(CPU_DMA_LATENCY)
+        .map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow!(
+                "{} does not exist; it requires a kernel built with CONFIG_CPU_IDLE",
+                CPU_DMA_LATENCY
+            ),
+            ErrorKind::PermissionDenied => anyhow!("Opening {} requires root", CPU_DMA_LATENCY),
+            _ => anyhow!(err).context(format!("Failed to open {}", CPU_DMA_LATENCY)),
+        })?;
+    // The device takes the latency as a binary 32-bit integer
+    file.write_all(&latency_us.to_ne_bytes())
+        .with_context(|| format!("Failed to write to {}", CPU_DMA_LATENCY))?;
+
+    Ok(file)
+}
+
+fn handle_latency(info: LatencyInfo) -> anyhow::Result<ExitCode> {
+    // clap requires --latency-us unless a subcommand is given
+    let latency_us = info.latency_us.unwrap();
+    let mut file = request_latency(latency_us)?;
+    let effective = read_latency(&mut file)?;
+    eprintln!("requested: {} us", latency_us);
+    eprintln!("effective: {} us", effective);
+
+    if info.exec.is_empty() {
+        eprintln!("holding the constraint until interrupted");
+        loop {
+            nix::unistd::pause();
+        }
+    }
+
+    let status = Command::new(&info.exec[0])
+        .args(&info.exec[1..])
+        .status()
+        .with_context(|| format!("Failed to execute '{}'", info.exec[0].to_string_lossy()))?;
+    // Closing the file releases the constraint
+    drop(file);
+
+    Ok(exit_code(status))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_latency(cli.latency),
+    }
+}
@@ -0,0 +1,258 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::copy::throughput;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::sys::uio::{pread, pwrite};
+use std::alloc::Layout;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read, Write};
+use std::ops::{Deref, DerefMut};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::ptr::NonNull;
+use std::time::{Duration, Instant};
+
+/// Alignment of the buffers, which satisfies the logical block size of any common device.
+const ALIGNMENT: usize = 4096;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Read a range of a file with O_DIRECT and write it to stdout
+    #[clap(display_order = 1, name = "read")]
+    Read {
+        /// File to read
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+        /// Start of the range, a multiple of the logical block size (accepts suffixes such as K,
+        /// MiB, GB)
+        #[clap(value_parser = parse_size, value_name = "OFFSET")]
+        offset: i64,
+        /// Length of the range, a multiple of the logical block size (accepts suffixes such as
+        /// K, MiB, GB)
+        #[clap(value_parser = parse_size, value_name = "LEN")]
+        len: i64,
+    },
+    /// Write the content of DATA_FILE to a file at an offset with O_DIRECT
+    #[clap(display_order = 2, name = "write")]
+    Write {
+        /// File to write
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+        /// Where to write, a multiple of the logical block size (accepts suffixes such as K, MiB,
+        /// GB)
+        #[clap(value_parser = parse_size, value_name = "OFFSET")]
+        offset: i64,
+        /// File whose size is a multiple of the logical block size
+        #[clap(value_parser, value_name = "DATA_FILE")]
+        data_file: PathBuf,
+        /// Create FILE if it does not exist
+        #[clap(short, long)]
+        create: bool,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// A zeroed buffer aligned to ALIGNMENT, as O_DIRECT requires of the memory of the I/O.
+struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    len: usize,
+    layout: Layout,
+}
+
+impl AlignedBuffer {
+    fn new(len: usize) -> anyhow::Result<Self> {
+        // A zero-sized allocation is not allowed, and the capacity is rounded up to the alignment
+        let size = len
+            .max(1)
+            .checked_add(ALIGNMENT - 1)
+            .map(|size| size / ALIGNMENT * ALIGNMENT);
+        let layout = size
+            .and_then(|size| Layout::from_size_align(size, ALIGNMENT).ok())
+            .ok_or_else(|| anyhow!("The length {} is too large for a buffer", len))?;
+        // SAFETY: the layout has a nonzero size.
+        let ptr = unsafe { std::alloc::alloc(layout) };
+        let ptr = NonNull::new(ptr)
+            .ok_or_else(|| anyhow!("Failed to allocate a buffer of {} bytes", layout.size()))?;
+        // SAFETY: ptr is valid for writes of the size of the layout.
+        unsafe { std::ptr::write_bytes(ptr.as_ptr(), 0, layout.size()) };
+
+        Ok(AlignedBuffer { ptr, len, layout })
+    }
+}
+
+impl Deref for AlignedBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // SAFETY: ptr is valid and initialized for the size of the layout, which is at least len.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl DerefMut for AlignedBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: ptr is valid and initialized for the size of the layout, and is borrowed
+        // uniquely through self.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: ptr was allocated with the same layout.
+        unsafe { std::alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+fn open_direct(path: &Path, options: &mut OpenOptions) -> anyhow::Result<File> {
+    options
+        .custom_flags((OFlag::O_DIRECT | OFlag::O_SYNC).bits())
+        .open(path)
+        .map_err(|err| match err.raw_os_error() {
+            Some(libc::EINVAL) => anyhow!(
+                "The filesystem of '{}' does not support O_DIRECT, such as tmpfs before Linux 6.6",
+                path.display()
+            ),
+            _ => anyhow!(err).context(format!("Failed to open '{}'", path.display())),
+        })
+}
+
+fn io_error(errno: Errno, operation: &str) -> anyhow::Error {
+    match errno {
+        Errno::EINVAL => anyhow!(
+            "Failed to {} with O_DIRECT; OFFSET and the length must be multiples of the logical \
+            block size of the device, which 'blockdev --getss' prints",
+            operation
+        ),
+        errno => anyhow!(errno).context(format!("Failed to {}", operation)),
+    }
+}
+
+fn check_offset(offset: i64) -> anyhow::Result<()> {
+    if offset < 0 {
+        return Err(anyhow!("OFFSET must not be negative"));
+    }
+    Ok(())
+}
+
+fn print_statistics(filename: &Path, offset: i64, bytes: usize, elapsed: Duration) {
+    eprintln!("file: {}", filename.display());
+    eprintln!("offset: {}", offset);
+    eprintln!("bytes: {}", bytes);
+    eprintln!("elapsed: {:.6}s", elapsed.as_secs_f64());
+    eprintln!(
+        "throughput: {:.0} bytes/sec",
+        throughput(bytes as i64, elapsed)
+    );
+}
+
+fn handle_read(filename: PathBuf, offset: i64, len: i64) -> anyhow::Result<()> {
+    check_offset(offset)?;
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len > 0)
+        .ok_or_else(|| anyhow!("LEN must be positive"))?;
+    let file = open_direct(&filename, OpenOptions::new().read(true))?;
+    let mut buffer = AlignedBuffer::new(len)?;
+
+    let start = Instant::now();
+    let mut read = 0;
+    while read < len {
+        let bytes = pread(file.as_raw_fd(), &mut buffer[read..], offset + read as i64)
+            .map_err(|errno| io_error(errno, "read"))?;
+        read += bytes;
+        // A short read is at the end of the file, which may not be aligned, and the next read
+        // would be unaligned
+        if bytes == 0 || read % ALIGNMENT != 0 {
+            break;
+        }
+    }
+    let elapsed = start.elapsed();
+
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(&buffer[..read])
+        .and_then(|_| stdout.flush())
+        .context("Failed to write to stdout")?;
+    print_statistics(&filename, offset, read, elapsed);
+
+    Ok(())
+}
+
+fn handle_write(
+    filename: PathBuf,
+    offset: i64,
+    data_file: PathBuf,
+    create: bool,
+) -> anyhow::Result<()> {
+    check_offset(offset)?;
+    let mut data = File::open(&data_file)
+        .with_context(|| format!("Failed to open '{}'", data_file.display()))?;
+    let len = data
+        .metadata()
+        .with_context(|| format!("Failed to stat '{}'", data_file.display()))?
+        .len();
+    let len = usize::try_from(len)
+        .ok()
+        .filter(|&len| len > 0)
+        .ok_or_else(|| anyhow!("'{}' is empty", data_file.display()))?;
+    let mut buffer = AlignedBuffer::new(len)?;
+    data.read_exact(&mut buffer)
+        .map_err(|err| match err.kind() {
+            ErrorKind::UnexpectedEof => anyhow!("'{}' was truncated", data_file.display()),
+            _ => anyhow!(err).context(format!("Failed to read '{}'", data_file.display())),
+        })?;
+    let file = open_direct(&filename, OpenOptions::new().write(true).create(create))?;
+
+    let start = Instant::now();
+    let mut written = 0;
+    while written < len {
+        written += pwrite(
+            file.as_raw_fd(),
+            &buffer[written..],
+            offset + written as i64,
+        )
+        .map_err(|errno| io_error(errno, "write"))?;
+    }
+    let elapsed = start.elapsed();
+    print_statistics(&filename, offset, written, elapsed);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Read {
+            filename,
+            offset,
+            len,
+        } => handle_read(filename, offset, len),
+        Commands::Write {
+            filename,
+            offset,
+            data_file,
+            create,
+        } => handle_write(filename, offset, data_file, create),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,196 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::ppoll;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal};
+use nix::sys::time::{TimeSpec, TimeValLike};
+use std::fs::File;
+use std::os::raw::c_int;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+// Flags of F_NOTIFY from linux/fcntl.h, which libc does not define
+const DN_ACCESS: c_int = 0x0000_0001;
+const DN_MODIFY: c_int = 0x0000_0002;
+const DN_CREATE: c_int = 0x0000_0004;
+const DN_DELETE: c_int = 0x0000_0008;
+const DN_RENAME: c_int = 0x0000_0010;
+const DN_ATTRIB: c_int = 0x0000_0020;
+const DN_MULTISHOT: c_int = 0x8000_0000_u32 as c_int;
+
+/// Set by the SIGIO handler when the directory changes
+static NOTIFIED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    notify: NotifyInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct NotifyInfo {
+    /// Directory to watch
+    #[clap(required = true, value_parser, value_name = "DIR")]
+    directory: Option<PathBuf>,
+    /// Events to watch, separated by commas [default: all]
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true,
+        value_name = "EVENTS"
+    )]
+    events: Vec<Event>,
+    /// Exit after the number of seconds
+    #[clap(short, long, value_parser, value_name = "SECONDS")]
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Event {
+    /// A file in the directory was read
+    #[clap(name = "access")]
+    Access,
+    /// A file in the directory was written or truncated
+    #[clap(name = "modify")]
+    Modify,
+    /// A file was created in the directory
+    #[clap(name = "create")]
+    Create,
+    /// A file was unlinked from the directory
+    #[clap(name = "delete")]
+    Delete,
+    /// A file in the directory was renamed
+    #[clap(name = "rename")]
+    Rename,
+    /// The attributes of a file in the directory were changed
+    #[clap(name = "attrib")]
+    Attrib,
+}
+
+impl Event {
+    fn flag(self) -> c_int {
+        match self {
+            Event::Access => DN_ACCESS,
+            Event::Modify => DN_MODIFY,
+            Event::Create => DN_CREATE,
+            Event::Delete => DN_DELETE,
+            Event::Rename => DN_RENAME,
+            Event::Attrib => DN_ATTRIB,
+        }
+    }
+}
+
+extern "C" fn handle_sigio(_: c_int) {
+    NOTIFIED.store(true, Ordering::SeqCst);
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::ENOTDIR => anyhow!("F_NOTIFY only watches directories"),
+        Errno::EINVAL => anyhow!(
+            "The kernel is not built with CONFIG_DNOTIFY, or dnotify is disabled in /proc/sys/fs/dir-notify-enable"
+        ),
+        errno => anyhow!(errno),
+    }
+}
+
+fn handle_notify(info: NotifyInfo) -> anyhow::Result<()> {
+    // clap requires DIR unless a subcommand is given
+    let directory = info.directory.unwrap();
+    let events = if info.events.is_empty() {
+        DN_ACCESS | DN_MODIFY | DN_CREATE | DN_DELETE | DN_RENAME | DN_ATTRIB
+    } else {
+        info.events
+            .iter()
+            .fold(0, |flags, event| flags | event.flag())
+    };
+    let start = Instant::now();
+    let deadline = info
+        .timeout
+        .map(|timeout| start + Duration::from_secs(timeout));
+
+    // The kernel notifies changes with SIGIO, which is blocked except while waiting so that
+    // no notification is missed
+    let mut sigio = SigSet::empty();
+    sigio.add(Signal::SIGIO);
+    let mut unblocked = SigSet::empty();
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_BLOCK, Some(&sigio), Some(&mut unblocked))
+        .context("Failed to block SIGIO")?;
+    unblocked.remove(Signal::SIGIO);
+    let action = SigAction::new(
+        SigHandler::Handler(handle_sigio),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // SAFETY: the handler only stores to an atomic variable, which is async-signal-safe.
+    unsafe { sigaction(Signal::SIGIO, &action) }.context("Failed to handle SIGIO")?;
+
+    let file = File::open(&directory)
+        .with_context(|| format!("Failed to open '{}'", directory.display()))?;
+    // Without DN_MULTISHOT, the kernel only notifies the first change
+    // SAFETY: F_NOTIFY only takes an integer argument.
+    let ret = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_NOTIFY, events | DN_MULTISHOT) };
+    Errno::result(ret)
+        .map_err(describe)
+        .with_context(|| format!("Failed to watch '{}'", directory.display()))?;
+
+    loop {
+        // None means no timeout
+        let timeout = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                Some(TimeSpec::nanoseconds(remaining.as_nanos() as i64))
+            }
+            None => None,
+        };
+        match ppoll(&mut [], timeout, Some(unblocked)) {
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(errno) => return Err(errno).context("Failed to wait for notifications"),
+        }
+
+        // Notifications arriving before the handler returns are merged into one, and
+        // dnotify does not tell which event happened
+        if NOTIFIED.swap(false, Ordering::SeqCst) {
+            println!(
+                "{:.3}\t{}",
+                start.elapsed().as_secs_f64(),
+                directory.display()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_notify(cli.notify),
+    }
+}
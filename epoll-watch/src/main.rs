@@ -0,0 +1,301 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::sys::epoll::{
+    epoll_create1, epoll_ctl, epoll_wait, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
+};
+use nix::unistd::read;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Events returned by a single epoll_wait.
+const MAX_EVENTS: usize = 16;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    watch: WatchInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct WatchInfo {
+    /// File descriptors of this process, such as 0 for stdin, or files to open such as FIFOs,
+    /// sockets in /proc/PID/fd/N or devices
+    #[clap(required = true, value_parser = parse_target, value_name = "FD|PATH")]
+    targets: Vec<Target>,
+    /// Events to watch, and the flags of the registration
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        value_delimiter = ',',
+        default_value = "in",
+        value_name = "EVENT,..."
+    )]
+    events: Vec<Event>,
+    /// Milliseconds to wait for an event before exiting [default: wait forever]
+    #[clap(short, long, value_parser, value_name = "MS")]
+    timeout: Option<u32>,
+    /// Read the available data on EPOLLIN and stop watching a file descriptor at its end,
+    /// instead of leaving the data, which a level-triggered watch reports again
+    #[clap(short, long)]
+    read: bool,
+    /// Exit after this number of events
+    #[clap(short, long, value_parser = clap::value_parser!(u64).range(1..), value_name = "N")]
+    count: Option<u64>,
+}
+
+#[derive(Clone)]
+enum Target {
+    Fd(RawFd),
+    Path(PathBuf),
+}
+
+fn parse_target(s: &str) -> Result<Target, String> {
+    if !s.is_empty() && s.bytes().all(|byte| byte.is_ascii_digit()) {
+        return s
+            .parse()
+            .map(Target::Fd)
+            .map_err(|_| format!("'{}' is not a file descriptor", s));
+    }
+    Ok(Target::Path(PathBuf::from(s)))
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Event {
+    /// EPOLLIN: data is available to read
+    In,
+    /// EPOLLOUT: writing does not block
+    Out,
+    /// EPOLLRDHUP: the peer of a stream socket shut down writing
+    Rdhup,
+    /// EPOLLPRI: exceptional data such as out-of-band data is available
+    Pri,
+    /// EPOLLERR: an error occurred, which is always reported
+    Err,
+    /// EPOLLHUP: the other end hung up, which is always reported
+    Hup,
+    /// EPOLLET: report edges, the changes of the readiness, instead of levels
+    Et,
+    /// EPOLLONESHOT: stop watching after the first event until rearmed
+    Oneshot,
+}
+
+impl From<Event> for EpollFlags {
+    fn from(event: Event) -> Self {
+        match event {
+            Event::In => EpollFlags::EPOLLIN,
+            Event::Out => EpollFlags::EPOLLOUT,
+            Event::Rdhup => EpollFlags::EPOLLRDHUP,
+            Event::Pri => EpollFlags::EPOLLPRI,
+            Event::Err => EpollFlags::EPOLLERR,
+            Event::Hup => EpollFlags::EPOLLHUP,
+            Event::Et => EpollFlags::EPOLLET,
+            Event::Oneshot => EpollFlags::EPOLLONESHOT,
+        }
+    }
+}
+
+/// A file descriptor being watched, which is closed on drop if it was opened from a path.
+struct Watched {
+    name: String,
+    fd: RawFd,
+    _file: Option<File>,
+}
+
+fn open_target(target: &Target, flags: EpollFlags) -> anyhow::Result<Watched> {
+    let path = match target {
+        Target::Fd(fd) => {
+            return Ok(Watched {
+                name: fd.to_string(),
+                fd: *fd,
+                _file: None,
+            })
+        }
+        Target::Path(path) => path,
+    };
+    // O_NONBLOCK avoids waiting for the other end of a FIFO on open and lets --read stop at the
+    // end of the available data
+    let readable = flags.contains(EpollFlags::EPOLLIN) || !flags.contains(EpollFlags::EPOLLOUT);
+    let file = OpenOptions::new()
+        .read(readable)
+        .write(flags.contains(EpollFlags::EPOLLOUT))
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(path)
+        .map_err(|err| match err.raw_os_error() {
+            // A FIFO without a reader cannot be opened for writing without blocking
+            Some(libc::ENXIO) => anyhow!(
+                "'{}' has no reader to open it for writing; watch it with 'in' as well",
+                path.display()
+            ),
+            _ => anyhow!(err).context(format!("Failed to open '{}'", path.display())),
+        })?;
+
+    Ok(Watched {
+        name: path.display().to_string(),
+        fd: file.as_raw_fd(),
+        _file: Some(file),
+    })
+}
+
+/// Reads what is available without blocking and returns the bytes read and whether the end of
+/// the file is reached.
+fn drain(fd: RawFd) -> nix::Result<(usize, bool)> {
+    let mut buffer = [0; 64 * 1024];
+    let mut total = 0;
+    loop {
+        match read(fd, &mut buffer) {
+            Ok(0) => return Ok((total, true)),
+            Ok(len) => total += len,
+            Err(Errno::EAGAIN) => return Ok((total, false)),
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno),
+        }
+        // A blocking file descriptor would block at the end of the data
+        if !is_nonblocking(fd)? {
+            return Ok((total, false));
+        }
+    }
+}
+
+fn is_nonblocking(fd: RawFd) -> nix::Result<bool> {
+    let flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFL)?;
+    Ok(OFlag::from_bits_truncate(flags).contains(OFlag::O_NONBLOCK))
+}
+
+fn event_names(flags: EpollFlags) -> String {
+    const NAMES: [(EpollFlags, &str); 6] = [
+        (EpollFlags::EPOLLIN, "EPOLLIN"),
+        (EpollFlags::EPOLLOUT, "EPOLLOUT"),
+        (EpollFlags::EPOLLRDHUP, "EPOLLRDHUP"),
+        (EpollFlags::EPOLLPRI, "EPOLLPRI"),
+        (EpollFlags::EPOLLERR, "EPOLLERR"),
+        (EpollFlags::EPOLLHUP, "EPOLLHUP"),
+    ];
+    let names: Vec<&str> = NAMES
+        .iter()
+        .filter(|(flag, _)| flags.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect();
+    names.join(" | ")
+}
+
+fn handle_watch(info: WatchInfo) -> anyhow::Result<()> {
+    let flags = info
+        .events
+        .iter()
+        .fold(EpollFlags::empty(), |flags, &event| flags | event.into());
+    let epoll =
+        epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC).context("Failed to call epoll_create1")?;
+    // SAFETY: epoll_create1 returns a new file descriptor owned by nobody else.
+    let epoll = unsafe { File::from_raw_fd(epoll) };
+
+    // The index of a target identifies it in the events
+    let mut watched = BTreeMap::new();
+    for (index, target) in info.targets.iter().enumerate() {
+        let target = open_target(target, flags)?;
+        let mut event = EpollEvent::new(flags, index as u64);
+        epoll_ctl(epoll.as_raw_fd(), EpollOp::EpollCtlAdd, target.fd, &mut event).map_err(
+            |errno| match errno {
+                Errno::EPERM => anyhow!(
+                    "{} is a regular file or a directory, which is always ready and cannot be watched with epoll",
+                    target.name
+                ),
+                Errno::EBADF => anyhow!("{} is not an open file descriptor", target.name),
+                Errno::EEXIST => anyhow!("{} is given more than once", target.name),
+                errno => anyhow!(errno).context(format!("Failed to watch {}", target.name)),
+            },
+        )?;
+        eprintln!("watching: {}", target.name);
+        watched.insert(index as u64, target);
+    }
+    eprintln!("flags: {:?}", flags);
+
+    let timeout = info.timeout.map_or(-1, |timeout| timeout as isize);
+    let start = Instant::now();
+    let mut events = [EpollEvent::empty(); MAX_EVENTS];
+    let mut count = 0;
+    while !watched.is_empty() {
+        let ready = match epoll_wait(epoll.as_raw_fd(), &mut events, timeout) {
+            Ok(ready) => ready,
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to call epoll_wait"),
+        };
+        if ready == 0 {
+            eprintln!("timeout: no events in {}ms", timeout);
+            return Ok(());
+        }
+
+        for event in &events[..ready] {
+            let target = match watched.get(&event.data()) {
+                Some(target) => target,
+                // Removed by an earlier event of the same epoll_wait
+                None => continue,
+            };
+            let elapsed = start.elapsed().as_secs_f64();
+            let mut line = format!(
+                "{:.6}s {}: {}",
+                elapsed,
+                target.name,
+                event_names(event.events())
+            );
+            let mut finished = false;
+            if info.read && event.events().contains(EpollFlags::EPOLLIN) {
+                let (bytes, end) =
+                    drain(target.fd).with_context(|| format!("Failed to read {}", target.name))?;
+                line.push_str(&format!(" (read {} bytes)", bytes));
+                finished = end;
+            }
+            // Without EPOLLIN, nothing is left to read when the other end hung up
+            finished |= info.read
+                && event.events().contains(EpollFlags::EPOLLHUP)
+                && !event.events().contains(EpollFlags::EPOLLIN);
+            println!("{}", line);
+
+            if finished {
+                epoll_ctl(epoll.as_raw_fd(), EpollOp::EpollCtlDel, target.fd, None)
+                    .with_context(|| format!("Failed to stop watching {}", target.name))?;
+                println!("{:.6}s {}: closed", elapsed, target.name);
+                watched.remove(&event.data());
+            }
+
+            count += 1;
+            if info.count == Some(count) {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_watch(cli.watch),
+    }
+}
@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::pidfd::{open_pidfd, pidfd_getfd};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::eventfd::{eventfd, EfdFlags};
+use nix::sys::wait::waitpid;
+use nix::unistd::{fork, ForkResult};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Create an eventfd and signal it from a child process to this process
+    #[clap(display_order = 1, name = "create")]
+    Create(CreateInfo),
+    /// Add a value to the counter of an eventfd held by another process
+    #[clap(display_order = 2, name = "write")]
+    Write {
+        /// Eventfd printed by 'create --hold', as /proc/PID/fd/FD
+        #[clap(value_parser = parse_fd_path, value_name = "PATH")]
+        path: FdPath,
+        /// Value to add to the counter
+        #[clap(value_parser = clap::value_parser!(u64).range(1..u64::MAX), value_name = "N")]
+        value: u64,
+    },
+    /// Read the counter of an eventfd held by another process
+    #[clap(display_order = 3, name = "read")]
+    Read {
+        /// Eventfd printed by 'create --hold', as /proc/PID/fd/FD
+        #[clap(value_parser = parse_fd_path, value_name = "PATH")]
+        path: FdPath,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 4, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CreateInfo {
+    /// Initial value of the counter
+    #[clap(short, long, default_value_t = 0, value_parser)]
+    initval: u32,
+    /// Make each read decrement the counter by 1 instead of resetting it
+    #[clap(short, long)]
+    semaphore: bool,
+    /// Make reads of the counter of 0 fail with EAGAIN instead of blocking
+    #[clap(short, long)]
+    nonblock: bool,
+    /// Number of times the child process adds 1 to the counter
+    #[clap(short, long, default_value_t = 3, value_parser)]
+    writes: u64,
+    /// Keep the eventfd open for 'write' and 'read' instead of running the child process
+    #[clap(long, conflicts_with = "writes")]
+    hold: bool,
+}
+
+/// A file descriptor of a process given as /proc/PID/fd/FD
+#[derive(Debug, Copy, Clone)]
+struct FdPath {
+    pid: i32,
+    fd: RawFd,
+}
+
+/// Parses a path such as `/proc/1234/fd/3`.
+fn parse_fd_path(s: &str) -> Result<FdPath, String> {
+    s.strip_prefix("/proc/")
+        .and_then(|rest| rest.split_once("/fd/"))
+        .and_then(|(pid, fd)| {
+            Some(FdPath {
+                pid: pid.parse().ok()?,
+                fd: fd.parse().ok()?,
+            })
+        })
+        .ok_or_else(|| format!("'{}' is not a path such as /proc/PID/fd/FD", s))
+}
+
+fn read_counter(fd: &OwnedFd) -> nix::Result<u64> {
+    let mut buf = [0u8; 8];
+    nix::unistd::read(fd.as_raw_fd(), &mut buf)?;
+    Ok(u64::from_ne_bytes(buf))
+}
+
+fn write_counter(fd: &OwnedFd, value: u64) -> nix::Result<()> {
+    nix::unistd::write(fd.as_raw_fd(), &value.to_ne_bytes()).map(drop)
+}
+
+/// Duplicates the file descriptor of another process with pidfd_getfd(2).
+///
+/// Opening /proc/PID/fd/FD does not work since an eventfd cannot be reopened.
+fn get_fd(path: FdPath) -> anyhow::Result<OwnedFd> {
+    let pidfd = open_pidfd(path.pid)?;
+    pidfd_getfd(&pidfd, path.pid, path.fd)
+}
+
+fn run_child(fd: &OwnedFd, writes: u64) -> anyhow::Result<()> {
+    for _ in 0..writes {
+        std::thread::sleep(Duration::from_millis(100));
+        // Printed first so that the output of the parent follows it
+        println!("child: write 1");
+        write_counter(fd, 1).context("Failed to write the eventfd")?;
+    }
+
+    Ok(())
+}
+
+fn run_parent(fd: &OwnedFd, info: &CreateInfo) -> anyhow::Result<()> {
+    let total = u64::from(info.initval) + info.writes;
+    let mut received = 0;
+    while received < total {
+        match read_counter(fd) {
+            Ok(value) => {
+                println!("parent: read {}", value);
+                received += value;
+            }
+            Err(Errno::EAGAIN) => {
+                println!("parent: read failed with EAGAIN; waiting with poll");
+                let mut fds = [PollFd::new(fd.as_raw_fd(), PollFlags::POLLIN)];
+                poll(&mut fds, -1).context("Failed to poll the eventfd")?;
+            }
+            Err(errno) => return Err(errno).context("Failed to read the eventfd"),
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_create(info: CreateInfo) -> anyhow::Result<()> {
+    let mut flags = EfdFlags::EFD_CLOEXEC;
+    if info.semaphore {
+        flags |= EfdFlags::EFD_SEMAPHORE;
+    }
+    if info.nonblock {
+        flags |= EfdFlags::EFD_NONBLOCK;
+    }
+    let fd = eventfd(info.initval, flags).context("Failed to create an eventfd")?;
+    // SAFETY: eventfd returns a new file descriptor owned by nobody else.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    if info.hold {
+        println!("/proc/{}/fd/{}", std::process::id(), fd.as_raw_fd());
+        loop {
+            nix::unistd::pause();
+        }
+    }
+
+    // SAFETY: this process is single-threaded, so the child can run any code.
+    match unsafe { fork() }.context("Failed to fork")? {
+        ForkResult::Child => {
+            let code = match run_child(&fd, info.writes) {
+                Ok(()) => 0,
+                Err(err) => {
+                    eprintln!("Error: {:?}", err);
+                    1
+                }
+            };
+            std::process::exit(code);
+        }
+        ForkResult::Parent { child } => {
+            run_parent(&fd, &info)?;
+            waitpid(child, None).context("Failed to wait for the child process")?;
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_write(path: FdPath, value: u64) -> anyhow::Result<()> {
+    let fd = get_fd(path)?;
+    match write_counter(&fd, value) {
+        Ok(()) => Ok(()),
+        Err(Errno::EAGAIN) => Err(anyhow!(
+            "The counter would exceed its maximum of {}",
+            u64::MAX - 1
+        )),
+        Err(Errno::EINVAL) => Err(anyhow!("{} is not an eventfd", path.fd)),
+        Err(errno) => Err(errno).context("Failed to write the eventfd"),
+    }
+}
+
+fn handle_read(path: FdPath) -> anyhow::Result<()> {
+    let fd = get_fd(path)?;
+    match read_counter(&fd) {
+        Ok(value) => {
+            println!("{}", value);
+            Ok(())
+        }
+        Err(Errno::EAGAIN) => Err(anyhow!("The counter is 0")),
+        Err(Errno::EINVAL) => Err(anyhow!("{} is not an eventfd", path.fd)),
+        Err(errno) => Err(errno).context("Failed to read the eventfd"),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Create(info) => handle_create(info),
+        Commands::Write { path, value } => handle_write(path, value),
+        Commands::Read { path } => handle_read(path),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
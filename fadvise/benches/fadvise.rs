@@ -0,0 +1,70 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use fadvise::{handle_advice, AdviseInfo, ColorWhen, FadviseType, GlobalOptions};
+use nix::fcntl::posix_fadvise;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Size of the sparse file, large enough for the advice to cover many pages.
+const FILE_SIZE: u64 = 1 << 30;
+
+fn options(dry_run: bool) -> GlobalOptions {
+    GlobalOptions {
+        quiet: true,
+        dry_run,
+        color: ColorWhen::Never,
+        ..Default::default()
+    }
+}
+
+fn info(filename: &Path) -> AdviseInfo {
+    AdviseInfo {
+        filenames: vec![filename.to_path_buf()],
+        ..Default::default()
+    }
+}
+
+/// Creates a sparse file on tmpfs, so that the benchmark does not depend on a disk.
+fn sparse_file() -> NamedTempFile {
+    let dir = Path::new("/dev/shm");
+    let file = if dir.is_dir() {
+        NamedTempFile::new_in(dir)
+    } else {
+        NamedTempFile::new()
+    }
+    .expect("Failed to create a temporary file");
+    file.as_file()
+        .set_len(FILE_SIZE)
+        .expect("Failed to extend the temporary file");
+    file
+}
+
+fn bench_advice(c: &mut Criterion, advice: FadviseType) {
+    let file = sparse_file();
+    let mut group = c.benchmark_group(format!("{}", advice));
+    group.throughput(Throughput::BytesDecimal(FILE_SIZE));
+
+    // posix_fadvise alone on a file that is already open
+    group.bench_function("kernel", |b| {
+        let fd = file.as_file().as_raw_fd();
+        b.iter(|| posix_fadvise(fd, 0, FILE_SIZE as i64, advice.into()).unwrap())
+    });
+    // Validation, metadata and open of the file without posix_fadvise
+    group.bench_function("validation", |b| {
+        b.iter(|| handle_advice(&options(true), advice, info(file.path())).unwrap())
+    });
+    // The whole handle_advice as the command runs it
+    group.bench_function("handle_advice", |b| {
+        b.iter(|| handle_advice(&options(false), advice, info(file.path())).unwrap())
+    });
+
+    group.finish();
+}
+
+fn benchmarks(c: &mut Criterion) {
+    bench_advice(c, FadviseType::DontNeed);
+    bench_advice(c, FadviseType::WillNeed);
+}
+
+criterion_group!(benches, benchmarks);
+criterion_main!(benches);
@@ -0,0 +1,182 @@
+//! Presets of advice loaded from a TOML file given with `--config`.
+
+use crate::{AdviseInfo, FadviseType};
+use anyhow::{anyhow, Context};
+use clap::ValueEnum;
+use linux_tools_core::args::parse_size;
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// Content of a configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub presets: Vec<Preset>,
+}
+
+/// Advice applied to files by `--preset NAME`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub name: String,
+    pub advice: Advice,
+    pub files: Vec<PathBuf>,
+    #[serde(default)]
+    pub offset: Size,
+    pub len: Option<Size>,
+}
+
+/// Advice given by the name of its subcommand such as `willneed`.
+pub struct Advice(pub FadviseType);
+
+/// Names of the subcommands of advice, which are the values accepted in a preset.
+const ADVICE_NAMES: &[&str] = &[
+    "normal",
+    "sequential",
+    "random",
+    "noreuse",
+    "willneed",
+    "dontneed",
+];
+
+impl<'de> Deserialize<'de> for Advice {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct AdviceVisitor;
+
+        impl<'de> Visitor<'de> for AdviceVisitor {
+            type Value = Advice;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "advice such as \"willneed\"")
+            }
+
+            // The error is raised inside the deserializer, which adds the line of the value
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Advice, E> {
+                FadviseType::from_str(v, true)
+                    .map(Advice)
+                    .map_err(|_| E::unknown_variant(v, ADVICE_NAMES))
+            }
+        }
+
+        deserializer.deserialize_str(AdviceVisitor)
+    }
+}
+
+/// Size given as a number of bytes or a string with a unit such as `"16K"`.
+#[derive(Default)]
+pub struct Size(pub i64);
+
+impl<'de> Deserialize<'de> for Size {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SizeVisitor;
+
+        impl<'de> Visitor<'de> for SizeVisitor {
+            type Value = Size;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a size such as 4096 or \"16K\"")
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<Size, E> {
+                if v < 0 {
+                    return Err(E::custom(format!(
+                        "'{}' is not a size; expected a positive number",
+                        v
+                    )));
+                }
+                Ok(Size(v))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Size, E> {
+                parse_size(v).map(Size).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(SizeVisitor)
+    }
+}
+
+impl Config {
+    /// Reads and validates a configuration file, where an invalid value is reported with its line.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the configuration '{}'", path.display()))?;
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("'{}' is not a valid configuration", path.display()))?;
+
+        for (i, preset) in config.presets.iter().enumerate() {
+            if preset.files.is_empty() {
+                return Err(anyhow!(
+                    "Preset '{}' of '{}' has no files",
+                    preset.name,
+                    path.display()
+                ));
+            }
+            if config.presets[..i].iter().any(|p| p.name == preset.name) {
+                return Err(anyhow!(
+                    "Preset '{}' is defined more than once in '{}'",
+                    preset.name,
+                    path.display()
+                ));
+            }
+        }
+
+        Ok(config)
+    }
+
+    /// Finds a preset by its name.
+    pub fn preset(&self, name: &str) -> anyhow::Result<&Preset> {
+        self.presets
+            .iter()
+            .find(|preset| preset.name == name)
+            .ok_or_else(|| {
+                let names: Vec<&str> = self.presets.iter().map(|p| p.name.as_str()).collect();
+                anyhow!(
+                    "'{}' is not a preset; available presets are {}",
+                    name,
+                    if names.is_empty() {
+                        "none".to_owned()
+                    } else {
+                        names.join(", ")
+                    }
+                )
+            })
+    }
+}
+
+impl Preset {
+    /// Builds the arguments of the subcommand of the advice with the files and the range.
+    pub fn advise_info(&self) -> AdviseInfo {
+        AdviseInfo {
+            filenames: self.files.clone(),
+            offset: self.offset.0,
+            len: self.len.as_ref().map(|len| len.0),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advice_accepts_names_of_subcommands() {
+        let preset: Preset =
+            toml::from_str("name = \"warm\"\nadvice = \"WillNeed\"\nfiles = [\"a\"]").unwrap();
+        assert_eq!(preset.advice.0, FadviseType::WillNeed);
+    }
+
+    #[test]
+    fn invalid_advice_is_reported_with_its_line() {
+        let content = "[[presets]]\nname = \"warm\"\nadvice = \"bogus\"\nfiles = [\"a\"]\n";
+        let err = toml::from_str::<Config>(content).map(|_| ()).unwrap_err();
+        // line_col counts from 0, while the message counts from 1
+        assert_eq!(err.line_col().map(|(line, _)| line), Some(2));
+        let message = err.to_string();
+        assert!(message.contains("unknown variant `bogus`"), "{}", message);
+        assert!(message.contains("at line 3"), "{}", message);
+    }
+}
@@ -0,0 +1,1350 @@
+//! Applies posix_fadvise(2) advice to files, shared by the fadvise command and its tests.
+
+pub mod config;
+
+use anyhow::{anyhow, Context};
+use clap::{Args, ValueEnum};
+use colored::Colorize;
+use config::Config;
+use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use linux_tools_core::args::{parse_duration, parse_size, read_paths, LogLevel, OutputFormat};
+use linux_tools_core::files::{validate_fd, validate_file};
+use linux_tools_core::mem::{page_size, query_residency};
+use linux_tools_core::output::{
+    format_duration, format_error, new_reporter, JsonReporter, Reporter, TextReporter,
+};
+use nix::errno::Errno;
+use nix::fcntl::posix_fadvise;
+use nix::fcntl::PosixFadviseAdvice;
+use nix::sys::statfs::{statfs, FsType};
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use serde_json::Value;
+use std::fs::File;
+use std::io::{ErrorKind, Write};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant, SystemTime};
+use walkdir::WalkDir;
+
+/// Options shared by all subcommands.
+#[derive(Args)]
+pub struct GlobalOptions {
+    /// Suppress informational output
+    #[clap(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// Additionally log the file descriptor and the result of the call at debug level
+    #[clap(short, long, global = true)]
+    pub verbose: bool,
+    /// Format of the result
+    #[clap(short = 'O', long, global = true, arg_enum, default_value_t = OutputFormat::Text)]
+    pub output_format: OutputFormat,
+    /// Validate the arguments without calling posix_fadvise
+    #[clap(short = 'n', long, global = true)]
+    pub dry_run: bool,
+    /// Color the messages on stderr: always, never or auto when stderr is a terminal
+    #[clap(long, global = true, arg_enum, value_name = "WHEN", default_value_t = ColorWhen::Auto)]
+    pub color: ColorWhen,
+    /// Append the messages on stderr to a file instead
+    #[clap(long, global = true, value_parser, value_name = "PATH")]
+    pub log_file: Option<PathBuf>,
+    /// Most detailed level of the log messages [default: RUST_LOG, or warn]
+    #[clap(long, global = true, arg_enum, value_name = "LEVEL")]
+    pub log_level: Option<LogLevel>,
+    /// Retry posix_fadvise this number of times when it is interrupted by a signal
+    #[clap(long, global = true, default_value_t = 3, value_name = "N")]
+    pub retry: u32,
+    /// Delay before the first retry, doubled at each retry (accepts units such as ms and s)
+    #[clap(
+        long,
+        global = true,
+        default_value = "10ms",
+        value_parser = parse_duration,
+        value_name = "DURATION"
+    )]
+    pub retry_delay: u64,
+}
+
+impl Default for GlobalOptions {
+    /// The options when none is given, as clap parses them.
+    fn default() -> Self {
+        GlobalOptions {
+            quiet: false,
+            verbose: false,
+            output_format: OutputFormat::Text,
+            dry_run: false,
+            color: ColorWhen::Auto,
+            log_file: None,
+            log_level: None,
+            retry: 3,
+            retry_delay: 10_000_000,
+        }
+    }
+}
+
+impl GlobalOptions {
+    /// Sends the messages to `--log-file` and enables the log messages up to `--log-level`,
+    /// which is debug with `--verbose` unless it is given.
+    pub fn init_log(&self) -> anyhow::Result<()> {
+        let level = self
+            .log_level
+            .or_else(|| self.verbose.then_some(LogLevel::Debug));
+        linux_tools_core::log::init(self.log_file.as_deref(), level, self.color_override())
+    }
+
+    /// Enables or disables the colors of all messages according to `--color`.
+    pub fn apply_color(&self) {
+        let enabled = self
+            .color_override()
+            .unwrap_or_else(|| atty::is(atty::Stream::Stderr));
+        colored::control::set_override(enabled);
+    }
+
+    /// Returns whether `--color` forces the colors on or off, which is None for auto.
+    fn color_override(&self) -> Option<bool> {
+        match self.color {
+            ColorWhen::Always => Some(true),
+            ColorWhen::Never => Some(false),
+            ColorWhen::Auto => None,
+        }
+    }
+}
+
+/// When to color the messages, as `--color` of git.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum ColorWhen {
+    Always,
+    Never,
+    Auto,
+}
+
+/// Files and the range advice is applied to.
+#[derive(Args)]
+pub struct AdviseInfo {
+    /// Filenames advice is applied
+    #[clap(
+        value_parser,
+        value_name = "FILE",
+        required_unless_present_any = &["files-from", "fd"]
+    )]
+    pub filenames: Vec<PathBuf>,
+    /// Interpret each FILE as a glob pattern such as 'fonts/**/*.ttf'
+    #[clap(long)]
+    pub glob: bool,
+    /// Fail instead of warning when a glob pattern matches no files
+    #[clap(long, requires = "glob")]
+    pub strict_glob: bool,
+    /// Read filenames from a NUL- or newline-delimited list ('-' for stdin)
+    #[clap(long, value_parser, value_name = "LIST")]
+    pub files_from: Option<PathBuf>,
+    /// Read the list of --files-from as NUL-delimited like 'xargs -0'
+    #[clap(short = '0', long, requires = "files-from")]
+    pub null: bool,
+    /// Apply advice to an open file descriptor instead of FILE
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(RawFd).range(0..),
+        conflicts_with_all = &["filenames", "files-from", "recursive"]
+    )]
+    pub fd: Option<RawFd>,
+    /// Apply advice to files open in the process, given by their file descriptors as FILE
+    #[clap(
+        short,
+        long,
+        value_parser,
+        value_name = "PID",
+        conflicts_with_all = &["fd", "glob", "recursive"]
+    )]
+    pub pid: Option<i32>,
+    /// Apply advice to every regular file under FILE when it is a directory
+    #[clap(short = 'R', long)]
+    pub recursive: bool,
+    /// Descend at most this number of directories below FILE
+    #[clap(long, requires = "recursive", value_name = "N")]
+    pub max_depth: Option<usize>,
+    /// Follow symbolic links while traversing directories instead of skipping them
+    #[clap(long, requires = "recursive")]
+    pub follow_symlinks: bool,
+    /// Offset of a range advice is applied (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    pub offset: i64,
+    /// Length of a range advice is applied [default: The size of FILE]
+    #[clap(short, long, value_parser = parse_size)]
+    pub len: Option<i64>,
+    /// Keep processing remaining files when advice fails for a file
+    #[clap(long)]
+    pub continue_on_error: bool,
+    /// Skip files on these filesystem types, such as 'tmpfs,cifs'
+    #[clap(
+        long,
+        value_parser = parse_fs_type,
+        value_delimiter = ',',
+        value_name = "FSTYPE,..."
+    )]
+    pub exclude_fs: Vec<FsType>,
+    /// Skip files last accessed longer ago than a duration such as '1h', '30m' or '1d'
+    #[clap(long, value_parser = parse_duration, value_name = "DURATION")]
+    pub accessed_since: Option<u64>,
+    /// Skip files last modified longer ago than a duration such as '1h', '30m' or '1d'
+    #[clap(long, value_parser = parse_duration, value_name = "DURATION")]
+    pub modified_since: Option<u64>,
+    /// Skip files smaller than a size (accepts suffixes such as K, MiB, GB)
+    #[clap(long, value_parser = parse_size, value_name = "SIZE")]
+    pub min_size: Option<i64>,
+    /// Skip files larger than a size (accepts suffixes such as K, MiB, GB)
+    #[clap(long, value_parser = parse_size, value_name = "SIZE")]
+    pub max_size: Option<i64>,
+    /// Number of files processed at the same time (0 for the number of CPUs)
+    #[clap(short, long, default_value_t = 1, value_name = "N")]
+    pub jobs: usize,
+    /// Show a progress bar of the files on stderr when it is a terminal
+    #[clap(long)]
+    pub progress: bool,
+}
+
+impl Default for AdviseInfo {
+    /// The options when none is given, as clap parses them, with no files.
+    fn default() -> Self {
+        AdviseInfo {
+            filenames: Vec::new(),
+            glob: false,
+            strict_glob: false,
+            files_from: None,
+            null: false,
+            fd: None,
+            pid: None,
+            recursive: false,
+            max_depth: None,
+            follow_symlinks: false,
+            offset: 0,
+            len: None,
+            continue_on_error: false,
+            exclude_fs: Vec::new(),
+            accessed_since: None,
+            modified_since: None,
+            min_size: None,
+            max_size: None,
+            jobs: 1,
+            progress: false,
+        }
+    }
+}
+
+/// Names of filesystem types with their magic numbers of statfs(2) from linux/magic.h.
+const FILESYSTEMS: &[(&str, u32)] = &[
+    ("9p", 0x01021997),
+    ("btrfs", 0x9123683e),
+    ("ceph", 0x00c36400),
+    ("cifs", 0xff534d42),
+    ("exfat", 0x2011bab0),
+    // ext2, ext3 and ext4 share the magic number
+    ("ext4", 0xef53),
+    ("f2fs", 0xf2f52010),
+    ("fuse", 0x65735546),
+    ("hugetlbfs", 0x958458f6),
+    ("iso9660", 0x9660),
+    ("nfs", 0x6969),
+    ("overlay", 0x794c7630),
+    ("proc", 0x9fa0),
+    ("ramfs", 0x858458f6),
+    ("smb2", 0xfe534d42),
+    ("squashfs", 0x73717368),
+    ("sysfs", 0x62656572),
+    ("tmpfs", 0x01021994),
+    ("vfat", 0x4d44),
+    ("xfs", 0x58465342),
+    ("zfs", 0x2fc12fc1),
+];
+
+/// Parses the name of a filesystem type in FILESYSTEMS.
+fn parse_fs_type(s: &str) -> Result<FsType, String> {
+    let name = match s {
+        "ext2" | "ext3" => "ext4",
+        "smb3" => "smb2",
+        "msdos" => "vfat",
+        _ => s,
+    };
+    FILESYSTEMS
+        .iter()
+        .find(|&&(fs_name, _)| fs_name == name)
+        .map(|&(_, magic)| FsType(magic as _))
+        .ok_or_else(|| {
+            let names: Vec<&str> = FILESYSTEMS.iter().map(|&(name, _)| name).collect();
+            format!(
+                "unknown filesystem type '{}'; supported types are {}",
+                s,
+                names.join(", ")
+            )
+        })
+}
+
+/// Advice of posix_fadvise(2).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum FadviseType {
+    Normal,
+    Sequential,
+    Random,
+    #[clap(name = "noreuse")]
+    NoReuse,
+    #[clap(name = "willneed")]
+    WillNeed,
+    #[clap(name = "dontneed")]
+    DontNeed,
+}
+
+impl std::fmt::Display for FadviseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            FadviseType::Normal => write!(f, "POSIX_FADV_NORMAL"),
+            FadviseType::Sequential => write!(f, "POSIX_FADV_SEQUENTIAL"),
+            FadviseType::Random => write!(f, "POSIX_FADV_RANDOM"),
+            FadviseType::NoReuse => write!(f, "POSIX_FADV_NOREUSE"),
+            FadviseType::WillNeed => write!(f, "POSIX_FADV_WILLNEED"),
+            FadviseType::DontNeed => write!(f, "POSIX_FADV_DONTNEED"),
+        }
+    }
+}
+
+/// A file and the advice applied to it in order.
+#[derive(Args)]
+pub struct MultiInfo {
+    /// Filename advice is applied
+    #[clap(value_parser, value_name = "FILE")]
+    pub filename: PathBuf,
+    /// Advice and its range such as 'dontneed:0:0' (a len of 0 is up to the end of FILE)
+    #[clap(required = true, value_parser = parse_step, value_name = "ADVICE:OFFSET:LEN")]
+    pub steps: Vec<AdviceStep>,
+    /// Keep applying remaining advice when advice fails
+    #[clap(long)]
+    pub continue_on_error: bool,
+}
+
+/// Files and the range whose cache state is checked after advice.
+#[derive(Args)]
+pub struct VerifyInfo {
+    /// Advice to apply before checking the cache state
+    #[clap(arg_enum, value_name = "ADVICE")]
+    pub advice: VerifyAdvice,
+    /// Filenames advice is applied and verified
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    pub filenames: Vec<PathBuf>,
+    /// Offset of a range advice is applied (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    pub offset: i64,
+    /// Length of a range advice is applied [default: The size of FILE]
+    #[clap(short, long, value_parser = parse_size)]
+    pub len: Option<i64>,
+    /// Percentage of the pages that must be resident after willneed, or evicted after dontneed
+    #[clap(
+        short,
+        long,
+        default_value_t = 90,
+        value_parser = clap::value_parser!(u8).range(0..=100),
+        value_name = "PERCENT"
+    )]
+    pub threshold: u8,
+    /// Time to wait for the pages read ahead by willneed to become resident (accepts units such
+    /// as ms and s)
+    #[clap(long, default_value = "1s", value_parser = parse_duration, value_name = "DURATION")]
+    pub timeout: u64,
+}
+
+/// A process whose open files advice is applied to, and the filters of their paths.
+#[derive(Args)]
+pub struct ProcInfo {
+    /// Process whose open regular files advice is applied to
+    #[clap(value_parser, value_name = "PID")]
+    pub pid: i32,
+    /// Advice to apply to the open files
+    #[clap(arg_enum, value_name = "ADVICE")]
+    pub advice: FadviseType,
+    /// Only apply advice to the files whose paths end with one of these suffixes, such as '.db'
+    #[clap(long, value_name = "SUFFIX")]
+    pub suffix: Vec<String>,
+    /// Only apply advice to the files whose paths contain one of these strings, such as '/var/cache'
+    #[clap(long, value_name = "STRING")]
+    pub path_contains: Vec<String>,
+    /// Number of files processed at the same time (0 for the number of CPUs)
+    #[clap(short, long, default_value_t = 1, value_name = "N")]
+    pub jobs: usize,
+}
+
+/// Advice whose effect on the page cache is checked by verify.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum VerifyAdvice {
+    #[clap(name = "willneed")]
+    WillNeed,
+    #[clap(name = "dontneed")]
+    DontNeed,
+}
+
+impl From<VerifyAdvice> for FadviseType {
+    fn from(advice: VerifyAdvice) -> Self {
+        match advice {
+            VerifyAdvice::WillNeed => FadviseType::WillNeed,
+            VerifyAdvice::DontNeed => FadviseType::DontNeed,
+        }
+    }
+}
+
+/// Advice and its range given to multi.
+#[derive(Debug, Clone)]
+pub struct AdviceStep {
+    pub advice: FadviseType,
+    pub offset: i64,
+    pub len: i64,
+}
+
+impl std::fmt::Display for AdviceStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // ValueEnum has a name for every variant
+        let name = self.advice.to_possible_value().unwrap();
+        write!(f, "{}:{}:{}", name.get_name(), self.offset, self.len)
+    }
+}
+
+/// Parses a step such as `willneed:4K:1M`, where the advice is the name of its subcommand.
+fn parse_step(s: &str) -> Result<AdviceStep, String> {
+    let mut parts = s.splitn(3, ':');
+    let (advice, offset, len) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(advice), Some(offset), Some(len)) => (advice, offset, len),
+        _ => return Err(format!("'{}' is not in the form of ADVICE:OFFSET:LEN", s)),
+    };
+
+    Ok(AdviceStep {
+        advice: FadviseType::from_str(advice, true).map_err(|_| {
+            format!(
+                "'{}' is not advice; expected normal, sequential, random, noreuse, willneed or dontneed",
+                advice
+            )
+        })?,
+        offset: parse_size(offset)?,
+        len: parse_size(len)?,
+    })
+}
+
+impl From<FadviseType> for PosixFadviseAdvice {
+    fn from(advise: FadviseType) -> Self {
+        match advise {
+            FadviseType::Normal => PosixFadviseAdvice::POSIX_FADV_NORMAL,
+            FadviseType::Sequential => PosixFadviseAdvice::POSIX_FADV_SEQUENTIAL,
+            FadviseType::Random => PosixFadviseAdvice::POSIX_FADV_RANDOM,
+            FadviseType::NoReuse => PosixFadviseAdvice::POSIX_FADV_NOREUSE,
+            FadviseType::WillNeed => PosixFadviseAdvice::POSIX_FADV_WILLNEED,
+            FadviseType::DontNeed => PosixFadviseAdvice::POSIX_FADV_DONTNEED,
+        }
+    }
+}
+
+/// Builds the reporter of a file, whose text output is the informational output to writer.
+fn info_reporter<'a>(options: &GlobalOptions, writer: impl Write + 'a) -> Box<dyn Reporter + 'a> {
+    match options.output_format {
+        OutputFormat::Text if options.quiet => Box::new(TextReporter::new(std::io::sink())),
+        OutputFormat::Text => new_reporter(OutputFormat::Text, writer),
+        OutputFormat::Json => new_reporter(OutputFormat::Json, std::io::stdout()),
+    }
+}
+
+/// Where the results of the files are printed, which is a JSON array on stdout for a batch in
+/// JSON and the informational output on stderr in text.
+struct Output {
+    format: OutputFormat,
+    batch: bool,
+    printed: usize,
+}
+
+impl Output {
+    fn new(options: &GlobalOptions, batch: bool) -> Self {
+        Output {
+            format: options.output_format,
+            batch,
+            printed: 0,
+        }
+    }
+
+    /// Prints the result of a file, which is an object in a line in JSON.
+    fn write(&mut self, result: &[u8]) -> std::io::Result<()> {
+        self.printed += 1;
+        if self.format == OutputFormat::Text {
+            return std::io::stderr().write_all(result);
+        }
+        let mut stdout = std::io::stdout().lock();
+        if !self.batch {
+            return stdout.write_all(result);
+        }
+        let separator: &[u8] = if self.printed == 1 { b"[" } else { b"," };
+        stdout.write_all(separator)?;
+        stdout.write_all(result.strip_suffix(b"\n").unwrap_or(result))
+    }
+
+    /// Closes the JSON array of a batch, which is empty when no file was printed.
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if self.format == OutputFormat::Text || !self.batch {
+            return Ok(());
+        }
+        let mut stdout = std::io::stdout().lock();
+        let end: &[u8] = if self.printed == 0 { b"[]\n" } else { b"]\n" };
+        stdout
+            .write_all(end)
+            .and_then(|_| stdout.flush())
+            .context("Failed to write the result")
+    }
+}
+
+/// Expands glob patterns into the matched paths, sorted within each pattern.
+fn expand_globs(patterns: &[PathBuf], strict: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for pattern in patterns {
+        let pattern = pattern
+            .to_str()
+            .ok_or_else(|| anyhow!("'{}' is not a valid UTF-8 pattern", pattern.display()))?;
+        let mut matched = glob::glob(pattern)
+            .with_context(|| format!("'{}' is not a valid glob pattern", pattern))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("Failed to expand '{}'", pattern))?;
+        if matched.is_empty() {
+            if strict {
+                return Err(anyhow!("'{}' matches no files", pattern));
+            }
+            tracing::warn!("'{}' matches no files", pattern);
+        }
+        matched.sort();
+        paths.append(&mut matched);
+    }
+
+    Ok(paths)
+}
+
+/// Checks whether a file is filtered out by `--exclude-fs`, the times or the sizes.
+fn is_skipped(info: &AdviseInfo, filename: &Path, now: SystemTime) -> bool {
+    (!info.exclude_fs.is_empty() && is_excluded_fs(filename, &info.exclude_fs))
+        || is_out_of_filters(info, filename, now)
+}
+
+/// Checks whether a file was last accessed or modified longer ago than `--accessed-since` or
+/// `--modified-since`, or its size is out of `--min-size` and `--max-size`. A file whose
+/// metadata is unknown is not filtered out, so that it is processed as usual.
+fn is_out_of_filters(info: &AdviseInfo, filename: &Path, now: SystemTime) -> bool {
+    if info.accessed_since.is_none()
+        && info.modified_since.is_none()
+        && info.min_size.is_none()
+        && info.max_size.is_none()
+    {
+        return false;
+    }
+    let metadata = match std::fs::metadata(filename) {
+        Ok(metadata) => metadata,
+        Err(_) => return false,
+    };
+    let older = |time: std::io::Result<SystemTime>, since: Option<u64>| match since {
+        Some(since) => time
+            .ok()
+            .and_then(|time| now.duration_since(time).ok())
+            .map_or(false, |age| age > Duration::from_nanos(since)),
+        None => false,
+    };
+
+    let reason = if older(metadata.accessed(), info.accessed_since) {
+        "accessed before --accessed-since"
+    } else if older(metadata.modified(), info.modified_since) {
+        "modified before --modified-since"
+    } else if info
+        .min_size
+        .map_or(false, |min| metadata.len() < min as u64)
+    {
+        "smaller than --min-size"
+    } else if info
+        .max_size
+        .map_or(false, |max| metadata.len() > max as u64)
+    {
+        "larger than --max-size"
+    } else {
+        return false;
+    };
+    tracing::debug!(filename = ?filename, size = metadata.len(), "Skipping the file {}", reason);
+
+    true
+}
+
+/// Checks whether a file is on a filesystem of `--exclude-fs`. A file that statfs(2) fails for
+/// is not excluded, so that the failure is reported when the advice is applied.
+fn is_excluded_fs(filename: &Path, exclude_fs: &[FsType]) -> bool {
+    match statfs(filename) {
+        Ok(stat) if exclude_fs.contains(&stat.filesystem_type()) => {
+            tracing::debug!(filename = ?filename, "Skipping the file on an excluded filesystem");
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Regular files found under the directories given with `--recursive`.
+struct Traversal {
+    files: Vec<PathBuf>,
+    skipped: usize,
+    errors: Vec<(PathBuf, anyhow::Error)>,
+}
+
+/// Replaces each directory with the regular files under it, in the order of their names.
+fn traverse(paths: Vec<PathBuf>, max_depth: Option<usize>, follow_symlinks: bool) -> Traversal {
+    let mut traversal = Traversal {
+        files: Vec::new(),
+        skipped: 0,
+        errors: Vec::new(),
+    };
+    for path in paths {
+        if !path.is_dir() {
+            // Anything else is validated as a file given directly
+            traversal.files.push(path);
+            continue;
+        }
+        let mut walk = WalkDir::new(&path)
+            .follow_links(follow_symlinks)
+            .sort_by_file_name();
+        if let Some(max_depth) = max_depth {
+            walk = walk.max_depth(max_depth);
+        }
+        for entry in walk {
+            match entry {
+                Ok(entry) if entry.file_type().is_file() => {
+                    traversal.files.push(entry.into_path());
+                }
+                Ok(entry) if entry.file_type().is_dir() => {}
+                // Symbolic links without --follow-symlinks, devices, FIFOs and sockets
+                Ok(_) => traversal.skipped += 1,
+                Err(err) => {
+                    let path = err.path().unwrap_or(&path).to_path_buf();
+                    // An I/O error of walkdir repeats its cause in its message, so only the cause
+                    // is kept, while a loop of symbolic links is described by walkdir itself
+                    let err = match err.io_error() {
+                        Some(_) => anyhow!(err.into_io_error().unwrap()),
+                        None => anyhow!(err),
+                    };
+                    traversal
+                        .errors
+                        .push((path, err.context("Failed to traverse the directory")));
+                }
+            }
+        }
+    }
+
+    traversal
+}
+
+/// Converts the size of a file to a length of posix_fadvise(2), which is signed.
+fn file_len(size: u64) -> anyhow::Result<i64> {
+    i64::try_from(size).map_err(|_| {
+        anyhow!(
+            "The size {} of the file exceeds the maximum file offset {}",
+            size,
+            i64::MAX
+        )
+    })
+}
+
+fn check_range(offset: i64, len: i64) -> anyhow::Result<()> {
+    if offset.checked_add(len).is_none() {
+        return Err(anyhow!(
+            "The range of offset {} and len {} exceeds the maximum file offset",
+            offset,
+            len
+        ));
+    }
+
+    Ok(())
+}
+
+/// Reports the file and the advice, which are colored in text.
+fn report_target(
+    options: &GlobalOptions,
+    reporter: &mut dyn Reporter,
+    filename: &Path,
+    advice: FadviseType,
+) {
+    let filename = filename.display().to_string();
+    let advice = advice.to_string();
+    if options.output_format == OutputFormat::Text {
+        reporter.report_field("filename", &filename.bold());
+        reporter.report_field("advice", &advice.cyan());
+    } else {
+        reporter.report_str("filename", &filename);
+        reporter.report_str("advice", &advice);
+    }
+}
+
+fn report_range(reporter: &mut dyn Reporter, offset: i64, len: i64) {
+    tracing::info!(offset, len, "Applying the advice");
+    reporter.report_field("offset", &offset);
+    reporter.report_field("len", &len);
+}
+
+fn call_fadvise(
+    options: &GlobalOptions,
+    reporter: &mut dyn Reporter,
+    advice: FadviseType,
+    fd: BorrowedFd,
+    offset: i64,
+    len: i64,
+) -> anyhow::Result<()> {
+    let fd = fd.as_raw_fd();
+    tracing::debug!(fd);
+
+    if options.dry_run {
+        // Reported with the rest of the file so that the output of parallel files is not mixed
+        reporter.report_value("dry_run", &"posix_fadvise is not called", Value::Bool(true));
+        return Ok(());
+    }
+
+    // Signals interrupt posix_fadvise(2) in the middle of reading ahead a large range
+    let mut delay = Duration::from_nanos(options.retry_delay);
+    let mut attempts = 0;
+    loop {
+        match posix_fadvise(fd, offset, len, advice.into()) {
+            Err(Errno::EINTR) if attempts < options.retry => {
+                attempts += 1;
+                tracing::debug!(
+                    attempt = attempts,
+                    delay = %format_duration(delay.as_nanos() as u64),
+                    "posix_fadvise was interrupted; retrying"
+                );
+                std::thread::sleep(delay);
+                delay = delay.saturating_mul(2);
+            }
+            result => break result?,
+        }
+    }
+    // posix_fadvise(2) returns 0 on success and the error number otherwise
+    tracing::debug!(result = 0);
+
+    Ok(())
+}
+
+fn advise_file(
+    options: &GlobalOptions,
+    reporter: &mut dyn Reporter,
+    advice: FadviseType,
+    filename: &Path,
+    offset: i64,
+    len: Option<i64>,
+) -> anyhow::Result<i64> {
+    let metadata = validate_file(filename)?;
+
+    // Prepare arguments
+    let len = match len {
+        Some(len) => len,
+        None => file_len(metadata.len())?,
+    };
+    check_range(offset, len)?;
+    report_range(reporter, offset, len);
+    let file = File::open(filename).context("Failed to open the file")?;
+
+    call_fadvise(options, reporter, advice, file.as_fd(), offset, len)?;
+
+    Ok(len)
+}
+
+fn advise_fd(
+    options: &GlobalOptions,
+    reporter: &mut dyn Reporter,
+    advice: FadviseType,
+    fd: RawFd,
+    offset: i64,
+    len: Option<i64>,
+) -> anyhow::Result<i64> {
+    let stat = validate_fd(fd)?;
+    // SAFETY: validate_fd has just confirmed that the file descriptor is open, and
+    // nothing in this process closes it while it is borrowed.
+    let fd = unsafe { BorrowedFd::borrow_raw(fd) };
+
+    // Prepare arguments
+    let len = len.unwrap_or(stat.st_size);
+    check_range(offset, len)?;
+    report_range(reporter, offset, len);
+
+    call_fadvise(options, reporter, advice, fd, offset, len)?;
+
+    Ok(len)
+}
+
+fn fd_path(fd: RawFd) -> PathBuf {
+    PathBuf::from(format!("/proc/self/fd/{}", fd))
+}
+
+/// Converts a file descriptor given as FILE with `--pid` to its path in /proc.
+fn proc_fd_path(pid: i32, fd: &Path) -> anyhow::Result<PathBuf> {
+    let fd: RawFd = fd
+        .to_str()
+        .and_then(|fd| fd.parse().ok())
+        .filter(|&fd| fd >= 0)
+        .ok_or_else(|| anyhow!("'{}' is not a file descriptor", fd.display()))?;
+
+    Ok(PathBuf::from(format!("/proc/{}/fd/{}", pid, fd)))
+}
+
+/// Checks that a path such as `/proc/PID/fd/N` refers to a file rather than a socket or a pipe.
+fn check_proc_fd(path: &Path) -> anyhow::Result<()> {
+    let target = std::fs::read_link(path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!(
+            "'{}' does not exist; the process has exited or closed the file descriptor",
+            path.display()
+        ),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to read '{}'; the file descriptors of a process of another user require CAP_SYS_PTRACE",
+            path.display()
+        ),
+        _ => anyhow!(err).context(format!("Failed to read '{}'", path.display())),
+    })?;
+    // Anything but a file on a filesystem is a name such as socket:[1234] or anon_inode:[eventfd]
+    if !target.is_absolute() {
+        return Err(anyhow!(
+            "'{}' is {}, not a file",
+            path.display(),
+            target.display()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Lists the file descriptors of the regular files open in a process whose paths pass the
+/// filters of info. File descriptors that cannot be read are skipped with a warning.
+fn open_files(info: &ProcInfo) -> anyhow::Result<Vec<PathBuf>> {
+    let dir = PathBuf::from(format!("/proc/{}/fd", info.pid));
+    let entries = std::fs::read_dir(&dir).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} does not exist", info.pid),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to read '{}'; the file descriptors of a process of another user require CAP_SYS_PTRACE",
+            dir.display()
+        ),
+        _ => anyhow!(err).context(format!("Failed to read '{}'", dir.display())),
+    })?;
+
+    let mut fds: Vec<RawFd> = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read '{}'", dir.display()))?;
+        // The directory only has the numbers of file descriptors
+        if let Some(fd) = entry.file_name().to_str().and_then(|fd| fd.parse().ok()) {
+            fds.push(fd);
+        }
+    }
+    fds.sort_unstable();
+
+    let mut files = Vec::new();
+    for fd in fds {
+        let path = dir.join(fd.to_string());
+        // The file descriptor may be closed or unreadable by the time it is looked at
+        let target = match std::fs::read_link(&path) {
+            Ok(target) => target,
+            Err(err) => {
+                tracing::warn!("Skipping '{}': {}", path.display(), err);
+                continue;
+            }
+        };
+        let is_file = match std::fs::metadata(&path) {
+            Ok(metadata) => metadata.is_file(),
+            Err(err) => {
+                tracing::warn!("Skipping '{}': {}", path.display(), err);
+                continue;
+            }
+        };
+        if !is_file {
+            continue;
+        }
+
+        let target = target.to_string_lossy();
+        let matches_suffix =
+            info.suffix.is_empty() || info.suffix.iter().any(|suffix| target.ends_with(suffix));
+        let matches_path = info.path_contains.is_empty()
+            || info
+                .path_contains
+                .iter()
+                .any(|s| target.contains(s.as_str()));
+        if matches_suffix && matches_path {
+            tracing::debug!(fd, target = %target, "Found an open file");
+            files.push(PathBuf::from(fd.to_string()));
+        } else {
+            tracing::debug!(fd, target = %target, "Skipping a file out of the filters");
+        }
+    }
+
+    Ok(files)
+}
+
+/// Builds the progress bar of the files, which is hidden unless enabled and stderr is a terminal.
+fn new_progress_bar(enabled: bool, len: usize) -> ProgressBar {
+    if !enabled || !atty::is(atty::Stream::Stderr) {
+        return ProgressBar::hidden();
+    }
+
+    // The template is fixed, so it is always valid
+    let style = ProgressStyle::with_template(
+        "{elapsed_precise} [{bar:40}] {pos}/{len} files, {remaining} remaining ({per_sec})",
+    )
+    .unwrap()
+    .with_key(
+        "remaining",
+        |state: &ProgressState, w: &mut dyn std::fmt::Write| {
+            let remaining = state.len().unwrap_or(0).saturating_sub(state.pos());
+            let _ = write!(w, "{}", remaining);
+        },
+    )
+    .progress_chars("=> ");
+
+    ProgressBar::new(len as u64).with_style(style)
+}
+
+/// Counts the resident pages of the range with mincore and returns them with the total pages.
+fn count_resident(file: &File, offset: i64, len: i64) -> anyhow::Result<(usize, usize)> {
+    if len == 0 {
+        return Ok((0, 0));
+    }
+    let page_size = page_size()?;
+    // mmap requires the offset to be page aligned
+    let start = offset / page_size * page_size;
+    let residency = query_residency(file, start, offset + len, page_size)?;
+
+    let resident = residency.iter().filter(|&&resident| resident).count();
+    Ok((resident, residency.len()))
+}
+
+/// Applies the advice of verify to a file and checks the cache state, returning whether it passed.
+fn verify_file(
+    options: &GlobalOptions,
+    reporter: &mut dyn Reporter,
+    info: &VerifyInfo,
+    filename: &Path,
+) -> anyhow::Result<bool> {
+    let len = advise_file(
+        options,
+        reporter,
+        info.advice.into(),
+        filename,
+        info.offset,
+        info.len,
+    )?;
+    let file = File::open(filename).context("Failed to open the file")?;
+
+    // willneed only starts reading the pages, so they are counted again until the timeout
+    let deadline = Instant::now() + Duration::from_nanos(info.timeout);
+    let threshold = info.threshold as f64;
+    let (resident, pages, percent, passed) = loop {
+        let (resident, pages) = count_resident(&file, info.offset, len)?;
+        // An empty range is in any cache state
+        let percent = if pages == 0 {
+            100.0
+        } else {
+            resident as f64 * 100.0 / pages as f64
+        };
+        let passed = match info.advice {
+            VerifyAdvice::WillNeed => pages == 0 || percent >= threshold,
+            VerifyAdvice::DontNeed => pages == 0 || 100.0 - percent >= threshold,
+        };
+        if passed || info.advice == VerifyAdvice::DontNeed || Instant::now() >= deadline {
+            break (resident, pages, percent, passed);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    reporter.report_field("resident_pages", &resident);
+    reporter.report_field("total_pages", &pages);
+    reporter.report_field("resident_percent", &format!("{:.1}", percent));
+    reporter.report_field("passed", &passed);
+
+    Ok(passed)
+}
+
+/// Applies advice to a file and reports the result, where the outer error is a failure to print it.
+fn advise_path(
+    options: &GlobalOptions,
+    advice: FadviseType,
+    info: &AdviseInfo,
+    filename: &Path,
+    output: &Mutex<Output>,
+    progress: &ProgressBar,
+) -> anyhow::Result<anyhow::Result<i64>> {
+    // The result is buffered so that the lines of files processed at the same time do not mix
+    let mut text = Vec::new();
+    let mut reporter = match options.output_format {
+        OutputFormat::Text => info_reporter(options, &mut text),
+        OutputFormat::Json => new_reporter(OutputFormat::Json, &mut text),
+    };
+    let _span = tracing::info_span!("advise", filename = ?filename, advice = %advice).entered();
+    report_target(options, reporter.as_mut(), filename, advice);
+    let result = match info.fd {
+        None if info.pid.is_some() => check_proc_fd(filename).and_then(|_| {
+            advise_file(
+                options,
+                reporter.as_mut(),
+                advice,
+                filename,
+                info.offset,
+                info.len,
+            )
+        }),
+        Some(fd) => advise_fd(
+            options,
+            reporter.as_mut(),
+            advice,
+            fd,
+            info.offset,
+            info.len,
+        ),
+        None => advise_file(
+            options,
+            reporter.as_mut(),
+            advice,
+            filename,
+            info.offset,
+            info.len,
+        ),
+    };
+    // In text, success is silent and failures are reported after all files are processed
+    if options.output_format == OutputFormat::Json {
+        reporter.report_field("success", &result.is_ok());
+        match &result {
+            Ok(_) => reporter.report_value("error_message", &"none", Value::Null),
+            Err(err) => reporter.report_str("error_message", &format_error(err)),
+        }
+    }
+
+    reporter.finish()?;
+    drop(reporter);
+
+    // A panic of another worker does not keep the rest from printing their results
+    let mut output = output.lock().unwrap_or_else(PoisonError::into_inner);
+    // The bar is cleared while the result is printed and drawn again after it, and a failure to
+    // print stops try_for_each of advise_files
+    progress
+        .suspend(|| output.write(&text))
+        .context("Failed to write the result")?;
+    progress.inc(1);
+
+    Ok(result)
+}
+
+/// Creates the thread pool of `--jobs`, or none for a single job, which runs on the current
+/// thread.
+fn new_thread_pool(jobs: usize) -> anyhow::Result<Option<ThreadPool>> {
+    if jobs == 1 {
+        return Ok(None);
+    }
+
+    // rayon uses the number of CPUs for 0 threads
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(jobs)
+        .build()
+        .context("Failed to create the thread pool")?;
+
+    Ok(Some(pool))
+}
+
+/// Applies advice to the files on the thread pool, or one by one without it, and returns the
+/// failures in the order of the files with the total length advised. Without
+/// `--continue-on-error`, the first failure is returned as an error after the results printed
+/// so far.
+fn advise_files(
+    options: &GlobalOptions,
+    advice: FadviseType,
+    info: &AdviseInfo,
+    filenames: &[PathBuf],
+    batch: bool,
+    pool: Option<&ThreadPool>,
+) -> anyhow::Result<(Vec<(PathBuf, anyhow::Error)>, u64)> {
+    let output = Mutex::new(Output::new(options, batch));
+    let progress = new_progress_bar(info.progress && !options.quiet, filenames.len());
+    let stop = AtomicBool::new(false);
+    let failures = Mutex::new(Vec::new());
+    let bytes = AtomicU64::new(0);
+    let advise_one = |(index, filename): (usize, &PathBuf)| -> anyhow::Result<()> {
+        // Files already being processed when another fails still finish
+        if stop.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        match advise_path(options, advice, info, filename, &output, &progress)? {
+            Ok(len) => {
+                bytes.fetch_add(len as u64, Ordering::Relaxed);
+            }
+            Err(err) => {
+                if !info.continue_on_error {
+                    stop.store(true, Ordering::Relaxed);
+                }
+                failures
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .push((index, filename.clone(), err));
+            }
+        }
+        Ok(())
+    };
+    match pool {
+        Some(pool) => {
+            pool.install(|| filenames.par_iter().enumerate().try_for_each(advise_one))?;
+        }
+        None => filenames.iter().enumerate().try_for_each(advise_one)?,
+    }
+
+    progress.finish_and_clear();
+    output
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner)
+        .finish()?;
+
+    let mut failures = failures
+        .into_inner()
+        .unwrap_or_else(PoisonError::into_inner);
+    failures.sort_by_key(|(index, _, _)| *index);
+    let mut failures = failures
+        .into_iter()
+        .map(|(_, filename, err)| (filename, err));
+    if !info.continue_on_error {
+        return match failures.next() {
+            Some((_, err)) => Err(err),
+            None => Ok((Vec::new(), bytes.into_inner())),
+        };
+    }
+
+    Ok((failures.collect(), bytes.into_inner()))
+}
+
+/// Applies the advice of info to its files and checks that the page cache is in the expected
+/// state with mincore, returning 0 when every file passes and 1 otherwise.
+pub fn handle_verify(options: &GlobalOptions, info: VerifyInfo) -> anyhow::Result<ExitCode> {
+    let advice = info.advice.into();
+    let mut passed = true;
+    for (i, filename) in info.filenames.iter().enumerate() {
+        if i > 0 && options.output_format == OutputFormat::Text && !options.quiet {
+            println!();
+        }
+        let mut reporter = info_reporter(options, std::io::stdout());
+        let _span = tracing::info_span!("verify", filename = ?filename, advice = %advice).entered();
+        report_target(options, reporter.as_mut(), filename, advice);
+        match verify_file(options, reporter.as_mut(), &info, filename) {
+            Ok(result) => passed &= result,
+            Err(err) => {
+                reporter.report_field("passed", &false);
+                reporter.report_error(&format_error(&err));
+                passed = false;
+            }
+        }
+        reporter.finish()?;
+    }
+
+    Ok(if passed {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Applies advice to the regular files open in the process of info, continuing after the files
+/// that fail such as the ones the process is not permitted to open.
+pub fn handle_proc(options: &GlobalOptions, info: ProcInfo) -> anyhow::Result<ExitCode> {
+    let fds = open_files(&info)?;
+    if fds.is_empty() {
+        tracing::warn!("Process {} has no open files to apply advice to", info.pid);
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let advise_info = AdviseInfo {
+        filenames: fds,
+        pid: Some(info.pid),
+        continue_on_error: true,
+        jobs: info.jobs,
+        ..Default::default()
+    };
+
+    handle_advice(options, info.advice, advise_info)
+}
+
+/// Applies the preset of a name in a configuration file and returns the exit code.
+pub fn handle_preset(
+    options: &GlobalOptions,
+    config: &Path,
+    name: &str,
+) -> anyhow::Result<ExitCode> {
+    let config = Config::load(config)?;
+    let preset = config.preset(name)?;
+
+    handle_advice(options, preset.advice.0, preset.advise_info())
+}
+
+/// Applies the advice of info to its file in order, opening it once, and returns the exit code.
+pub fn handle_multi(options: &GlobalOptions, info: MultiInfo) -> anyhow::Result<ExitCode> {
+    validate_file(&info.filename)?;
+    let file = File::open(&info.filename).context("Failed to open the file")?;
+
+    let mut errors: Vec<(&AdviceStep, anyhow::Error)> = Vec::new();
+    for step in &info.steps {
+        let mut reporter = info_reporter(options, std::io::stderr());
+        let _span = tracing::info_span!("advise", filename = ?info.filename, advice = %step.advice)
+            .entered();
+        report_target(options, reporter.as_mut(), &info.filename, step.advice);
+        let result = check_range(step.offset, step.len).and_then(|_| {
+            report_range(reporter.as_mut(), step.offset, step.len);
+            call_fadvise(
+                options,
+                reporter.as_mut(),
+                step.advice,
+                file.as_fd(),
+                step.offset,
+                step.len,
+            )
+        });
+        // In text, success is silent and failures are reported after all advice is applied
+        if options.output_format == OutputFormat::Json {
+            reporter.report_field("success", &result.is_ok());
+            if let Err(err) = &result {
+                reporter.report_error(&format_error(err));
+            }
+        }
+        reporter.finish()?;
+        match result {
+            Ok(_) => {}
+            Err(err) if info.continue_on_error => errors.push((step, err)),
+            Err(err) => return Err(err.context(format!("Failed to apply {}", step))),
+        }
+    }
+
+    if errors.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    let summary = format!(
+        "Failed to apply {} of {} advice:",
+        errors.len(),
+        info.steps.len()
+    );
+    eprintln!("{}", summary.red());
+    for (step, err) in &errors {
+        eprintln!("  {}: {}", step.to_string().bold(), format_error(err).red());
+    }
+
+    // The exit code reflects the number of failed advice as in handle_advice
+    let code = errors.len().min(125) as u8;
+    Ok(ExitCode::from(code))
+}
+
+/// Applies advice to the files of info and reports the results, returning the exit code.
+pub fn handle_advice(
+    options: &GlobalOptions,
+    advice: FadviseType,
+    info: AdviseInfo,
+) -> anyhow::Result<ExitCode> {
+    let started = Instant::now();
+    let mut filenames = if info.glob {
+        expand_globs(&info.filenames, info.strict_glob)?
+    } else {
+        info.filenames.clone()
+    };
+    if let Some(list) = &info.files_from {
+        filenames.extend(read_paths(list, info.null)?);
+    }
+    if let Some(fd) = info.fd {
+        filenames.push(fd_path(fd));
+    }
+    let batch = filenames.len() > 1 || info.files_from.is_some() || info.glob || info.recursive;
+    if let Some(pid) = info.pid {
+        filenames = filenames
+            .iter()
+            .map(|fd| proc_fd_path(pid, fd))
+            .collect::<anyhow::Result<_>>()?;
+    }
+
+    let mut errors: Vec<(PathBuf, anyhow::Error)> = Vec::new();
+    let mut skipped = 0;
+    let mut traversal_failures = 0;
+    if info.recursive {
+        let traversal = traverse(filenames, info.max_depth, info.follow_symlinks);
+        filenames = traversal.files;
+        skipped = traversal.skipped;
+        errors = traversal.errors;
+        traversal_failures = errors.len();
+        if !info.continue_on_error && !errors.is_empty() {
+            return Err(errors.remove(0).1);
+        }
+    }
+
+    let count = filenames.len();
+    let now = SystemTime::now();
+    filenames.retain(|filename| !is_skipped(&info, filename, now));
+    skipped += count - filenames.len();
+
+    let pool = new_thread_pool(info.jobs)?;
+    let (failures, bytes) = advise_files(options, advice, &info, &filenames, batch, pool.as_ref())?;
+    errors.extend(failures);
+
+    // Directories that failed to be traversed count as failed files
+    let total = filenames.len() + traversal_failures;
+    if batch {
+        let elapsed = started.elapsed().as_secs_f64();
+        let mut reporter = match options.output_format {
+            OutputFormat::Text => info_reporter(options, std::io::stderr()),
+            // stdout only has the array of the results
+            OutputFormat::Json => Box::new(JsonReporter::under(std::io::stderr(), "summary")),
+        };
+        reporter.report_field("processed", &(total - errors.len()));
+        reporter.report_field("skipped", &skipped);
+        reporter.report_field("failed", &errors.len());
+        reporter.report_field("bytes", &bytes);
+        reporter.report_field("elapsed_seconds", &format!("{:.3}", elapsed));
+        let throughput = if elapsed > 0.0 {
+            bytes as f64 / elapsed
+        } else {
+            0.0
+        };
+        reporter.report_field("bytes_per_second", &format!("{:.0}", throughput));
+        reporter.finish()?;
+    }
+    if errors.is_empty() {
+        return Ok(ExitCode::SUCCESS);
+    }
+    let summary = format!(
+        "Failed to apply advice to {} of {} files:",
+        errors.len(),
+        total
+    );
+    eprintln!("{}", summary.red());
+    for (filename, err) in &errors {
+        let filename = filename.display().to_string();
+        eprintln!("  {}: {}", filename.bold(), format_error(err).red());
+    }
+
+    // The exit code reflects the number of failed files
+    let code = errors.len().min(125) as u8;
+    Ok(ExitCode::from(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_len_rejects_size_beyond_i64() {
+        assert_eq!(file_len(4096).unwrap(), 4096);
+        assert_eq!(file_len(i64::MAX as u64).unwrap(), i64::MAX);
+        let err = file_len(u64::MAX).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "The size {} of the file exceeds the maximum file offset {}",
+                u64::MAX,
+                i64::MAX
+            )
+        );
+    }
+
+    #[test]
+    fn check_range_rejects_overflow() {
+        assert!(check_range(0, i64::MAX).is_ok());
+        assert!(check_range(i64::MAX, 0).is_ok());
+        let err = check_range(1, i64::MAX).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "The range of offset 1 and len {} exceeds the maximum file offset",
+                i64::MAX
+            )
+        );
+    }
+}
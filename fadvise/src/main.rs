@@ -1,18 +1,26 @@
-use anyhow::{anyhow, Context};
-use clap::CommandFactory;
-use clap::{Args, Parser, Subcommand, ValueEnum};
-use clap_complete::{generate, Generator, Shell};
-use nix::fcntl::posix_fadvise;
-use nix::fcntl::PosixFadviseAdvice;
-use std::fs::File;
-use std::os::unix::io::AsRawFd;
+use clap::{CommandFactory, ErrorKind, Parser, Subcommand};
+use clap_complete::Shell;
+use fadvise::{
+    handle_advice, handle_multi, handle_preset, handle_proc, handle_verify, AdviseInfo,
+    FadviseType, GlobalOptions, MultiInfo, ProcInfo, VerifyInfo,
+};
+use linux_tools_core::print_completer;
 use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
+    #[clap(flatten)]
+    options: GlobalOptions,
+    /// Read presets of advice from a TOML file
+    #[clap(long, value_parser, value_name = "PATH", requires = "preset")]
+    config: Option<PathBuf>,
+    /// Apply the preset of this name in --config instead of a subcommand
+    #[clap(long, value_name = "NAME", requires = "config")]
+    preset: Option<String>,
     #[clap(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
@@ -35,8 +43,17 @@ enum Commands {
     /// Apply advice of POSIX_FADV_DONTNEED
     #[clap(display_order = 6, name = "dontneed")]
     DontNeed(AdviseInfo),
+    /// Apply a sequence of advice to a file such as 'dontneed:0:0 willneed:0:1M'
+    #[clap(display_order = 7, name = "multi")]
+    Multi(MultiInfo),
+    /// Apply willneed or dontneed and check the page cache state of the range with mincore
+    #[clap(display_order = 8, name = "verify")]
+    Verify(VerifyInfo),
+    /// Apply advice to every regular file open in a running process
+    #[clap(display_order = 9, name = "advice-from-proc")]
+    AdviceFromProc(ProcInfo),
     /// Generate code for completion
-    #[clap(display_order = 7, name = "completion")]
+    #[clap(display_order = 10, name = "completion")]
     Completion {
         /// Target shell to create completion code
         #[clap(long, short, arg_enum)]
@@ -44,106 +61,43 @@ enum Commands {
     },
 }
 
-#[derive(Args)]
-struct AdviseInfo {
-    /// Filename advice is applied
-    #[clap(value_parser, value_name = "FILE")]
-    filename: PathBuf,
-    /// Offset of a range advice is applied
-    #[clap(default_value_t = 0, value_parser = clap::value_parser!(i64).range(0..))]
-    offset: i64,
-    /// Length of a range advice is applied [default: The size of FILE]
-    #[clap(value_parser = clap::value_parser ! (i64).range(0..))]
-    len: Option<i64>,
-}
-
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
-enum FadviseType {
-    Normal,
-    Sequential,
-    Random,
-    NoReuse,
-    WillNeed,
-    DontNeed,
-}
-
-impl std::fmt::Display for FadviseType {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        match *self {
-            FadviseType::Normal => write!(f, "POSIX_FADV_NORMAL"),
-            FadviseType::Sequential => write!(f, "POSIX_FADV_SEQUENTIAL"),
-            FadviseType::Random => write!(f, "POSIX_FADV_RANDOM"),
-            FadviseType::NoReuse => write!(f, "POSIX_FADV_NOREUSE"),
-            FadviseType::WillNeed => write!(f, "POSIX_FADV_WILLNEED"),
-            FadviseType::DontNeed => write!(f, "POSIX_FADV_DONTNEED"),
-        }
-    }
-}
-
-impl From<FadviseType> for PosixFadviseAdvice {
-    fn from(advise: FadviseType) -> Self {
-        match advise {
-            FadviseType::Normal => PosixFadviseAdvice::POSIX_FADV_NORMAL,
-            FadviseType::Sequential => PosixFadviseAdvice::POSIX_FADV_SEQUENTIAL,
-            FadviseType::Random => PosixFadviseAdvice::POSIX_FADV_RANDOM,
-            FadviseType::NoReuse => PosixFadviseAdvice::POSIX_FADV_NOREUSE,
-            FadviseType::WillNeed => PosixFadviseAdvice::POSIX_FADV_WILLNEED,
-            FadviseType::DontNeed => PosixFadviseAdvice::POSIX_FADV_DONTNEED,
-        }
-    }
-}
-
-fn print_completer<G: Generator>(generator: G) -> anyhow::Result<()> {
-    let mut app = Cli::into_app();
-    let name = app.get_name().to_owned();
-
-    generate(generator, &mut app, name, &mut std::io::stdout());
-
-    Ok(())
-}
-
-fn handle_advice(advice: FadviseType, info: AdviseInfo) -> anyhow::Result<()> {
-    let filename = info.filename;
-    let offset = info.offset;
-
-    // Check file existence and metadata
-    let exists = filename
-        .try_exists()
-        .context("Failed to check existence of the file")?;
-    if !exists {
-        return Err(anyhow!("'{}' does not exist", filename.display()));
-    }
-    let metadata = filename
-        .metadata()
-        .context("Failed to retrieve metadata of the file")?;
-    if !metadata.is_file() {
-        return Err(anyhow!("'{}' is not a file", filename.display()));
-    }
-
-    // Prepare arguments
-    let len = info.len.unwrap_or(metadata.len() as i64);
-    eprintln!("filename: {}", filename.display());
-    eprintln!("advice: {}", advice);
-    eprintln!("offset: {}", offset);
-    eprintln!("len: {}", len);
-    let file = File::open(filename).context("Failed to open the file")?;
-    let fd = file.as_raw_fd();
-
-    posix_fadvise(fd, offset, len, advice.into())?;
-
-    Ok(())
-}
-
-fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<ExitCode> {
     let cli = Cli::parse();
-
-    match cli.command {
-        Commands::Normal(info) => handle_advice(FadviseType::Normal, info),
-        Commands::Sequential(info) => handle_advice(FadviseType::Sequential, info),
-        Commands::Random(info) => handle_advice(FadviseType::Random, info),
-        Commands::NoReuse(info) => handle_advice(FadviseType::NoReuse, info),
-        Commands::WillNeed(info) => handle_advice(FadviseType::WillNeed, info),
-        Commands::DontNeed(info) => handle_advice(FadviseType::DontNeed, info),
-        Commands::Completion { shell } => print_completer(shell),
+    let options = &cli.options;
+    // The log file replaces stderr, so colors are decided after it
+    options.init_log()?;
+    options.apply_color();
+
+    let command = match (cli.command, &cli.config, &cli.preset) {
+        (Some(command), None, None) => command,
+        (None, Some(config), Some(preset)) => return handle_preset(options, config, preset),
+        (Some(_), _, _) => Cli::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--preset cannot be used with a subcommand",
+            )
+            .exit(),
+        // clap requires --config and --preset together
+        (None, _, _) => Cli::command()
+            .error(
+                ErrorKind::MissingSubcommand,
+                "A subcommand or --preset is required",
+            )
+            .exit(),
+    };
+
+    match command {
+        Commands::Normal(info) => handle_advice(options, FadviseType::Normal, info),
+        Commands::Sequential(info) => handle_advice(options, FadviseType::Sequential, info),
+        Commands::Random(info) => handle_advice(options, FadviseType::Random, info),
+        Commands::NoReuse(info) => handle_advice(options, FadviseType::NoReuse, info),
+        Commands::WillNeed(info) => handle_advice(options, FadviseType::WillNeed, info),
+        Commands::DontNeed(info) => handle_advice(options, FadviseType::DontNeed, info),
+        Commands::Multi(info) => handle_multi(options, info),
+        Commands::Verify(info) => handle_verify(options, info),
+        Commands::AdviceFromProc(info) => handle_proc(options, info),
+        Commands::Completion { shell } => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
     }
 }
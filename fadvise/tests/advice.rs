@@ -0,0 +1,72 @@
+#![cfg(target_os = "linux")]
+
+use clap::ValueEnum;
+use fadvise::{handle_advice, AdviseInfo, ColorWhen, FadviseType, GlobalOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use tempfile::NamedTempFile;
+
+const CONTENT: &[u8] = b"The quick brown fox jumps over the lazy dog.\n";
+
+fn options() -> GlobalOptions {
+    GlobalOptions {
+        quiet: true,
+        color: ColorWhen::Never,
+        ..Default::default()
+    }
+}
+
+fn info(filename: &Path, offset: i64) -> AdviseInfo {
+    AdviseInfo {
+        filenames: vec![filename.to_path_buf()],
+        offset,
+        ..Default::default()
+    }
+}
+
+/// Creates a file with CONTENT on tmpfs, so that advice does not touch a disk.
+fn temp_file() -> NamedTempFile {
+    let dir = Path::new("/dev/shm");
+    let mut file = if dir.is_dir() {
+        NamedTempFile::new_in(dir)
+    } else {
+        NamedTempFile::new()
+    }
+    .unwrap();
+    file.write_all(CONTENT).unwrap();
+    file.flush().unwrap();
+    file
+}
+
+#[test]
+fn every_advice_succeeds_and_keeps_content() {
+    let file = temp_file();
+    for &advice in FadviseType::value_variants() {
+        let code = handle_advice(&options(), advice, info(file.path(), 0)).unwrap();
+        assert_eq!(code, ExitCode::SUCCESS, "{:?}", advice);
+        assert_eq!(std::fs::read(file.path()).unwrap(), CONTENT, "{:?}", advice);
+    }
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    let file = temp_file();
+    let missing = PathBuf::from(format!("{}.missing", file.path().display()));
+    let err = handle_advice(&options(), FadviseType::DontNeed, info(&missing, 0)).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!("'{}' does not exist", missing.display())
+    );
+}
+
+#[test]
+fn offset_beyond_end_of_file_succeeds() {
+    // posix_fadvise(2) accepts a range past the end of the file and returns 0, as the advice
+    // only concerns pages that may be cached for the range
+    let file = temp_file();
+    let offset = CONTENT.len() as i64 * 2;
+    let code = handle_advice(&options(), FadviseType::WillNeed, info(file.path(), offset)).unwrap();
+    assert_eq!(code, ExitCode::SUCCESS);
+    assert_eq!(std::fs::read(file.path()).unwrap(), CONTENT);
+}
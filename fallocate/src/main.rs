@@ -0,0 +1,500 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{fallocate, FallocateFlags};
+use nix::sys::statfs::fstatfs;
+use nix::unistd::{lseek, Whence};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    allocate: AllocateInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Punch a hole in a file and print the data and hole regions of the file afterwards
+    #[clap(display_order = 1, name = "punch-hole")]
+    PunchHole {
+        #[clap(flatten)]
+        range: RangeInfo,
+    },
+    /// Insert a hole into a file, shifting the data after OFFSET by LEN
+    #[clap(display_order = 2, name = "insert-range")]
+    InsertRange {
+        #[clap(flatten)]
+        range: RangeInfo,
+    },
+    /// Zero a range of a file without writing zeros
+    #[clap(display_order = 3, name = "zero-range")]
+    ZeroRange {
+        #[clap(flatten)]
+        range: RangeInfo,
+        /// Do not extend the file when the range is beyond its end
+        #[clap(short, long)]
+        keep_size: bool,
+        /// Read the range back and check that it is zeroed
+        #[clap(long)]
+        verify: bool,
+    },
+    /// Remove a range from a file, shifting the data after the range down to OFFSET
+    #[clap(display_order = 4, name = "collapse-range")]
+    CollapseRange {
+        #[clap(flatten)]
+        range: RangeInfo,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 5, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct AllocateInfo {
+    /// Filename space is allocated for
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Offset of a range space is allocated for (accepts suffixes such as K, MiB, GB)
+    #[clap(required = true, value_parser = parse_size)]
+    offset: Option<i64>,
+    /// Length of a range space is allocated for (accepts suffixes such as K, MiB, GB)
+    #[clap(required = true, value_parser = parse_size)]
+    len: Option<i64>,
+    /// Mode of the allocation (can be specified multiple times)
+    #[clap(long, short, arg_enum, use_value_delimiter = true)]
+    mode: Vec<FallocateMode>,
+}
+
+#[derive(Args)]
+struct RangeInfo {
+    /// File to modify
+    #[clap(value_parser, value_name = "FILE")]
+    filename: PathBuf,
+    /// Offset of the range (accepts suffixes such as K, MiB, GB)
+    #[clap(value_parser = parse_size)]
+    offset: i64,
+    /// Length of the range (accepts suffixes such as K, MiB, GB)
+    #[clap(value_parser = parse_size)]
+    len: i64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum FallocateMode {
+    KeepSize,
+    PunchHole,
+    CollapseRange,
+    ZeroRange,
+    InsertRange,
+}
+
+impl std::fmt::Display for FallocateMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            FallocateMode::KeepSize => write!(f, "FALLOC_FL_KEEP_SIZE"),
+            FallocateMode::PunchHole => write!(f, "FALLOC_FL_PUNCH_HOLE"),
+            FallocateMode::CollapseRange => write!(f, "FALLOC_FL_COLLAPSE_RANGE"),
+            FallocateMode::ZeroRange => write!(f, "FALLOC_FL_ZERO_RANGE"),
+            FallocateMode::InsertRange => write!(f, "FALLOC_FL_INSERT_RANGE"),
+        }
+    }
+}
+
+impl From<FallocateMode> for FallocateFlags {
+    fn from(mode: FallocateMode) -> Self {
+        match mode {
+            FallocateMode::KeepSize => FallocateFlags::FALLOC_FL_KEEP_SIZE,
+            FallocateMode::PunchHole => FallocateFlags::FALLOC_FL_PUNCH_HOLE,
+            FallocateMode::CollapseRange => FallocateFlags::FALLOC_FL_COLLAPSE_RANGE,
+            FallocateMode::ZeroRange => FallocateFlags::FALLOC_FL_ZERO_RANGE,
+            FallocateMode::InsertRange => FallocateFlags::FALLOC_FL_INSERT_RANGE,
+        }
+    }
+}
+
+fn validate_modes(modes: &[FallocateMode]) -> anyhow::Result<()> {
+    let has = |mode| modes.contains(&mode);
+
+    // The kernel rejects FALLOC_FL_PUNCH_HOLE without FALLOC_FL_KEEP_SIZE
+    if has(FallocateMode::PunchHole) && !has(FallocateMode::KeepSize) {
+        return Err(anyhow!(
+            "punch-hole must be specified together with keep-size"
+        ));
+    }
+    if has(FallocateMode::PunchHole) && has(FallocateMode::ZeroRange) {
+        return Err(anyhow!("punch-hole cannot be combined with zero-range"));
+    }
+    for exclusive in [FallocateMode::CollapseRange, FallocateMode::InsertRange] {
+        if has(exclusive) && modes.iter().any(|&mode| mode != exclusive) {
+            return Err(anyhow!(
+                "{} cannot be combined with other modes",
+                exclusive.to_possible_value().unwrap().get_name()
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn check_range(offset: i64, len: i64) -> anyhow::Result<()> {
+    if offset.checked_add(len).is_none() {
+        return Err(anyhow!(
+            "The range of offset {} and len {} exceeds the maximum file offset",
+            offset,
+            len
+        ));
+    }
+    Ok(())
+}
+
+fn open_existing(filename: &Path) -> anyhow::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .open(filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))
+}
+
+fn call_fallocate(file: &File, flags: FallocateFlags, offset: i64, len: i64) -> anyhow::Result<()> {
+    fallocate(file.as_raw_fd(), flags, offset, len).map_err(|errno| match errno {
+        Errno::EOPNOTSUPP => anyhow!("The filesystem does not support {:?}", flags),
+        errno => anyhow!(errno).context("Failed to call fallocate"),
+    })
+}
+
+fn file_size(file: &File) -> anyhow::Result<u64> {
+    Ok(file
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?
+        .len())
+}
+
+/// Checks that the range is aligned to the block size of the filesystem, which the kernel
+/// requires of the operations shifting data.
+fn check_block_aligned(file: &File, offset: i64, len: i64) -> anyhow::Result<()> {
+    let block_size = fstatfs(file)
+        .context("Failed to call fstatfs")?
+        .block_size() as i64;
+    if block_size > 0 && (offset % block_size != 0 || len % block_size != 0) {
+        return Err(anyhow!(
+            "OFFSET {} and LEN {} must be multiples of the block size {} of the filesystem",
+            offset,
+            len,
+            block_size
+        ));
+    }
+    Ok(())
+}
+
+/// Reads the range up to the end of the file and returns the number of bytes that are not zero.
+fn count_nonzero(file: &File, offset: i64, len: i64) -> anyhow::Result<u64> {
+    let end = (offset + len).min(file_size(file)? as i64);
+    let mut buffer = vec![0; 64 * 1024];
+    let mut position = offset;
+    let mut nonzero = 0;
+    while position < end {
+        let chunk = ((end - position) as usize).min(buffer.len());
+        let read = file
+            .read_at(&mut buffer[..chunk], position as u64)
+            .context("Failed to read the range")?;
+        if read == 0 {
+            break;
+        }
+        nonzero += buffer[..read].iter().filter(|&&byte| byte != 0).count() as u64;
+        position += read as i64;
+    }
+
+    Ok(nonzero)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum RegionKind {
+    Data,
+    Hole,
+}
+
+/// A range of a file from `start` to just before `end`.
+#[derive(Debug, PartialEq, Eq)]
+struct Region {
+    kind: RegionKind,
+    start: i64,
+    end: i64,
+}
+
+/// Enumerates the data and hole regions of a file with SEEK_DATA and SEEK_HOLE, which report
+/// what the filesystem has actually allocated.
+fn regions(file: &File) -> anyhow::Result<Vec<Region>> {
+    let size = file_size(file)? as i64;
+    let fd = file.as_raw_fd();
+    let mut regions = Vec::new();
+    let mut push = |kind, start, end| {
+        if start < end {
+            regions.push(Region { kind, start, end })
+        }
+    };
+
+    let mut position = 0;
+    while position < size {
+        let data = match lseek(fd, position, Whence::SeekData) {
+            Ok(data) => data,
+            // No data follows, so the rest is a hole
+            Err(Errno::ENXIO) => size,
+            Err(errno) => return Err(errno).context("Failed to seek with SEEK_DATA"),
+        };
+        push(RegionKind::Hole, position, data);
+        if data >= size {
+            break;
+        }
+        // The end of the file is an implicit hole
+        let hole = lseek(fd, data, Whence::SeekHole).context("Failed to seek with SEEK_HOLE")?;
+        push(RegionKind::Data, data, hole);
+        position = hole;
+    }
+
+    Ok(regions)
+}
+
+fn handle_punch_hole(range: RangeInfo) -> anyhow::Result<()> {
+    let RangeInfo {
+        filename,
+        offset,
+        len,
+    } = range;
+    check_range(offset, len)?;
+    let file = open_existing(&filename)?;
+
+    eprintln!("filename: {}", filename.display());
+    eprintln!("offset: {}", offset);
+    eprintln!("len: {}", len);
+    // The kernel requires FALLOC_FL_KEEP_SIZE with FALLOC_FL_PUNCH_HOLE
+    let flags = FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE;
+    call_fallocate(&file, flags, offset, len)?;
+
+    println!("size: {}", file_size(&file)?);
+    for region in regions(&file)? {
+        let kind = match region.kind {
+            RegionKind::Data => "data",
+            RegionKind::Hole => "hole",
+        };
+        println!("{}: {}..{}", kind, region.start, region.end);
+    }
+
+    Ok(())
+}
+
+fn handle_insert_range(range: RangeInfo) -> anyhow::Result<()> {
+    let RangeInfo {
+        filename,
+        offset,
+        len,
+    } = range;
+    check_range(offset, len)?;
+    let file = open_existing(&filename)?;
+    check_block_aligned(&file, offset, len)?;
+    let old_size = file_size(&file)?;
+    if offset >= old_size as i64 {
+        return Err(anyhow!(
+            "OFFSET {} must be less than the size {} of the file; extend the file instead",
+            offset,
+            old_size
+        ));
+    }
+
+    eprintln!("filename: {}", filename.display());
+    eprintln!("offset: {}", offset);
+    eprintln!("len: {}", len);
+    call_fallocate(&file, FallocateFlags::FALLOC_FL_INSERT_RANGE, offset, len)?;
+
+    println!("old_size: {}", old_size);
+    println!("new_size: {}", file_size(&file)?);
+
+    Ok(())
+}
+
+fn handle_collapse_range(range: RangeInfo) -> anyhow::Result<()> {
+    let RangeInfo {
+        filename,
+        offset,
+        len,
+    } = range;
+    check_range(offset, len)?;
+    let file = open_existing(&filename)?;
+    check_block_aligned(&file, offset, len)?;
+    let old_size = file_size(&file)?;
+    if offset + len >= old_size as i64 {
+        return Err(anyhow!(
+            "The range of OFFSET {} and LEN {} must end before the size {} of the file; truncate the file instead",
+            offset,
+            len,
+            old_size
+        ));
+    }
+
+    eprintln!("filename: {}", filename.display());
+    eprintln!("offset: {}", offset);
+    eprintln!("len: {}", len);
+    call_fallocate(&file, FallocateFlags::FALLOC_FL_COLLAPSE_RANGE, offset, len)?;
+
+    println!("old_size: {}", old_size);
+    println!("new_size: {}", file_size(&file)?);
+
+    Ok(())
+}
+
+fn handle_zero_range(range: RangeInfo, keep_size: bool, verify: bool) -> anyhow::Result<()> {
+    let RangeInfo {
+        filename,
+        offset,
+        len,
+    } = range;
+    check_range(offset, len)?;
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+
+    let mut flags = FallocateFlags::FALLOC_FL_ZERO_RANGE;
+    if keep_size {
+        flags |= FallocateFlags::FALLOC_FL_KEEP_SIZE;
+    }
+    eprintln!("filename: {}", filename.display());
+    eprintln!("offset: {}", offset);
+    eprintln!("len: {}", len);
+    call_fallocate(&file, flags, offset, len)?;
+
+    println!("size: {}", file_size(&file)?);
+    if verify {
+        let nonzero = count_nonzero(&file, offset, len)?;
+        if nonzero > 0 {
+            return Err(anyhow!("{} bytes of the range are not zero", nonzero));
+        }
+        println!("verified: true");
+    }
+
+    Ok(())
+}
+
+fn handle_allocate(info: AllocateInfo) -> anyhow::Result<()> {
+    let (filename, offset, len) = match (info.filename, info.offset, info.len) {
+        (Some(filename), Some(offset), Some(len)) => (filename, offset, len),
+        // clap requires them unless a subcommand is given
+        _ => unreachable!(),
+    };
+    let mut modes = info.mode;
+    modes.sort();
+    modes.dedup();
+    validate_modes(&modes)?;
+    check_range(offset, len)?;
+
+    // Only a plain allocation may create a new file
+    let create = modes.iter().all(|&mode| mode == FallocateMode::KeepSize);
+    let file = OpenOptions::new()
+        .write(true)
+        .create(create)
+        .open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+
+    let flags = modes
+        .iter()
+        .fold(FallocateFlags::empty(), |flags, &mode| flags | mode.into());
+    let mode_names: Vec<String> = modes.iter().map(|mode| mode.to_string()).collect();
+    eprintln!("filename: {}", filename.display());
+    if mode_names.is_empty() {
+        eprintln!("mode: none");
+    } else {
+        eprintln!("mode: {}", mode_names.join(" | "));
+    }
+    eprintln!("offset: {}", offset);
+    eprintln!("len: {}", len);
+
+    fallocate(file.as_raw_fd(), flags, offset, len).context("Failed to call fallocate")?;
+
+    let size = file
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?
+        .len();
+    println!("size: {}", size);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::PunchHole { range }) => handle_punch_hole(range),
+        Some(Commands::InsertRange { range }) => handle_insert_range(range),
+        Some(Commands::CollapseRange { range }) => handle_collapse_range(range),
+        Some(Commands::ZeroRange {
+            range,
+            keep_size,
+            verify,
+        }) => handle_zero_range(range, keep_size, verify),
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_allocate(cli.allocate),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    /// Creates a file on tmpfs, which supports hole punching and SEEK_HOLE.
+    fn temp_file() -> NamedTempFile {
+        let dir = Path::new("/dev/shm");
+        if dir.is_dir() {
+            NamedTempFile::new_in(dir)
+        } else {
+            NamedTempFile::new()
+        }
+        .unwrap()
+    }
+
+    fn region(kind: RegionKind, start: i64, end: i64) -> Region {
+        Region { kind, start, end }
+    }
+
+    #[test]
+    fn sparse_file_is_a_single_hole() {
+        let file = temp_file();
+        file.as_file().set_len(64 * 1024).unwrap();
+        assert_eq!(
+            regions(file.as_file()).unwrap(),
+            vec![region(RegionKind::Hole, 0, 64 * 1024)]
+        );
+    }
+
+    #[test]
+    fn punched_hole_splits_data() {
+        let mut file = temp_file();
+        file.write_all(&[0xa5; 64 * 1024]).unwrap();
+        file.flush().unwrap();
+        let flags = FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE;
+        call_fallocate(file.as_file(), flags, 16 * 1024, 16 * 1024).unwrap();
+
+        assert_eq!(
+            regions(file.as_file()).unwrap(),
+            vec![
+                region(RegionKind::Data, 0, 16 * 1024),
+                region(RegionKind::Hole, 16 * 1024, 32 * 1024),
+                region(RegionKind::Data, 32 * 1024, 64 * 1024),
+            ]
+        );
+        assert_eq!(file.as_file().metadata().unwrap().len(), 64 * 1024);
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(target_os = "linux")]
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::NamedTempFile;
+
+const BLOCK_SIZE: usize = 4096;
+const BLOCKS: usize = 8;
+
+fn fallocate(args: &[&str], file: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_fallocate"))
+        .args(&args[..1])
+        .arg(file)
+        .args(&args[1..])
+        .output()
+        .unwrap()
+}
+
+/// Creates a file whose each block is filled with its index, in the target directory rather
+/// than /tmp since tmpfs does not support collapsing.
+fn pattern_file() -> NamedTempFile {
+    let mut file = NamedTempFile::new_in(env!("CARGO_TARGET_TMPDIR")).unwrap();
+    for block in 0..BLOCKS {
+        file.write_all(&[block as u8; BLOCK_SIZE]).unwrap();
+    }
+    file.flush().unwrap();
+    file
+}
+
+fn unsupported(output: &Output) -> bool {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    stderr.contains("does not support") || stderr.contains("block size")
+}
+
+#[test]
+fn collapse_range_shifts_data_down() {
+    let file = pattern_file();
+    let output = fallocate(&["collapse-range", "8K", "8K"], file.path());
+    if !output.status.success() && unsupported(&output) {
+        eprintln!("skipped: {}", String::from_utf8_lossy(&output.stderr));
+        return;
+    }
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!(
+            "old_size: {}\nnew_size: {}\n",
+            BLOCKS * BLOCK_SIZE,
+            (BLOCKS - 2) * BLOCK_SIZE
+        )
+    );
+
+    let content = std::fs::read(file.path()).unwrap();
+    let blocks: Vec<u8> = content.chunks(BLOCK_SIZE).map(|block| block[0]).collect();
+    assert_eq!(blocks, [0, 1, 4, 5, 6, 7]);
+    for block in content.chunks(BLOCK_SIZE) {
+        assert!(block.iter().all(|&byte| byte == block[0]));
+    }
+}
+
+#[test]
+fn collapse_range_rejects_range_reaching_end() {
+    let file = pattern_file();
+    let output = fallocate(&["collapse-range", "24K", "8K"], file.path());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("must end before the size"));
+    assert_eq!(
+        std::fs::metadata(file.path()).unwrap().len(),
+        (BLOCKS * BLOCK_SIZE) as u64
+    );
+}
+
+#[test]
+fn collapse_range_rejects_unaligned_range() {
+    let file = pattern_file();
+    let output = fallocate(&["collapse-range", "100", "4K"], file.path());
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("must be multiples of the block size"));
+}
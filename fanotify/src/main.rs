@@ -0,0 +1,314 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Events with a single bit and the names printed for them
+const EVENT_NAMES: &[(u64, &str)] = &[
+    (libc::FAN_ACCESS, "ACCESS"),
+    (libc::FAN_MODIFY, "MODIFY"),
+    (libc::FAN_CLOSE_WRITE, "CLOSE_WRITE"),
+    (libc::FAN_CLOSE_NOWRITE, "CLOSE_NOWRITE"),
+    (libc::FAN_OPEN, "OPEN"),
+    (libc::FAN_Q_OVERFLOW, "Q_OVERFLOW"),
+    (libc::FAN_OPEN_PERM, "OPEN_PERM"),
+    (libc::FAN_ACCESS_PERM, "ACCESS_PERM"),
+    (libc::FAN_ONDIR, "ONDIR"),
+];
+
+/// Events which wait for a response before the access proceeds
+const PERMISSION_EVENTS: u64 = libc::FAN_OPEN_PERM | libc::FAN_ACCESS_PERM;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    monitor: MonitorInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct MonitorInfo {
+    /// Files, directories or, with --mount, mount points to monitor
+    #[clap(required = true, value_parser, value_name = "PATH")]
+    paths: Vec<PathBuf>,
+    /// Events to monitor, separated by commas [default: access,modify,open,close_write]
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true,
+        value_name = "EVENTS"
+    )]
+    events: Vec<Event>,
+    /// Monitor the whole mounts containing the paths
+    #[clap(short, long)]
+    mount: bool,
+    /// Allow the accesses of permission events (default)
+    #[clap(long, conflicts_with = "deny")]
+    allow: bool,
+    /// Deny the accesses of permission events, which fail with EPERM
+    #[clap(long)]
+    deny: bool,
+    /// Exit after the number of seconds
+    #[clap(short, long, value_parser, value_name = "SECONDS")]
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Event {
+    #[clap(name = "access")]
+    Access,
+    #[clap(name = "modify")]
+    Modify,
+    #[clap(name = "open")]
+    Open,
+    #[clap(name = "close_write")]
+    CloseWrite,
+    #[clap(name = "close_nowrite")]
+    CloseNowrite,
+    #[clap(name = "close")]
+    Close,
+    #[clap(name = "open_perm")]
+    OpenPerm,
+    #[clap(name = "access_perm")]
+    AccessPerm,
+}
+
+impl Event {
+    fn mask(self) -> u64 {
+        match self {
+            Event::Access => libc::FAN_ACCESS,
+            Event::Modify => libc::FAN_MODIFY,
+            Event::Open => libc::FAN_OPEN,
+            Event::CloseWrite => libc::FAN_CLOSE_WRITE,
+            Event::CloseNowrite => libc::FAN_CLOSE_NOWRITE,
+            Event::Close => libc::FAN_CLOSE,
+            Event::OpenPerm => libc::FAN_OPEN_PERM,
+            Event::AccessPerm => libc::FAN_ACCESS_PERM,
+        }
+    }
+}
+
+fn format_mask(mask: u64) -> String {
+    EVENT_NAMES
+        .iter()
+        .filter(|&&(flag, _)| mask & flag != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::EPERM => anyhow!("fanotify requires CAP_SYS_ADMIN"),
+        Errno::EINVAL => anyhow!("The kernel does not support one of the events"),
+        Errno::ENOSYS => anyhow!("The kernel does not support fanotify"),
+        errno => anyhow!(errno),
+    }
+}
+
+fn init(permission: bool) -> anyhow::Result<OwnedFd> {
+    // Permission events require a class which the kernel waits for responses in
+    let class = if permission {
+        libc::FAN_CLASS_CONTENT
+    } else {
+        libc::FAN_CLASS_NOTIF
+    };
+    let event_flags = (libc::O_RDONLY | libc::O_CLOEXEC | libc::O_LARGEFILE) as u32;
+    // SAFETY: fanotify_init only takes integer arguments.
+    let ret = unsafe { libc::fanotify_init(libc::FAN_CLOEXEC | class, event_flags) };
+    let fd = Errno::result(ret)
+        .map_err(describe)
+        .context("Failed to initialize fanotify")?;
+
+    // SAFETY: fanotify_init returns a new file descriptor owned by nobody else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+fn mark(fanotify: &OwnedFd, path: &Path, mask: u64, mount: bool) -> anyhow::Result<()> {
+    let (flags, mask) = if mount {
+        (libc::FAN_MARK_ADD | libc::FAN_MARK_MOUNT, mask)
+    } else {
+        // Without FAN_EVENT_ON_CHILD, a directory mark only reports the directory itself
+        (
+            libc::FAN_MARK_ADD,
+            mask | libc::FAN_EVENT_ON_CHILD | libc::FAN_ONDIR,
+        )
+    };
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .with_context(|| format!("'{}' contains a null byte", path.display()))?;
+    // SAFETY: c_path is a valid null-terminated string.
+    let ret = unsafe {
+        libc::fanotify_mark(
+            fanotify.as_raw_fd(),
+            flags,
+            mask,
+            libc::AT_FDCWD,
+            c_path.as_ptr(),
+        )
+    };
+    Errno::result(ret)
+        .map(drop)
+        .map_err(describe)
+        .with_context(|| format!("Failed to mark '{}'", path.display()))
+}
+
+/// Returns the path of a file descriptor received with an event.
+fn fd_path(fd: RawFd) -> String {
+    std::fs::read_link(format!("/proc/self/fd/{}", fd))
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "?".to_owned())
+}
+
+fn respond(fanotify: &OwnedFd, fd: RawFd, allow: bool) -> nix::Result<()> {
+    let response = libc::fanotify_response {
+        fd,
+        response: if allow {
+            libc::FAN_ALLOW
+        } else {
+            libc::FAN_DENY
+        },
+    };
+    // SAFETY: response is a valid fanotify_response for the kernel to read.
+    let bytes = unsafe {
+        std::slice::from_raw_parts(
+            &response as *const libc::fanotify_response as *const u8,
+            std::mem::size_of::<libc::fanotify_response>(),
+        )
+    };
+    nix::unistd::write(fanotify.as_raw_fd(), bytes).map(drop)
+}
+
+/// Prints the events in `buf` and answers the permission events among them.
+fn handle_events(fanotify: &OwnedFd, buf: &[u8], allow: bool) -> anyhow::Result<()> {
+    let metadata_len = std::mem::size_of::<libc::fanotify_event_metadata>();
+    let mut offset = 0;
+    while offset + metadata_len <= buf.len() {
+        // SAFETY: the buffer holds a whole fanotify_event_metadata at offset,
+        // which may be unaligned.
+        let event = unsafe {
+            std::ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::fanotify_event_metadata)
+        };
+        if event.vers != libc::FANOTIFY_METADATA_VERSION {
+            return Err(anyhow!(
+                "Unsupported fanotify metadata version {}",
+                event.vers
+            ));
+        }
+        offset += event.event_len as usize;
+
+        if event.mask & libc::FAN_Q_OVERFLOW != 0 {
+            eprintln!("warning: the event queue overflowed; some events are lost");
+            continue;
+        }
+        // SAFETY: the kernel opened the file descriptor for this process.
+        let fd = unsafe { OwnedFd::from_raw_fd(event.fd) };
+        let path = fd_path(fd.as_raw_fd());
+
+        if event.mask & PERMISSION_EVENTS != 0 {
+            // The accessing process waits until it receives the response
+            respond(fanotify, fd.as_raw_fd(), allow).context("Failed to respond to an event")?;
+            println!(
+                "{}\t{}\t{}\t{}\t{}",
+                format_mask(event.mask),
+                event.pid,
+                fd.as_raw_fd(),
+                path,
+                if allow { "allow" } else { "deny" }
+            );
+        } else {
+            println!(
+                "{}\t{}\t{}\t{}",
+                format_mask(event.mask),
+                event.pid,
+                fd.as_raw_fd(),
+                path
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_monitor(info: MonitorInfo) -> anyhow::Result<()> {
+    let mask = if info.events.is_empty() {
+        libc::FAN_ACCESS | libc::FAN_MODIFY | libc::FAN_OPEN | libc::FAN_CLOSE_WRITE
+    } else {
+        info.events
+            .iter()
+            .fold(0, |mask, event| mask | event.mask())
+    };
+    let deadline = info
+        .timeout
+        .map(|timeout| Instant::now() + Duration::from_secs(timeout));
+
+    // Allowing is the default to keep the monitored files usable
+    let allow = info.allow || !info.deny;
+
+    let fanotify = init(mask & PERMISSION_EVENTS != 0)?;
+    for path in &info.paths {
+        mark(&fanotify, path, mask, info.mount)?;
+    }
+
+    // Large enough for many events at once
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        // -1 means no timeout
+        let timeout_ms = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                i32::try_from(remaining.as_millis()).unwrap_or(i32::MAX)
+            }
+            None => -1,
+        };
+        let mut fds = [PollFd::new(fanotify.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout_ms) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(_) => {}
+            Err(errno) => return Err(errno).context("Failed to wait for events"),
+        }
+
+        let len = match nix::unistd::read(fanotify.as_raw_fd(), &mut buf) {
+            Ok(len) => len,
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to read events"),
+        };
+        handle_events(&fanotify, &buf[..len], allow)?;
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_monitor(cli.monitor),
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::fs::{File, OpenOptions};
+use std::os::raw::c_long;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+// The expansion of the nix macros refers to libc::c_int, which is newer than the MSRV for clippy
+#[allow(clippy::incompatible_msrv)]
+mod ioctl {
+    use std::os::raw::c_long;
+
+    // The kernel declares the argument as long but only reads and writes an int
+    nix::ioctl_read!(fs_ioc_getflags, b'f', 1, c_long);
+    nix::ioctl_write_ptr!(fs_ioc_setflags, b'f', 2, c_long);
+}
+
+/// Supported flags with their names and FS_*_FL values
+const FLAGS: &[(&str, u32)] = &[
+    ("sync", 0x0000_0008),
+    ("immutable", 0x0000_0010),
+    ("append", 0x0000_0020),
+    ("nodump", 0x0000_0040),
+    ("noatime", 0x0000_0080),
+    ("dirsync", 0x0001_0000),
+    ("extents", 0x0008_0000),
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the flags of a file
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// File to print the flags of
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+    },
+    /// Add or remove flags of a file
+    #[clap(display_order = 2, name = "set")]
+    Set {
+        /// File to change the flags of
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+        /// Flags to add with '+' or remove with '-' (immutable, append, noatime, sync, dirsync, nodump, extents)
+        #[clap(
+            required = true,
+            allow_hyphen_values = true,
+            value_parser = parse_change,
+            value_name = "[+|-]FLAG"
+        )]
+        changes: Vec<Change>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// Addition or removal of a flag
+#[derive(Debug, Copy, Clone)]
+struct Change {
+    add: bool,
+    flag: u32,
+}
+
+/// Parses a flag change such as `+immutable`, `-append` or `noatime`, which adds the flag.
+fn parse_change(s: &str) -> Result<Change, String> {
+    let (add, name) = match s.strip_prefix('-') {
+        Some(name) => (false, name),
+        None => (true, s.strip_prefix('+').unwrap_or(s)),
+    };
+    FLAGS
+        .iter()
+        .find(|(flag_name, _)| flag_name.eq_ignore_ascii_case(name))
+        .map(|&(_, flag)| Change { add, flag })
+        .ok_or_else(|| {
+            format!(
+                "unknown flag '{}'; supported flags are {}",
+                name,
+                FLAGS
+                    .iter()
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+fn format_flags(flags: u32) -> String {
+    let mut names: Vec<String> = FLAGS
+        .iter()
+        .filter(|&&(_, flag)| flags & flag != 0)
+        .map(|(name, _)| (*name).to_owned())
+        .collect();
+    let unknown = FLAGS.iter().fold(flags, |flags, &(_, flag)| flags & !flag);
+    if unknown != 0 {
+        names.push(format!("{:#x}", unknown));
+    }
+
+    if names.is_empty() {
+        format!("none ({:#010x})", flags)
+    } else {
+        format!("{} ({:#010x})", names.join(" | "), flags)
+    }
+}
+
+fn open(filename: &Path) -> anyhow::Result<File> {
+    // The ioctls work on a read-only file descriptor, even for immutable files and directories.
+    // O_NONBLOCK avoids blocking on FIFOs and devices.
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::ENOTTY | Errno::EOPNOTSUPP => {
+            anyhow!("The file system does not support the flags or one of them")
+        }
+        Errno::EPERM => anyhow!(
+            "Changing the flags requires the owner of the file or CAP_FOWNER, \
+            and immutable and append require CAP_LINUX_IMMUTABLE"
+        ),
+        errno => anyhow!(errno),
+    }
+}
+
+fn get_flags(file: &File) -> nix::Result<u32> {
+    let mut flags: c_long = 0;
+    // SAFETY: flags is a valid long for the kernel to write an int into.
+    unsafe { ioctl::fs_ioc_getflags(file.as_raw_fd(), &mut flags) }?;
+    Ok(flags as u32)
+}
+
+fn set_flags(file: &File, flags: u32) -> nix::Result<()> {
+    let flags = flags as c_long;
+    // SAFETY: flags is a valid long for the kernel to read an int from.
+    unsafe { ioctl::fs_ioc_setflags(file.as_raw_fd(), &flags) }.map(drop)
+}
+
+fn handle_get(filename: &Path) -> anyhow::Result<()> {
+    let file = open(filename)?;
+    let flags = get_flags(&file)
+        .map_err(describe)
+        .with_context(|| format!("Failed to get the flags of '{}'", filename.display()))?;
+    println!("flags: {}", format_flags(flags));
+
+    Ok(())
+}
+
+fn handle_set(filename: &Path, changes: &[Change]) -> anyhow::Result<()> {
+    let file = open(filename)?;
+    let old = get_flags(&file)
+        .map_err(describe)
+        .with_context(|| format!("Failed to get the flags of '{}'", filename.display()))?;
+    let new = changes.iter().fold(old, |flags, change| {
+        if change.add {
+            flags | change.flag
+        } else {
+            flags & !change.flag
+        }
+    });
+
+    set_flags(&file, new)
+        .map_err(describe)
+        .with_context(|| format!("Failed to set the flags of '{}'", filename.display()))?;
+    // The file system may silently ignore some of the flags
+    let new = get_flags(&file)
+        .map_err(describe)
+        .with_context(|| format!("Failed to get the flags of '{}'", filename.display()))?;
+    eprintln!("flags: {} -> {}", format_flags(old), format_flags(new));
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { filename } => handle_get(&filename),
+        Commands::Set { filename, changes } => handle_set(&filename, &changes),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,128 @@
+use anyhow::Context;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::run_command;
+use nix::errno::Errno;
+use nix::fcntl::{flock, FlockArg};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    lock: LockInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("mode").args(&["shared", "exclusive", "unlock"])))]
+struct LockInfo {
+    /// Lock file, created if it does not exist
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Command to execute while holding the lock [default: wait for EOF on stdin]
+    #[clap(
+        value_parser,
+        value_name = "COMMAND",
+        multiple_values = true,
+        allow_hyphen_values = true
+    )]
+    command: Vec<OsString>,
+    /// Acquire a shared lock
+    #[clap(short, long)]
+    shared: bool,
+    /// Acquire an exclusive lock (default)
+    #[clap(short = 'x', long)]
+    exclusive: bool,
+    /// Release a lock
+    #[clap(short, long)]
+    unlock: bool,
+    /// Fail instead of waiting when the lock is held by another process
+    #[clap(short, long)]
+    nonblock: bool,
+    /// Exit code used when --nonblock fails to acquire the lock
+    #[clap(short = 'E', long, default_value_t = 1, value_parser)]
+    conflict_exit_code: u8,
+}
+
+impl LockInfo {
+    fn flock_arg(&self) -> FlockArg {
+        match (self.shared, self.unlock, self.nonblock) {
+            (true, _, false) => FlockArg::LockShared,
+            (true, _, true) => FlockArg::LockSharedNonblock,
+            (_, true, false) => FlockArg::Unlock,
+            (_, true, true) => FlockArg::UnlockNonblock,
+            (_, _, false) => FlockArg::LockExclusive,
+            (_, _, true) => FlockArg::LockExclusiveNonblock,
+        }
+    }
+}
+
+fn open_lock_file(filename: &Path) -> anyhow::Result<File> {
+    // Fall back to read-only for directories and files which are not writable
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(filename)
+        .or_else(|_| File::open(filename))
+        .with_context(|| format!("Failed to open '{}'", filename.display()))
+}
+
+fn handle_lock(info: LockInfo) -> anyhow::Result<ExitCode> {
+    // clap requires FILE unless a subcommand is given
+    let filename = info.filename.as_deref().unwrap();
+    let file = open_lock_file(filename)?;
+
+    match flock(file.as_raw_fd(), info.flock_arg()) {
+        Ok(()) => {}
+        Err(Errno::EWOULDBLOCK) => {
+            eprintln!("'{}' is locked by another process", filename.display());
+            return Ok(ExitCode::from(info.conflict_exit_code));
+        }
+        Err(errno) => {
+            return Err(errno).with_context(|| format!("Failed to lock '{}'", filename.display()))
+        }
+    }
+
+    if !info.command.is_empty() {
+        return run_command(&info.command);
+    }
+    if !info.unlock {
+        std::io::copy(&mut std::io::stdin(), &mut std::io::sink())
+            .context("Failed to wait for EOF on stdin")?;
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_lock(cli.lock),
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::unistd::{fdatasync, fsync};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Flush data and metadata of files with fsync(2)
+    #[clap(display_order = 1, name = "fsync")]
+    Fsync(SyncInfo),
+    /// Flush data of files, and only the metadata needed to read it, with fdatasync(2)
+    #[clap(display_order = 2, name = "fdatasync")]
+    Fdatasync(SyncInfo),
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct SyncInfo {
+    /// Files to flush
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filenames: Vec<PathBuf>,
+    /// Also call fsync on the parent directories, which makes new files durable
+    #[clap(short = 'd', long)]
+    sync_dir: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum SyncType {
+    Fsync,
+    Fdatasync,
+}
+
+impl std::fmt::Display for SyncType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            SyncType::Fsync => write!(f, "fsync"),
+            SyncType::Fdatasync => write!(f, "fdatasync"),
+        }
+    }
+}
+
+fn sync_file(sync_type: SyncType, filename: &Path) -> anyhow::Result<()> {
+    let file = File::open(filename).context("Failed to open the file")?;
+    match sync_type {
+        SyncType::Fsync => fsync(file.as_raw_fd()),
+        SyncType::Fdatasync => fdatasync(file.as_raw_fd()),
+    }
+    .with_context(|| format!("Failed to call {}", sync_type))?;
+
+    Ok(())
+}
+
+fn parent_dir(filename: &Path) -> PathBuf {
+    match filename.parent() {
+        Some(parent) if parent != Path::new("") => parent.to_owned(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn handle_sync(sync_type: SyncType, info: SyncInfo) -> anyhow::Result<ExitCode> {
+    let start = Instant::now();
+
+    let mut targets: Vec<(SyncType, PathBuf)> = info
+        .filenames
+        .iter()
+        .map(|filename| (sync_type, filename.clone()))
+        .collect();
+    if info.sync_dir {
+        let mut dirs: Vec<PathBuf> = info.filenames.iter().map(|f| parent_dir(f)).collect();
+        // Each directory is flushed once however many files it contains
+        dirs.sort();
+        dirs.dedup();
+        targets.extend(dirs.into_iter().map(|dir| (SyncType::Fsync, dir)));
+    }
+
+    let mut failures = 0;
+    for (sync_type, filename) in &targets {
+        match sync_file(*sync_type, filename) {
+            Ok(()) => eprintln!("{}: {}: ok", sync_type, filename.display()),
+            Err(err) => {
+                eprintln!("{}: {}: {:#}", sync_type, filename.display(), err);
+                failures += 1;
+            }
+        }
+    }
+    eprintln!("elapsed: {:.3}s", start.elapsed().as_secs_f64());
+
+    let code = failures.min(125) as u8;
+    Ok(ExitCode::from(code))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Fsync(info) => handle_sync(SyncType::Fsync, info),
+        Commands::Fdatasync(info) => handle_sync(SyncType::Fdatasync, info),
+        Commands::Completion { shell } => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+    }
+}
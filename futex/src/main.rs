@@ -0,0 +1,367 @@
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::output::format_duration;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::time::{clock_gettime, ClockId};
+use nix::unistd::{fork, ftruncate, ForkResult, Pid};
+use std::ffi::{c_void, CString};
+use std::fs::File;
+use std::os::raw::{c_int, c_long};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Bitset of FUTEX_WAIT and FUTEX_WAKE, which matches any other bitset.
+const BITSET_MATCH_ANY: u32 = u32::MAX;
+/// Time given to the children to enter futex(2) after they announce that they are about to.
+const SETTLE_TIME: Duration = Duration::from_millis(10);
+
+/// Exit codes of a child, which tell the parent the result of its wait.
+const EXIT_WOKEN: i32 = 0;
+const EXIT_FAILED: i32 = 1;
+const EXIT_TIMED_OUT: i32 = 10;
+const EXIT_NOT_WAITING: i32 = 11;
+const EXIT_INTERRUPTED: i32 = 12;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    demo: DemoInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct DemoInfo {
+    /// Number of child processes waiting on the futex
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u32).range(1..=1024),
+        default_value_t = 2,
+        value_name = "N"
+    )]
+    children: u32,
+    /// Maximum number of waiters to wake [default: the number of children]
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u32).range(1..=i32::MAX as i64),
+        value_name = "N"
+    )]
+    wake: Option<u32>,
+    /// Omit FUTEX_PRIVATE_FLAG, which a futex shared between processes requires
+    #[clap(short, long)]
+    shared: bool,
+    /// Milliseconds a child waits before giving up
+    #[clap(short, long, value_parser, default_value_t = 1000, value_name = "MS")]
+    timeout: u64,
+    /// Wait with FUTEX_WAIT_BITSET and these bitsets in hex, given to the children in turn
+    /// [default: all bits]
+    #[clap(long, value_parser = parse_bitset, value_delimiter = ',', value_name = "BITSET,...")]
+    wait_bitset: Vec<u32>,
+    /// Wake with FUTEX_WAKE_BITSET and this bitset in hex [default: all bits]
+    #[clap(long, value_parser = parse_bitset, value_name = "BITSET")]
+    wake_bitset: Option<u32>,
+}
+
+fn parse_bitset(s: &str) -> Result<u32, String> {
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    match u32::from_str_radix(digits, 16) {
+        Ok(0) => Err("A bitset must have at least one bit".to_owned()),
+        Ok(bitset) => Ok(bitset),
+        Err(_) => Err(format!("'{}' is not a hex bitset", s)),
+    }
+}
+
+/// The beginning of the shared memory, followed by the wake-up time of each child.
+#[repr(C)]
+struct Header {
+    /// The futex word, which is 0 until the parent wakes the children.
+    word: AtomicU32,
+    /// Number of the children about to wait.
+    ready: AtomicU32,
+    /// CLOCK_MONOTONIC time of the wake in nanoseconds.
+    wake_time: AtomicU64,
+}
+
+/// A memfd mapped shared, which the children inherit, unmapped on drop.
+struct SharedMemory {
+    start: usize,
+    len: usize,
+}
+
+impl SharedMemory {
+    fn new(children: usize) -> anyhow::Result<Self> {
+        let len = std::mem::size_of::<Header>() + children * std::mem::size_of::<AtomicU64>();
+        let name = CString::new("futex").unwrap();
+        // SAFETY: name is NUL-terminated.
+        let fd = unsafe { libc::memfd_create(name.as_ptr(), libc::MFD_CLOEXEC) };
+        let fd = Errno::result(fd).context("Failed to call memfd_create")?;
+        // SAFETY: memfd_create returns a new file descriptor owned by nobody else.
+        let memfd = unsafe { File::from_raw_fd(fd) };
+        ftruncate(memfd.as_raw_fd(), len as i64).context("Failed to resize the memfd")?;
+
+        // SAFETY: a new mapping does not alias any memory, and the memfd is zero-filled.
+        let start = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_SHARED,
+                memfd.as_raw_fd(),
+                0,
+            )
+        }
+        .context("Failed to map the memfd")? as usize;
+
+        Ok(SharedMemory { start, len })
+    }
+
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping is page aligned and starts with a header, whose fields are atomic
+        // and valid when zero.
+        unsafe { &*(self.start as *const Header) }
+    }
+
+    fn wake_time(&self, index: usize) -> &AtomicU64 {
+        let offset = std::mem::size_of::<Header>() + index * std::mem::size_of::<AtomicU64>();
+        assert!(offset < self.len);
+        // SAFETY: the offset is aligned and within the mapping, which has a slot of each child.
+        unsafe { &*((self.start + offset) as *const AtomicU64) }
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        // SAFETY: nothing refers to the mapping after the drop.
+        let _ = unsafe { munmap(self.start as *mut c_void, self.len) };
+    }
+}
+
+fn futex(
+    word: &AtomicU32,
+    op: c_int,
+    val: u32,
+    timeout: Option<&libc::timespec>,
+    val3: u32,
+) -> nix::Result<c_long> {
+    let timeout = timeout.map_or(std::ptr::null(), |timeout| timeout as *const _);
+    // SAFETY: word is a valid futex word, and timeout is null or a valid timespec.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            word as *const AtomicU32,
+            op,
+            val,
+            timeout,
+            std::ptr::null::<u32>(),
+            val3,
+        )
+    };
+    Errno::result(ret)
+}
+
+fn now_ns() -> nix::Result<u64> {
+    let time = clock_gettime(ClockId::CLOCK_MONOTONIC)?;
+    Ok(time.tv_sec() as u64 * 1_000_000_000 + time.tv_nsec() as u64)
+}
+
+fn timespec(ns: u64) -> libc::timespec {
+    libc::timespec {
+        tv_sec: (ns / 1_000_000_000) as _,
+        tv_nsec: (ns % 1_000_000_000) as _,
+    }
+}
+
+fn op_name(op: c_int) -> String {
+    let name = match op & !libc::FUTEX_PRIVATE_FLAG {
+        libc::FUTEX_WAIT => "FUTEX_WAIT",
+        libc::FUTEX_WAKE => "FUTEX_WAKE",
+        libc::FUTEX_WAIT_BITSET => "FUTEX_WAIT_BITSET",
+        libc::FUTEX_WAKE_BITSET => "FUTEX_WAKE_BITSET",
+        _ => "unknown",
+    };
+    if op & libc::FUTEX_PRIVATE_FLAG != 0 {
+        format!("{} | FUTEX_PRIVATE_FLAG", name)
+    } else {
+        name.to_owned()
+    }
+}
+
+/// Waits on the futex word in a child and returns its exit code.
+fn run_child(shared: &SharedMemory, index: usize, op: c_int, bitset: u32, timeout_ms: u64) -> i32 {
+    let header = shared.header();
+    let timeout_ns = timeout_ms * 1_000_000;
+    // FUTEX_WAIT takes a relative timeout, while FUTEX_WAIT_BITSET takes an absolute time
+    let timeout = if op & !libc::FUTEX_PRIVATE_FLAG == libc::FUTEX_WAIT_BITSET {
+        match now_ns() {
+            Ok(now) => timespec(now + timeout_ns),
+            Err(errno) => {
+                eprintln!("Error: Failed to call clock_gettime: {}", errno);
+                return EXIT_FAILED;
+            }
+        }
+    } else {
+        timespec(timeout_ns)
+    };
+
+    header.ready.fetch_add(1, Ordering::SeqCst);
+    let result = futex(&header.word, op, 0, Some(&timeout), bitset);
+    shared
+        .wake_time(index)
+        .store(now_ns().unwrap_or(0), Ordering::SeqCst);
+    match result {
+        Ok(_) => EXIT_WOKEN,
+        Err(Errno::ETIMEDOUT) => EXIT_TIMED_OUT,
+        // The word was no longer 0 when the child started to wait
+        Err(Errno::EAGAIN) => EXIT_NOT_WAITING,
+        Err(Errno::EINTR) => EXIT_INTERRUPTED,
+        Err(errno) => {
+            eprintln!("Error: Failed to call {}: {}", op_name(op), errno);
+            EXIT_FAILED
+        }
+    }
+}
+
+/// Waits until every child is about to wait, or gives up after the timeout of the children.
+fn wait_ready(shared: &SharedMemory, children: u32, timeout_ms: u64) {
+    let begin = Instant::now();
+    while shared.header().ready.load(Ordering::SeqCst) < children
+        && begin.elapsed() < Duration::from_millis(timeout_ms)
+    {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    // A child announces that it is ready just before calling futex(2)
+    std::thread::sleep(SETTLE_TIME);
+}
+
+fn describe(status: WaitStatus, latency: Option<u64>) -> String {
+    match status {
+        WaitStatus::Exited(_, EXIT_WOKEN) => match latency {
+            Some(latency) => format!("woken after {}", format_duration(latency)),
+            None => "woken".to_owned(),
+        },
+        WaitStatus::Exited(_, EXIT_TIMED_OUT) => "timed out".to_owned(),
+        WaitStatus::Exited(_, EXIT_NOT_WAITING) => "not waiting yet when woken (EAGAIN)".to_owned(),
+        WaitStatus::Exited(_, EXIT_INTERRUPTED) => "interrupted by a signal".to_owned(),
+        WaitStatus::Exited(_, code) => format!("failed with exit code {}", code),
+        WaitStatus::Signaled(_, signal, _) => format!("killed by {}", signal),
+        status => format!("{:?}", status),
+    }
+}
+
+fn handle_demo(info: DemoInfo) -> anyhow::Result<()> {
+    let bitset = !info.wait_bitset.is_empty() || info.wake_bitset.is_some();
+    let private = if info.shared {
+        0
+    } else {
+        libc::FUTEX_PRIVATE_FLAG
+    };
+    let (wait_op, wake_op) = if bitset {
+        (libc::FUTEX_WAIT_BITSET, libc::FUTEX_WAKE_BITSET)
+    } else {
+        (libc::FUTEX_WAIT, libc::FUTEX_WAKE)
+    };
+    let (wait_op, wake_op) = (wait_op | private, wake_op | private);
+    let wake_bitset = info.wake_bitset.unwrap_or(BITSET_MATCH_ANY);
+    let wait_bitset = |index: usize| match info.wait_bitset.len() {
+        0 => BITSET_MATCH_ANY,
+        len => info.wait_bitset[index % len],
+    };
+
+    let shared = SharedMemory::new(info.children as usize)?;
+    let mut children = Vec::new();
+    for index in 0..info.children as usize {
+        // SAFETY: this process is single-threaded, so the child can run any code.
+        match unsafe { fork() }.context("Failed to fork")? {
+            ForkResult::Child => {
+                let code = run_child(&shared, index, wait_op, wait_bitset(index), info.timeout);
+                std::process::exit(code);
+            }
+            ForkResult::Parent { child } => children.push(child),
+        }
+    }
+
+    wait_ready(&shared, info.children, info.timeout);
+    let header = shared.header();
+    header.word.store(1, Ordering::SeqCst);
+    let wake_time = now_ns().context("Failed to call clock_gettime")?;
+    header.wake_time.store(wake_time, Ordering::SeqCst);
+    let wake = info.wake.unwrap_or(info.children);
+    let woken = futex(&header.word, wake_op, wake, None, wake_bitset);
+
+    println!("wait: {}", op_name(wait_op));
+    println!("wake: {}", op_name(wake_op));
+    if bitset {
+        println!("wake_bitset: {:#x}", wake_bitset);
+    }
+    println!("children: {}", info.children);
+    let result = woken
+        .map(|woken| println!("woken: {}", woken))
+        .with_context(|| format!("Failed to call {}", op_name(wake_op)));
+
+    // The children finish by the timeout even if the wake failed
+    let mut all_woken = true;
+    for (index, child) in children.into_iter().enumerate() {
+        let status = waitpid(child, None)
+            .with_context(|| format!("Failed to wait for child process {}", child))?;
+        let latency = shared
+            .wake_time(index)
+            .load(Ordering::SeqCst)
+            .checked_sub(wake_time);
+        all_woken &= status == WaitStatus::Exited(child, EXIT_WOKEN);
+        print_child(child, bitset.then(|| wait_bitset(index)), status, latency);
+    }
+    result?;
+
+    if !all_woken && !info.shared {
+        eprintln!(
+            "warning: FUTEX_PRIVATE_FLAG keys the futex by the memory of each process, so a wake \
+            from another process misses it; give --shared to wait across processes"
+        );
+    }
+
+    Ok(())
+}
+
+fn print_child(child: Pid, bitset: Option<u32>, status: WaitStatus, latency: Option<u64>) {
+    match bitset {
+        Some(bitset) => println!(
+            "child {} (bitset {:#x}): {}",
+            child,
+            bitset,
+            describe(status, latency)
+        ),
+        None => println!("child {}: {}", child, describe(status, latency)),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_demo(cli.demo),
+    }
+}
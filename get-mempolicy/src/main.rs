@@ -0,0 +1,437 @@
+use anyhow::anyhow;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_address;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::io::ErrorKind;
+use std::os::raw::{c_int, c_ulong};
+
+/// Nodes in the node masks passed to the kernel, which covers the kernel maximum of 1024
+const NODEMASK_BITS: usize = 1024;
+
+const BITS_PER_WORD: usize = c_ulong::BITS as usize;
+
+// Flags of get_mempolicy(2) from linux/mempolicy.h
+const MPOL_F_NODE: c_int = 1;
+const MPOL_F_ADDR: c_int = 2;
+const MPOL_F_MEMS_ALLOWED: c_int = 4;
+
+// Mode flags which get_mempolicy(2) returns with the mode
+const MPOL_F_STATIC_NODES: c_int = 1 << 15;
+const MPOL_F_RELATIVE_NODES: c_int = 1 << 14;
+const MPOL_F_NUMA_BALANCING: c_int = 1 << 13;
+const MPOL_MODE_FLAGS: c_int = MPOL_F_STATIC_NODES | MPOL_F_RELATIVE_NODES | MPOL_F_NUMA_BALANCING;
+
+/// Names of the modes of linux/mempolicy.h
+const MODE_NAMES: &[&str] = &[
+    "default",
+    "preferred",
+    "bind",
+    "interleave",
+    "local",
+    "preferred_many",
+    "weighted_interleave",
+];
+
+/// Names of the same modes in /proc/PID/numa_maps
+const NUMA_MAPS_NAMES: &[&str] = &[
+    "default",
+    "prefer",
+    "bind",
+    "interleave",
+    "local",
+    "prefer (many)",
+    "weighted interleave",
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    policy: PolicyInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct PolicyInfo {
+    /// Print the policy of the mapping at this address, in hex with 0x or in decimal
+    #[clap(short, long, value_parser = parse_address, value_name = "ADDR")]
+    addr: Option<u64>,
+    /// Read the policies of the mappings of this process from /proc/PID/numa_maps, since
+    /// get_mempolicy only queries the calling process
+    #[clap(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    pid: Option<i32>,
+}
+
+/// A node mask in the layout of the kernel, an array of unsigned longs.
+struct NodeMask(Vec<c_ulong>);
+
+impl NodeMask {
+    fn new() -> Self {
+        NodeMask(vec![0; NODEMASK_BITS / BITS_PER_WORD])
+    }
+
+    fn nodes(&self) -> Vec<usize> {
+        (0..NODEMASK_BITS)
+            .filter(|&node| self.0[node / BITS_PER_WORD] & (1 << (node % BITS_PER_WORD)) != 0)
+            .collect()
+    }
+
+    /// Returns maxnode for the system calls, which count one more than the bits for a historical bug.
+    fn maxnode(&self) -> c_ulong {
+        (NODEMASK_BITS + 1) as c_ulong
+    }
+}
+
+/// A memory policy returned by get_mempolicy(2).
+struct Policy {
+    mode: c_int,
+    flags: c_int,
+    nodes: Vec<usize>,
+}
+
+impl Policy {
+    /// Queries the policy of this process, or the policy governing an address of this process.
+    fn query(addr: Option<u64>) -> nix::Result<Self> {
+        let mut mask = NodeMask::new();
+        let flags = if addr.is_some() { MPOL_F_ADDR } else { 0 };
+        let mode = get_mempolicy(Some(&mut mask), addr.unwrap_or(0) as usize, flags)?;
+        Ok(Policy {
+            mode: mode & !MPOL_MODE_FLAGS,
+            flags: mode & MPOL_MODE_FLAGS,
+            nodes: mask.nodes(),
+        })
+    }
+
+    fn mode_name(&self) -> String {
+        match MODE_NAMES.get(self.mode as usize) {
+            Some(name) => (*name).to_owned(),
+            None => format!("unknown ({})", self.mode),
+        }
+    }
+
+    fn flag_names(&self) -> String {
+        let names: Vec<&str> = [
+            (MPOL_F_STATIC_NODES, "static_nodes"),
+            (MPOL_F_RELATIVE_NODES, "relative_nodes"),
+            (MPOL_F_NUMA_BALANCING, "numa_balancing"),
+        ]
+        .iter()
+        .filter(|(flag, _)| self.flags & flag != 0)
+        .map(|(_, name)| *name)
+        .collect();
+        if names.is_empty() {
+            "none".to_owned()
+        } else {
+            names.join(" | ")
+        }
+    }
+
+    /// Formats the policy as /proc/PID/numa_maps does, such as `bind=static:0-1`.
+    fn numa_maps_format(&self) -> String {
+        let mut s = match NUMA_MAPS_NAMES.get(self.mode as usize) {
+            Some(name) => (*name).to_owned(),
+            None => "unknown".to_owned(),
+        };
+        if self.flags != 0 {
+            s.push('=');
+            if self.flags & MPOL_F_STATIC_NODES != 0 {
+                s.push_str("static");
+            } else if self.flags & MPOL_F_RELATIVE_NODES != 0 {
+                s.push_str("relative");
+            }
+            if self.flags & MPOL_F_NUMA_BALANCING != 0 {
+                if self.flags & (MPOL_F_STATIC_NODES | MPOL_F_RELATIVE_NODES) != 0 {
+                    s.push('|');
+                }
+                s.push_str("balancing");
+            }
+        }
+        if !self.nodes.is_empty() {
+            s.push(':');
+            s.push_str(&format_node_list(&self.nodes));
+        }
+        s
+    }
+}
+
+/// Formats sorted node indices as a node list such as `0,1-3`.
+fn format_node_list(nodes: &[usize]) -> String {
+    if nodes.is_empty() {
+        return "none".to_owned();
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &node in nodes {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == node => *last = node,
+            _ => ranges.push((node, node)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn get_mempolicy(mask: Option<&mut NodeMask>, addr: usize, flags: c_int) -> nix::Result<c_int> {
+    let mut mode: c_int = 0;
+    let (mask_ptr, maxnode) = match mask {
+        Some(mask) => (mask.0.as_mut_ptr(), mask.maxnode()),
+        None => (std::ptr::null_mut(), 0),
+    };
+    // SAFETY: mode is a valid int, and mask_ptr is null or a node mask of maxnode - 1 bits.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_get_mempolicy,
+            &mut mode as *mut c_int,
+            mask_ptr,
+            maxnode,
+            addr,
+            flags,
+        )
+    };
+    Errno::result(ret).map(|_| mode)
+}
+
+fn describe(errno: Errno, addr: Option<u64>) -> anyhow::Error {
+    match (errno, addr) {
+        (Errno::ENOSYS, _) => anyhow!("The kernel does not support NUMA"),
+        (Errno::EFAULT, Some(addr)) => anyhow!("{:#x} is not mapped in this process", addr),
+        (errno, _) => anyhow!(errno).context("Failed to call get_mempolicy"),
+    }
+}
+
+/// A mapping of /proc/PID/numa_maps.
+struct NumaMap {
+    start: u64,
+    policy: String,
+    /// Pages on each node such as `N0=2`.
+    pages: Vec<String>,
+}
+
+impl NumaMap {
+    /// Parses a line such as `7f2a4c000000 bind:0-1 anon=3 dirty=3 N0=2 N1=1 kernelpagesize_kB=4`.
+    fn parse(line: &str) -> Option<NumaMap> {
+        let (start, rest) = line.split_once(' ')?;
+        // Some names of the modes such as "prefer (many)" contain a space
+        let name_len = NUMA_MAPS_NAMES
+            .iter()
+            .filter(|name| rest.starts_with(*name))
+            .map(|name| name.len())
+            .max()
+            .unwrap_or(0);
+        let end = rest[name_len..]
+            .find(' ')
+            .map_or(rest.len(), |index| name_len + index);
+        let pages = rest[end..]
+            .split_whitespace()
+            .filter(|field| {
+                field.strip_prefix('N').map_or(false, |rest| {
+                    rest.split_once('=')
+                        .map_or(false, |(node, _)| node.parse::<usize>().is_ok())
+                })
+            })
+            .map(str::to_owned)
+            .collect();
+
+        Some(NumaMap {
+            start: u64::from_str_radix(start, 16).ok()?,
+            policy: rest[..end].to_owned(),
+            pages,
+        })
+    }
+}
+
+/// Returns the path of a file under /proc of the process, or of this process.
+fn proc_path(pid: Option<i32>, name: &str) -> String {
+    match pid {
+        Some(pid) => format!("/proc/{}/{}", pid, name),
+        None => format!("/proc/self/{}", name),
+    }
+}
+
+fn read_proc_file(pid: Option<i32>, name: &str) -> anyhow::Result<String> {
+    let path = proc_path(pid, name);
+    std::fs::read_to_string(&path).map_err(|err| match (err.kind(), pid) {
+        (ErrorKind::NotFound, Some(pid)) => anyhow!("Process {} does not exist", pid),
+        _ => anyhow!(err).context(format!("Failed to read {}", path)),
+    })
+}
+
+fn read_numa_maps(pid: Option<i32>) -> anyhow::Result<Vec<NumaMap>> {
+    let content = read_proc_file(pid, "numa_maps")?;
+    content
+        .lines()
+        .map(|line| {
+            NumaMap::parse(line).ok_or_else(|| {
+                anyhow!(
+                    "Unexpected line in {}: {}",
+                    proc_path(pid, "numa_maps"),
+                    line
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the range of the mapping of /proc/PID/maps that contains the address.
+fn find_region(pid: Option<i32>, addr: u64) -> anyhow::Result<(u64, u64)> {
+    let content = read_proc_file(pid, "maps")?;
+
+    // Each line is like "7ffd5a3a1000-7ffd5a3c2000 rw-p 00000000 00:00 0  [stack]"
+    content
+        .lines()
+        .filter_map(|line| {
+            let (start, end) = line.split_whitespace().next()?.split_once('-')?;
+            Some((
+                u64::from_str_radix(start, 16).ok()?,
+                u64::from_str_radix(end, 16).ok()?,
+            ))
+        })
+        .find(|&(start, end)| start <= addr && addr < end)
+        .ok_or_else(|| match pid {
+            Some(pid) => anyhow!("{:#x} is not mapped in process {}", addr, pid),
+            None => anyhow!("{:#x} is not mapped in this process", addr),
+        })
+}
+
+/// Returns the policy that /proc/PID/numa_maps shows for the mapping containing the address.
+fn numa_maps_policy(pid: Option<i32>, addr: u64) -> anyhow::Result<(u64, u64, NumaMap)> {
+    let (start, end) = find_region(pid, addr)?;
+    let map = read_numa_maps(pid)?
+        .into_iter()
+        .find(|map| map.start == start)
+        .ok_or_else(|| {
+            anyhow!(
+                "The mapping at {:#x} is not in {}",
+                start,
+                proc_path(pid, "numa_maps")
+            )
+        })?;
+    Ok((start, end, map))
+}
+
+fn print_consistency(syscall: &str, numa_maps: &str) {
+    println!("consistent: {}", syscall == numa_maps);
+    if syscall != numa_maps {
+        eprintln!(
+            "warning: get_mempolicy returned {} but numa_maps shows {}",
+            syscall, numa_maps
+        );
+    }
+}
+
+fn handle_process() -> anyhow::Result<()> {
+    // The policy of this process is inherited from the parent process
+    let policy = Policy::query(None).map_err(|errno| describe(errno, None))?;
+    let mut allowed = NodeMask::new();
+    get_mempolicy(Some(&mut allowed), 0, MPOL_F_MEMS_ALLOWED)
+        .map_err(|errno| describe(errno, None))?;
+
+    println!("mode: {}", policy.mode_name());
+    println!("flags: {}", policy.flag_names());
+    println!("nodes: {}", format_node_list(&policy.nodes));
+    println!("mems_allowed: {}", format_node_list(&allowed.nodes()));
+
+    // numa_maps shows the policy of the process for a mapping without its own, such as the stack
+    let local = 0u8;
+    let (_, _, map) = numa_maps_policy(None, &local as *const u8 as u64)?;
+    println!("numa_maps: {}", map.policy);
+    print_consistency(&policy.numa_maps_format(), &map.policy);
+
+    Ok(())
+}
+
+fn handle_address(pid: Option<i32>, addr: u64) -> anyhow::Result<()> {
+    let (start, end, map) = numa_maps_policy(pid, addr)?;
+    println!("address: {:#x}", addr);
+    println!("range: {:#x}-{:#x}", start, end);
+    println!("numa_maps: {}", map.policy);
+    println!(
+        "pages: {}",
+        if map.pages.is_empty() {
+            "none".to_owned()
+        } else {
+            map.pages.join(" ")
+        }
+    );
+    // get_mempolicy only queries the memory of the calling process
+    if pid.is_some() {
+        return Ok(());
+    }
+
+    // The policy of the mapping itself, which is default without one set by mbind
+    let policy = Policy::query(Some(addr)).map_err(|errno| describe(errno, Some(addr)))?;
+    println!("mode: {}", policy.mode_name());
+    println!("flags: {}", policy.flag_names());
+    println!("nodes: {}", format_node_list(&policy.nodes));
+    // With MPOL_F_NODE | MPOL_F_ADDR, the node of the page is returned as the mode, which faults
+    // the page in
+    let node = get_mempolicy(None, addr as usize, MPOL_F_NODE | MPOL_F_ADDR)
+        .map_err(|errno| describe(errno, Some(addr)))?;
+    println!("page_node: {}", node);
+
+    // A mapping without its own policy follows the policy of the process, which numa_maps shows
+    let effective = if policy.mode == libc::MPOL_DEFAULT {
+        Policy::query(None).map_err(|errno| describe(errno, None))?
+    } else {
+        policy
+    };
+    println!("effective: {}", effective.numa_maps_format());
+    print_consistency(&effective.numa_maps_format(), &map.policy);
+
+    Ok(())
+}
+
+fn handle_mappings(pid: i32) -> anyhow::Result<()> {
+    for map in read_numa_maps(Some(pid))? {
+        if map.pages.is_empty() {
+            println!("{:#x}: {}", map.start, map.policy);
+        } else {
+            println!("{:#x}: {} {}", map.start, map.policy, map.pages.join(" "));
+        }
+    }
+    Ok(())
+}
+
+fn handle_policy(info: PolicyInfo) -> anyhow::Result<()> {
+    // This process is queried with get_mempolicy, while another one only with its numa_maps
+    let pid = info.pid.filter(|&pid| pid != std::process::id() as i32);
+    match (pid, info.addr) {
+        (None, None) => handle_process(),
+        (pid, Some(addr)) => handle_address(pid, addr),
+        (Some(pid), None) => handle_mappings(pid),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_policy(cli.policy),
+    }
+}
@@ -0,0 +1,316 @@
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::OutputFormat;
+use linux_tools_core::output::new_reporter;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::exit_code;
+use nix::sys::resource::{getrusage, UsageWho};
+use nix::sys::time::TimeValLike;
+use nix::unistd::{sysconf, SysconfVar};
+use serde_json::json;
+use std::ffi::OsString;
+use std::io::ErrorKind;
+use std::process::{Command, ExitCode};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    usage: UsageInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("target").required(true).args(&["command", "pid", "who"])))]
+struct UsageInfo {
+    /// Command to run and print the resource usage of after it exits
+    #[clap(value_parser, value_name = "COMMAND", multiple_values = true)]
+    command: Vec<OsString>,
+    /// Print the resource usage of the running process from /proc/PID instead
+    #[clap(short, long, value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// Print the resource usage of getrusage itself, its thread or its children instead
+    #[clap(long, arg_enum)]
+    who: Option<Who>,
+    /// Print the resource usage every number of seconds until the process exits
+    #[clap(
+        short,
+        long,
+        requires = "pid",
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "SECONDS"
+    )]
+    watch: Option<u64>,
+    /// Format of the result
+    #[clap(short = 'O', long, arg_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Print the result in JSON, the same as --output-format json
+    #[clap(short, long, conflicts_with = "output-format")]
+    json: bool,
+}
+
+impl UsageInfo {
+    fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output_format
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Who {
+    #[clap(name = "self")]
+    Myself,
+    #[clap(name = "children")]
+    Children,
+    #[clap(name = "thread")]
+    Thread,
+}
+
+impl Who {
+    fn usage_who(self) -> UsageWho {
+        match self {
+            Who::Myself => UsageWho::RUSAGE_SELF,
+            Who::Children => UsageWho::RUSAGE_CHILDREN,
+            Who::Thread => UsageWho::RUSAGE_THREAD,
+        }
+    }
+}
+
+/// The fields of struct rusage, where None is a field /proc/PID does not provide.
+struct ResourceUsage {
+    /// User CPU time in seconds
+    utime: f64,
+    /// System CPU time in seconds
+    stime: f64,
+    /// Maximum resident set size in kB
+    maxrss: Option<i64>,
+    ixrss: Option<i64>,
+    idrss: Option<i64>,
+    isrss: Option<i64>,
+    minflt: Option<i64>,
+    majflt: Option<i64>,
+    nswap: Option<i64>,
+    /// Blocks of 512 bytes read from storage
+    inblock: Option<i64>,
+    /// Blocks of 512 bytes written to storage
+    oublock: Option<i64>,
+    msgsnd: Option<i64>,
+    msgrcv: Option<i64>,
+    nsignals: Option<i64>,
+    nvcsw: Option<i64>,
+    nivcsw: Option<i64>,
+}
+
+impl ResourceUsage {
+    fn get(who: UsageWho) -> anyhow::Result<Self> {
+        let usage = getrusage(who).context("Failed to get the resource usage")?;
+        let seconds = |time: nix::sys::time::TimeVal| time.num_microseconds() as f64 / 1e6;
+
+        Ok(ResourceUsage {
+            utime: seconds(usage.user_time()),
+            stime: seconds(usage.system_time()),
+            maxrss: Some(usage.max_rss()),
+            ixrss: Some(usage.shared_integral()),
+            idrss: Some(usage.unshared_data_integral()),
+            isrss: Some(usage.unshared_stack_integral()),
+            minflt: Some(usage.minor_page_faults()),
+            majflt: Some(usage.major_page_faults()),
+            nswap: Some(usage.full_swaps()),
+            inblock: Some(usage.block_reads()),
+            oublock: Some(usage.block_writes()),
+            msgsnd: Some(usage.ipc_sends()),
+            msgrcv: Some(usage.ipc_receives()),
+            nsignals: Some(usage.signals()),
+            nvcsw: Some(usage.voluntary_context_switches()),
+            nivcsw: Some(usage.involuntary_context_switches()),
+        })
+    }
+
+    /// Reads the fields the kernel keeps for a running process from /proc/PID.
+    fn read(pid: i32, ticks_per_second: f64) -> anyhow::Result<Self> {
+        let stat = read_proc(pid, "stat")?;
+        // The command name in parentheses may contain spaces, so the fields are split after it
+        let fields: Vec<&str> = stat
+            .rsplit_once(')')
+            .map(|(_, fields)| fields.split_whitespace().collect())
+            .unwrap_or_default();
+        // Fields are numbered from 1 in proc(5), and the first field after the name is 3
+        let field = |number: usize| -> anyhow::Result<i64> {
+            fields
+                .get(number - 3)
+                .and_then(|field| field.parse().ok())
+                .ok_or_else(|| anyhow!("Unexpected content of /proc/{}/stat: {}", pid, stat))
+        };
+
+        let status = read_proc(pid, "status")?;
+        let status_field = |key: &str| {
+            status
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+                .and_then(|value| value.trim().trim_end_matches(" kB").parse().ok())
+        };
+
+        // /proc/PID/io requires the permission to ptrace the process
+        let io = read_proc(pid, "io").ok();
+        let io_field = |key: &str| {
+            io.as_deref()?
+                .lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix(':'))
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                .map(|bytes| bytes / 512)
+        };
+
+        Ok(ResourceUsage {
+            utime: field(14)? as f64 / ticks_per_second,
+            stime: field(15)? as f64 / ticks_per_second,
+            maxrss: status_field("VmHWM"),
+            ixrss: None,
+            idrss: None,
+            isrss: None,
+            minflt: Some(field(10)?),
+            majflt: Some(field(12)?),
+            nswap: Some(field(36)?),
+            inblock: io_field("read_bytes"),
+            oublock: io_field("write_bytes"),
+            msgsnd: None,
+            msgrcv: None,
+            nsignals: None,
+            nvcsw: status_field("voluntary_ctxt_switches"),
+            nivcsw: status_field("nonvoluntary_ctxt_switches"),
+        })
+    }
+
+    /// Prints the usage to stderr or stdout, where each field is a line or a key of a JSON object.
+    fn print(&self, format: OutputFormat, to_stderr: bool) -> anyhow::Result<()> {
+        // The usage of a command goes to stderr so that it is not mixed with the output of it
+        let mut reporter = if to_stderr {
+            new_reporter(format, std::io::stderr())
+        } else {
+            new_reporter(format, std::io::stdout())
+        };
+        let seconds = |seconds: f64| format!("{:.6} s", seconds);
+        reporter.report_value("utime", &seconds(self.utime), json!(self.utime));
+        reporter.report_value("stime", &seconds(self.stime), json!(self.stime));
+        let maxrss = match self.maxrss {
+            Some(maxrss) => format!("{} kB", maxrss),
+            None => "none".to_owned(),
+        };
+        reporter.report_value("maxrss", &maxrss, json!(self.maxrss));
+        let fields = [
+            ("ixrss", self.ixrss),
+            ("idrss", self.idrss),
+            ("isrss", self.isrss),
+            ("minflt", self.minflt),
+            ("majflt", self.majflt),
+            ("nswap", self.nswap),
+            ("inblock", self.inblock),
+            ("oublock", self.oublock),
+            ("msgsnd", self.msgsnd),
+            ("msgrcv", self.msgrcv),
+            ("nsignals", self.nsignals),
+            ("nvcsw", self.nvcsw),
+            ("nivcsw", self.nivcsw),
+        ];
+        for (key, value) in fields {
+            let text = match value {
+                Some(value) => value.to_string(),
+                None => "none".to_owned(),
+            };
+            reporter.report_value(key, &text, json!(value));
+        }
+
+        reporter.finish()
+    }
+}
+
+fn read_proc(pid: i32, name: &str) -> anyhow::Result<String> {
+    let path = format!("/proc/{}/{}", pid, name);
+    std::fs::read_to_string(&path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} does not exist", pid),
+        _ => anyhow!(err).context(format!("Failed to read {}", path)),
+    })
+}
+
+fn handle_command(command: &[OsString], format: OutputFormat) -> anyhow::Result<ExitCode> {
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))?;
+
+    // The command is the only child that has been waited for
+    ResourceUsage::get(UsageWho::RUSAGE_CHILDREN)?.print(format, true)?;
+
+    Ok(exit_code(status))
+}
+
+fn handle_pid(pid: i32, watch: Option<u64>, format: OutputFormat) -> anyhow::Result<()> {
+    let ticks_per_second = sysconf(SysconfVar::CLK_TCK)
+        .context("Failed to get the clock ticks per second")?
+        .ok_or_else(|| anyhow!("The clock ticks per second is unknown"))?
+        as f64;
+
+    ResourceUsage::read(pid, ticks_per_second)?.print(format, false)?;
+    let watch = match watch {
+        Some(watch) => watch,
+        None => return Ok(()),
+    };
+    loop {
+        std::thread::sleep(Duration::from_secs(watch));
+        let usage = match ResourceUsage::read(pid, ticks_per_second) {
+            Ok(usage) => usage,
+            Err(err) => {
+                eprintln!("{:#}", err);
+                return Ok(());
+            }
+        };
+        if format == OutputFormat::Text {
+            println!();
+        }
+        usage.print(format, false)?;
+    }
+}
+
+fn handle_usage(info: UsageInfo) -> anyhow::Result<ExitCode> {
+    if !info.command.is_empty() {
+        return handle_command(&info.command, info.format());
+    }
+    if let Some(pid) = info.pid {
+        return handle_pid(pid, info.watch, info.format()).map(|_| ExitCode::SUCCESS);
+    }
+
+    // clap requires COMMAND, --pid or --who
+    let who = info.who.unwrap();
+    ResourceUsage::get(who.usage_who())?.print(info.format(), false)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_usage(cli.usage),
+    }
+}
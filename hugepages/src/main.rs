@@ -0,0 +1,384 @@
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::mem::page_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use std::ffi::c_void;
+use std::fs::File;
+use std::os::raw::c_int;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+/// MADV_COLLAPSE from linux/mman.h, which libc may not define yet
+const MADV_COLLAPSE: c_int = 25;
+
+const THP_DIR: &str = "/sys/kernel/mm/transparent_hugepage";
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the system-wide THP policy and statistics
+    #[clap(display_order = 1, name = "status")]
+    Status,
+    /// Map a file or anonymous memory, apply advice and print its huge page usage
+    #[clap(display_order = 2, name = "advise")]
+    Advise(AdviseInfo),
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("region").required(true).args(&["filename", "anonymous"])))]
+struct AdviseInfo {
+    /// File to map read-only
+    #[clap(value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Map anonymous memory of the size instead (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, value_parser = parse_size, value_name = "SIZE")]
+    anonymous: Option<i64>,
+    /// Advice to apply to the mapping
+    #[clap(short = 'A', long, arg_enum, default_value = "hugepage")]
+    advice: Advice,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Advice {
+    /// Allow huge pages with MADV_HUGEPAGE, even when THP is only enabled with madvise
+    #[clap(name = "hugepage")]
+    HugePage,
+    /// Prevent huge pages with MADV_NOHUGEPAGE
+    #[clap(name = "nohugepage")]
+    NoHugePage,
+    /// Collapse the pages into huge pages synchronously with MADV_COLLAPSE
+    #[clap(name = "collapse")]
+    Collapse,
+}
+
+impl std::fmt::Display for Advice {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Advice::HugePage => write!(f, "MADV_HUGEPAGE"),
+            Advice::NoHugePage => write!(f, "MADV_NOHUGEPAGE"),
+            Advice::Collapse => write!(f, "MADV_COLLAPSE"),
+        }
+    }
+}
+
+impl Advice {
+    fn raw(self) -> c_int {
+        match self {
+            Advice::HugePage => libc::MADV_HUGEPAGE,
+            Advice::NoHugePage => libc::MADV_NOHUGEPAGE,
+            Advice::Collapse => MADV_COLLAPSE,
+        }
+    }
+}
+
+fn read_thp_file(name: &str) -> anyhow::Result<String> {
+    let path = Path::new(THP_DIR).join(name);
+    std::fs::read_to_string(&path)
+        .map(|content| content.trim().to_owned())
+        .with_context(|| format!("Failed to read {}", path.display()))
+}
+
+/// Returns the selected value of a policy such as `always [madvise] never`.
+fn selected_policy(content: &str) -> &str {
+    content
+        .split_whitespace()
+        .find_map(|value| value.strip_prefix('[')?.strip_suffix(']'))
+        .unwrap_or(content)
+}
+
+fn pmd_size() -> anyhow::Result<usize> {
+    let content = read_thp_file("hpage_pmd_size")?;
+    content
+        .parse()
+        .with_context(|| format!("Unexpected hpage_pmd_size: {}", content))
+}
+
+fn handle_status() -> anyhow::Result<()> {
+    if !Path::new(THP_DIR).exists() {
+        return Err(anyhow!(
+            "{} does not exist; THP requires a kernel built with CONFIG_TRANSPARENT_HUGEPAGE",
+            THP_DIR
+        ));
+    }
+
+    for name in ["enabled", "defrag", "shmem_enabled"] {
+        let content = read_thp_file(name)?;
+        println!("{}: {}", name, selected_policy(&content));
+    }
+    println!("hpage_pmd_size: {}", pmd_size()?);
+    for name in [
+        "khugepaged/pages_to_scan",
+        "khugepaged/scan_sleep_millisecs",
+        "khugepaged/full_scans",
+        "khugepaged/pages_collapsed",
+    ] {
+        let key = name.replace('/', "_");
+        match read_thp_file(name) {
+            Ok(value) => println!("{}: {}", key, value),
+            Err(_) => println!("{}: none", key),
+        }
+    }
+
+    Ok(())
+}
+
+/// The huge page usage of a mapping in /proc/self/smaps, in kB.
+#[derive(Default)]
+struct Usage {
+    rss: u64,
+    anon_huge_pages: u64,
+    file_pmd_mapped: u64,
+    thp_eligible: u64,
+}
+
+fn read_usage(start: usize) -> anyhow::Result<Usage> {
+    let path = "/proc/self/smaps";
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+
+    let mut usage = None;
+    for line in content.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(field) => field,
+            None => continue,
+        };
+        // A header line starts a new mapping
+        if let Some((range_start, range_end)) = key.split_once('-') {
+            if key.contains(' ') {
+                if usage.is_some() {
+                    break;
+                }
+                // The mapping may have merged with an adjacent one of the same flags
+                let range_end = range_end.split(' ').next().unwrap_or_default();
+                let contains = match (
+                    usize::from_str_radix(range_start, 16),
+                    usize::from_str_radix(range_end, 16),
+                ) {
+                    (Ok(range_start), Ok(range_end)) => (range_start..range_end).contains(&start),
+                    _ => false,
+                };
+                if contains {
+                    usage = Some(Usage::default());
+                }
+                continue;
+            }
+        }
+        let usage = match &mut usage {
+            Some(usage) => usage,
+            None => continue,
+        };
+        let value = value.trim();
+        let value = value
+            .strip_suffix(" kB")
+            .unwrap_or(value)
+            .parse()
+            .unwrap_or(0);
+        match key {
+            "Rss" => usage.rss = value,
+            "AnonHugePages" => usage.anon_huge_pages = value,
+            "FilePmdMapped" => usage.file_pmd_mapped = value,
+            "THPeligible" => usage.thp_eligible = value,
+            _ => {}
+        }
+    }
+
+    usage.ok_or_else(|| anyhow!("The mapping at {:#x} is not in {}", start, path))
+}
+
+/// A mapping aligned to the huge page size, unmapped on drop.
+struct Mapping {
+    start: usize,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(file: Option<&File>, len: usize, pmd_size: usize) -> nix::Result<Self> {
+        // Huge pages require the addresses aligned to their size, so an aligned range is
+        // chosen from a larger reservation and the rest of the reservation is unmapped
+        let reserved_len = len + pmd_size;
+        // SAFETY: a new mapping does not alias any memory.
+        let reserved = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                reserved_len,
+                ProtFlags::PROT_NONE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        }? as usize;
+        let start = (reserved + pmd_size - 1) / pmd_size * pmd_size;
+
+        let (prot, flags, fd) = match file {
+            Some(file) => (
+                ProtFlags::PROT_READ,
+                MapFlags::MAP_PRIVATE,
+                file.as_raw_fd(),
+            ),
+            None => (
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                -1,
+            ),
+        };
+        // SAFETY: the range is within the reservation, which nothing else uses.
+        let mapped = unsafe {
+            mmap(
+                start as *mut c_void,
+                len,
+                prot,
+                flags | MapFlags::MAP_FIXED,
+                fd,
+                0,
+            )
+        };
+        let head = start - reserved;
+        let tail = reserved_len - head - len;
+        // SAFETY: the ranges are the rest of the reservation, which nothing uses.
+        unsafe {
+            if head > 0 {
+                let _ = munmap(reserved as *mut c_void, head);
+            }
+            if tail > 0 {
+                let _ = munmap((start + len) as *mut c_void, tail);
+            }
+        }
+        if let Err(errno) = mapped {
+            // SAFETY: the aligned range is still reserved and nothing uses it.
+            let _ = unsafe { munmap(start as *mut c_void, len) };
+            return Err(errno);
+        }
+
+        Ok(Mapping { start, len })
+    }
+
+    /// Faults in every page to allocate memory or read the file.
+    fn touch(&self, write: bool, page_size: usize) {
+        for offset in (0..self.len).step_by(page_size) {
+            let ptr = (self.start + offset) as *mut u8;
+            // SAFETY: the page is within the aligned part of the mapping, which is writable when
+            // write is true.
+            unsafe {
+                if write {
+                    std::ptr::write_volatile(ptr, 1);
+                } else {
+                    std::ptr::read_volatile(ptr);
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is no longer used.
+        let _ = unsafe { munmap(self.start as *mut c_void, self.len) };
+    }
+}
+
+fn describe(errno: Errno, advice: Advice) -> anyhow::Error {
+    match (errno, advice) {
+        (Errno::EINVAL, Advice::Collapse) => {
+            anyhow!(
+                "MADV_COLLAPSE requires Linux 6.1 or later and a mapping eligible for THP; files also require CONFIG_READ_ONLY_THP_FOR_FS"
+            )
+        }
+        (Errno::EINVAL, _) => {
+            anyhow!("The kernel is not built with CONFIG_TRANSPARENT_HUGEPAGE")
+        }
+        (Errno::EAGAIN, Advice::Collapse) => {
+            anyhow!("The kernel failed to collapse the pages temporarily; try again")
+        }
+        (Errno::ENOMEM, Advice::Collapse) => {
+            anyhow!("The kernel failed to allocate huge pages to collapse into")
+        }
+        (errno, _) => anyhow!(errno),
+    }
+}
+
+fn handle_advise(info: AdviseInfo) -> anyhow::Result<()> {
+    let pmd_size = pmd_size()?;
+    let page_size = page_size()? as usize;
+
+    let file = match &info.filename {
+        Some(filename) => Some(
+            File::open(filename)
+                .with_context(|| format!("Failed to open '{}'", filename.display()))?,
+        ),
+        None => None,
+    };
+    let len = match (&file, info.anonymous) {
+        (Some(file), _) => file.metadata().context("Failed to stat the file")?.len() as usize,
+        // clap requires FILE or --anonymous
+        (None, anonymous) => anonymous.unwrap() as usize,
+    };
+    if len == 0 {
+        return Err(anyhow!("The size of the mapping must be larger than 0"));
+    }
+    if len < pmd_size {
+        eprintln!(
+            "warning: the mapping is smaller than the huge page size of {} bytes",
+            pmd_size
+        );
+    }
+
+    let mapping = Mapping::new(file.as_ref(), len, pmd_size).context("Failed to map the region")?;
+    // MADV_COLLAPSE works on pages already present, while the others apply to faults afterwards
+    if info.advice == Advice::Collapse {
+        mapping.touch(file.is_none(), page_size);
+    }
+    // The raw system call passes MADV_COLLAPSE through even if the C library does not know it
+    // SAFETY: the range is within the mapping, which only this function uses.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_madvise,
+            mapping.start as *mut c_void,
+            len,
+            info.advice.raw(),
+        )
+    };
+    Errno::result(ret)
+        .map_err(|errno| describe(errno, info.advice))
+        .with_context(|| format!("Failed to apply {}", info.advice))?;
+    if info.advice != Advice::Collapse {
+        mapping.touch(file.is_none(), page_size);
+    }
+
+    let usage = read_usage(mapping.start)?;
+    println!("address: {:#x}", mapping.start);
+    println!("length: {}", len);
+    println!("advice: {}", info.advice);
+    println!("rss: {} kB", usage.rss);
+    println!("anon_huge_pages: {} kB", usage.anon_huge_pages);
+    println!("file_pmd_mapped: {} kB", usage.file_pmd_mapped);
+    println!("thp_eligible: {}", usage.thp_eligible);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Status => handle_status(),
+        Commands::Advise(info) => handle_advise(info),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,303 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+use std::collections::HashMap;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Flags with a single bit and the names printed for them
+const EVENT_NAMES: &[(AddWatchFlags, &str)] = &[
+    (AddWatchFlags::IN_ACCESS, "ACCESS"),
+    (AddWatchFlags::IN_MODIFY, "MODIFY"),
+    (AddWatchFlags::IN_ATTRIB, "ATTRIB"),
+    (AddWatchFlags::IN_CLOSE_WRITE, "CLOSE_WRITE"),
+    (AddWatchFlags::IN_CLOSE_NOWRITE, "CLOSE_NOWRITE"),
+    (AddWatchFlags::IN_OPEN, "OPEN"),
+    (AddWatchFlags::IN_MOVED_FROM, "MOVED_FROM"),
+    (AddWatchFlags::IN_MOVED_TO, "MOVED_TO"),
+    (AddWatchFlags::IN_CREATE, "CREATE"),
+    (AddWatchFlags::IN_DELETE, "DELETE"),
+    (AddWatchFlags::IN_DELETE_SELF, "DELETE_SELF"),
+    (AddWatchFlags::IN_MOVE_SELF, "MOVE_SELF"),
+    (AddWatchFlags::IN_UNMOUNT, "UNMOUNT"),
+    (AddWatchFlags::IN_Q_OVERFLOW, "Q_OVERFLOW"),
+    (AddWatchFlags::IN_IGNORED, "IGNORED"),
+    (AddWatchFlags::IN_ISDIR, "ISDIR"),
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    watch: WatchInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct WatchInfo {
+    /// Files or directories to watch
+    #[clap(required = true, value_parser, value_name = "PATH")]
+    paths: Vec<PathBuf>,
+    /// Events to watch, separated by commas [default: all]
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true,
+        value_name = "EVENTS"
+    )]
+    events: Vec<Event>,
+    /// Also watch every subdirectory, including ones created later
+    #[clap(short, long)]
+    recursive: bool,
+    /// Exit after the number of seconds
+    #[clap(short, long, value_parser, value_name = "SECONDS")]
+    timeout: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Event {
+    #[clap(name = "access")]
+    Access,
+    #[clap(name = "modify")]
+    Modify,
+    #[clap(name = "attrib")]
+    Attrib,
+    #[clap(name = "close_write")]
+    CloseWrite,
+    #[clap(name = "close_nowrite")]
+    CloseNowrite,
+    #[clap(name = "close")]
+    Close,
+    #[clap(name = "open")]
+    Open,
+    #[clap(name = "moved_from")]
+    MovedFrom,
+    #[clap(name = "moved_to")]
+    MovedTo,
+    #[clap(name = "move")]
+    Move,
+    #[clap(name = "create")]
+    Create,
+    #[clap(name = "delete")]
+    Delete,
+    #[clap(name = "delete_self")]
+    DeleteSelf,
+    #[clap(name = "move_self")]
+    MoveSelf,
+    #[clap(name = "all")]
+    All,
+}
+
+impl Event {
+    fn flags(self) -> AddWatchFlags {
+        match self {
+            Event::Access => AddWatchFlags::IN_ACCESS,
+            Event::Modify => AddWatchFlags::IN_MODIFY,
+            Event::Attrib => AddWatchFlags::IN_ATTRIB,
+            Event::CloseWrite => AddWatchFlags::IN_CLOSE_WRITE,
+            Event::CloseNowrite => AddWatchFlags::IN_CLOSE_NOWRITE,
+            Event::Close => AddWatchFlags::IN_CLOSE,
+            Event::Open => AddWatchFlags::IN_OPEN,
+            Event::MovedFrom => AddWatchFlags::IN_MOVED_FROM,
+            Event::MovedTo => AddWatchFlags::IN_MOVED_TO,
+            Event::Move => AddWatchFlags::IN_MOVE,
+            Event::Create => AddWatchFlags::IN_CREATE,
+            Event::Delete => AddWatchFlags::IN_DELETE,
+            Event::DeleteSelf => AddWatchFlags::IN_DELETE_SELF,
+            Event::MoveSelf => AddWatchFlags::IN_MOVE_SELF,
+            Event::All => AddWatchFlags::IN_ALL_EVENTS,
+        }
+    }
+}
+
+fn format_mask(mask: AddWatchFlags) -> String {
+    EVENT_NAMES
+        .iter()
+        .filter(|(flag, _)| mask.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+struct Watcher {
+    inotify: Inotify,
+    /// Events requested by the user
+    events: AddWatchFlags,
+    recursive: bool,
+    watches: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl Watcher {
+    fn new(events: AddWatchFlags, recursive: bool) -> anyhow::Result<Self> {
+        let inotify =
+            Inotify::init(InitFlags::IN_CLOEXEC).context("Failed to initialize inotify")?;
+
+        Ok(Watcher {
+            inotify,
+            events,
+            recursive,
+            watches: HashMap::new(),
+        })
+    }
+
+    fn watch_flags(&self) -> AddWatchFlags {
+        // The self events are always needed to drop watches of removed paths,
+        // and CREATE to follow new subdirectories.
+        let mut flags = self.events | AddWatchFlags::IN_DELETE_SELF | AddWatchFlags::IN_MOVE_SELF;
+        if self.recursive {
+            flags |= AddWatchFlags::IN_CREATE;
+        }
+        flags
+    }
+
+    fn add(&mut self, path: &Path) -> anyhow::Result<()> {
+        let wd = self
+            .inotify
+            .add_watch(path, self.watch_flags())
+            .map_err(|errno| match errno {
+                Errno::ENOSPC => anyhow!(
+                    "The limit of inotify watches is reached; see /proc/sys/fs/inotify/max_user_watches"
+                ),
+                errno => anyhow!(errno),
+            })
+            .with_context(|| format!("Failed to watch '{}'", path.display()))?;
+        self.watches.insert(wd, path.to_owned());
+
+        if self.recursive && path.is_dir() {
+            let entries = std::fs::read_dir(path)
+                .with_context(|| format!("Failed to read directory '{}'", path.display()))?;
+            for entry in entries {
+                let entry = entry
+                    .with_context(|| format!("Failed to read directory '{}'", path.display()))?;
+                // file_type does not follow symbolic links, so cycles are not possible
+                if entry.file_type().map_or(false, |t| t.is_dir()) {
+                    self.add(&entry.path())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, wd: WatchDescriptor) {
+        if self.watches.remove(&wd).is_some() {
+            // The kernel may have already removed it
+            let _ = self.inotify.rm_watch(wd);
+        }
+    }
+
+    /// Prints an event and updates the watches for it.
+    fn handle(&mut self, event: InotifyEvent) {
+        if event.mask.contains(AddWatchFlags::IN_Q_OVERFLOW) {
+            eprintln!("warning: the event queue overflowed; some events are lost");
+            return;
+        }
+        let path = match self.watches.get(&event.wd) {
+            Some(path) => path.clone(),
+            // An event queued before its watch was removed
+            None => return,
+        };
+        let name = event.name.unwrap_or_default();
+
+        if event.mask.intersects(self.events) {
+            println!(
+                "{}\t{}\t{}",
+                format_mask(event.mask),
+                path.display(),
+                name.to_string_lossy()
+            );
+        }
+
+        if event.mask.intersects(
+            AddWatchFlags::IN_DELETE_SELF | AddWatchFlags::IN_MOVE_SELF | AddWatchFlags::IN_IGNORED,
+        ) {
+            self.remove(event.wd);
+        } else if self.recursive
+            && event
+                .mask
+                .contains(AddWatchFlags::IN_CREATE | AddWatchFlags::IN_ISDIR)
+        {
+            let subdirectory = path.join(&name);
+            if let Err(err) = self.add(&subdirectory) {
+                eprintln!("warning: {:#}", err);
+            }
+        }
+    }
+}
+
+fn handle_watch(info: WatchInfo) -> anyhow::Result<()> {
+    let events = if info.events.is_empty() {
+        AddWatchFlags::IN_ALL_EVENTS
+    } else {
+        info.events
+            .iter()
+            .fold(AddWatchFlags::empty(), |flags, event| flags | event.flags())
+    };
+    let deadline = info
+        .timeout
+        .map(|timeout| Instant::now() + Duration::from_secs(timeout));
+
+    let mut watcher = Watcher::new(events, info.recursive)?;
+    for path in &info.paths {
+        watcher.add(path)?;
+    }
+
+    while !watcher.watches.is_empty() {
+        // -1 means no timeout
+        let timeout_ms = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    break;
+                }
+                i32::try_from(remaining.as_millis()).unwrap_or(i32::MAX)
+            }
+            None => -1,
+        };
+        let mut fds = [PollFd::new(watcher.inotify.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, timeout_ms) {
+            Ok(0) | Err(Errno::EINTR) => continue,
+            Ok(_) => {}
+            Err(errno) => return Err(errno).context("Failed to wait for events"),
+        }
+
+        let events = watcher
+            .inotify
+            .read_events()
+            .context("Failed to read events")?;
+        for event in events {
+            watcher.handle(event);
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_watch(cli.watch),
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::OutputFormat;
+use linux_tools_core::output::new_reporter;
+use linux_tools_core::print_completer;
+use serde_json::{json, Map, Value};
+use std::io::ErrorKind;
+use std::time::{Duration, Instant};
+
+/// Names of the counters in /proc/PID/io, in the order of the columns
+const COUNTER_NAMES: [&str; 7] = [
+    "rchar",
+    "wchar",
+    "syscr",
+    "syscw",
+    "read_bytes",
+    "write_bytes",
+    "cancelled_write_bytes",
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    accounting: AccountingInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct AccountingInfo {
+    /// Processes to print the I/O statistics of
+    #[clap(required = true, value_parser, value_name = "PID")]
+    pids: Vec<i32>,
+    /// Print the changes per second every number of seconds until interrupted
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "SECONDS"
+    )]
+    watch: Option<u64>,
+    /// Format of the result, where JSON is an object in a line for each sample
+    #[clap(short = 'O', long, arg_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Print the result in JSON, the same as --output-format json
+    #[clap(short, long, conflicts_with = "output-format")]
+    json: bool,
+}
+
+impl AccountingInfo {
+    fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output_format
+        }
+    }
+}
+
+/// The counters of /proc/PID/io in the order of COUNTER_NAMES.
+#[derive(Clone, Copy, Default)]
+struct Counters([u64; 7]);
+
+fn read_counters(pid: i32) -> anyhow::Result<Counters> {
+    let path = format!("/proc/{}/io", pid);
+    let content = std::fs::read_to_string(&path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} does not exist", pid),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Reading {} requires root, or the same UID as process {} with the permission to ptrace it",
+            path,
+            pid
+        ),
+        _ => anyhow!(err).context(format!("Failed to read {}", path)),
+    })?;
+
+    let mut counters = Counters::default();
+    for line in content.lines() {
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("Unexpected line in {}: {}", path, line))?;
+        if let Some(index) = COUNTER_NAMES.iter().position(|&name| name == key) {
+            counters.0[index] = value
+                .trim()
+                .parse()
+                .with_context(|| format!("Unexpected line in {}: {}", path, line))?;
+        }
+    }
+
+    Ok(counters)
+}
+
+/// A row of a sample, holding totals or changes per second.
+struct Row {
+    pid: i32,
+    rchar: u64,
+    wchar: u64,
+    syscr: u64,
+    syscw: u64,
+    read_bytes: u64,
+    write_bytes: u64,
+    cancelled_write_bytes: u64,
+}
+
+impl Row {
+    fn new(pid: i32, values: [u64; 7]) -> Self {
+        Row {
+            pid,
+            rchar: values[0],
+            wchar: values[1],
+            syscr: values[2],
+            syscw: values[3],
+            read_bytes: values[4],
+            write_bytes: values[5],
+            cancelled_write_bytes: values[6],
+        }
+    }
+
+    fn values(&self) -> [u64; 7] {
+        [
+            self.rchar,
+            self.wchar,
+            self.syscr,
+            self.syscw,
+            self.read_bytes,
+            self.write_bytes,
+            self.cancelled_write_bytes,
+        ]
+    }
+
+    fn to_json(&self) -> Value {
+        let mut object = Map::new();
+        object.insert("pid".to_owned(), json!(self.pid));
+        for (name, value) in COUNTER_NAMES.iter().zip(self.values()) {
+            object.insert((*name).to_owned(), json!(value));
+        }
+        Value::Object(object)
+    }
+}
+
+struct Sample {
+    /// Seconds since the first sample
+    elapsed: f64,
+    /// Whether the values are changes per second rather than totals
+    per_second: bool,
+    processes: Vec<Row>,
+}
+
+fn print_sample(sample: &Sample, format: OutputFormat) -> anyhow::Result<()> {
+    // The processes are an array in JSON, which has no text form other than the table
+    if format == OutputFormat::Json {
+        let mut reporter = new_reporter(format, std::io::stdout());
+        reporter.report_value("elapsed", &sample.elapsed, json!(sample.elapsed));
+        reporter.report_value("per_second", &sample.per_second, json!(sample.per_second));
+        let processes: Vec<Value> = sample.processes.iter().map(Row::to_json).collect();
+        reporter.report_value(
+            "processes",
+            &sample.processes.len(),
+            Value::Array(processes),
+        );
+        return reporter.finish();
+    }
+
+    let suffix = if sample.per_second { "/s" } else { "" };
+    let mut header = format!("{:>8}", "PID");
+    for name in COUNTER_NAMES {
+        let name = format!("{}{}", name.to_ascii_uppercase(), suffix);
+        header.push_str(&format!(" {:>width$}", name, width = column_width(&name)));
+    }
+    println!("{}", header);
+    for row in &sample.processes {
+        let mut line = format!("{:>8}", row.pid);
+        for (name, value) in COUNTER_NAMES.iter().zip(row.values()) {
+            let width = column_width(&format!("{}{}", name, suffix));
+            line.push_str(&format!(" {:>width$}", value, width = width));
+        }
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+/// Wide enough for the header and a value of 100 TB.
+fn column_width(name: &str) -> usize {
+    name.len().max(15)
+}
+
+fn handle_accounting(info: AccountingInfo) -> anyhow::Result<()> {
+    let start = Instant::now();
+    let mut previous = info
+        .pids
+        .iter()
+        .map(|&pid| Ok((pid, read_counters(pid)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    print_sample(
+        &Sample {
+            elapsed: 0.0,
+            per_second: false,
+            processes: previous
+                .iter()
+                .map(|&(pid, counters)| Row::new(pid, counters.0))
+                .collect(),
+        },
+        info.format(),
+    )?;
+
+    let watch = match info.watch {
+        Some(watch) => watch,
+        None => return Ok(()),
+    };
+    let mut last = start;
+    loop {
+        std::thread::sleep(Duration::from_secs(watch));
+        let now = Instant::now();
+        let seconds = now.duration_since(last).as_secs_f64();
+        last = now;
+
+        let mut current = Vec::with_capacity(previous.len());
+        let mut rows = Vec::with_capacity(previous.len());
+        for &(pid, before) in &previous {
+            let after = match read_counters(pid) {
+                Ok(counters) => counters,
+                Err(err) => {
+                    eprintln!("warning: {:#}; it is no longer printed", err);
+                    continue;
+                }
+            };
+            let mut rates = [0; 7];
+            for (rate, (after, before)) in rates.iter_mut().zip(after.0.iter().zip(before.0)) {
+                *rate = (after.saturating_sub(before) as f64 / seconds).round() as u64;
+            }
+            rows.push(Row::new(pid, rates));
+            current.push((pid, after));
+        }
+        if current.is_empty() {
+            return Err(anyhow!("None of the processes can be read any longer"));
+        }
+        previous = current;
+
+        if info.format() == OutputFormat::Text {
+            println!();
+        }
+        print_sample(
+            &Sample {
+                elapsed: now.duration_since(start).as_secs_f64(),
+                per_second: true,
+                processes: rows,
+            },
+            info.format(),
+        )?;
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_accounting(cli.accounting),
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+
+const IOPRIO_CLASS_SHIFT: i32 = 13;
+const IOPRIO_PRIO_MASK: i32 = (1 << IOPRIO_CLASS_SHIFT) - 1;
+
+const IOPRIO_WHO_PROCESS: i32 = 1;
+const IOPRIO_WHO_PGRP: i32 = 2;
+const IOPRIO_WHO_USER: i32 = 3;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Set the I/O scheduling class and priority
+    #[clap(display_order = 1, name = "set")]
+    Set {
+        /// I/O scheduling class
+        #[clap(short, long, arg_enum)]
+        class: IoClass,
+        /// Priority within the class, from 0 (highest) to 7 (lowest) [default: 4]
+        #[clap(short, long, value_parser = clap::value_parser!(i32).range(0..=7))]
+        level: Option<i32>,
+        #[clap(flatten)]
+        target: Target,
+    },
+    /// Print the I/O scheduling class and priority
+    #[clap(display_order = 2, name = "get")]
+    Get {
+        #[clap(flatten)]
+        target: Target,
+        /// Print a table of every thread in the process
+        #[clap(short, long, conflicts_with_all = &["pgid", "uid"])]
+        threads: bool,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("target").args(&["pid", "pgid", "uid"])))]
+struct Target {
+    /// Target a process [default: this process]
+    #[clap(short, long, value_parser)]
+    pid: Option<i32>,
+    /// Target all processes in a process group
+    #[clap(short = 'P', long, value_parser)]
+    pgid: Option<i32>,
+    /// Target all processes of a user
+    #[clap(short, long, value_parser)]
+    uid: Option<i32>,
+}
+
+impl Target {
+    fn which_who(&self) -> (i32, i32) {
+        match (self.pid, self.pgid, self.uid) {
+            (_, Some(pgid), _) => (IOPRIO_WHO_PGRP, pgid),
+            (_, _, Some(uid)) => (IOPRIO_WHO_USER, uid),
+            // 0 means the calling process
+            (pid, _, _) => (IOPRIO_WHO_PROCESS, pid.unwrap_or(0)),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (self.pid, self.pgid, self.uid) {
+            (_, Some(pgid), _) => write!(f, "process group {}", pgid),
+            (_, _, Some(uid)) => write!(f, "user {}", uid),
+            (Some(pid), _, _) => write!(f, "process {}", pid),
+            (None, _, _) => write!(f, "this process"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum IoClass {
+    None,
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+impl std::fmt::Display for IoClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            IoClass::None => write!(f, "none"),
+            IoClass::Realtime => write!(f, "realtime"),
+            IoClass::BestEffort => write!(f, "best-effort"),
+            IoClass::Idle => write!(f, "idle"),
+        }
+    }
+}
+
+impl IoClass {
+    fn raw(self) -> i32 {
+        match self {
+            IoClass::None => 0,
+            IoClass::Realtime => 1,
+            IoClass::BestEffort => 2,
+            IoClass::Idle => 3,
+        }
+    }
+
+    fn from_raw(class: i32) -> Option<Self> {
+        match class {
+            0 => Some(IoClass::None),
+            1 => Some(IoClass::Realtime),
+            2 => Some(IoClass::BestEffort),
+            3 => Some(IoClass::Idle),
+            _ => None,
+        }
+    }
+}
+
+fn ioprio_get(which: i32, who: i32) -> nix::Result<i32> {
+    // SAFETY: ioprio_get only takes integer arguments.
+    let ret = unsafe { libc::syscall(libc::SYS_ioprio_get, which, who) };
+    Errno::result(ret).map(|ioprio| ioprio as i32)
+}
+
+fn ioprio_set(which: i32, who: i32, ioprio: i32) -> nix::Result<()> {
+    // SAFETY: ioprio_set only takes integer arguments.
+    let ret = unsafe { libc::syscall(libc::SYS_ioprio_set, which, who, ioprio) };
+    Errno::result(ret).map(drop)
+}
+
+fn handle_set(class: IoClass, level: Option<i32>, target: Target) -> anyhow::Result<()> {
+    let level = match class {
+        IoClass::Realtime | IoClass::BestEffort => level.unwrap_or(4),
+        IoClass::None | IoClass::Idle => {
+            if level.is_some() {
+                eprintln!("warning: --level is ignored for the {} class", class);
+            }
+            0
+        }
+    };
+
+    let (which, who) = target.which_who();
+    let ioprio = (class.raw() << IOPRIO_CLASS_SHIFT) | level;
+    ioprio_set(which, who, ioprio)
+        .with_context(|| format!("Failed to set the I/O priority of {}", target))?;
+
+    Ok(())
+}
+
+/// Splits an I/O priority into the class and the level.
+fn decode(ioprio: i32) -> anyhow::Result<(IoClass, i32)> {
+    let raw_class = ioprio >> IOPRIO_CLASS_SHIFT;
+    let class = IoClass::from_raw(raw_class)
+        .ok_or_else(|| anyhow!("Unknown I/O scheduling class {}", raw_class))?;
+
+    Ok((class, ioprio & IOPRIO_PRIO_MASK))
+}
+
+fn handle_get(target: Target) -> anyhow::Result<()> {
+    let (which, who) = target.which_who();
+    let ioprio = ioprio_get(which, who)
+        .with_context(|| format!("Failed to get the I/O priority of {}", target))?;
+
+    let (class, level) = decode(ioprio)?;
+    println!("class: {} ({})", class, class.raw());
+    match class {
+        IoClass::Realtime | IoClass::BestEffort => println!("level: {}", level),
+        IoClass::None | IoClass::Idle => println!("level: none ({})", level),
+    }
+
+    Ok(())
+}
+
+/// Returns the name of a thread from /proc/PID/task/TID/status, or None if it has exited.
+fn thread_name(pid: &str, tid: i32) -> Option<String> {
+    let status = std::fs::read_to_string(format!("/proc/{}/task/{}/status", pid, tid)).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Name:"))
+        .map(|name| name.trim().to_owned())
+}
+
+fn handle_get_threads(target: Target) -> anyhow::Result<()> {
+    let pid = match target.pid {
+        Some(pid) => pid.to_string(),
+        None => "self".to_owned(),
+    };
+    let path = format!("/proc/{}/task", pid);
+    let entries = std::fs::read_dir(&path).with_context(|| format!("Failed to read {}", path))?;
+    let mut tids = entries
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<i32>().ok())
+        .collect::<Vec<_>>();
+    tids.sort_unstable();
+
+    println!("{:>8} {:<12} {:>5}  NAME", "TID", "CLASS", "LEVEL");
+    for tid in tids {
+        let ioprio = match ioprio_get(IOPRIO_WHO_PROCESS, tid) {
+            Ok(ioprio) => ioprio,
+            // The thread has exited since listing the threads
+            Err(Errno::ESRCH) => continue,
+            Err(errno) => {
+                return Err(errno)
+                    .with_context(|| format!("Failed to get the I/O priority of thread {}", tid))
+            }
+        };
+        let name = match thread_name(&pid, tid) {
+            Some(name) => name,
+            None => continue,
+        };
+        let (class, level) = decode(ioprio)?;
+        let level = match class {
+            IoClass::Realtime | IoClass::BestEffort => level.to_string(),
+            IoClass::None | IoClass::Idle => "none".to_owned(),
+        };
+        println!(
+            "{:>8} {:<12} {:>5}  {}",
+            tid,
+            class.to_string(),
+            level,
+            name
+        );
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Set {
+            class,
+            level,
+            target,
+        } => handle_set(class, level, target),
+        Commands::Get { target, threads } => {
+            if threads {
+                handle_get_threads(target)
+            } else {
+                handle_get(target)
+            }
+        }
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
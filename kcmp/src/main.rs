@@ -0,0 +1,142 @@
+use anyhow::anyhow;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::os::unix::io::RawFd;
+use std::process::ExitCode;
+
+// Types of comparison from linux/kcmp.h
+const KCMP_FILE: i32 = 0;
+const KCMP_VM: i32 = 1;
+const KCMP_FILES: i32 = 2;
+const KCMP_FS: i32 = 3;
+const KCMP_SIGHAND: i32 = 4;
+const KCMP_IO: i32 = 5;
+const KCMP_SYSVSEM: i32 = 6;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Compare the file descriptor tables, which CLONE_FILES shares
+    #[clap(display_order = 1, name = "files")]
+    Files(Pids),
+    /// Compare the file system information such as the root and the umask, which CLONE_FS shares
+    #[clap(display_order = 2, name = "fs")]
+    Fs(Pids),
+    /// Compare the I/O contexts, which CLONE_IO shares
+    #[clap(display_order = 3, name = "io")]
+    Io(Pids),
+    /// Compare the tables of signal handlers, which CLONE_SIGHAND shares
+    #[clap(display_order = 4, name = "sighand")]
+    Sighand(Pids),
+    /// Compare the lists of System V semaphore undo operations, which CLONE_SYSVSEM shares
+    #[clap(display_order = 5, name = "sysvsem")]
+    Sysvsem(Pids),
+    /// Compare the address spaces, which CLONE_VM shares
+    #[clap(display_order = 6, name = "vm")]
+    Vm(Pids),
+    /// Compare the open files of two file descriptors, which dup or fork shares
+    #[clap(display_order = 7, name = "file")]
+    File {
+        #[clap(flatten)]
+        pids: Pids,
+        /// File descriptor in PID1
+        #[clap(value_parser = clap::value_parser!(RawFd).range(0..))]
+        fd1: RawFd,
+        /// File descriptor in PID2
+        #[clap(value_parser = clap::value_parser!(RawFd).range(0..))]
+        fd2: RawFd,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 8, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct Pids {
+    /// First process
+    #[clap(value_parser)]
+    pid1: i32,
+    /// Second process
+    #[clap(value_parser)]
+    pid2: i32,
+}
+
+fn describe(errno: Errno, pids: &Pids) -> anyhow::Error {
+    match errno {
+        Errno::ENOSYS => anyhow!("kcmp(2) requires a kernel built with CONFIG_KCMP"),
+        Errno::EPERM => anyhow!(
+            "Comparing the processes requires the permission to ptrace both {} and {}",
+            pids.pid1,
+            pids.pid2
+        ),
+        Errno::ESRCH => anyhow!("Process {} or {} does not exist", pids.pid1, pids.pid2),
+        Errno::EBADF => anyhow!("The file descriptor is not open in the process"),
+        errno => anyhow!(errno),
+    }
+}
+
+fn kcmp(pids: &Pids, type_: i32, idx1: u64, idx2: u64) -> anyhow::Result<i64> {
+    // SAFETY: kcmp only takes integer arguments for these types.
+    let ret = unsafe { libc::syscall(libc::SYS_kcmp, pids.pid1, pids.pid2, type_, idx1, idx2) };
+    Errno::result(ret).map_err(|errno| describe(errno, pids))
+}
+
+fn handle_kcmp(
+    name: &str,
+    pids: &Pids,
+    type_: i32,
+    idx1: u64,
+    idx2: u64,
+) -> anyhow::Result<ExitCode> {
+    let ret = kcmp(pids, type_, idx1, idx2)?;
+    // 1 and 2 order the resources by their obfuscated kernel addresses, and 3 means different but unordered
+    if ret == 0 {
+        println!("{}: identical ({})", name, ret);
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("{}: different ({})", name, ret);
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<ExitCode> {
+    match cli.command {
+        Commands::Files(pids) => handle_kcmp("files", &pids, KCMP_FILES, 0, 0),
+        Commands::Fs(pids) => handle_kcmp("fs", &pids, KCMP_FS, 0, 0),
+        Commands::Io(pids) => handle_kcmp("io", &pids, KCMP_IO, 0, 0),
+        Commands::Sighand(pids) => handle_kcmp("sighand", &pids, KCMP_SIGHAND, 0, 0),
+        Commands::Sysvsem(pids) => handle_kcmp("sysvsem", &pids, KCMP_SYSVSEM, 0, 0),
+        Commands::Vm(pids) => handle_kcmp("vm", &pids, KCMP_VM, 0, 0),
+        Commands::File { pids, fd1, fd2 } => {
+            handle_kcmp("file", &pids, KCMP_FILE, fd1 as u64, fd2 as u64)
+        }
+        Commands::Completion { shell } => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    // Follow cmp(1), which exits with 1 for different inputs and 2 for errors
+    match run(cli) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(2)
+        }
+    }
+}
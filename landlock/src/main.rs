@@ -0,0 +1,286 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::default_command;
+use nix::errno::Errno;
+use nix::fcntl::{open, OFlag};
+use nix::sys::stat::Mode;
+use std::ffi::OsString;
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Flag of landlock_create_ruleset(2) to get the ABI version instead of creating a ruleset
+const LANDLOCK_CREATE_RULESET_VERSION: u32 = 1 << 0;
+
+/// Type of rules for file hierarchies
+const LANDLOCK_RULE_PATH_BENEATH: i32 = 1;
+
+/// Access rights with their names, LANDLOCK_ACCESS_FS_* values and the first ABI versions supporting them
+const ACCESS_RIGHTS: &[(&str, u64, i64)] = &[
+    ("execute", 1 << 0, 1),
+    ("write_file", 1 << 1, 1),
+    ("read_file", 1 << 2, 1),
+    ("read_dir", 1 << 3, 1),
+    ("remove_dir", 1 << 4, 1),
+    ("remove_file", 1 << 5, 1),
+    ("make_char", 1 << 6, 1),
+    ("make_dir", 1 << 7, 1),
+    ("make_reg", 1 << 8, 1),
+    ("make_sock", 1 << 9, 1),
+    ("make_fifo", 1 << 10, 1),
+    ("make_block", 1 << 11, 1),
+    ("make_sym", 1 << 12, 1),
+    ("refer", 1 << 13, 2),
+    ("truncate", 1 << 14, 3),
+    ("ioctl_dev", 1 << 15, 5),
+];
+
+/// Access rights which apply to files rather than directories
+const FILE_ACCESS_RIGHTS: u64 = (1 << 0) | (1 << 1) | (1 << 2) | (1 << 14) | (1 << 15);
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    sandbox: SandboxInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct SandboxInfo {
+    /// Allow the accesses, separated by commas, to PATH and beneath it (e.g. read_file,read_dir or all)
+    #[clap(
+        short,
+        long,
+        number_of_values = 2,
+        multiple_occurrences = true,
+        value_parser,
+        value_names = &["PATH", "ACCESS"]
+    )]
+    allow: Vec<OsString>,
+    /// Command to run in the sandbox [default: $SHELL or /bin/sh]
+    #[clap(value_parser, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+/// A path with the access rights allowed beneath it
+struct Rule {
+    path: PathBuf,
+    access: u64,
+}
+
+/// Parses access rights such as `read_file,read_dir`, `FS_EXECUTE` or `all`.
+fn parse_access(s: &str) -> Result<u64, String> {
+    s.split(',')
+        .map(|name| {
+            let name = name.trim().to_ascii_lowercase();
+            let name = name.strip_prefix("fs_").unwrap_or(&name);
+            if name == "all" {
+                return Ok(ACCESS_RIGHTS
+                    .iter()
+                    .fold(0, |access, &(_, bit, _)| access | bit));
+            }
+            ACCESS_RIGHTS
+                .iter()
+                .find(|(right, _, _)| *right == name)
+                .map(|&(_, bit, _)| bit)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown access '{}'; supported accesses are all, {}",
+                        name,
+                        ACCESS_RIGHTS
+                            .iter()
+                            .map(|(name, _, _)| *name)
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                })
+        })
+        .try_fold(0, |access, bit| bit.map(|bit| access | bit))
+}
+
+fn format_access(access: u64) -> String {
+    ACCESS_RIGHTS
+        .iter()
+        .filter(|&&(_, bit, _)| access & bit != 0)
+        .map(|(name, _, _)| *name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn parse_rules(allow: &[OsString]) -> anyhow::Result<Vec<Rule>> {
+    // clap gives the values of each --allow in pairs
+    allow
+        .chunks(2)
+        .map(|pair| {
+            let access = pair[1].to_string_lossy();
+            let access = parse_access(&access)
+                .map_err(|err| anyhow!("Invalid value '{}' for ACCESS: {}", access, err))?;
+            Ok(Rule {
+                path: PathBuf::from(&pair[0]),
+                access,
+            })
+        })
+        .collect()
+}
+
+#[repr(C)]
+struct RulesetAttr {
+    handled_access_fs: u64,
+}
+
+#[repr(C, packed)]
+struct PathBeneathAttr {
+    allowed_access: u64,
+    parent_fd: i32,
+}
+
+/// Returns the Landlock ABI version of the kernel.
+fn abi_version() -> anyhow::Result<i64> {
+    // SAFETY: a null attribute of size 0 is valid with LANDLOCK_CREATE_RULESET_VERSION.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            std::ptr::null::<RulesetAttr>(),
+            0usize,
+            LANDLOCK_CREATE_RULESET_VERSION,
+        )
+    };
+    match Errno::result(ret) {
+        Ok(version) => Ok(version),
+        Err(Errno::ENOSYS) => Err(anyhow!(
+            "Landlock requires Linux 5.13 or later built with CONFIG_SECURITY_LANDLOCK"
+        )),
+        Err(Errno::EOPNOTSUPP) => Err(anyhow!(
+            "Landlock is disabled; add landlock to the lsm= kernel parameter to enable it"
+        )),
+        Err(errno) => Err(errno).context("Failed to get the Landlock ABI version"),
+    }
+}
+
+fn create_ruleset(handled_access: u64) -> anyhow::Result<OwnedFd> {
+    let attr = RulesetAttr {
+        handled_access_fs: handled_access,
+    };
+    // SAFETY: attr is a valid ruleset attribute of the given size.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_create_ruleset,
+            &attr as *const RulesetAttr,
+            std::mem::size_of::<RulesetAttr>(),
+            0u32,
+        )
+    };
+    let fd = Errno::result(ret).context("Failed to create a Landlock ruleset")?;
+
+    // SAFETY: landlock_create_ruleset returns a new file descriptor owned by nobody else.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+fn add_rule(ruleset: &OwnedFd, path: &Path, access: u64) -> anyhow::Result<()> {
+    let fd = open(path, OFlag::O_PATH | OFlag::O_CLOEXEC, Mode::empty())
+        .with_context(|| format!("Failed to open '{}'", path.display()))?;
+    // SAFETY: open returns a new file descriptor owned by nobody else.
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let attr = PathBeneathAttr {
+        allowed_access: access,
+        parent_fd: fd.as_raw_fd(),
+    };
+    // SAFETY: attr is a valid path beneath attribute.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_landlock_add_rule,
+            ruleset.as_raw_fd(),
+            LANDLOCK_RULE_PATH_BENEATH,
+            &attr as *const PathBeneathAttr,
+            0u32,
+        )
+    };
+    Errno::result(ret)
+        .map(drop)
+        .with_context(|| format!("Failed to add a rule for '{}'", path.display()))
+}
+
+fn restrict_self(ruleset: &OwnedFd) -> anyhow::Result<()> {
+    // Required to restrict itself without CAP_SYS_ADMIN, and inherited by COMMAND
+    // SAFETY: PR_SET_NO_NEW_PRIVS only takes integer arguments.
+    let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    Errno::result(ret).context("Failed to set no_new_privs")?;
+
+    // SAFETY: landlock_restrict_self only takes integer arguments.
+    let ret = unsafe { libc::syscall(libc::SYS_landlock_restrict_self, ruleset.as_raw_fd(), 0u32) };
+    Errno::result(ret)
+        .map(drop)
+        .context("Failed to enforce the Landlock ruleset")
+}
+
+fn handle_sandbox(info: SandboxInfo) -> anyhow::Result<()> {
+    let rules = parse_rules(&info.allow)?;
+    let version = abi_version()?;
+    // Every access the kernel supports is handled, so that only the allowed ones are permitted
+    let supported = ACCESS_RIGHTS
+        .iter()
+        .filter(|&&(_, _, since)| since <= version)
+        .fold(0, |access, &(_, bit, _)| access | bit);
+
+    for rule in &rules {
+        let unsupported = rule.access & !supported;
+        if unsupported != 0 {
+            return Err(anyhow!(
+                "The Landlock ABI version {} of the kernel does not support {}",
+                version,
+                format_access(unsupported)
+            ));
+        }
+        let metadata = std::fs::metadata(&rule.path)
+            .with_context(|| format!("Failed to stat '{}'", rule.path.display()))?;
+        if !metadata.is_dir() && rule.access & !FILE_ACCESS_RIGHTS != 0 {
+            return Err(anyhow!(
+                "'{}' is not a directory, which only takes {}",
+                rule.path.display(),
+                format_access(FILE_ACCESS_RIGHTS & supported)
+            ));
+        }
+    }
+
+    let ruleset = create_ruleset(supported)?;
+    for rule in &rules {
+        add_rule(&ruleset, &rule.path, rule.access)?;
+    }
+    restrict_self(&ruleset)?;
+    drop(ruleset);
+
+    let command = if info.command.is_empty() {
+        default_command()
+    } else {
+        info.command
+    };
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_sandbox(cli.sandbox),
+    }
+}
@@ -0,0 +1,292 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::ppoll;
+use nix::sys::signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, SigmaskHow, Signal};
+use nix::sys::stat::{major, minor};
+use nix::sys::time::{TimeSpec, TimeValLike};
+use std::fs::{File, OpenOptions};
+use std::os::raw::c_int;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Set by the SIGIO handler when another process opens the file
+static BROKEN: AtomicBool = AtomicBool::new(false);
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the leases of the file held by any process
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// File to print the leases of
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+    },
+    /// Take a lease to check whether it can be taken, which is released on exit
+    #[clap(display_order = 2, name = "set")]
+    Set {
+        /// File to take the lease on
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+        /// Type of the lease
+        #[clap(arg_enum, value_name = "TYPE")]
+        lease_type: LeaseType,
+    },
+    /// Take a lease and hold it until another process breaks it or the timeout expires
+    #[clap(display_order = 3, name = "hold")]
+    Hold {
+        /// File to take the lease on
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+        /// Type of the lease
+        #[clap(arg_enum, value_name = "TYPE")]
+        lease_type: LeaseType,
+        /// Release the lease after the number of seconds even if it is not broken
+        #[clap(
+            short,
+            long,
+            value_parser = clap::value_parser!(u64).range(1..),
+            value_name = "SECONDS"
+        )]
+        timeout: Option<u64>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 4, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum LeaseType {
+    /// Notified when another process opens the file for writing or truncates it
+    #[clap(name = "read")]
+    Read,
+    /// Notified when another process opens the file
+    #[clap(name = "write")]
+    Write,
+}
+
+impl std::fmt::Display for LeaseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            LeaseType::Read => write!(f, "read"),
+            LeaseType::Write => write!(f, "write"),
+        }
+    }
+}
+
+impl LeaseType {
+    fn raw(self) -> c_int {
+        match self {
+            LeaseType::Read => libc::F_RDLCK,
+            LeaseType::Write => libc::F_WRLCK,
+        }
+    }
+}
+
+/// A lease in /proc/locks.
+struct Lease {
+    pid: i32,
+    lease_type: String,
+    state: String,
+}
+
+/// Reads the leases of the file from lines of /proc/locks such as
+/// `1: LEASE  ACTIVE    READ  1234 08:01:5678 0 EOF`.
+fn read_leases(filename: &Path) -> anyhow::Result<Vec<Lease>> {
+    let metadata = std::fs::metadata(filename)
+        .with_context(|| format!("Failed to stat '{}'", filename.display()))?;
+    let id = format!(
+        "{:02x}:{:02x}:{}",
+        major(metadata.dev()),
+        minor(metadata.dev()),
+        metadata.ino()
+    );
+
+    let path = "/proc/locks";
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+
+    let mut leases = Vec::new();
+    for line in content.lines() {
+        // Locks waiting for another one are prefixed with ->
+        let fields: Vec<&str> = line
+            .split_whitespace()
+            .skip(1)
+            .filter(|&field| field != "->")
+            .collect();
+        if let ["LEASE", state, lease_type, pid, lock_id, ..] = fields[..] {
+            if lock_id == id {
+                leases.push(Lease {
+                    pid: pid
+                        .parse()
+                        .with_context(|| format!("Unexpected line in {}: {}", path, line))?,
+                    lease_type: lease_type.to_ascii_lowercase(),
+                    state: state.to_ascii_lowercase(),
+                });
+            }
+        }
+    }
+
+    Ok(leases)
+}
+
+fn handle_get(filename: &Path) -> anyhow::Result<()> {
+    let leases = read_leases(filename)?;
+    if leases.is_empty() {
+        println!("none");
+        return Ok(());
+    }
+
+    println!("{:>8} {:<6} STATE", "PID", "TYPE");
+    for lease in leases {
+        println!("{:>8} {:<6} {}", lease.pid, lease.lease_type, lease.state);
+    }
+
+    Ok(())
+}
+
+fn describe(errno: Errno, lease_type: LeaseType) -> anyhow::Error {
+    match (errno, lease_type) {
+        (Errno::EAGAIN, LeaseType::Read) => {
+            anyhow!("Another process has the file open for writing")
+        }
+        (Errno::EAGAIN, LeaseType::Write) => anyhow!("Another process has the file open"),
+        (Errno::EACCES, _) => {
+            anyhow!("Taking a lease requires owning the file or CAP_LEASE")
+        }
+        (Errno::EINVAL, _) => anyhow!(
+            "The filesystem does not support leases, or they are disabled in /proc/sys/fs/leases-enable"
+        ),
+        (errno, _) => anyhow!(errno),
+    }
+}
+
+/// Opens the file and takes the lease, which lasts until the file is closed.
+fn take_lease(filename: &Path, lease_type: LeaseType) -> anyhow::Result<File> {
+    // A read lease requires the file open read-only, and a write lease requires it writable
+    let file = match lease_type {
+        LeaseType::Read => File::open(filename),
+        LeaseType::Write => OpenOptions::new().write(true).open(filename),
+    }
+    .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+
+    // SAFETY: F_SETLEASE only takes an integer argument.
+    let ret = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLEASE, lease_type.raw()) };
+    Errno::result(ret)
+        .map_err(|errno| describe(errno, lease_type))
+        .with_context(|| {
+            format!(
+                "Failed to take a {} lease on '{}'",
+                lease_type,
+                filename.display()
+            )
+        })?;
+
+    Ok(file)
+}
+
+fn print_lease(file: &File) -> anyhow::Result<()> {
+    // SAFETY: F_GETLEASE takes no argument.
+    let ret = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETLEASE) };
+    let lease = match Errno::result(ret).context("Failed to get the lease")? {
+        libc::F_RDLCK => "read",
+        libc::F_WRLCK => "write",
+        _ => "none",
+    };
+    println!("lease: {}", lease);
+
+    Ok(())
+}
+
+fn handle_set(filename: &Path, lease_type: LeaseType) -> anyhow::Result<()> {
+    let file = take_lease(filename, lease_type)?;
+    print_lease(&file)
+}
+
+extern "C" fn handle_sigio(_: c_int) {
+    BROKEN.store(true, Ordering::SeqCst);
+}
+
+fn handle_hold(
+    filename: &Path,
+    lease_type: LeaseType,
+    timeout: Option<u64>,
+) -> anyhow::Result<ExitCode> {
+    // The kernel notifies the holder of the lease with SIGIO when another process opens the
+    // file. It is blocked except while waiting so that no notification is missed.
+    let mut sigio = SigSet::empty();
+    sigio.add(Signal::SIGIO);
+    let mut unblocked = SigSet::empty();
+    nix::sys::signal::sigprocmask(SigmaskHow::SIG_BLOCK, Some(&sigio), Some(&mut unblocked))
+        .context("Failed to block SIGIO")?;
+    unblocked.remove(Signal::SIGIO);
+    let action = SigAction::new(
+        SigHandler::Handler(handle_sigio),
+        SaFlags::empty(),
+        SigSet::empty(),
+    );
+    // SAFETY: the handler only stores to an atomic variable, which is async-signal-safe.
+    unsafe { sigaction(Signal::SIGIO, &action) }.context("Failed to handle SIGIO")?;
+
+    let file = take_lease(filename, lease_type)?;
+    print_lease(&file)?;
+
+    let deadline = timeout.map(|timeout| TimeSpec::seconds(timeout as i64));
+    let result = ppoll(&mut [], deadline, Some(unblocked));
+    let broken = BROKEN.load(Ordering::SeqCst);
+    match result {
+        // The handler interrupts the wait
+        Err(Errno::EINTR) if broken => {}
+        Ok(_) => {}
+        Err(errno) => return Err(errno).context("Failed to wait for a break of the lease"),
+    }
+
+    // Releasing the lease lets the process breaking it proceed with opening the file
+    // SAFETY: F_SETLEASE only takes an integer argument.
+    let ret = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETLEASE, libc::F_UNLCK) };
+    Errno::result(ret).context("Failed to release the lease")?;
+
+    if broken {
+        eprintln!("lease broken: another process opened the file; released the lease");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        eprintln!("timeout: released the lease");
+        Ok(ExitCode::FAILURE)
+    }
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { filename } => handle_get(&filename).map(|_| ExitCode::SUCCESS),
+        Commands::Set {
+            filename,
+            lease_type,
+        } => handle_set(&filename, lease_type).map(|_| ExitCode::SUCCESS),
+        Commands::Hold {
+            filename,
+            lease_type,
+            timeout,
+        } => handle_hold(&filename, lease_type, timeout),
+        Commands::Completion { shell } => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+    }
+}
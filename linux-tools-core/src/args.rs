@@ -0,0 +1,290 @@
+//! Parsers and types of arguments shared by the tools.
+
+use anyhow::Context;
+use clap::ValueEnum;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Format of the result selected with `--output-format`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Level of the diagnostic messages selected with `--log-level`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Parses a size such as `4096`, `16K`, `1GiB` or `10MB`.
+///
+/// `K`, `M`, `G` and `T` are binary units like `KiB`, `MiB`, `GiB` and `TiB`,
+/// while `KB`, `MB`, `GB` and `TB` are decimal units.
+pub fn parse_size(s: &str) -> Result<i64, String> {
+    let digits_end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (digits, suffix) = s.split_at(digits_end);
+    if digits.is_empty() {
+        return Err(format!(
+            "'{}' is not a size; expected a number optionally followed by a unit such as K, MiB or GB",
+            s
+        ));
+    }
+
+    let multiplier: u64 = match suffix.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KIB" => 1 << 10,
+        "M" | "MIB" => 1 << 20,
+        "G" | "GIB" => 1 << 30,
+        "T" | "TIB" => 1 << 40,
+        "KB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        _ => {
+            return Err(format!(
+                "unknown unit '{}'; supported units are K, M, G, T, KiB, MiB, GiB, TiB, KB, MB, GB and TB",
+                suffix
+            ))
+        }
+    };
+
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .and_then(|n| i64::try_from(n).ok())
+        .ok_or_else(|| format!("'{}' exceeds the maximum size of {} bytes", s, i64::MAX))
+}
+
+/// Parses an address such as `0x7ffd5a3c1000`, which is hex, or `140726117568512`.
+///
+/// A number without `0x` that has a leading zero or a hex digit such as `00400000` or `7ffd`,
+/// which /proc/PID/maps prints, is rejected since reading it as decimal would silently give
+/// another address.
+pub fn parse_address(s: &str) -> Result<u64, String> {
+    let result = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None if s.len() > 1 && s.starts_with('0')
+            || s.bytes().any(|b| b.is_ascii_hexdigit() && !b.is_ascii_digit()) =>
+        {
+            return Err(format!(
+                "'{}' is ambiguous as an address; use 0x{} for hex, or remove the leading zeros for decimal",
+                s, s
+            ))
+        }
+        None => s.parse::<u64>(),
+    };
+    result.map_err(|_| {
+        format!(
+            "'{}' is not an address; expected a hex number with 0x or a decimal number",
+            s
+        )
+    })
+}
+
+/// Parses a CPU list such as `0-3,5,7` into sorted CPU indices without duplicates.
+///
+/// This is the format of `taskset -c` and of /sys/devices/system/cpu/online, whose trailing
+/// newline is ignored.
+pub fn parse_cpu_list(s: &str) -> Result<Vec<usize>, String> {
+    let parse_cpu = |cpu: &str| {
+        cpu.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("'{}' is not a CPU index", cpu))
+    };
+
+    let mut cpus = Vec::new();
+    for part in s.trim_end().split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_cpu(first)?, parse_cpu(last)?);
+                if first > last {
+                    return Err(format!("'{}' is not an ascending range", part));
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(parse_cpu(part)?),
+        }
+    }
+    cpus.sort_unstable();
+    cpus.dedup();
+
+    Ok(cpus)
+}
+
+/// Reads a list of paths from a file, or from stdin for `-`, skipping empty entries.
+///
+/// With `null`, the list is NUL-delimited as the output of `find -print0`. Otherwise it is
+/// newline-delimited unless it contains a NUL, in which case it is NUL-delimited as well.
+pub fn read_paths(list: &Path, null: bool) -> anyhow::Result<Vec<PathBuf>> {
+    let mut content = Vec::new();
+    if list == Path::new("-") {
+        std::io::stdin()
+            .read_to_end(&mut content)
+            .context("Failed to read the list of paths from stdin")?;
+    } else {
+        File::open(list)
+            .and_then(|mut file| file.read_to_end(&mut content))
+            .with_context(|| format!("Failed to read the list of paths '{}'", list.display()))?;
+    }
+
+    let delimiter = if null || content.contains(&b'\0') {
+        b'\0'
+    } else {
+        b'\n'
+    };
+    let paths = content
+        .split(|&b| b == delimiter)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| PathBuf::from(OsStr::from_bytes(entry)))
+        .collect();
+
+    Ok(paths)
+}
+
+/// Units of durations with their lengths in nanoseconds, from the largest one
+pub(crate) const UNITS: &[(&str, u64)] = &[
+    ("d", 86_400_000_000_000),
+    ("h", 3_600_000_000_000),
+    ("m", 60_000_000_000),
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+/// Index of seconds in UNITS, the unit of a number without one
+pub(crate) const SECONDS: usize = 3;
+
+/// Parses a duration in nanoseconds such as `1.5s`, `500ms`, `1d` or `2`, which means seconds.
+///
+/// This is the grammar of every duration argument of the tools.
+pub fn parse_duration(s: &str) -> Result<u64, String> {
+    let number_end = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, suffix) = s.split_at(number_end);
+    let (integer, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if integer.is_empty() && fraction.is_empty() || fraction.contains('.') {
+        return Err(format!(
+            "'{}' is not a duration; expected a number optionally followed by d, h, m, s, ms, us or ns",
+            s
+        ));
+    }
+
+    let unit = match suffix {
+        "" => UNITS[SECONDS].1,
+        _ => UNITS
+            .iter()
+            .find(|(name, _)| *name == suffix)
+            .map(|&(_, unit)| unit)
+            .ok_or_else(|| {
+                format!(
+                    "unknown unit '{}'; supported units are d, h, m, s, ms, us and ns",
+                    suffix
+                )
+            })?,
+    };
+
+    // The fraction is computed digit by digit to keep the precision of nanoseconds
+    let mut nanos = 0u64;
+    let mut scale = unit;
+    for digit in fraction.bytes() {
+        scale /= 10;
+        nanos += u64::from(digit - b'0') * scale;
+    }
+    let integer = if integer.is_empty() { "0" } else { integer };
+    integer
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+        .and_then(|n| n.checked_add(nanos))
+        .filter(|&n| i64::try_from(n).is_ok())
+        .ok_or_else(|| format!("'{}' is too long", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn size_has_binary_and_decimal_units() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("16K"), Ok(16 << 10));
+        assert_eq!(parse_size("1gib"), Ok(1 << 30));
+        assert_eq!(parse_size("10MB"), Ok(10_000_000));
+        assert!(parse_size("K").is_err());
+        assert!(parse_size("1X").is_err());
+        assert!(parse_size("8E").is_err());
+        assert!(parse_size("9223372036854775808").is_err());
+    }
+
+    #[test]
+    fn address_is_hex_with_0x_or_decimal() {
+        assert_eq!(parse_address("0x7ffd5a3c1000"), Ok(0x7ffd_5a3c_1000));
+        assert_eq!(parse_address("0XFF"), Ok(0xff));
+        assert_eq!(parse_address("4096"), Ok(4096));
+        assert_eq!(parse_address("0"), Ok(0));
+        assert!(parse_address("ff").is_err());
+        assert!(parse_address("7ffd5a3c1000").is_err());
+        // Pasted from /proc/PID/maps, which would be 400000 in decimal
+        assert!(parse_address("00400000").is_err());
+        assert_eq!(parse_address("0x00400000"), Ok(0x40_0000));
+        assert!(parse_address("0x").is_err());
+        assert!(parse_address("-1").is_err());
+        assert!(parse_address("0x10000000000000000").is_err());
+    }
+
+    #[test]
+    fn cpu_list_is_sorted_without_duplicates() {
+        assert_eq!(parse_cpu_list("7,0-3,5"), Ok(vec![0, 1, 2, 3, 5, 7]));
+        assert_eq!(parse_cpu_list("1-2,2,0-1"), Ok(vec![0, 1, 2]));
+        assert_eq!(parse_cpu_list(" 3 - 4 "), Ok(vec![3, 4]));
+        assert_eq!(parse_cpu_list("0-1,4\n"), Ok(vec![0, 1, 4]));
+    }
+
+    #[test]
+    fn invalid_cpu_list_is_rejected() {
+        assert!(parse_cpu_list("").is_err());
+        assert!(parse_cpu_list("a").is_err());
+        assert!(parse_cpu_list("3-1").is_err());
+        assert!(parse_cpu_list("0,,1").is_err());
+        assert!(parse_cpu_list("-1").is_err());
+    }
+
+    #[test]
+    fn duration_without_unit_is_seconds() {
+        assert_eq!(parse_duration("2"), Ok(2_000_000_000));
+        assert_eq!(parse_duration("1.5"), Ok(1_500_000_000));
+    }
+
+    #[test]
+    fn duration_has_units_and_fractions() {
+        assert_eq!(parse_duration("1d"), Ok(86_400_000_000_000));
+        assert_eq!(parse_duration("1.5h"), Ok(5_400_000_000_000));
+        assert_eq!(parse_duration("500ms"), Ok(500_000_000));
+        assert_eq!(parse_duration(".25us"), Ok(250));
+        assert_eq!(parse_duration("0.123456789s"), Ok(123_456_789));
+        assert_eq!(parse_duration("3ns"), Ok(3));
+    }
+
+    #[test]
+    fn invalid_duration_is_rejected() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration(".").is_err());
+        assert!(parse_duration("1.2.3s").is_err());
+        assert!(parse_duration("1w").is_err());
+        assert!(parse_duration("1 s").is_err());
+        assert!(parse_duration("300000d").is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Cgroups of processes and the cgroup filesystems they are in.
+
+use anyhow::{anyhow, Context};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// The cgroups of a process in /proc/PID/cgroup.
+pub struct Membership {
+    /// Path in the cgroup v2 hierarchy, the line of `0::PATH`
+    pub unified: Option<String>,
+    /// Controllers of each cgroup v1 hierarchy and the path in it
+    pub hierarchies: Vec<(Vec<String>, String)>,
+}
+
+impl Membership {
+    /// Reads /proc/PID/cgroup.
+    pub fn read(pid: i32) -> anyhow::Result<Self> {
+        let path = format!("/proc/{}/cgroup", pid);
+        let content = std::fs::read_to_string(&path).map_err(|err| match err.kind() {
+            ErrorKind::NotFound => anyhow!("Process {} does not exist", pid),
+            _ => anyhow!(err).context(format!("Failed to read {}", path)),
+        })?;
+
+        Self::parse(&content).with_context(|| format!("Failed to parse {}", path))
+    }
+
+    fn parse(content: &str) -> anyhow::Result<Self> {
+        let mut membership = Membership {
+            unified: None,
+            hierarchies: Vec::new(),
+        };
+        for line in content.lines() {
+            // Each line is HIERARCHY-ID:CONTROLLER-LIST:PATH, where the path may contain ':'
+            let mut fields = line.splitn(3, ':');
+            let (id, controllers, cgroup) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(id), Some(controllers), Some(cgroup)) => (id, controllers, cgroup),
+                _ => return Err(anyhow!("Unexpected line: {}", line)),
+            };
+            if id == "0" && controllers.is_empty() {
+                membership.unified = Some(cgroup.to_owned());
+            } else {
+                let controllers = controllers.split(',').map(str::to_owned).collect();
+                membership
+                    .hierarchies
+                    .push((controllers, cgroup.to_owned()));
+            }
+        }
+
+        Ok(membership)
+    }
+
+    /// Returns the path in the cgroup v1 hierarchy of a controller.
+    pub fn hierarchy(&self, name: &str) -> Option<&str> {
+        self.hierarchies
+            .iter()
+            .find(|(controllers, _)| controllers.iter().any(|controller| controller == name))
+            .map(|(_, cgroup)| cgroup.as_str())
+    }
+
+    /// Returns 1, 2 or hybrid, which has both cgroup v1 hierarchies and cgroup v2.
+    pub fn version(&self) -> &'static str {
+        match (self.unified.is_some(), self.hierarchies.is_empty()) {
+            (true, true) => "2",
+            (false, _) => "1",
+            (true, false) => "hybrid",
+        }
+    }
+}
+
+/// A cgroup filesystem mounted in this mount namespace.
+pub struct Mount {
+    /// Path of the hierarchy that is mounted, which is not / in a cgroup namespace
+    pub root: PathBuf,
+    pub mount_point: PathBuf,
+    /// Controllers of a cgroup v1 hierarchy, which is empty for cgroup v2
+    pub controllers: Vec<String>,
+}
+
+/// Lists the cgroup filesystems in /proc/self/mountinfo.
+pub fn read_mounts() -> anyhow::Result<Vec<Mount>> {
+    let content = std::fs::read_to_string("/proc/self/mountinfo")
+        .context("Failed to read /proc/self/mountinfo")?;
+
+    Ok(parse_mounts(&content))
+}
+
+fn parse_mounts(content: &str) -> Vec<Mount> {
+    let mut mounts = Vec::new();
+    for line in content.lines() {
+        // The optional fields before '-' vary in number, so the fields after it are split apart
+        let (fields, rest) = match line.split_once(" - ") {
+            Some(split) => split,
+            None => continue,
+        };
+        let fields: Vec<&str> = fields.split(' ').collect();
+        let rest: Vec<&str> = rest.split(' ').collect();
+        let (root, mount_point) = match (fields.get(3), fields.get(4)) {
+            (Some(root), Some(mount_point)) => (unescape(root), unescape(mount_point)),
+            _ => continue,
+        };
+        let controllers = match (rest.first(), rest.get(2)) {
+            (Some(&"cgroup2"), _) => Vec::new(),
+            (Some(&"cgroup"), Some(options)) => options.split(',').map(str::to_owned).collect(),
+            _ => continue,
+        };
+        mounts.push(Mount {
+            root: PathBuf::from(root),
+            mount_point: PathBuf::from(mount_point),
+            controllers,
+        });
+    }
+
+    mounts
+}
+
+/// Restores the spaces and other characters escaped as octal such as `\040` in mountinfo.
+fn unescape(s: &str) -> String {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let octal = tail
+            .get(..3)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(value) if byte == b'\\' => {
+                bytes.push(value);
+                rest = &tail[3..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// Finds the directory of a cgroup in the mount of its hierarchy, where `hierarchy` is None for
+/// cgroup v2.
+pub fn cgroup_dir(mounts: &[Mount], hierarchy: Option<&str>, cgroup: &str) -> Option<PathBuf> {
+    let mount = mounts.iter().find(|mount| match hierarchy {
+        None => mount.controllers.is_empty(),
+        Some(name) => mount
+            .controllers
+            .iter()
+            .any(|controller| controller == name),
+    })?;
+    // A cgroup out of the mounted part of the hierarchy cannot be reached
+    let relative = Path::new(cgroup).strip_prefix(&mount.root).ok()?;
+
+    Some(mount.mount_point.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MOUNTINFO: &str = "\
+25 30 0:23 / /sys rw,nosuid,nodev,noexec,relatime shared:7 - sysfs sysfs rw
+35 25 0:30 / /sys/fs/cgroup rw,nosuid,nodev,noexec,relatime shared:9 - cgroup2 cgroup2 rw
+36 25 0:31 /kubepods /sys/fs/cgroup/cpu,cpuacct rw,relatime shared:10 - cgroup cgroup rw,cpu,cpuacct
+37 25 0:32 / /mnt/my\\040cgroup rw,relatime - cgroup cgroup rw,memory
+";
+
+    #[test]
+    fn membership_has_unified_and_v1_hierarchies() {
+        let membership =
+            Membership::parse("12:cpu,cpuacct:/kubepods/pod:1\n3:memory:/user\n0::/user.slice\n")
+                .unwrap();
+        assert_eq!(membership.unified.as_deref(), Some("/user.slice"));
+        assert_eq!(membership.hierarchy("cpuacct"), Some("/kubepods/pod:1"));
+        assert_eq!(membership.hierarchy("memory"), Some("/user"));
+        assert_eq!(membership.hierarchy("pids"), None);
+        assert_eq!(membership.version(), "hybrid");
+
+        assert_eq!(Membership::parse("0::/\n").unwrap().version(), "2");
+        assert_eq!(Membership::parse("3:memory:/\n").unwrap().version(), "1");
+        assert!(Membership::parse("0:/\n").is_err());
+    }
+
+    #[test]
+    fn mounts_are_cgroup_filesystems() {
+        let mounts = parse_mounts(MOUNTINFO);
+        let summary: Vec<(&Path, &Path, Vec<&str>)> = mounts
+            .iter()
+            .map(|mount| {
+                let controllers = mount.controllers.iter().map(String::as_str).collect();
+                (
+                    mount.root.as_path(),
+                    mount.mount_point.as_path(),
+                    controllers,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (Path::new("/"), Path::new("/sys/fs/cgroup"), vec![]),
+                (
+                    Path::new("/kubepods"),
+                    Path::new("/sys/fs/cgroup/cpu,cpuacct"),
+                    vec!["rw", "cpu", "cpuacct"]
+                ),
+                (
+                    Path::new("/"),
+                    Path::new("/mnt/my cgroup"),
+                    vec!["rw", "memory"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn cgroup_dir_is_resolved_in_its_mount() {
+        let mounts = parse_mounts(MOUNTINFO);
+        assert_eq!(
+            cgroup_dir(&mounts, None, "/user.slice"),
+            Some(PathBuf::from("/sys/fs/cgroup/user.slice"))
+        );
+        assert_eq!(
+            cgroup_dir(&mounts, Some("cpu"), "/kubepods/pod1"),
+            Some(PathBuf::from("/sys/fs/cgroup/cpu,cpuacct/pod1"))
+        );
+        assert_eq!(
+            cgroup_dir(&mounts, Some("memory"), "/"),
+            Some(PathBuf::from("/mnt/my cgroup"))
+        );
+        // Out of the mounted part of the hierarchy, or of no mounted hierarchy
+        assert_eq!(cgroup_dir(&mounts, Some("cpu"), "/system.slice"), None);
+        assert_eq!(cgroup_dir(&mounts, Some("pids"), "/"), None);
+    }
+
+    #[test]
+    fn octal_escapes_are_restored() {
+        assert_eq!(unescape("a\\040b\\011c"), "a b\tc");
+        assert_eq!(unescape("trailing\\04"), "trailing\\04");
+    }
+}
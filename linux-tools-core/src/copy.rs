@@ -0,0 +1,42 @@
+//! Copies between files in the kernel.
+
+use nix::sys::sendfile::sendfile;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+/// Copies with sendfile(2), which writes at the file position of `destination`, and returns
+/// the number of bytes copied.
+///
+/// `offset` in `source` is advanced as bytes are copied, even when it fails, so that a fallback
+/// can resume from it. It stops early at the end of `source`.
+pub fn copy_with_sendfile(
+    source: &File,
+    destination: &File,
+    offset: &mut i64,
+    count: i64,
+) -> nix::Result<i64> {
+    let end = *offset + count;
+    let mut copied = 0;
+    while *offset < end {
+        let remaining = usize::try_from(end - *offset).unwrap_or(usize::MAX);
+        let sent = sendfile(
+            destination.as_raw_fd(),
+            source.as_raw_fd(),
+            Some(offset),
+            remaining,
+        )?;
+        if sent == 0 {
+            // The source is shorter than expected
+            break;
+        }
+        copied += sent as i64;
+    }
+
+    Ok(copied)
+}
+
+/// Returns the bytes per second of a copy, which is finite even when it took no time.
+pub fn throughput(bytes: i64, elapsed: Duration) -> f64 {
+    bytes as f64 / elapsed.as_secs_f64().max(f64::MIN_POSITIVE)
+}
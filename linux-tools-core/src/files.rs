@@ -0,0 +1,35 @@
+//! Validation of files given to the tools.
+
+use anyhow::{anyhow, Context};
+use nix::sys::stat::{fstat, FileStat, SFlag};
+use std::fs::Metadata;
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+/// Checks that the path exists and is a regular file, and returns its metadata.
+pub fn validate_file(path: &Path) -> anyhow::Result<Metadata> {
+    let exists = path
+        .try_exists()
+        .context("Failed to check existence of the file")?;
+    if !exists {
+        return Err(anyhow!("'{}' does not exist", path.display()));
+    }
+    let metadata = path
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?;
+    if !metadata.is_file() {
+        return Err(anyhow!("'{}' is not a file", path.display()));
+    }
+
+    Ok(metadata)
+}
+
+/// Checks that the file descriptor is open and refers to a regular file, and returns its status.
+pub fn validate_fd(fd: RawFd) -> anyhow::Result<FileStat> {
+    let stat = fstat(fd).with_context(|| format!("File descriptor {} is not open", fd))?;
+    if SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT != SFlag::S_IFREG {
+        return Err(anyhow!("File descriptor {} is not a file", fd));
+    }
+
+    Ok(stat)
+}
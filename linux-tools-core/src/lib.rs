@@ -0,0 +1,24 @@
+//! Shared code of the CLI tools in linux-tools.
+
+pub mod args;
+pub mod cgroup;
+pub mod copy;
+pub mod files;
+pub mod log;
+pub mod mem;
+pub mod output;
+pub mod pidfd;
+pub mod process;
+
+use clap::CommandFactory;
+use clap_complete::{generate, Generator};
+
+/// Prints the completion code of the command `C` for the shell to stdout.
+pub fn print_completer<C: CommandFactory, G: Generator>(generator: G) -> anyhow::Result<()> {
+    let mut app = C::command();
+    let name = app.get_name().to_owned();
+
+    generate(generator, &mut app, name, &mut std::io::stdout());
+
+    Ok(())
+}
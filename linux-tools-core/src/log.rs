@@ -0,0 +1,62 @@
+//! Destination and level of the diagnostic messages.
+
+use crate::args::LogLevel;
+use anyhow::{anyhow, Context};
+use nix::libc::STDERR_FILENO;
+use nix::unistd::{dup2, isatty};
+use std::fs::OpenOptions;
+use std::os::unix::io::AsRawFd;
+use std::path::Path;
+use tracing_subscriber::filter::{EnvFilter, LevelFilter};
+
+impl From<LogLevel> for LevelFilter {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => LevelFilter::ERROR,
+            LogLevel::Warn => LevelFilter::WARN,
+            LogLevel::Info => LevelFilter::INFO,
+            LogLevel::Debug => LevelFilter::DEBUG,
+            LogLevel::Trace => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Sends the diagnostic messages on stderr to the end of `log_file` if given, and installs a
+/// subscriber printing the events of tracing up to `level` to stderr. Without `level`, the
+/// filter is taken from `RUST_LOG`, and only warnings and errors are printed without it.
+///
+/// The file replaces stderr itself, so that messages printed with `eprintln!` and the error
+/// returned from `main` are written to it as well.
+///
+/// `color` turns the colors of the events on or off as `--color always` and `--color never`.
+/// Without it, they are colored when stderr is a terminal after it is replaced by the file.
+pub fn init(
+    log_file: Option<&Path>,
+    level: Option<LogLevel>,
+    color: Option<bool>,
+) -> anyhow::Result<()> {
+    if let Some(log_file) = log_file {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_file)
+            .with_context(|| format!("Failed to open the log file '{}'", log_file.display()))?;
+        dup2(file.as_raw_fd(), STDERR_FILENO)
+            .context("Failed to redirect stderr to the log file")?;
+    }
+
+    let ansi = color.unwrap_or_else(|| isatty(STDERR_FILENO).unwrap_or(false));
+    let filter = match level {
+        Some(level) => EnvFilter::default().add_directive(LevelFilter::from(level).into()),
+        None => EnvFilter::builder()
+            .with_default_directive(LevelFilter::WARN.into())
+            .from_env_lossy(),
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(ansi)
+        .try_init()
+        .map_err(|err| anyhow!(err))
+        .context("Failed to initialize the logging")
+}
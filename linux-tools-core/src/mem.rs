@@ -0,0 +1,56 @@
+//! Pages of memory and of the page cache.
+
+use anyhow::{anyhow, Context};
+use nix::errno::Errno;
+use nix::libc;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::unistd::{sysconf, SysconfVar};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+
+/// Returns the size of a page in bytes.
+pub fn page_size() -> anyhow::Result<i64> {
+    sysconf(SysconfVar::PAGE_SIZE)
+        .context("Failed to get the page size")?
+        .ok_or_else(|| anyhow!("The page size is unknown"))
+}
+
+/// Returns whether each page of a file from `start`, which must be page aligned, to `end` is in
+/// the page cache, as mincore(2) tells.
+pub fn query_residency(
+    file: &File,
+    start: i64,
+    end: i64,
+    page_size: i64,
+) -> anyhow::Result<Vec<bool>> {
+    let len = (end - start) as usize;
+    let pages = ((end - start + page_size - 1) / page_size) as usize;
+    // mmap fails for an empty range
+    if pages == 0 {
+        return Ok(Vec::new());
+    }
+
+    // SAFETY: the mapping is read-only and never dereferenced since mincore only inspects it.
+    let addr = unsafe {
+        mmap(
+            std::ptr::null_mut(),
+            len,
+            ProtFlags::PROT_READ,
+            MapFlags::MAP_SHARED,
+            file.as_raw_fd(),
+            start,
+        )
+    }
+    .context("Failed to map the file")?;
+
+    let mut vec = vec![0u8; pages];
+    // SAFETY: addr is a mapping of len bytes, and vec has an entry for each of its pages.
+    let ret = unsafe { libc::mincore(addr, len, vec.as_mut_ptr()) };
+    let result = Errno::result(ret).context("Failed to call mincore");
+    // SAFETY: the mapping is no longer used.
+    unsafe { munmap(addr, len) }.context("Failed to unmap the file")?;
+    result?;
+
+    // Only the least significant bit tells the residency
+    Ok(vec.into_iter().map(|v| v & 1 != 0).collect())
+}
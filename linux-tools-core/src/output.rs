@@ -0,0 +1,182 @@
+//! Reporting of results in text or JSON.
+
+use crate::args::{OutputFormat, SECONDS, UNITS};
+use anyhow::Context;
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use std::io::{Stdout, Write};
+
+/// Formats an error and its causes in a line such as `Failed to open the file: No such file`.
+pub fn format_error(err: &anyhow::Error) -> String {
+    format!("{:#}", err)
+}
+
+/// Formats a duration in the largest of s, ms, us and ns, such as `1.5s` or `500ms`.
+pub fn format_duration(ns: u64) -> String {
+    // Fractions are only exact in decimal units
+    let decimal_units = &UNITS[SECONDS..];
+    let (index, &(name, unit)) = decimal_units
+        .iter()
+        .enumerate()
+        .find(|&(_, &(_, unit))| ns >= unit)
+        .unwrap_or((
+            decimal_units.len() - 1,
+            &decimal_units[decimal_units.len() - 1],
+        ));
+    let integer = ns / unit;
+    let fraction = ns % unit;
+    if fraction == 0 {
+        return format!("{}{}", integer, name);
+    }
+
+    let digits = 3 * (decimal_units.len() - 1 - index);
+    let fraction = format!("{:0width$}", fraction, width = digits);
+    format!("{}.{}{}", integer, fraction.trim_end_matches('0'), name)
+}
+
+/// Receives the fields of a result and prints them in a format.
+pub trait Reporter {
+    /// Reports a field of the result.
+    fn report_field(&mut self, key: &str, value: &dyn Display);
+
+    /// Reports a field whose JSON value is given apart from its text, such as `null` for a value
+    /// printed as `none`.
+    fn report_value(&mut self, key: &str, text: &dyn Display, value: Value);
+
+    /// Reports a field that stays a string in JSON even when it reads as a number, such as a
+    /// filename.
+    fn report_str(&mut self, key: &str, value: &str) {
+        self.report_value(key, &value, Value::String(value.to_owned()));
+    }
+
+    /// Reports the error that made the result fail.
+    fn report_error(&mut self, msg: &str);
+
+    /// Prints what has not been printed yet.
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+/// Prints each field as a `key: value` line to a writer as soon as it is reported.
+pub struct TextReporter<W: Write = Stdout> {
+    writer: W,
+    result: std::io::Result<()>,
+}
+
+impl<W: Write> TextReporter<W> {
+    pub fn new(writer: W) -> Self {
+        TextReporter {
+            writer,
+            result: Ok(()),
+        }
+    }
+
+    fn write_line(&mut self, key: &str, value: &dyn Display) {
+        // The first failure to write is kept and returned from finish
+        if self.result.is_ok() {
+            self.result = writeln!(self.writer, "{}: {}", key, value);
+        }
+    }
+}
+
+impl Default for TextReporter<Stdout> {
+    fn default() -> Self {
+        TextReporter::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> Reporter for TextReporter<W> {
+    fn report_field(&mut self, key: &str, value: &dyn Display) {
+        self.write_line(key, value);
+    }
+
+    fn report_value(&mut self, key: &str, text: &dyn Display, _value: Value) {
+        self.write_line(key, text);
+    }
+
+    fn report_error(&mut self, msg: &str) {
+        self.write_line("error", &msg);
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        std::mem::replace(&mut self.result, Ok(()))
+            .and_then(|_| self.writer.flush())
+            .context("Failed to write the result")
+    }
+}
+
+/// Builds the reporter of `--output-format`, which prints the result to the writer.
+pub fn new_reporter<'a>(format: OutputFormat, writer: impl Write + 'a) -> Box<dyn Reporter + 'a> {
+    match format {
+        OutputFormat::Text => Box::new(TextReporter::new(writer)),
+        OutputFormat::Json => Box::new(JsonReporter::new(writer)),
+    }
+}
+
+/// Collects the fields and prints them as a JSON object in a line to a writer on finish.
+///
+/// A value of `report_field` that reads as a JSON number or boolean, such as `4096` or `true`, is
+/// kept as it is, and anything else becomes a string. `report_str` always gives a string. Errors are joined into the `error` field.
+pub struct JsonReporter<W: Write = Stdout> {
+    writer: W,
+    fields: Map<String, Value>,
+    key: Option<String>,
+}
+
+impl<W: Write> JsonReporter<W> {
+    pub fn new(writer: W) -> Self {
+        JsonReporter {
+            writer,
+            fields: Map::new(),
+            key: None,
+        }
+    }
+
+    /// Prints the fields as an object under a key, such as `{"summary":{...}}`.
+    pub fn under(writer: W, key: &str) -> Self {
+        JsonReporter {
+            writer,
+            fields: Map::new(),
+            key: Some(key.to_owned()),
+        }
+    }
+}
+
+impl Default for JsonReporter<Stdout> {
+    fn default() -> Self {
+        JsonReporter::new(std::io::stdout())
+    }
+}
+
+impl<W: Write> Reporter for JsonReporter<W> {
+    fn report_field(&mut self, key: &str, value: &dyn Display) {
+        let text = value.to_string();
+        let value = match serde_json::from_str(&text) {
+            Ok(value @ (Value::Number(_) | Value::Bool(_))) => value,
+            _ => Value::String(text),
+        };
+        self.fields.insert(key.to_owned(), value);
+    }
+
+    fn report_value(&mut self, key: &str, _text: &dyn Display, value: Value) {
+        self.fields.insert(key.to_owned(), value);
+    }
+
+    fn report_error(&mut self, msg: &str) {
+        let error = match self.fields.remove("error") {
+            Some(Value::String(error)) => format!("{}; {}", error, msg),
+            _ => msg.to_owned(),
+        };
+        self.fields.insert("error".to_owned(), Value::String(error));
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        let mut value = Value::Object(std::mem::take(&mut self.fields));
+        if let Some(key) = &self.key {
+            value = Value::Object(Map::from_iter([(key.clone(), value)]));
+        }
+        let json = serde_json::to_string(&value).context("Failed to serialize the result")?;
+        writeln!(self.writer, "{}", json)
+            .and_then(|_| self.writer.flush())
+            .context("Failed to write the result")
+    }
+}
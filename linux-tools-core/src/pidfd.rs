@@ -0,0 +1,43 @@
+//! File descriptors of processes with pidfd_open(2) and pidfd_getfd(2).
+
+use anyhow::{anyhow, Context};
+use nix::errno::Errno;
+use nix::libc;
+use std::os::unix::io::{AsFd, AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Opens a pidfd of a process.
+pub fn open_pidfd(pid: i32) -> anyhow::Result<OwnedFd> {
+    // SAFETY: pidfd_open only takes integer arguments.
+    let ret = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0u32) };
+    match Errno::result(ret) {
+        // SAFETY: pidfd_open returns a new file descriptor owned by nobody else.
+        Ok(fd) => Ok(unsafe { OwnedFd::from_raw_fd(fd as RawFd) }),
+        Err(Errno::ESRCH) => Err(anyhow!("Process {} does not exist", pid)),
+        Err(Errno::EINVAL) => Err(anyhow!("{} is not a valid PID", pid)),
+        Err(Errno::ENOSYS) => Err(anyhow!("pidfd_open(2) requires Linux 5.3 or later")),
+        Err(errno) => Err(errno).with_context(|| format!("Failed to open process {}", pid)),
+    }
+}
+
+/// Duplicates a file descriptor of the process of a pidfd, which shares the open file with it.
+///
+/// This works for sockets and anonymous inodes such as an eventfd, which cannot be reopened
+/// through /proc/PID/fd/FD. The duplicate has O_CLOEXEC set.
+pub fn pidfd_getfd(pidfd: impl AsFd, pid: i32, fd: RawFd) -> anyhow::Result<OwnedFd> {
+    let pidfd = pidfd.as_fd().as_raw_fd();
+    // SAFETY: pidfd_getfd only takes integer arguments.
+    let ret = unsafe { libc::syscall(libc::SYS_pidfd_getfd, pidfd, fd, 0u32) };
+    match Errno::result(ret) {
+        // SAFETY: pidfd_getfd returns a new file descriptor owned by nobody else.
+        Ok(duplicate) => Ok(unsafe { OwnedFd::from_raw_fd(duplicate as RawFd) }),
+        Err(Errno::EBADF) => Err(anyhow!("Process {} has no file descriptor {}", pid, fd)),
+        Err(Errno::EPERM) => Err(anyhow!(
+            "Getting a file descriptor of process {} requires the permission to ptrace it",
+            pid
+        )),
+        Err(Errno::ESRCH) => Err(anyhow!("Process {} has already exited", pid)),
+        Err(Errno::ENOSYS) => Err(anyhow!("pidfd_getfd(2) requires Linux 5.6 or later")),
+        Err(errno) => Err(errno)
+            .with_context(|| format!("Failed to get file descriptor {} of process {}", fd, pid)),
+    }
+}
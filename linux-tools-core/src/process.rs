@@ -0,0 +1,32 @@
+//! Commands run by the tools, such as the one given after `--`.
+
+use anyhow::Context;
+use std::ffi::OsString;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, ExitCode, ExitStatus};
+
+/// Returns the command run when none is given, which is `$SHELL` or `/bin/sh`.
+pub fn default_command() -> Vec<OsString> {
+    vec![std::env::var_os("SHELL").unwrap_or_else(|| "/bin/sh".into())]
+}
+
+/// Follows the shell convention for a command killed by a signal.
+pub fn exit_code(status: ExitStatus) -> ExitCode {
+    let code = match (status.code(), status.signal()) {
+        (Some(code), _) => code as u8,
+        (None, Some(signal)) => 128 + signal as u8,
+        (None, None) => 1,
+    };
+
+    ExitCode::from(code)
+}
+
+/// Runs a command and waits for it, returning its exit code.
+pub fn run_command(command: &[OsString]) -> anyhow::Result<ExitCode> {
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .status()
+        .with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))?;
+
+    Ok(exit_code(status))
+}
@@ -0,0 +1,283 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_address;
+use linux_tools_core::pidfd::open_pidfd;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::path::Path;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Apply advice of MADV_NORMAL
+    #[clap(display_order = 1, name = "normal")]
+    Normal(AdviseInfo),
+    /// Apply advice of MADV_SEQUENTIAL
+    #[clap(display_order = 2, name = "sequential")]
+    Sequential(AdviseInfo),
+    /// Apply advice of MADV_RANDOM
+    #[clap(display_order = 3, name = "random")]
+    Random(AdviseInfo),
+    /// Apply advice of MADV_WILLNEED
+    #[clap(display_order = 4, name = "willneed")]
+    WillNeed(AdviseInfo),
+    /// Apply advice of MADV_DONTNEED
+    #[clap(display_order = 5, name = "dontneed")]
+    DontNeed(AdviseInfo),
+    /// Apply advice of MADV_FREE
+    #[clap(display_order = 6, name = "free")]
+    Free(AdviseInfo),
+    /// Apply advice of MADV_COLD
+    #[clap(display_order = 7, name = "cold")]
+    Cold(AdviseInfo),
+    /// Apply advice of MADV_PAGEOUT
+    #[clap(display_order = 8, name = "pageout")]
+    PageOut(AdviseInfo),
+    /// Apply advice of MADV_MERGEABLE
+    #[clap(display_order = 9, name = "mergeable")]
+    Mergeable(AdviseInfo),
+    /// Apply advice of MADV_HUGEPAGE
+    #[clap(display_order = 10, name = "hugepage")]
+    HugePage(AdviseInfo),
+    /// Apply advice of MADV_NOHUGEPAGE
+    #[clap(display_order = 11, name = "nohugepage")]
+    NoHugePage(AdviseInfo),
+    /// Apply advice of MADV_DONTFORK
+    #[clap(display_order = 12, name = "dontfork")]
+    DontFork(AdviseInfo),
+    /// Generate code for completion
+    #[clap(display_order = 13, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct AdviseInfo {
+    /// Process whose memory advice is applied [default: this process]
+    #[clap(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    pid: Option<i32>,
+    /// Start address of a range advice is applied, such as 0x7f0c2a400000
+    #[clap(
+        value_parser = parse_address,
+        value_name = "ADDR",
+        required_unless_present = "region-name"
+    )]
+    address: Option<u64>,
+    /// Length of a range advice is applied
+    #[clap(value_parser, required_unless_present = "region-name")]
+    len: Option<usize>,
+    /// Apply advice to every mapping in /proc/PID/maps with this pathname (e.g. '[heap]', 'libc.so.6')
+    #[clap(long, short, value_name = "NAME", conflicts_with_all = &["address", "len"])]
+    region_name: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum MadviseType {
+    Normal,
+    Sequential,
+    Random,
+    WillNeed,
+    DontNeed,
+    Free,
+    Cold,
+    PageOut,
+    Mergeable,
+    HugePage,
+    NoHugePage,
+    DontFork,
+}
+
+impl std::fmt::Display for MadviseType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            MadviseType::Normal => write!(f, "MADV_NORMAL"),
+            MadviseType::Sequential => write!(f, "MADV_SEQUENTIAL"),
+            MadviseType::Random => write!(f, "MADV_RANDOM"),
+            MadviseType::WillNeed => write!(f, "MADV_WILLNEED"),
+            MadviseType::DontNeed => write!(f, "MADV_DONTNEED"),
+            MadviseType::Free => write!(f, "MADV_FREE"),
+            MadviseType::Cold => write!(f, "MADV_COLD"),
+            MadviseType::PageOut => write!(f, "MADV_PAGEOUT"),
+            MadviseType::Mergeable => write!(f, "MADV_MERGEABLE"),
+            MadviseType::HugePage => write!(f, "MADV_HUGEPAGE"),
+            MadviseType::NoHugePage => write!(f, "MADV_NOHUGEPAGE"),
+            MadviseType::DontFork => write!(f, "MADV_DONTFORK"),
+        }
+    }
+}
+
+impl MadviseType {
+    fn raw(self) -> i32 {
+        match self {
+            MadviseType::Normal => libc::MADV_NORMAL,
+            MadviseType::Sequential => libc::MADV_SEQUENTIAL,
+            MadviseType::Random => libc::MADV_RANDOM,
+            MadviseType::WillNeed => libc::MADV_WILLNEED,
+            MadviseType::DontNeed => libc::MADV_DONTNEED,
+            MadviseType::Free => libc::MADV_FREE,
+            MadviseType::Cold => libc::MADV_COLD,
+            MadviseType::PageOut => libc::MADV_PAGEOUT,
+            MadviseType::Mergeable => libc::MADV_MERGEABLE,
+            MadviseType::HugePage => libc::MADV_HUGEPAGE,
+            MadviseType::NoHugePage => libc::MADV_NOHUGEPAGE,
+            MadviseType::DontFork => libc::MADV_DONTFORK,
+        }
+    }
+}
+
+/// A mapping listed in `/proc/PID/maps`.
+struct Region {
+    start: usize,
+    end: usize,
+    pathname: String,
+}
+
+fn read_regions(pid: Option<i32>) -> anyhow::Result<Vec<Region>> {
+    let path = match pid {
+        Some(pid) => format!("/proc/{}/maps", pid),
+        None => "/proc/self/maps".to_owned(),
+    };
+    let maps =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+
+    let mut regions = Vec::new();
+    for line in maps.lines() {
+        // e.g. "7f0c2a400000-7f0c2a428000 r--p 00000000 08:01 1234   /usr/lib/libc.so.6"
+        let mut fields = line.splitn(6, ' ');
+        let range = fields.next().unwrap_or_default();
+        let pathname = fields.nth(4).unwrap_or_default().trim_start();
+        let (start, end) = range
+            .split_once('-')
+            .and_then(|(start, end)| {
+                Some((
+                    usize::from_str_radix(start, 16).ok()?,
+                    usize::from_str_radix(end, 16).ok()?,
+                ))
+            })
+            .ok_or_else(|| anyhow!("Unexpected line in {}: {}", path, line))?;
+        regions.push(Region {
+            start,
+            end,
+            pathname: pathname.to_owned(),
+        });
+    }
+
+    Ok(regions)
+}
+
+fn matches_region_name(region: &Region, name: &str) -> bool {
+    region.pathname == name
+        || Path::new(&region.pathname)
+            .file_name()
+            .map_or(false, |file_name| file_name == name)
+}
+
+fn madvise_self(advice: MadviseType, address: usize, len: usize) -> anyhow::Result<()> {
+    // SAFETY: the range is chosen by the user, who is responsible for not
+    // discarding memory this process still relies on.
+    let ret = unsafe { libc::madvise(address as *mut libc::c_void, len, advice.raw()) };
+    Errno::result(ret).context("Failed to call madvise")?;
+
+    Ok(())
+}
+
+fn madvise_process(
+    pidfd: &OwnedFd,
+    advice: MadviseType,
+    address: usize,
+    len: usize,
+) -> anyhow::Result<()> {
+    let iov = libc::iovec {
+        iov_base: address as *mut libc::c_void,
+        iov_len: len,
+    };
+    // SAFETY: the kernel only reads the single iovec, which outlives the call.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_process_madvise,
+            pidfd.as_raw_fd(),
+            &iov as *const libc::iovec,
+            1usize,
+            advice.raw(),
+            0u32,
+        )
+    };
+    match Errno::result(ret) {
+        Ok(_) => Ok(()),
+        Err(Errno::ENOSYS) => Err(anyhow!("process_madvise(2) requires Linux 5.10 or later")),
+        Err(Errno::EINVAL) => Err(anyhow!(
+            "Failed to call process_madvise: {} is invalid for another process; \
+            only MADV_COLD, MADV_PAGEOUT and MADV_WILLNEED are supported",
+            advice
+        )),
+        Err(errno) => Err(errno).context("Failed to call process_madvise"),
+    }
+}
+
+fn handle_advice(advice: MadviseType, info: AdviseInfo) -> anyhow::Result<()> {
+    let ranges: Vec<(usize, usize)> = match (&info.region_name, info.address, info.len) {
+        (Some(name), _, _) => {
+            let ranges: Vec<(usize, usize)> = read_regions(info.pid)?
+                .iter()
+                .filter(|region| matches_region_name(region, name))
+                .map(|region| (region.start, region.end - region.start))
+                .collect();
+            if ranges.is_empty() {
+                return Err(anyhow!("No mapping named '{}' was found", name));
+            }
+            ranges
+        }
+        (None, Some(address), Some(len)) => vec![(address as usize, len)],
+        // clap requires ADDR and LEN unless --region-name is given
+        _ => unreachable!(),
+    };
+
+    let pidfd = info.pid.map(open_pidfd).transpose()?;
+    for (address, len) in ranges {
+        match info.pid {
+            Some(pid) => eprintln!("pid: {}", pid),
+            None => eprintln!("pid: self"),
+        }
+        eprintln!("advice: {}", advice);
+        eprintln!("address: {:#x}", address);
+        eprintln!("len: {}", len);
+
+        match &pidfd {
+            Some(pidfd) => madvise_process(pidfd, advice, address, len)?,
+            None => madvise_self(advice, address, len)?,
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Normal(info) => handle_advice(MadviseType::Normal, info),
+        Commands::Sequential(info) => handle_advice(MadviseType::Sequential, info),
+        Commands::Random(info) => handle_advice(MadviseType::Random, info),
+        Commands::WillNeed(info) => handle_advice(MadviseType::WillNeed, info),
+        Commands::DontNeed(info) => handle_advice(MadviseType::DontNeed, info),
+        Commands::Free(info) => handle_advice(MadviseType::Free, info),
+        Commands::Cold(info) => handle_advice(MadviseType::Cold, info),
+        Commands::PageOut(info) => handle_advice(MadviseType::PageOut, info),
+        Commands::Mergeable(info) => handle_advice(MadviseType::Mergeable, info),
+        Commands::HugePage(info) => handle_advice(MadviseType::HugePage, info),
+        Commands::NoHugePage(info) => handle_advice(MadviseType::NoHugePage, info),
+        Commands::DontFork(info) => handle_advice(MadviseType::DontFork, info),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,252 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::output::format_duration;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::time::{clock_gettime, ClockId};
+use std::os::raw::c_int;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// List the commands the kernel supports with MEMBARRIER_CMD_QUERY
+    #[clap(display_order = 1, name = "query")]
+    Query,
+    /// Issue a barrier on every thread of every process with MEMBARRIER_CMD_GLOBAL
+    #[clap(display_order = 2, name = "global")]
+    Global(BarrierInfo),
+    /// Issue a barrier on the running threads of the registered processes with
+    /// MEMBARRIER_CMD_GLOBAL_EXPEDITED
+    #[clap(display_order = 3, name = "global-expedited")]
+    GlobalExpedited(ExpeditedInfo),
+    /// Register this process to receive expedited global barriers with
+    /// MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED
+    #[clap(display_order = 4, name = "register-global-expedited")]
+    RegisterGlobalExpedited(BarrierInfo),
+    /// Issue a barrier on the running threads of this process with
+    /// MEMBARRIER_CMD_PRIVATE_EXPEDITED
+    #[clap(display_order = 5, name = "private-expedited")]
+    PrivateExpedited(ExpeditedInfo),
+    /// Register this process for expedited private barriers with
+    /// MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED
+    #[clap(display_order = 6, name = "register-private-expedited")]
+    RegisterPrivateExpedited(BarrierInfo),
+    /// Generate code for completion
+    #[clap(display_order = 7, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct BarrierInfo {
+    /// Value of the flags argument, such as 1 for MEMBARRIER_CMD_FLAG_CPU
+    #[clap(short, long, value_parser, default_value_t = 0)]
+    flags: u32,
+    /// Number of times to call membarrier to measure the latency
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value_t = 1,
+        value_name = "N"
+    )]
+    count: u64,
+}
+
+#[derive(Args)]
+struct ExpeditedInfo {
+    #[clap(flatten)]
+    barrier: BarrierInfo,
+    /// Register this process first, which an expedited barrier requires since the registration
+    /// only lasts for the process
+    #[clap(short, long)]
+    register: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Command {
+    Query,
+    Global,
+    GlobalExpedited,
+    RegisterGlobalExpedited,
+    PrivateExpedited,
+    RegisterPrivateExpedited,
+    PrivateExpeditedSyncCore,
+    RegisterPrivateExpeditedSyncCore,
+    PrivateExpeditedRseq,
+    RegisterPrivateExpeditedRseq,
+}
+
+/// Commands reported by MEMBARRIER_CMD_QUERY, in the order of their bits.
+const QUERIED: [Command; 9] = [
+    Command::Global,
+    Command::GlobalExpedited,
+    Command::RegisterGlobalExpedited,
+    Command::PrivateExpedited,
+    Command::RegisterPrivateExpedited,
+    Command::PrivateExpeditedSyncCore,
+    Command::RegisterPrivateExpeditedSyncCore,
+    Command::PrivateExpeditedRseq,
+    Command::RegisterPrivateExpeditedRseq,
+];
+
+impl Command {
+    /// Value of enum membarrier_cmd in linux/membarrier.h.
+    fn raw(self) -> c_int {
+        match self {
+            Command::Query => 0,
+            Command::Global => 1 << 0,
+            Command::GlobalExpedited => 1 << 1,
+            Command::RegisterGlobalExpedited => 1 << 2,
+            Command::PrivateExpedited => 1 << 3,
+            Command::RegisterPrivateExpedited => 1 << 4,
+            Command::PrivateExpeditedSyncCore => 1 << 5,
+            Command::RegisterPrivateExpeditedSyncCore => 1 << 6,
+            Command::PrivateExpeditedRseq => 1 << 7,
+            Command::RegisterPrivateExpeditedRseq => 1 << 8,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Command::Query => "MEMBARRIER_CMD_QUERY",
+            Command::Global => "MEMBARRIER_CMD_GLOBAL",
+            Command::GlobalExpedited => "MEMBARRIER_CMD_GLOBAL_EXPEDITED",
+            Command::RegisterGlobalExpedited => "MEMBARRIER_CMD_REGISTER_GLOBAL_EXPEDITED",
+            Command::PrivateExpedited => "MEMBARRIER_CMD_PRIVATE_EXPEDITED",
+            Command::RegisterPrivateExpedited => "MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED",
+            Command::PrivateExpeditedSyncCore => "MEMBARRIER_CMD_PRIVATE_EXPEDITED_SYNC_CORE",
+            Command::RegisterPrivateExpeditedSyncCore => {
+                "MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_SYNC_CORE"
+            }
+            Command::PrivateExpeditedRseq => "MEMBARRIER_CMD_PRIVATE_EXPEDITED_RSEQ",
+            Command::RegisterPrivateExpeditedRseq => {
+                "MEMBARRIER_CMD_REGISTER_PRIVATE_EXPEDITED_RSEQ"
+            }
+        }
+    }
+
+    /// The registration an expedited barrier requires.
+    fn registration(self) -> Option<Command> {
+        match self {
+            Command::GlobalExpedited => Some(Command::RegisterGlobalExpedited),
+            Command::PrivateExpedited => Some(Command::RegisterPrivateExpedited),
+            Command::PrivateExpeditedSyncCore => Some(Command::RegisterPrivateExpeditedSyncCore),
+            Command::PrivateExpeditedRseq => Some(Command::RegisterPrivateExpeditedRseq),
+            _ => None,
+        }
+    }
+}
+
+fn membarrier(command: Command, flags: u32) -> anyhow::Result<c_int> {
+    // The cpu_id argument is only read with MEMBARRIER_CMD_FLAG_CPU
+    // SAFETY: membarrier takes only integers.
+    let ret = unsafe { libc::syscall(libc::SYS_membarrier, command.raw(), flags, 0) };
+    Errno::result(ret)
+        .map(|ret| ret as c_int)
+        .map_err(|errno| match errno {
+            Errno::ENOSYS => {
+                anyhow!("membarrier is not supported; it requires Linux 4.3 with CONFIG_MEMBARRIER")
+            }
+            Errno::EINVAL if command == Command::Global && flags == 0 => anyhow!(
+                "{} is not supported; it is disabled when the kernel runs with nohz_full",
+                command.name()
+            ),
+            Errno::EINVAL => anyhow!(
+                "{} with the flags {} is not supported; 'membarrier query' lists the supported \
+                commands",
+                command.name(),
+                flags
+            ),
+            Errno::EPERM => match command.registration() {
+                Some(registration) => anyhow!(
+                    "This process is not registered with {}; give --register to register it first",
+                    registration.name()
+                ),
+                None => anyhow!(errno).context(format!("Failed to call {}", command.name())),
+            },
+            errno => anyhow!(errno).context(format!("Failed to call {}", command.name())),
+        })
+}
+
+fn now_ns() -> anyhow::Result<u64> {
+    let time = clock_gettime(ClockId::CLOCK_MONOTONIC).context("Failed to call clock_gettime")?;
+    Ok(time.tv_sec() as u64 * 1_000_000_000 + time.tv_nsec() as u64)
+}
+
+fn handle_query() -> anyhow::Result<()> {
+    let supported = membarrier(Command::Query, 0)?;
+    for command in QUERIED {
+        let state = if supported & command.raw() != 0 {
+            "supported"
+        } else {
+            "not supported"
+        };
+        println!("{}: {}", command.name(), state);
+    }
+    Ok(())
+}
+
+fn handle_barrier(command: Command, info: BarrierInfo, register: bool) -> anyhow::Result<()> {
+    let registration = command.registration().filter(|_| register);
+    if let Some(registration) = registration {
+        membarrier(registration, 0)?;
+    }
+
+    // The latencies are summarized as they are measured, since --count has no upper bound
+    let (mut min, mut max, mut sum) = (u64::MAX, 0, 0u128);
+    for _ in 0..info.count {
+        let start = now_ns()?;
+        membarrier(command, info.flags)?;
+        let latency = now_ns()? - start;
+        min = min.min(latency);
+        max = max.max(latency);
+        sum += latency as u128;
+    }
+
+    println!("command: {}", command.name());
+    println!("flags: {}", info.flags);
+    if let Some(registration) = registration {
+        println!("registered: {}", registration.name());
+    }
+    println!("count: {}", info.count);
+    // count is at least 1, and the average is at most max
+    let avg = (sum / info.count as u128) as u64;
+    println!("min: {}", format_duration(min));
+    println!("avg: {}", format_duration(avg));
+    println!("max: {}", format_duration(max));
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Query => handle_query(),
+        Commands::Global(info) => handle_barrier(Command::Global, info, false),
+        Commands::GlobalExpedited(info) => {
+            handle_barrier(Command::GlobalExpedited, info.barrier, info.register)
+        }
+        Commands::RegisterGlobalExpedited(info) => {
+            handle_barrier(Command::RegisterGlobalExpedited, info, false)
+        }
+        Commands::PrivateExpedited(info) => {
+            handle_barrier(Command::PrivateExpedited, info.barrier, info.register)
+        }
+        Commands::RegisterPrivateExpedited(info) => {
+            handle_barrier(Command::RegisterPrivateExpedited, info, false)
+        }
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
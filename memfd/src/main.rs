@@ -0,0 +1,277 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, SealFlag};
+use std::ffi::{CString, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable holding the file descriptor for COMMAND
+const FD_ENV_VAR: &str = "MEMFD_FD";
+
+/// Seals and the names printed for them
+const SEAL_NAMES: &[(SealFlag, &str)] = &[
+    (SealFlag::F_SEAL_SEAL, "F_SEAL_SEAL"),
+    (SealFlag::F_SEAL_SHRINK, "F_SEAL_SHRINK"),
+    (SealFlag::F_SEAL_GROW, "F_SEAL_GROW"),
+    (SealFlag::F_SEAL_WRITE, "F_SEAL_WRITE"),
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Create a memory file and pass it to a command, or keep it open until interrupted
+    #[clap(display_order = 1, name = "create", trailing_var_arg = true)]
+    Create {
+        /// Name of the file, shown in /proc/PID/fd as memfd:NAME
+        #[clap(short = 'N', long, default_value = "memfd", value_parser)]
+        name: String,
+        /// Flags of memfd_create, separated by commas
+        #[clap(short, long, arg_enum, use_value_delimiter = true)]
+        flags: Vec<CreateFlag>,
+        /// File to copy into the memory file, '-' for stdin
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        input: Option<PathBuf>,
+        /// Seals to add after the input is copied, separated by commas (requires allow-sealing)
+        #[clap(short, long, arg_enum, use_value_delimiter = true)]
+        seals: Vec<SealType>,
+        /// Command to execute with the file descriptor in $MEMFD_FD [default: print the path]
+        #[clap(value_parser, value_name = "COMMAND", multiple_values = true)]
+        command: Vec<OsString>,
+    },
+    /// Add seals to a memory file
+    #[clap(display_order = 2, name = "seal")]
+    Seal {
+        /// Memory file, such as /proc/PID/fd/N
+        #[clap(value_parser, value_name = "PATH")]
+        path: PathBuf,
+        /// Seals to add
+        #[clap(required = true, arg_enum, value_name = "SEAL")]
+        seals: Vec<SealType>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum CreateFlag {
+    AllowSealing,
+    Cloexec,
+    Hugetlb,
+}
+
+impl std::fmt::Display for CreateFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CreateFlag::AllowSealing => write!(f, "MFD_ALLOW_SEALING"),
+            CreateFlag::Cloexec => write!(f, "MFD_CLOEXEC"),
+            CreateFlag::Hugetlb => write!(f, "MFD_HUGETLB"),
+        }
+    }
+}
+
+impl CreateFlag {
+    fn bits(self) -> u32 {
+        // nix does not define MFD_HUGETLB, so the flags are passed to libc as they are
+        match self {
+            CreateFlag::AllowSealing => libc::MFD_ALLOW_SEALING,
+            CreateFlag::Cloexec => libc::MFD_CLOEXEC,
+            CreateFlag::Hugetlb => libc::MFD_HUGETLB,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SealType {
+    Seal,
+    Shrink,
+    Grow,
+    Write,
+}
+
+impl SealType {
+    fn flag(self) -> SealFlag {
+        match self {
+            SealType::Seal => SealFlag::F_SEAL_SEAL,
+            SealType::Shrink => SealFlag::F_SEAL_SHRINK,
+            SealType::Grow => SealFlag::F_SEAL_GROW,
+            SealType::Write => SealFlag::F_SEAL_WRITE,
+        }
+    }
+}
+
+fn format_seals(seals: SealFlag) -> String {
+    let names: Vec<&str> = SEAL_NAMES
+        .iter()
+        .filter(|(flag, _)| seals.contains(*flag))
+        .map(|(_, name)| *name)
+        .collect();
+    if names.is_empty() {
+        "none".to_owned()
+    } else {
+        names.join(" | ")
+    }
+}
+
+fn memfd_create(name: &str, flags: u32) -> anyhow::Result<File> {
+    let c_name = CString::new(name).map_err(|_| anyhow!("NAME must not contain a NUL byte"))?;
+    // SAFETY: c_name is NUL-terminated.
+    let fd = unsafe { libc::memfd_create(c_name.as_ptr(), flags) };
+    let fd = Errno::result(fd)
+        .map_err(|errno| match errno {
+            Errno::EINVAL if flags & libc::MFD_HUGETLB != 0 => {
+                anyhow!("hugetlb is not supported; see /proc/sys/vm/nr_hugepages")
+            }
+            errno => anyhow!(errno),
+        })
+        .context("Failed to call memfd_create")?;
+
+    // SAFETY: memfd_create returns a new file descriptor owned by nobody else.
+    Ok(unsafe { File::from_raw_fd(fd) })
+}
+
+fn copy_input(path: &Path, memfd: &mut File) -> anyhow::Result<u64> {
+    let mut input: Box<dyn Read> = if path == Path::new("-") {
+        Box::new(std::io::stdin().lock())
+    } else {
+        Box::new(File::open(path).with_context(|| format!("Failed to open '{}'", path.display()))?)
+    };
+
+    let copied = std::io::copy(&mut input, memfd)
+        .map_err(|err| match err.raw_os_error() {
+            // hugetlbfs only supports mmap(2)
+            Some(libc::EINVAL) => anyhow!("A hugetlb memory file cannot be written with write(2)"),
+            _ => anyhow!(err),
+        })
+        .context("Failed to copy the input to the memory file")?;
+    memfd
+        .flush()
+        .context("Failed to copy the input to the memory file")?;
+
+    Ok(copied)
+}
+
+/// Adds seals and returns all the seals of the file.
+fn add_seals(file: &File, seals: &[SealType]) -> anyhow::Result<SealFlag> {
+    let flags = seals
+        .iter()
+        .fold(SealFlag::empty(), |flags, seal| flags | seal.flag());
+    fcntl(file.as_raw_fd(), FcntlArg::F_ADD_SEALS(flags))
+        .map_err(|errno| match errno {
+            Errno::EINVAL => anyhow!("The file is not a memory file"),
+            Errno::EPERM => {
+                anyhow!("The file is created without allow-sealing, or sealed with F_SEAL_SEAL")
+            }
+            Errno::EBUSY => anyhow!("F_SEAL_WRITE is not allowed while writable mappings exist"),
+            errno => anyhow!(errno),
+        })
+        .context("Failed to add seals")?;
+
+    let seals = fcntl(file.as_raw_fd(), FcntlArg::F_GET_SEALS).context("Failed to get seals")?;
+    Ok(SealFlag::from_bits_truncate(seals))
+}
+
+fn handle_create(
+    name: String,
+    flags: Vec<CreateFlag>,
+    input: Option<PathBuf>,
+    seals: Vec<SealType>,
+    command: Vec<OsString>,
+) -> anyhow::Result<()> {
+    if flags.contains(&CreateFlag::Cloexec) && !command.is_empty() {
+        return Err(anyhow!(
+            "With --flags cloexec, the file descriptor is closed before COMMAND runs"
+        ));
+    }
+    if !seals.is_empty() && !flags.contains(&CreateFlag::AllowSealing) {
+        return Err(anyhow!("--seals requires --flags allow-sealing"));
+    }
+
+    let bits = flags.iter().fold(0, |bits, flag| bits | flag.bits());
+    let mut memfd = memfd_create(&name, bits)?;
+    let fd = memfd.as_raw_fd();
+    eprintln!("name: memfd:{}", name);
+    eprintln!(
+        "flags: {}",
+        if flags.is_empty() {
+            "none".to_owned()
+        } else {
+            flags
+                .iter()
+                .map(|flag| flag.to_string())
+                .collect::<Vec<_>>()
+                .join(" | ")
+        }
+    );
+    eprintln!("fd: {}", fd);
+
+    if let Some(input) = input {
+        let copied = copy_input(&input, &mut memfd)?;
+        eprintln!("size: {}", copied);
+    }
+    if !seals.is_empty() {
+        let seals = add_seals(&memfd, &seals)?;
+        eprintln!("seals: {}", format_seals(seals));
+    }
+
+    if command.is_empty() {
+        // Other processes can open the file through /proc while this process is alive
+        println!("/proc/{}/fd/{}", std::process::id(), fd);
+        loop {
+            nix::unistd::pause();
+        }
+    }
+
+    let err = Command::new(&command[0])
+        .args(&command[1..])
+        .env(FD_ENV_VAR, fd.to_string())
+        .exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn handle_seal(path: PathBuf, seals: Vec<SealType>) -> anyhow::Result<()> {
+    // Adding seals requires write access
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open '{}'", path.display()))?;
+
+    let seals = add_seals(&file, &seals)?;
+    println!("seals: {}", format_seals(seals));
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Create {
+            name,
+            flags,
+            input,
+            seals,
+            command,
+        } => handle_create(name, flags, input, seals, command),
+        Commands::Seal { path, seals } => handle_seal(path, seals),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
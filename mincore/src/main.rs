@@ -0,0 +1,118 @@
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::mem::{page_size, query_residency};
+use linux_tools_core::print_completer;
+use std::fs::File;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    query: QueryInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct QueryInfo {
+    /// File to query the page cache residency of
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Start of the range to query (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Length of the range to query [default: up to the end of FILE]
+    #[clap(short, long, value_parser = parse_size)]
+    length: Option<i64>,
+    /// Print contiguous spans of resident and non-resident pages
+    #[clap(short, long)]
+    verbose: bool,
+}
+
+/// Splits the pages into spans of the same residency as `(first page, number of pages, resident)`.
+fn spans(residency: &[bool]) -> Vec<(usize, usize, bool)> {
+    let mut spans: Vec<(usize, usize, bool)> = Vec::new();
+    for (page, &resident) in residency.iter().enumerate() {
+        match spans.last_mut() {
+            Some((_, count, last)) if *last == resident => *count += 1,
+            _ => spans.push((page, 1, resident)),
+        }
+    }
+    spans
+}
+
+fn handle_query(info: QueryInfo) -> anyhow::Result<()> {
+    // clap requires FILE unless a subcommand is given
+    let filename = info.filename.unwrap();
+    let file = File::open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+    let file_len = file
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?
+        .len() as i64;
+
+    // The range is limited to the file since pages beyond its end are never cached
+    let offset = info.offset.min(file_len);
+    let end = match info.length {
+        Some(length) => offset.saturating_add(length).min(file_len),
+        None => file_len,
+    };
+    let page_size = page_size()?;
+    // mmap requires the offset to be page aligned
+    let start = offset / page_size * page_size;
+    let residency = query_residency(&file, start, end, page_size)?;
+    let resident = residency.iter().filter(|&&resident| resident).count();
+
+    println!("filename: {}", filename.display());
+    println!("offset: {}", offset);
+    println!("length: {}", end - offset);
+    println!("pages: {}", residency.len());
+    println!("resident: {}", resident);
+    println!(
+        "percentage: {:.2}%",
+        resident as f64 * 100.0 / residency.len().max(1) as f64
+    );
+
+    if info.verbose {
+        println!();
+        println!("{:>16} {:>16} {:>10}  STATE", "OFFSET", "LENGTH", "PAGES");
+        for (page, count, resident) in spans(&residency) {
+            let span_start = start + page as i64 * page_size;
+            let span_end = (span_start + count as i64 * page_size).min(end);
+            let span_start = span_start.max(offset);
+            println!(
+                "{:>16} {:>16} {:>10}  {}",
+                span_start,
+                span_end - span_start,
+                count,
+                if resident { "resident" } else { "not resident" }
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_query(cli.query),
+    }
+}
@@ -0,0 +1,250 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::mem::page_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Lock pages of a file in memory until interrupted
+    #[clap(display_order = 1, name = "lock")]
+    Lock {
+        #[clap(flatten)]
+        range: RangeInfo,
+        /// Exit after the number of seconds instead of waiting to be interrupted
+        #[clap(short, long, value_parser, value_name = "SECONDS")]
+        timeout: Option<u64>,
+    },
+    /// Unlock pages of a file mapped by this process
+    #[clap(display_order = 2, name = "unlock")]
+    Unlock {
+        #[clap(flatten)]
+        range: RangeInfo,
+    },
+    /// Lock all the memory of this process with mlockall until interrupted
+    #[clap(display_order = 3, name = "mlockall")]
+    MlockAll {
+        /// Lock the pages currently mapped
+        #[clap(short, long, required_unless_present = "future")]
+        current: bool,
+        /// Lock the pages mapped in the future
+        #[clap(short, long)]
+        future: bool,
+        /// Lock the pages only when they are faulted in
+        #[clap(short, long)]
+        onfault: bool,
+        /// Exit after the number of seconds instead of waiting to be interrupted
+        #[clap(short, long, value_parser, value_name = "SECONDS")]
+        timeout: Option<u64>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 4, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct RangeInfo {
+    /// File whose pages are locked
+    #[clap(value_parser, value_name = "FILE")]
+    filename: PathBuf,
+    /// Start of the range (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Length of the range [default: up to the end of FILE]
+    #[clap(short, long, value_parser = parse_size)]
+    length: Option<i64>,
+}
+
+/// A read-only shared mapping of a range of a file, unmapped on drop.
+struct Mapping {
+    addr: *mut libc::c_void,
+    len: usize,
+    pages: usize,
+}
+
+impl Mapping {
+    fn new(info: &RangeInfo) -> anyhow::Result<Self> {
+        let file = File::open(&info.filename)
+            .with_context(|| format!("Failed to open '{}'", info.filename.display()))?;
+        let file_len = file
+            .metadata()
+            .context("Failed to retrieve metadata of the file")?
+            .len() as i64;
+
+        let offset = info.offset.min(file_len);
+        let end = match info.length {
+            Some(length) => offset.saturating_add(length).min(file_len),
+            None => file_len,
+        };
+        let page_size = page_size()?;
+        // mmap requires the offset to be page aligned
+        let start = offset / page_size * page_size;
+        if end <= start {
+            return Err(anyhow!(
+                "The range of '{}' is empty",
+                info.filename.display()
+            ));
+        }
+
+        let len = (end - start) as usize;
+        // SAFETY: the mapping is read-only and only passed to the kernel.
+        let addr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                ProtFlags::PROT_READ,
+                MapFlags::MAP_SHARED,
+                file.as_raw_fd(),
+                start,
+            )
+        }
+        .context("Failed to map the file")?;
+
+        Ok(Mapping {
+            addr,
+            len,
+            pages: ((end - start + page_size - 1) / page_size) as usize,
+        })
+    }
+
+    /// Returns the number of the pages resident in memory.
+    fn resident_pages(&self) -> anyhow::Result<usize> {
+        let mut vec = vec![0u8; self.pages];
+        // SAFETY: the mapping is len bytes, and vec has an entry for each of its pages.
+        let ret = unsafe { libc::mincore(self.addr, self.len, vec.as_mut_ptr()) };
+        Errno::result(ret).context("Failed to call mincore")?;
+
+        Ok(vec.into_iter().filter(|v| v & 1 != 0).count())
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is no longer used.
+        let _ = unsafe { munmap(self.addr, self.len) };
+    }
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::ENOMEM => anyhow!(
+            "Locking exceeds RLIMIT_MEMLOCK; raise it with prlimit or run with CAP_IPC_LOCK"
+        ),
+        Errno::EPERM => anyhow!("RLIMIT_MEMLOCK is 0, so locking requires CAP_IPC_LOCK"),
+        Errno::EAGAIN => anyhow!("Some of the pages could not be locked"),
+        errno => anyhow!(errno),
+    }
+}
+
+/// Reads the size of locked memory of this process from /proc/self/status.
+fn locked_size() -> anyhow::Result<String> {
+    let status =
+        std::fs::read_to_string("/proc/self/status").context("Failed to read /proc/self/status")?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmLck:"))
+        .map(|size| size.trim().to_owned())
+        .ok_or_else(|| anyhow!("VmLck is not found in /proc/self/status"))
+}
+
+/// Keeps this process alive, and so its locks, until the timeout or a signal.
+fn hold(timeout: Option<u64>) {
+    match timeout {
+        Some(timeout) => std::thread::sleep(Duration::from_secs(timeout)),
+        None => loop {
+            nix::unistd::pause();
+        },
+    }
+}
+
+fn handle_lock(range: RangeInfo, timeout: Option<u64>) -> anyhow::Result<()> {
+    let mapping = Mapping::new(&range)?;
+    // SAFETY: the mapping is valid for len bytes.
+    let ret = unsafe { libc::mlock(mapping.addr, mapping.len) };
+    Errno::result(ret)
+        .map_err(describe)
+        .with_context(|| format!("Failed to lock '{}'", range.filename.display()))?;
+
+    eprintln!("filename: {}", range.filename.display());
+    eprintln!("pages: {}", mapping.pages);
+    eprintln!("locked: {}", mapping.resident_pages()?);
+    hold(timeout);
+
+    Ok(())
+}
+
+fn handle_unlock(range: RangeInfo) -> anyhow::Result<()> {
+    let mapping = Mapping::new(&range)?;
+    // SAFETY: the mapping is valid for len bytes.
+    let ret = unsafe { libc::munlock(mapping.addr, mapping.len) };
+    Errno::result(ret)
+        .with_context(|| format!("Failed to unlock '{}'", range.filename.display()))?;
+
+    eprintln!("filename: {}", range.filename.display());
+    eprintln!("pages: {}", mapping.pages);
+    eprintln!("resident: {}", mapping.resident_pages()?);
+
+    Ok(())
+}
+
+fn handle_mlockall(
+    current: bool,
+    future: bool,
+    onfault: bool,
+    timeout: Option<u64>,
+) -> anyhow::Result<()> {
+    let mut flags = 0;
+    for (enabled, flag) in [
+        (current, libc::MCL_CURRENT),
+        (future, libc::MCL_FUTURE),
+        (onfault, libc::MCL_ONFAULT),
+    ] {
+        if enabled {
+            flags |= flag;
+        }
+    }
+    // SAFETY: mlockall only takes an integer argument.
+    let ret = unsafe { libc::mlockall(flags) };
+    Errno::result(ret)
+        .map_err(describe)
+        .context("Failed to call mlockall")?;
+
+    eprintln!("locked: {}", locked_size()?);
+    hold(timeout);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Lock { range, timeout } => handle_lock(range, timeout),
+        Commands::Unlock { range } => handle_unlock(range),
+        Commands::MlockAll {
+            current,
+            future,
+            onfault,
+            timeout,
+        } => handle_mlockall(current, future, onfault, timeout),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,466 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::OutputFormat;
+use linux_tools_core::print_completer;
+use serde::Serialize;
+use std::io::ErrorKind;
+
+/// Names of the fields usable in templates, which are the keys of the JSON output
+const FIELD_NAMES: [&str; 11] = [
+    "mount_id",
+    "parent_id",
+    "major",
+    "minor",
+    "root",
+    "mountpoint",
+    "mount_options",
+    "optional_fields",
+    "fstype",
+    "source",
+    "super_options",
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    mount_info: MountInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct MountInfo {
+    /// Print the mounts in the mount namespace of the process [default: self]
+    #[clap(short, long, value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// Only print the mounts of the filesystem type, or whose mount point matches the pattern,
+    /// where * matches any characters and ? matches a character
+    #[clap(
+        short,
+        long,
+        number_of_values = 2,
+        multiple_occurrences = true,
+        value_names = &["fstype|mountpoint", "VALUE"],
+        value_parser
+    )]
+    filter: Vec<String>,
+    /// Format of the result
+    #[clap(short = 'O', long, arg_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Print the result in JSON, the same as --output-format json
+    #[clap(short, long, conflicts_with_all = &["output-format", "format"])]
+    json: bool,
+    /// Print each mount with the template, where {{.FIELD}} is replaced with the field such as
+    /// {{.mountpoint}}
+    #[clap(long, value_parser, value_name = "TEMPLATE")]
+    format: Option<String>,
+}
+
+/// A line of /proc/PID/mountinfo.
+#[derive(Serialize)]
+struct Mount {
+    mount_id: u32,
+    parent_id: u32,
+    major: u32,
+    minor: u32,
+    root: String,
+    mountpoint: String,
+    mount_options: String,
+    optional_fields: Vec<String>,
+    fstype: String,
+    source: String,
+    super_options: String,
+}
+
+impl Mount {
+    /// Parses a line such as
+    /// `36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue`.
+    fn parse(line: &str) -> Option<Mount> {
+        let mut fields = line.split(' ');
+        let mount_id = fields.next()?.parse().ok()?;
+        let parent_id = fields.next()?.parse().ok()?;
+        let (major, minor) = fields.next()?.split_once(':')?;
+        let root = unescape(fields.next()?);
+        let mountpoint = unescape(fields.next()?);
+        let mount_options = fields.next()?.to_owned();
+        // The optional fields are terminated by a single hyphen
+        let optional_fields = fields
+            .by_ref()
+            .take_while(|&field| field != "-")
+            .map(str::to_owned)
+            .collect();
+        let fstype = unescape(fields.next()?);
+        let source = unescape(fields.next()?);
+        let super_options = fields.next()?.to_owned();
+
+        Some(Mount {
+            mount_id,
+            parent_id,
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+            root,
+            mountpoint,
+            mount_options,
+            optional_fields,
+            fstype,
+            source,
+            super_options,
+        })
+    }
+
+    fn field(&self, name: &str) -> Option<String> {
+        let value = match name {
+            "mount_id" => self.mount_id.to_string(),
+            "parent_id" => self.parent_id.to_string(),
+            "major" => self.major.to_string(),
+            "minor" => self.minor.to_string(),
+            "root" => self.root.clone(),
+            "mountpoint" => self.mountpoint.clone(),
+            "mount_options" => self.mount_options.clone(),
+            "optional_fields" => self.optional_fields.join(" "),
+            "fstype" => self.fstype.clone(),
+            "source" => self.source.clone(),
+            "super_options" => self.super_options.clone(),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+/// Decodes the octal escapes such as `\040` the kernel uses for spaces, tabs, newlines and
+/// backslashes.
+fn unescape(field: &str) -> String {
+    let bytes = field.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let octal = bytes
+            .get(i + 1..i + 4)
+            .filter(|_| bytes[i] == b'\\')
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+        match octal {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 4;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn read_mounts(pid: Option<i32>) -> anyhow::Result<Vec<Mount>> {
+    let path = match pid {
+        Some(pid) => format!("/proc/{}/mountinfo", pid),
+        None => "/proc/self/mountinfo".to_owned(),
+    };
+    let content = std::fs::read_to_string(&path).map_err(|err| match (err.kind(), pid) {
+        (ErrorKind::NotFound, Some(pid)) => anyhow!("Process {} does not exist", pid),
+        (ErrorKind::PermissionDenied, Some(pid)) => anyhow!(
+            "Reading {} requires root, or the permission to ptrace process {}",
+            path,
+            pid
+        ),
+        _ => anyhow!(err).context(format!("Failed to read {}", path)),
+    })?;
+
+    content
+        .lines()
+        .map(|line| {
+            Mount::parse(line).ok_or_else(|| anyhow!("Unexpected line in {}: {}", path, line))
+        })
+        .collect()
+}
+
+/// Matches a text with a pattern, where `*` matches any characters and `?` matches a character.
+fn matches_pattern(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| matches_pattern(rest, &text[skip..])),
+        Some((&expected, rest)) => match text.split_first() {
+            Some((&actual, text)) => {
+                (expected == '?' || expected == actual) && matches_pattern(rest, text)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Filters of the same key match a mount if any of them matches, while filters of
+/// different keys all have to match.
+#[derive(Default)]
+struct Filters {
+    fstypes: Vec<String>,
+    mountpoints: Vec<Vec<char>>,
+}
+
+impl Filters {
+    fn parse(values: &[String]) -> anyhow::Result<Self> {
+        let mut filters = Filters::default();
+        for pair in values.chunks(2) {
+            match pair[0].as_str() {
+                "fstype" => filters.fstypes.push(pair[1].clone()),
+                "mountpoint" => filters.mountpoints.push(pair[1].chars().collect()),
+                key => {
+                    return Err(anyhow!(
+                        "Unknown filter '{}'; supported filters are fstype and mountpoint",
+                        key
+                    ))
+                }
+            }
+        }
+
+        Ok(filters)
+    }
+
+    fn matches(&self, mount: &Mount) -> bool {
+        let fstype_matches = self.fstypes.is_empty() || self.fstypes.contains(&mount.fstype);
+        let mountpoint: Vec<char> = mount.mountpoint.chars().collect();
+        let mountpoint_matches = self.mountpoints.is_empty()
+            || self
+                .mountpoints
+                .iter()
+                .any(|pattern| matches_pattern(pattern, &mountpoint));
+
+        fstype_matches && mountpoint_matches
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(String),
+}
+
+/// Splits a template such as `{{.source}} on {{.mountpoint}}` into literals and fields.
+fn parse_template(template: &str) -> anyhow::Result<Vec<Segment>> {
+    let mut segments = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find("{{") {
+        if open > 0 {
+            segments.push(Segment::Literal(rest[..open].to_owned()));
+        }
+        let close = rest[open..]
+            .find("}}")
+            .ok_or_else(|| anyhow!("'{{{{' is not closed in the template"))?;
+        let selector = rest[open + 2..open + close].trim();
+        let name = selector
+            .strip_prefix('.')
+            .filter(|name| FIELD_NAMES.contains(name))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Unknown field '{}' in the template; supported fields are .{}",
+                    selector,
+                    FIELD_NAMES.join(", .")
+                )
+            })?;
+        segments.push(Segment::Field(name.to_owned()));
+        rest = &rest[open + close + 2..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest.to_owned()));
+    }
+
+    Ok(segments)
+}
+
+fn print_template(mounts: &[Mount], segments: &[Segment]) {
+    for mount in mounts {
+        let mut line = String::new();
+        for segment in segments {
+            match segment {
+                Segment::Literal(literal) => line.push_str(literal),
+                // The template only contains fields in FIELD_NAMES
+                Segment::Field(name) => line.push_str(&mount.field(name).unwrap_or_default()),
+            }
+        }
+        println!("{}", line);
+    }
+}
+
+fn print_table(mounts: &[Mount]) {
+    let width = |header: &str, value: &dyn Fn(&Mount) -> usize| {
+        mounts
+            .iter()
+            .map(value)
+            .max()
+            .unwrap_or(0)
+            .max(header.len())
+    };
+    let fstype_width = width("FSTYPE", &|mount| mount.fstype.len());
+    let source_width = width("SOURCE", &|mount| mount.source.len());
+    let mountpoint_width = width("MOUNTPOINT", &|mount| mount.mountpoint.len());
+
+    println!(
+        "{:>8} {:>8} {:>9} {:<fstype_width$} {:<source_width$} {:<mountpoint_width$} OPTIONS",
+        "ID",
+        "PARENT",
+        "MAJ:MIN",
+        "FSTYPE",
+        "SOURCE",
+        "MOUNTPOINT",
+        fstype_width = fstype_width,
+        source_width = source_width,
+        mountpoint_width = mountpoint_width
+    );
+    for mount in mounts {
+        println!(
+            "{:>8} {:>8} {:>9} {:<fstype_width$} {:<source_width$} {:<mountpoint_width$} {}",
+            mount.mount_id,
+            mount.parent_id,
+            format!("{}:{}", mount.major, mount.minor),
+            mount.fstype,
+            mount.source,
+            mount.mountpoint,
+            mount.mount_options,
+            fstype_width = fstype_width,
+            source_width = source_width,
+            mountpoint_width = mountpoint_width
+        );
+    }
+}
+
+fn handle_mount_info(info: MountInfo) -> anyhow::Result<()> {
+    let filters = Filters::parse(&info.filter)?;
+    let segments = info.format.as_deref().map(parse_template).transpose()?;
+
+    let mut mounts = read_mounts(info.pid)?;
+    mounts.retain(|mount| filters.matches(mount));
+
+    let json = info.json || info.output_format == OutputFormat::Json;
+    if json && info.format.is_some() {
+        return Err(anyhow!("--format cannot be used with --output-format json"));
+    }
+    if json {
+        let json = serde_json::to_string(&mounts).context("Failed to serialize the result")?;
+        println!("{}", json);
+    } else if let Some(segments) = segments {
+        print_template(&mounts, &segments);
+    } else {
+        print_table(&mounts);
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_mount_info(cli.mount_info),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: &str = "36 35 98:0 /mnt1 /mnt\\040two rw,noatime master:1 shared:2 - ext3 /dev/root rw,errors=continue";
+
+    #[test]
+    fn mountinfo_line_is_parsed() {
+        let mount = Mount::parse(LINE).unwrap();
+        assert_eq!((mount.mount_id, mount.parent_id), (36, 35));
+        assert_eq!((mount.major, mount.minor), (98, 0));
+        assert_eq!(mount.root, "/mnt1");
+        assert_eq!(mount.mountpoint, "/mnt two");
+        assert_eq!(mount.mount_options, "rw,noatime");
+        assert_eq!(mount.optional_fields, vec!["master:1", "shared:2"]);
+        assert_eq!(mount.fstype, "ext3");
+        assert_eq!(mount.source, "/dev/root");
+        assert_eq!(mount.super_options, "rw,errors=continue");
+    }
+
+    #[test]
+    fn mountinfo_line_without_optional_fields_is_parsed() {
+        let mount = Mount::parse("25 30 0:23 / /sys rw,nosuid - sysfs sysfs rw").unwrap();
+        assert!(mount.optional_fields.is_empty());
+        assert_eq!(mount.fstype, "sysfs");
+    }
+
+    #[test]
+    fn malformed_mountinfo_line_is_rejected() {
+        assert!(Mount::parse("").is_none());
+        assert!(Mount::parse("36 35 98-0 / / rw - ext3 /dev/root rw").is_none());
+        assert!(Mount::parse("36 35 98:0 / / rw master:1 ext3 /dev/root rw").is_none());
+    }
+
+    #[test]
+    fn template_is_split_into_literals_and_fields() {
+        assert_eq!(
+            parse_template("{{.source}} on {{ .mountpoint }}\\n").unwrap(),
+            vec![
+                Segment::Field("source".to_owned()),
+                Segment::Literal(" on ".to_owned()),
+                Segment::Field("mountpoint".to_owned()),
+                Segment::Literal("\\n".to_owned()),
+            ]
+        );
+        assert_eq!(
+            parse_template("no fields").unwrap(),
+            vec![Segment::Literal("no fields".to_owned())]
+        );
+    }
+
+    #[test]
+    fn invalid_template_is_rejected() {
+        assert!(parse_template("{{.mountpoint").is_err());
+        assert!(parse_template("{{mountpoint}}").is_err());
+        assert!(parse_template("{{.size}}").is_err());
+    }
+
+    #[test]
+    fn every_field_name_is_a_field() {
+        let mount = Mount::parse(LINE).unwrap();
+        for name in FIELD_NAMES {
+            assert!(mount.field(name).is_some(), "{}", name);
+        }
+        assert_eq!(mount.field("optional_fields").unwrap(), "master:1 shared:2");
+    }
+
+    #[test]
+    fn pattern_has_wildcards() {
+        let matches = |pattern: &str, text: &str| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let text: Vec<char> = text.chars().collect();
+            matches_pattern(&pattern, &text)
+        };
+        assert!(matches("/sys/*", "/sys/fs/cgroup"));
+        assert!(matches("/*/fs/*", "/sys/fs/cgroup"));
+        assert!(matches("/mnt?", "/mnt1"));
+        assert!(matches("*", ""));
+        assert!(!matches("/mnt?", "/mnt"));
+        assert!(!matches("/sys", "/sys/fs"));
+    }
+
+    #[test]
+    fn filters_of_different_keys_all_have_to_match() {
+        let values = ["fstype", "ext3", "fstype", "xfs", "mountpoint", "/mnt*"];
+        let filters = Filters::parse(&values.map(str::to_owned)).unwrap();
+        assert!(filters.matches(&Mount::parse(LINE).unwrap()));
+        assert!(!filters.matches(&Mount::parse("1 0 8:1 / / rw - ext3 /dev/sda1 rw").unwrap()));
+        assert!(!filters.matches(&Mount::parse("1 0 8:1 / /mnt rw - tmpfs tmpfs rw").unwrap()));
+
+        assert!(Filters::parse(&["size".to_owned(), "0".to_owned()]).is_err());
+    }
+}
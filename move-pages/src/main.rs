@@ -0,0 +1,259 @@
+use anyhow::anyhow;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::{parse_address, parse_size};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::collections::BTreeMap;
+use std::ffi::c_void;
+use std::io::ErrorKind;
+use std::os::raw::c_int;
+
+/// Moves the pages used only by the process, which does not require CAP_SYS_NICE.
+const MPOL_MF_MOVE: c_int = 1 << 1;
+/// Pages passed to a single move_pages(2).
+const CHUNK_PAGES: usize = 4096;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    pages: PagesInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct PagesInfo {
+    /// Process whose pages are queried or moved [default: this process]
+    #[clap(short, long, value_parser = clap::value_parser!(i32).range(1..))]
+    pid: Option<i32>,
+    /// Move the pages to this node, instead of printing the node each page is on
+    #[clap(short, long, value_parser, value_name = "N")]
+    node: Option<c_int>,
+    /// Only the pages from START, an address such as 0x7f0c2a400000, for LEN bytes (accepts
+    /// suffixes such as K, MiB, GB) [default: every mapping in /proc/PID/smaps]
+    #[clap(
+        short,
+        long,
+        number_of_values = 2,
+        value_parser,
+        value_names = &["START", "LEN"]
+    )]
+    range: Vec<String>,
+    /// Only print the number of pages on each node or with each error
+    #[clap(short, long)]
+    quiet: bool,
+}
+
+/// A mapping of /proc/PID/smaps with the size of its pages.
+struct Region {
+    start: u64,
+    end: u64,
+    page_size: u64,
+}
+
+/// Parses the values of --range into the start and the end.
+fn parse_range(range: &[String]) -> anyhow::Result<Option<(u64, u64)>> {
+    let (start, len) = match range {
+        [start, len] => (start, len),
+        _ => return Ok(None),
+    };
+    let start = parse_address(start).map_err(|err| anyhow!(err))?;
+    let len = parse_size(len).map_err(|err| anyhow!(err))?;
+    if len <= 0 {
+        return Err(anyhow!("LEN must be larger than 0"));
+    }
+    let end = start
+        .checked_add(len as u64)
+        .ok_or_else(|| anyhow!("The range from {:#x} exceeds the address space", start))?;
+    Ok(Some((start, end)))
+}
+
+fn read_regions(pid: Option<i32>) -> anyhow::Result<Vec<Region>> {
+    let path = match pid {
+        Some(pid) => format!("/proc/{}/smaps", pid),
+        None => "/proc/self/smaps".to_owned(),
+    };
+    let content = std::fs::read_to_string(&path).map_err(|err| match (err.kind(), pid) {
+        (ErrorKind::NotFound, Some(pid)) => anyhow!("Process {} does not exist", pid),
+        _ => anyhow!(err).context(format!("Failed to read {}", path)),
+    })?;
+
+    let mut regions: Vec<Region> = Vec::new();
+    for line in content.lines() {
+        // A header line is like "7f0c2a400000-7f0c2a428000 r--p 00000000 08:01 1234  /usr/lib/libc.so"
+        let range = line
+            .split_whitespace()
+            .next()
+            .and_then(|range| range.split_once('-'))
+            .and_then(|(start, end)| {
+                Some((
+                    u64::from_str_radix(start, 16).ok()?,
+                    u64::from_str_radix(end, 16).ok()?,
+                ))
+            });
+        if let Some((start, end)) = range {
+            regions.push(Region {
+                start,
+                end,
+                page_size: 4096,
+            });
+            continue;
+        }
+        // A field line is like "KernelPageSize:        4 kB"
+        if let Some(value) = line.strip_prefix("KernelPageSize:") {
+            let region = regions
+                .last_mut()
+                .ok_or_else(|| anyhow!("Unexpected line in {}: {}", path, line))?;
+            let kb: u64 = value
+                .trim()
+                .trim_end_matches("kB")
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("Unexpected line in {}: {}", path, line))?;
+            region.page_size = kb * 1024;
+        }
+    }
+
+    Ok(regions)
+}
+
+/// Returns the address of each page of the regions within the range.
+fn page_addresses(regions: &[Region], range: Option<(u64, u64)>) -> Vec<u64> {
+    let (range_start, range_end) = range.unwrap_or((0, u64::MAX));
+    let mut pages = Vec::new();
+    for region in regions {
+        let start = region
+            .start
+            .max(range_start / region.page_size * region.page_size);
+        let end = region.end.min(range_end);
+        let mut page = start;
+        while page < end {
+            pages.push(page);
+            page += region.page_size;
+        }
+    }
+    pages
+}
+
+fn move_pages(
+    pid: i32,
+    pages: &[u64],
+    nodes: Option<&[c_int]>,
+    status: &mut [c_int],
+) -> nix::Result<()> {
+    let pointers: Vec<*mut c_void> = pages.iter().map(|&page| page as *mut c_void).collect();
+    let nodes = nodes.map_or(std::ptr::null(), |nodes| nodes.as_ptr());
+    // SAFETY: the arrays have an entry for each page, and the pages are only addresses, which the
+    // kernel validates.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_move_pages,
+            pid,
+            pointers.len() as u64,
+            pointers.as_ptr(),
+            nodes,
+            status.as_mut_ptr(),
+            MPOL_MF_MOVE,
+        )
+    };
+    Errno::result(ret).map(drop)
+}
+
+fn describe(errno: Errno, pid: i32, node: Option<c_int>) -> anyhow::Error {
+    match errno {
+        Errno::ENOSYS => anyhow!("The kernel does not support NUMA"),
+        Errno::ESRCH => anyhow!("Process {} does not exist", pid),
+        Errno::EPERM => {
+            anyhow!("Permission denied; moving the pages of another process requires CAP_SYS_NICE")
+        }
+        Errno::ENODEV => anyhow!(
+            "Node {} is not online; /sys/devices/system/node/online lists the online nodes",
+            node.unwrap_or_default()
+        ),
+        errno => anyhow!(errno).context("Failed to call move_pages"),
+    }
+}
+
+/// Describes the status of a page, a node or a negative errno.
+fn status_name(status: c_int) -> String {
+    if status >= 0 {
+        return format!("node {}", status);
+    }
+    let errno = Errno::from_i32(-status);
+    let description = match errno {
+        Errno::ENOENT => "not resident",
+        Errno::EFAULT => "the zero page or not mapped",
+        Errno::EACCES => "mapped by multiple processes",
+        Errno::EBUSY => "busy",
+        Errno::EIO => "cannot be written back",
+        Errno::EINVAL => "cannot be moved",
+        Errno::ENOMEM => "no memory on the node",
+        errno => errno.desc(),
+    };
+    format!("{} ({:?})", description, errno)
+}
+
+fn handle_pages(info: PagesInfo) -> anyhow::Result<()> {
+    let range = parse_range(&info.range)?;
+    let regions = read_regions(info.pid)?;
+    let pages = page_addresses(&regions, range);
+    if pages.is_empty() {
+        return Err(match range {
+            Some((start, end)) => anyhow!("No page is mapped in {:#x}-{:#x}", start, end),
+            None => anyhow!("No page is mapped"),
+        });
+    }
+
+    // 0 is this process for move_pages
+    let pid = info.pid.unwrap_or(0);
+    let shown_pid = info.pid.unwrap_or_else(|| std::process::id() as i32);
+    let mut counts = BTreeMap::new();
+    for chunk in pages.chunks(CHUNK_PAGES) {
+        let nodes = info.node.map(|node| vec![node; chunk.len()]);
+        let mut status = vec![0; chunk.len()];
+        move_pages(pid, chunk, nodes.as_deref(), &mut status)
+            .map_err(|errno| describe(errno, shown_pid, info.node))?;
+        for (page, status) in chunk.iter().zip(status) {
+            if !info.quiet {
+                println!("{:#x}: {}", page, status_name(status));
+            }
+            *counts.entry(status).or_insert(0) += 1;
+        }
+    }
+
+    println!("pages: {}", pages.len());
+    if let Some(node) = info.node {
+        println!("target: node {}", node);
+    }
+    // Nodes come before the errors, which are negative
+    let (nodes, errors): (Vec<_>, Vec<_>) =
+        counts.into_iter().partition(|&(status, _)| status >= 0);
+    for (status, count) in nodes.into_iter().chain(errors) {
+        println!("{}: {} pages", status_name(status), count);
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_pages(cli.pages),
+    }
+}
@@ -0,0 +1,219 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::stat::fstat;
+use std::ffi::CString;
+use std::fs::File;
+use std::os::raw::{c_int, c_uint};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// MAX_HANDLE_SZ from linux/exportfs.h, the largest handle of any filesystem
+const MAX_HANDLE_SZ: usize = 128;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the file handles and mount IDs of files with name_to_handle_at
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// Files to get the handles of
+        #[clap(required = true, value_parser, value_name = "FILE")]
+        filenames: Vec<PathBuf>,
+        /// Follow symbolic links instead of getting the handles of them
+        #[clap(short = 'L', long)]
+        follow: bool,
+    },
+    /// Open a file by its handle with open_by_handle_at and print its path and inode
+    #[clap(display_order = 2, name = "open")]
+    Open {
+        /// Any file or directory on the filesystem of the handle, such as its mount point
+        #[clap(value_parser, value_name = "MOUNT")]
+        mount: PathBuf,
+        /// Handle printed by get, in the form of TYPE:HEX
+        #[clap(value_parser = parse_handle, value_name = "HANDLE")]
+        handle: FileHandle,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// struct file_handle from fcntl.h with the space for the largest handle.
+#[repr(C)]
+#[derive(Clone)]
+struct FileHandle {
+    handle_bytes: c_uint,
+    handle_type: c_int,
+    f_handle: [u8; MAX_HANDLE_SZ],
+}
+
+impl FileHandle {
+    fn new() -> Self {
+        FileHandle {
+            handle_bytes: MAX_HANDLE_SZ as c_uint,
+            handle_type: 0,
+            f_handle: [0; MAX_HANDLE_SZ],
+        }
+    }
+
+    fn bytes(&self) -> &[u8] {
+        &self.f_handle[..self.handle_bytes as usize]
+    }
+}
+
+impl std::fmt::Display for FileHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}:", self.handle_type)?;
+        for byte in self.bytes() {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a handle such as `1:a1b2c3d4e5f60708`, which is the type and the bytes in hex.
+fn parse_handle(s: &str) -> Result<FileHandle, String> {
+    let (handle_type, hex) = s
+        .split_once(':')
+        .ok_or_else(|| format!("'{}' is not a handle; expected TYPE:HEX", s))?;
+    let mut handle = FileHandle::new();
+    handle.handle_type = handle_type
+        .parse()
+        .map_err(|_| format!("'{}' is not a handle type", handle_type))?;
+    if hex.len() % 2 != 0 || hex.len() / 2 > MAX_HANDLE_SZ {
+        return Err(format!(
+            "'{}' is not a handle; expected an even number of hex digits up to {} bytes",
+            hex, MAX_HANDLE_SZ
+        ));
+    }
+    for (i, byte) in handle.f_handle[..hex.len() / 2].iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16)
+            .map_err(|_| format!("'{}' is not a handle; expected hex digits", hex))?;
+    }
+    handle.handle_bytes = (hex.len() / 2) as c_uint;
+
+    Ok(handle)
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::EOPNOTSUPP => anyhow!("The filesystem does not support file handles"),
+        Errno::EPERM => anyhow!("Opening a file by its handle requires CAP_DAC_READ_SEARCH"),
+        Errno::ESTALE => anyhow!("The handle no longer refers to a file"),
+        Errno::EINVAL => anyhow!("The handle is not valid for the filesystem"),
+        errno => anyhow!(errno),
+    }
+}
+
+fn name_to_handle(filename: &Path, follow: bool) -> anyhow::Result<(FileHandle, c_int)> {
+    let path = CString::new(filename.as_os_str().as_bytes())
+        .with_context(|| format!("'{}' contains a NUL byte", filename.display()))?;
+    let flags = if follow { libc::AT_SYMLINK_FOLLOW } else { 0 };
+    let mut handle = FileHandle::new();
+    let mut mount_id: c_int = 0;
+    // SAFETY: path is a NUL-terminated string, handle has the space of handle_bytes and
+    // mount_id is a valid int.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_name_to_handle_at,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            &mut handle as *mut FileHandle,
+            &mut mount_id as *mut c_int,
+            flags,
+        )
+    };
+    Errno::result(ret).map_err(describe)?;
+
+    Ok((handle, mount_id))
+}
+
+fn handle_get(filenames: &[PathBuf], follow: bool) -> anyhow::Result<ExitCode> {
+    let mut failures = 0usize;
+    let mut printed = false;
+    for filename in filenames {
+        match name_to_handle(filename, follow) {
+            Ok((handle, mount_id)) => {
+                if printed {
+                    println!();
+                }
+                printed = true;
+                println!("file: {}", filename.display());
+                println!("handle: {}", handle);
+                println!("mount_id: {}", mount_id);
+            }
+            Err(err) => {
+                eprintln!("name-to-handle: {}: {:#}", filename.display(), err);
+                failures += 1;
+            }
+        }
+    }
+
+    Ok(if failures == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn handle_open(mount: &Path, handle: &FileHandle) -> anyhow::Result<()> {
+    let mount_file =
+        File::open(mount).with_context(|| format!("Failed to open '{}'", mount.display()))?;
+    let mut handle = handle.clone();
+    // O_PATH opens any type of file, including symbolic links, without reading it
+    // SAFETY: handle has the space of handle_bytes.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_open_by_handle_at,
+            mount_file.as_raw_fd(),
+            &mut handle as *mut FileHandle,
+            libc::O_PATH | libc::O_CLOEXEC,
+        )
+    };
+    let fd = Errno::result(ret)
+        .map_err(describe)
+        .with_context(|| format!("Failed to open the file of handle {}", handle))?;
+    // SAFETY: the file descriptor was just opened and nothing else owns it.
+    let file = unsafe { File::from_raw_fd(fd as c_int) };
+
+    // The path is the one in the mount namespace, which may differ from the one given to get
+    let path = std::fs::read_link(format!("/proc/self/fd/{}", file.as_raw_fd()))
+        .context("Failed to get the path of the file")?;
+    let stat = fstat(file.as_raw_fd()).context("Failed to stat the file")?;
+    println!("path: {}", path.display());
+    println!("inode: {}", stat.st_ino);
+    println!(
+        "device: {}:{}",
+        nix::sys::stat::major(stat.st_dev),
+        nix::sys::stat::minor(stat.st_dev)
+    );
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { filenames, follow } => handle_get(&filenames, follow),
+        Commands::Open { mount, handle } => handle_open(&mount, &handle).map(|_| ExitCode::SUCCESS),
+        Commands::Completion { shell } => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::cgroup::{cgroup_dir, read_mounts, Membership};
+use linux_tools_core::pidfd::{open_pidfd, pidfd_getfd};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::socket::{getsockopt, setsockopt, GetSockOpt, SetSockOpt};
+use std::ffi::c_void;
+use std::io::ErrorKind;
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the priorities of the cgroup of a process and of its sockets
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// Process to print the priorities of
+        #[clap(value_parser, value_name = "PID")]
+        pid: i32,
+        /// Only print the priority of this socket of the process
+        #[clap(long, value_parser, value_name = "FD")]
+        fd: Option<RawFd>,
+    },
+    /// Set the priority of a socket with SO_PRIORITY or of an interface for the cgroup of a process
+    #[clap(display_order = 2, name = "set")]
+    #[clap(group(ArgGroup::new("target").required(true).args(&["fd", "interface"])))]
+    Set {
+        /// Process whose socket or cgroup is prioritized
+        #[clap(value_parser, value_name = "PID")]
+        pid: i32,
+        /// Priority, which is 0 to 6 unless CAP_NET_ADMIN is given
+        #[clap(value_parser, value_name = "PRIORITY")]
+        priority: u32,
+        /// Set SO_PRIORITY of this socket of the process
+        #[clap(long, value_parser, value_name = "FD")]
+        fd: Option<RawFd>,
+        /// Set the priority of the interface in net_prio.ifpriomap of the cgroup of the process
+        #[clap(short, long, value_name = "IFNAME")]
+        interface: Option<String>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// SO_PRIORITY, which nix does not provide.
+#[derive(Copy, Clone)]
+struct Priority;
+
+impl GetSockOpt for Priority {
+    type Val = u32;
+
+    fn get(&self, fd: RawFd) -> nix::Result<u32> {
+        let mut value: c_int = 0;
+        let mut len = std::mem::size_of::<c_int>() as libc::socklen_t;
+        // SAFETY: value and len are valid for the kernel to write an int and its size.
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PRIORITY,
+                &mut value as *mut c_int as *mut c_void,
+                &mut len,
+            )
+        };
+        Errno::result(ret).map(|_| value as u32)
+    }
+}
+
+impl SetSockOpt for Priority {
+    type Val = u32;
+
+    fn set(&self, fd: RawFd, value: &u32) -> nix::Result<()> {
+        let value = *value as c_int;
+        // SAFETY: value is a valid int for the kernel to read.
+        let ret = unsafe {
+            libc::setsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                libc::SO_PRIORITY,
+                &value as *const c_int as *const c_void,
+                std::mem::size_of::<c_int>() as libc::socklen_t,
+            )
+        };
+        Errno::result(ret).map(drop)
+    }
+}
+
+/// Duplicates a socket of another process with pidfd_getfd(2), which shares the socket with it.
+///
+/// Opening /proc/PID/fd/FD does not work since a socket cannot be reopened.
+fn get_socket(pidfd: &OwnedFd, pid: i32, fd: RawFd) -> anyhow::Result<OwnedFd> {
+    let link = PathBuf::from(format!("/proc/{}/fd/{}", pid, fd));
+    let target = std::fs::read_link(&link).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} has no file descriptor {}", pid, fd),
+        _ => anyhow!(err).context(format!("Failed to read '{}'", link.display())),
+    })?;
+    if !target.to_string_lossy().starts_with("socket:") {
+        return Err(anyhow!(
+            "File descriptor {} of process {} is '{}', not a socket",
+            fd,
+            pid,
+            target.display()
+        ));
+    }
+
+    pidfd_getfd(pidfd, pid, fd)
+}
+
+/// Lists the file descriptors of the sockets of a process.
+fn list_sockets(pid: i32) -> anyhow::Result<Vec<RawFd>> {
+    let dir = PathBuf::from(format!("/proc/{}/fd", pid));
+    let entries = std::fs::read_dir(&dir).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} does not exist", pid),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to read '{}'; the file descriptors of a process of another user require CAP_SYS_PTRACE",
+            dir.display()
+        ),
+        _ => anyhow!(err).context(format!("Failed to read '{}'", dir.display())),
+    })?;
+
+    let mut fds = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read '{}'", dir.display()))?;
+        // A file descriptor closed in the meantime has no link
+        let is_socket = std::fs::read_link(entry.path())
+            .map(|target| target.to_string_lossy().starts_with("socket:"))
+            .unwrap_or(false);
+        if let Some(fd) = entry.file_name().to_str().and_then(|fd| fd.parse().ok()) {
+            if is_socket {
+                fds.push(fd);
+            }
+        }
+    }
+    fds.sort_unstable();
+
+    Ok(fds)
+}
+
+/// Finds the directory of the cgroup of a process in the net_prio hierarchy of cgroup v1.
+fn net_prio_dir(pid: i32) -> anyhow::Result<(String, PathBuf)> {
+    let membership = Membership::read(pid)?;
+    let cgroup = membership.hierarchy("net_prio").ok_or_else(|| {
+        anyhow!("The net_prio hierarchy of cgroup v1 is not mounted; cgroup v2 has no net_prio controller")
+    })?;
+    let mounts = read_mounts()?;
+    let dir = cgroup_dir(&mounts, Some("net_prio"), cgroup)
+        .ok_or_else(|| anyhow!("The net_prio hierarchy of '{}' is not mounted", cgroup))?;
+
+    Ok((cgroup.to_owned(), dir))
+}
+
+fn read_file(path: &Path) -> anyhow::Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn handle_get(pid: i32, fd: Option<RawFd>) -> anyhow::Result<()> {
+    let pidfd = open_pidfd(pid)?;
+    if let Some(fd) = fd {
+        let socket = get_socket(&pidfd, pid, fd)?;
+        let priority =
+            getsockopt(socket.as_raw_fd(), Priority).context("Failed to get SO_PRIORITY")?;
+        println!("fd.{}: {}", fd, priority);
+        return Ok(());
+    }
+
+    println!("pid: {}", pid);
+    match net_prio_dir(pid) {
+        Ok((cgroup, dir)) => {
+            println!("cgroup: {}", cgroup);
+            let prioidx = read_file(&dir.join("net_prio.prioidx"))?;
+            println!("prioidx: {}", prioidx.trim());
+            for line in read_file(&dir.join("net_prio.ifpriomap"))?.lines() {
+                if let Some((interface, priority)) = line.split_once(' ') {
+                    println!("ifpriomap.{}: {}", interface, priority);
+                }
+            }
+        }
+        Err(err) => eprintln!("warning: {:#}", err),
+    }
+    for fd in list_sockets(pid)? {
+        let socket = match get_socket(&pidfd, pid, fd) {
+            Ok(socket) => socket,
+            Err(err) => {
+                eprintln!("warning: {:#}", err);
+                continue;
+            }
+        };
+        let priority =
+            getsockopt(socket.as_raw_fd(), Priority).context("Failed to get SO_PRIORITY")?;
+        println!("fd.{}: {}", fd, priority);
+    }
+
+    Ok(())
+}
+
+fn handle_set_socket(pid: i32, fd: RawFd, priority: u32) -> anyhow::Result<()> {
+    let pidfd = open_pidfd(pid)?;
+    let socket = get_socket(&pidfd, pid, fd)?;
+    let old = getsockopt(socket.as_raw_fd(), Priority).context("Failed to get SO_PRIORITY")?;
+    setsockopt(socket.as_raw_fd(), Priority, &priority).map_err(|errno| match errno {
+        Errno::EPERM => anyhow!("A priority over 6 requires CAP_NET_ADMIN"),
+        errno => anyhow!(errno).context("Failed to set SO_PRIORITY"),
+    })?;
+    let new = getsockopt(socket.as_raw_fd(), Priority).context("Failed to get SO_PRIORITY")?;
+    println!("fd.{}: {} -> {}", fd, old, new);
+
+    Ok(())
+}
+
+fn handle_set_interface(pid: i32, interface: &str, priority: u32) -> anyhow::Result<()> {
+    if !Path::new("/sys/class/net").join(interface).exists() {
+        return Err(anyhow!("Interface '{}' does not exist", interface));
+    }
+    let (cgroup, dir) = net_prio_dir(pid)?;
+    let ifpriomap = dir.join("net_prio.ifpriomap");
+    let line = format!("{} {}", interface, priority);
+    std::fs::write(&ifpriomap, &line).map_err(|err| match err.kind() {
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to write {}; it requires root or the ownership of the cgroup",
+            ifpriomap.display()
+        ),
+        _ => anyhow!(err).context(format!(
+            "Failed to write '{}' to {}",
+            line,
+            ifpriomap.display()
+        )),
+    })?;
+
+    println!("cgroup: {}", cgroup);
+    for line in read_file(&ifpriomap)?.lines() {
+        if let Some(priority) = line
+            .strip_prefix(interface)
+            .and_then(|rest| rest.strip_prefix(' '))
+        {
+            println!("ifpriomap.{}: {}", interface, priority);
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { pid, fd } => handle_get(pid, fd),
+        Commands::Set {
+            pid,
+            priority,
+            fd,
+            interface,
+        } => match (fd, interface) {
+            (Some(fd), _) => handle_set_socket(pid, fd, priority),
+            // clap requires --fd or --interface
+            (None, interface) => handle_set_interface(pid, &interface.unwrap(), priority),
+        },
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
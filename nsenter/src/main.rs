@@ -0,0 +1,180 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::{default_command, run_command};
+use nix::errno::Errno;
+use nix::sched::{setns, CloneFlags};
+use std::ffi::OsString;
+use std::fs::File;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitCode};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    enter: EnterInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct EnterInfo {
+    /// Process whose namespaces are entered
+    #[clap(short = 't', long, required = true, value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// Enter the user namespace
+    #[clap(short = 'U', long)]
+    user: bool,
+    /// Enter the mount namespace
+    #[clap(short, long)]
+    mnt: bool,
+    /// Enter the PID namespace, where COMMAND runs as a child of nsenter
+    #[clap(short = 'p', long)]
+    pid_ns: bool,
+    /// Enter the network namespace
+    #[clap(short, long)]
+    net: bool,
+    /// Enter the IPC namespace
+    #[clap(short, long)]
+    ipc: bool,
+    /// Enter the UTS namespace
+    #[clap(short, long)]
+    uts: bool,
+    /// Enter the cgroup namespace
+    #[clap(short = 'C', long)]
+    cgroup: bool,
+    /// Command to run in the namespaces [default: $SHELL or /bin/sh]
+    #[clap(value_parser, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+/// A namespace with its file name in /proc/PID/ns
+struct Namespace {
+    name: &'static str,
+    flag: CloneFlags,
+}
+
+impl EnterInfo {
+    /// Returns the requested namespaces in the order to enter them.
+    ///
+    /// The user namespace comes first since it gives the capabilities required to
+    /// enter the others, and the mount namespace comes last since it changes the root.
+    fn namespaces(&self) -> Vec<Namespace> {
+        [
+            (self.user, "user", CloneFlags::CLONE_NEWUSER),
+            (self.cgroup, "cgroup", CloneFlags::CLONE_NEWCGROUP),
+            (self.ipc, "ipc", CloneFlags::CLONE_NEWIPC),
+            (self.uts, "uts", CloneFlags::CLONE_NEWUTS),
+            (self.net, "net", CloneFlags::CLONE_NEWNET),
+            (self.pid_ns, "pid", CloneFlags::CLONE_NEWPID),
+            (self.mnt, "mnt", CloneFlags::CLONE_NEWNS),
+        ]
+        .into_iter()
+        .filter(|&(enabled, _, _)| enabled)
+        .map(|(_, name, flag)| Namespace { name, flag })
+        .collect()
+    }
+}
+
+fn describe(errno: Errno, namespace: &Namespace, user: bool) -> anyhow::Error {
+    match errno {
+        Errno::EPERM if namespace.flag == CloneFlags::CLONE_NEWUSER => anyhow!(
+            "Entering a user namespace requires CAP_SYS_ADMIN in it, and a multithreaded process cannot enter it"
+        ),
+        Errno::EPERM if !user => anyhow!(
+            "Entering the namespace requires CAP_SYS_ADMIN in the user namespace owning it; try also --user"
+        ),
+        Errno::EPERM => anyhow!(
+            "Entering the namespace requires CAP_SYS_ADMIN in the user namespace owning it"
+        ),
+        Errno::EINVAL => anyhow!("The kernel does not support the namespace or rejected it"),
+        errno => anyhow!(errno),
+    }
+}
+
+/// Returns whether this process is already in the namespace opened as `file`.
+fn is_current(file: &File, namespace: &Namespace) -> anyhow::Result<bool> {
+    let path = format!("/proc/self/ns/{}", namespace.name);
+    let current = std::fs::metadata(&path).with_context(|| format!("Failed to stat {}", path))?;
+    let target = file
+        .metadata()
+        .with_context(|| format!("Failed to stat the {} namespace", namespace.name))?;
+
+    Ok(current.dev() == target.dev() && current.ino() == target.ino())
+}
+
+fn handle_enter(info: EnterInfo) -> anyhow::Result<ExitCode> {
+    // clap requires PID unless a subcommand is given
+    let pid = info.pid.unwrap();
+    let namespaces = info.namespaces();
+    if namespaces.is_empty() {
+        return Err(anyhow!(
+            "No namespace is given; use --user, --mnt, --pid-ns, --net, --ipc, --uts or --cgroup"
+        ));
+    }
+
+    // All the files are opened first since /proc may change after entering the mount namespace
+    let mut files = Vec::with_capacity(namespaces.len());
+    for namespace in &namespaces {
+        let path = format!("/proc/{}/ns/{}", pid, namespace.name);
+        let file = File::open(&path).with_context(|| format!("Failed to open {}", path))?;
+        files.push(file);
+    }
+
+    for (namespace, file) in namespaces.iter().zip(&files) {
+        // setns fails with EINVAL for the user namespace this process is already in
+        if namespace.flag == CloneFlags::CLONE_NEWUSER && is_current(file, namespace)? {
+            eprintln!("warning: already in the user namespace of process {}", pid);
+            continue;
+        }
+        setns(file.as_raw_fd(), namespace.flag)
+            .map_err(|errno| describe(errno, namespace, info.user))
+            .with_context(|| {
+                format!(
+                    "Failed to enter the {} namespace of process {}",
+                    namespace.name, pid
+                )
+            })?;
+    }
+
+    let command = if info.command.is_empty() {
+        default_command()
+    } else {
+        info.command
+    };
+    if info.pid_ns {
+        // Only children of this process join the PID namespace
+        return run_command(&command);
+    }
+
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_enter(cli.enter),
+    }
+}
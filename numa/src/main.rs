@@ -0,0 +1,564 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::{parse_address, parse_size};
+use linux_tools_core::mem::page_size;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::default_command;
+use nix::errno::Errno;
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use std::collections::BTreeMap;
+use std::ffi::{c_void, OsString};
+use std::os::raw::{c_int, c_ulong};
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+const NODE_PATH: &str = "/sys/devices/system/node";
+
+/// Nodes in the node masks passed to the kernel, which covers the kernel maximum of 1024
+const NODEMASK_BITS: usize = 1024;
+
+const BITS_PER_WORD: usize = c_ulong::BITS as usize;
+
+// Flags of get_mempolicy(2) from linux/mempolicy.h
+const MPOL_F_NODE: c_int = 1;
+const MPOL_F_ADDR: c_int = 2;
+
+/// Mode flags which get_mempolicy(2) returns with the mode
+const MPOL_MODE_FLAGS: c_int = (1 << 15) | (1 << 14) | (1 << 13);
+
+/// Names of the modes of linux/mempolicy.h
+const MODE_NAMES: &[&str] = &[
+    "default",
+    "preferred",
+    "bind",
+    "interleave",
+    "local",
+    "preferred_many",
+    "weighted_interleave",
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the NUMA nodes with their memory, CPUs and distances
+    #[clap(display_order = 1, name = "nodes")]
+    Nodes,
+    /// Print the memory policy of this process or of a mapping of a process
+    #[clap(display_order = 2, name = "get-policy")]
+    GetPolicy {
+        /// Process to print the policy of the mapping at --addr of
+        #[clap(short, long, value_parser, requires = "addr")]
+        pid: Option<i32>,
+        /// Address in the mapping of the process given with --pid, in hex with 0x or in decimal
+        #[clap(short, long, value_parser = parse_address, requires = "pid")]
+        addr: Option<u64>,
+    },
+    /// Run a command with a memory policy
+    #[clap(display_order = 3, name = "set-policy")]
+    #[clap(trailing_var_arg = true)]
+    SetPolicy {
+        #[clap(flatten)]
+        policy: PolicyInfo,
+        /// Command to run with the policy [default: $SHELL or /bin/sh]
+        #[clap(value_parser, value_name = "COMMAND")]
+        command: Vec<OsString>,
+    },
+    /// Map anonymous memory, bind it to nodes with mbind and print where its pages are
+    #[clap(display_order = 4, name = "bind-range")]
+    BindRange {
+        /// Address to map the memory at, in hex with 0x or in decimal (0 lets the kernel choose)
+        #[clap(value_parser = parse_address, value_name = "ADDR")]
+        addr: u64,
+        /// Length of the memory (accepts suffixes such as K, MiB)
+        #[clap(value_parser = parse_size, value_name = "LEN")]
+        len: i64,
+        /// Policy to bind the memory with
+        #[clap(short, long, arg_enum, default_value_t = Policy::Bind)]
+        mode: Policy,
+        /// Nodes to bind the memory to (e.g. 0,1-3), required except for default and local
+        #[clap(short, long, value_parser = parse_node_list, value_name = "NODES")]
+        nodes: Option<NodeList>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 5, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct PolicyInfo {
+    /// Policy to allocate memory with
+    #[clap(short, long, arg_enum)]
+    mode: Policy,
+    /// Nodes of the policy (e.g. 0,1-3), required except for default and local
+    #[clap(short, long, value_parser = parse_node_list, value_name = "NODES")]
+    nodemask: Option<NodeList>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Policy {
+    Default,
+    Bind,
+    Interleave,
+    Preferred,
+    Local,
+}
+
+impl Policy {
+    fn mode(self) -> c_int {
+        match self {
+            Policy::Default => libc::MPOL_DEFAULT,
+            Policy::Bind => libc::MPOL_BIND,
+            Policy::Interleave => libc::MPOL_INTERLEAVE,
+            Policy::Preferred => libc::MPOL_PREFERRED,
+            Policy::Local => libc::MPOL_LOCAL,
+        }
+    }
+
+    fn takes_nodes(self) -> bool {
+        !matches!(self, Policy::Default | Policy::Local)
+    }
+}
+
+fn mode_name(mode: c_int) -> String {
+    let mode = mode & !MPOL_MODE_FLAGS;
+    match MODE_NAMES.get(mode as usize) {
+        Some(name) => (*name).to_owned(),
+        None => format!("unknown ({})", mode),
+    }
+}
+
+/// Sorted node indices without duplicates.
+#[derive(Clone)]
+struct NodeList(Vec<usize>);
+
+/// Parses a node list such as `0,1-3`.
+fn parse_node_list(s: &str) -> Result<NodeList, String> {
+    let parse_node = |node: &str| {
+        node.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("'{}' is not a node index", node))
+    };
+
+    let mut nodes = Vec::new();
+    for part in s.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (parse_node(first)?, parse_node(last)?);
+                if first > last {
+                    return Err(format!("'{}' is not an ascending range", part));
+                }
+                nodes.extend(first..=last);
+            }
+            None => nodes.push(parse_node(part)?),
+        }
+    }
+    nodes.sort_unstable();
+    nodes.dedup();
+    if let Some(&node) = nodes.iter().find(|&&node| node >= NODEMASK_BITS) {
+        return Err(format!(
+            "node {} exceeds the maximum of {}",
+            node,
+            NODEMASK_BITS - 1
+        ));
+    }
+
+    Ok(NodeList(nodes))
+}
+
+/// Formats sorted node indices as a node list such as `0,1-3`.
+fn format_node_list(nodes: &[usize]) -> String {
+    if nodes.is_empty() {
+        return "none".to_owned();
+    }
+
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &node in nodes {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == node => *last = node,
+            _ => ranges.push((node, node)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// A node mask in the layout of the kernel, an array of unsigned longs.
+struct NodeMask(Vec<c_ulong>);
+
+impl NodeMask {
+    fn new() -> Self {
+        NodeMask(vec![0; NODEMASK_BITS / BITS_PER_WORD])
+    }
+
+    fn from_nodes(nodes: &[usize]) -> Self {
+        let mut mask = NodeMask::new();
+        for &node in nodes {
+            mask.0[node / BITS_PER_WORD] |= 1 << (node % BITS_PER_WORD);
+        }
+        mask
+    }
+
+    fn nodes(&self) -> Vec<usize> {
+        (0..NODEMASK_BITS)
+            .filter(|&node| self.0[node / BITS_PER_WORD] & (1 << (node % BITS_PER_WORD)) != 0)
+            .collect()
+    }
+
+    /// Returns maxnode for the system calls, which count one more than the bits for a historical bug.
+    fn maxnode(&self) -> c_ulong {
+        (NODEMASK_BITS + 1) as c_ulong
+    }
+}
+
+fn read_sys_file(path: &str) -> anyhow::Result<String> {
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    Ok(content.trim().to_owned())
+}
+
+fn read_online_nodes() -> anyhow::Result<NodeList> {
+    let path = format!("{}/online", NODE_PATH);
+    parse_node_list(&read_sys_file(&path)?)
+        .map_err(|err| anyhow!(err))
+        .with_context(|| format!("Unexpected content in {}", path))
+}
+
+/// Fails unless all of the nodes are online.
+fn check_online(nodes: &[usize]) -> anyhow::Result<()> {
+    let NodeList(online) = read_online_nodes()?;
+    let offline: Vec<usize> = nodes
+        .iter()
+        .copied()
+        .filter(|node| !online.contains(node))
+        .collect();
+    if !offline.is_empty() {
+        return Err(anyhow!(
+            "Node {} is not online; online nodes are {}",
+            format_node_list(&offline),
+            format_node_list(&online)
+        ));
+    }
+
+    Ok(())
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::ENOSYS => anyhow!("The kernel does not support NUMA"),
+        Errno::EINVAL => anyhow!("The mode does not accept the nodes"),
+        errno => anyhow!(errno),
+    }
+}
+
+/// Reads a value in kB of a line such as "Node 0 MemTotal:  6158152 kB" in nodeN/meminfo.
+fn meminfo_value(meminfo: &str, key: &str) -> Option<u64> {
+    meminfo.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if !name.ends_with(key) {
+            return None;
+        }
+        value.trim().trim_end_matches("kB").trim().parse().ok()
+    })
+}
+
+fn handle_nodes() -> anyhow::Result<()> {
+    let NodeList(nodes) = read_online_nodes()?;
+
+    println!(
+        "{:>4} {:>16} {:>16}  {:<16} DISTANCES",
+        "NODE", "MEM_TOTAL(kB)", "MEM_FREE(kB)", "CPUS"
+    );
+    for node in nodes {
+        let dir = format!("{}/node{}", NODE_PATH, node);
+        let meminfo = read_sys_file(&format!("{}/meminfo", dir))?;
+        let cpus = read_sys_file(&format!("{}/cpulist", dir))?;
+        let distances = read_sys_file(&format!("{}/distance", dir))?;
+        let value = |key| meminfo_value(&meminfo, key).map_or("?".to_owned(), |v| v.to_string());
+        println!(
+            "{:>4} {:>16} {:>16}  {:<16} {}",
+            node,
+            value("MemTotal"),
+            value("MemFree"),
+            // A node with only memory has no CPU
+            if cpus.is_empty() { "none" } else { &cpus },
+            distances
+        );
+    }
+
+    Ok(())
+}
+
+fn get_mempolicy(mask: Option<&mut NodeMask>, addr: usize, flags: c_int) -> nix::Result<c_int> {
+    let mut mode: c_int = 0;
+    let (mask_ptr, maxnode) = match mask {
+        Some(mask) => (mask.0.as_mut_ptr(), mask.maxnode()),
+        None => (std::ptr::null_mut(), 0),
+    };
+    // SAFETY: mode is a valid int, and mask_ptr is null or a node mask of maxnode - 1 bits.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_get_mempolicy,
+            &mut mode as *mut c_int,
+            mask_ptr,
+            maxnode,
+            addr,
+            flags,
+        )
+    };
+    Errno::result(ret).map(|_| mode)
+}
+
+/// A mapping of /proc/PID/maps
+struct Region {
+    start: u64,
+    end: u64,
+}
+
+fn find_region(pid: i32, addr: u64) -> anyhow::Result<Region> {
+    let path = format!("/proc/{}/maps", pid);
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+
+    // Each line is like "7ffd5a3a1000-7ffd5a3c2000 rw-p 00000000 00:00 0  [stack]"
+    content
+        .lines()
+        .filter_map(|line| {
+            let (start, end) = line.split_whitespace().next()?.split_once('-')?;
+            Some(Region {
+                start: u64::from_str_radix(start, 16).ok()?,
+                end: u64::from_str_radix(end, 16).ok()?,
+            })
+        })
+        .find(|region| region.start <= addr && addr < region.end)
+        .ok_or_else(|| anyhow!("{:#x} is not mapped in process {}", addr, pid))
+}
+
+fn handle_get_policy_of(pid: i32, addr: u64) -> anyhow::Result<()> {
+    let region = find_region(pid, addr)?;
+    let path = format!("/proc/{}/numa_maps", pid);
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+
+    // Each line is like "7f2a4c000000 bind:0-1 anon=3 dirty=3 N0=2 N1=1 kernelpagesize_kB=4"
+    let start = format!("{:x}", region.start);
+    let fields: Vec<&str> = content
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.first() == Some(&start.as_str()))
+        .ok_or_else(|| anyhow!("The mapping at {:#x} is not in {}", region.start, path))?;
+    let pages: Vec<&str> = fields
+        .iter()
+        .copied()
+        .filter(|field| {
+            field.strip_prefix('N').map_or(false, |rest| {
+                rest.split_once('=')
+                    .map_or(false, |(node, _)| node.parse::<usize>().is_ok())
+            })
+        })
+        .collect();
+
+    println!("range: {:#x}-{:#x}", region.start, region.end);
+    println!("policy: {}", fields.get(1).unwrap_or(&"?"));
+    println!(
+        "pages: {}",
+        if pages.is_empty() {
+            "none".to_owned()
+        } else {
+            pages.join(" ")
+        }
+    );
+
+    Ok(())
+}
+
+fn handle_get_policy(pid: Option<i32>, addr: Option<u64>) -> anyhow::Result<()> {
+    if let Some((pid, addr)) = pid.zip(addr) {
+        return handle_get_policy_of(pid, addr);
+    }
+
+    // The policy of this process is inherited from the parent process
+    let mut mask = NodeMask::new();
+    let mode = get_mempolicy(Some(&mut mask), 0, 0)
+        .map_err(describe)
+        .context("Failed to get the memory policy")?;
+    println!("mode: {}", mode_name(mode));
+    println!("nodes: {}", format_node_list(&mask.nodes()));
+
+    Ok(())
+}
+
+/// Returns the node mask for the policy, validating that it is given only when the policy takes it.
+fn policy_mask(mode: Policy, nodes: Option<&NodeList>) -> anyhow::Result<Option<NodeMask>> {
+    match (mode.takes_nodes(), nodes) {
+        (true, Some(NodeList(nodes))) => {
+            check_online(nodes)?;
+            Ok(Some(NodeMask::from_nodes(nodes)))
+        }
+        (true, None) => Err(anyhow!(
+            "The {} mode requires nodes",
+            mode_name(mode.mode())
+        )),
+        (false, Some(_)) => Err(anyhow!(
+            "The {} mode does not take nodes",
+            mode_name(mode.mode())
+        )),
+        (false, None) => Ok(None),
+    }
+}
+
+fn handle_set_policy(policy: PolicyInfo, command: Vec<OsString>) -> anyhow::Result<()> {
+    let mask = policy_mask(policy.mode, policy.nodemask.as_ref())?;
+    let (mask_ptr, maxnode) = match &mask {
+        Some(mask) => (mask.0.as_ptr(), mask.maxnode()),
+        None => (std::ptr::null(), 0),
+    };
+    // SAFETY: mask_ptr is null or a node mask of maxnode - 1 bits.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_set_mempolicy,
+            policy.mode.mode(),
+            mask_ptr,
+            maxnode,
+        )
+    };
+    Errno::result(ret)
+        .map_err(describe)
+        .context("Failed to set the memory policy")?;
+
+    // The policy is inherited across fork and exec
+    let command = if command.is_empty() {
+        default_command()
+    } else {
+        command
+    };
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn handle_bind_range(
+    addr: u64,
+    len: i64,
+    mode: Policy,
+    nodes: Option<NodeList>,
+) -> anyhow::Result<()> {
+    let mask = policy_mask(mode, nodes.as_ref())?.unwrap_or_else(NodeMask::new);
+    let page_size = page_size()? as usize;
+    if len == 0 {
+        return Err(anyhow!("LEN must be larger than 0"));
+    }
+    let len = len as usize;
+
+    let mut flags = MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS;
+    if addr != 0 {
+        // Fails instead of replacing an existing mapping
+        flags |= MapFlags::MAP_FIXED_NOREPLACE;
+    }
+    // SAFETY: a new anonymous mapping does not alias any memory, and MAP_FIXED_NOREPLACE keeps existing ones.
+    let ptr = unsafe {
+        mmap(
+            addr as *mut c_void,
+            len,
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            flags,
+            -1,
+            0,
+        )
+    }
+    .map_err(|errno| match errno {
+        Errno::EEXIST => anyhow!("{:#x} overlaps an existing mapping", addr),
+        Errno::EINVAL => anyhow!("{:#x} is not page aligned", addr),
+        errno => anyhow!(errno),
+    })
+    .context("Failed to map memory")?;
+
+    let result = bind_and_count(ptr, len, page_size, mode, &mask);
+    // SAFETY: the mapping is no longer used.
+    unsafe { munmap(ptr, len) }.context("Failed to unmap memory")?;
+    let counts = result?;
+
+    println!("range: {:#x}-{:#x}", ptr as usize, ptr as usize + len);
+    println!("mode: {}", mode_name(mode.mode()));
+    println!("nodes: {}", format_node_list(&mask.nodes()));
+    for (node, pages) in counts {
+        println!("node {}: {} pages", node, pages);
+    }
+
+    Ok(())
+}
+
+/// Binds the mapping with mbind, touches its pages and counts them for each node they are on.
+fn bind_and_count(
+    ptr: *mut c_void,
+    len: usize,
+    page_size: usize,
+    mode: Policy,
+    mask: &NodeMask,
+) -> anyhow::Result<BTreeMap<c_int, usize>> {
+    // SAFETY: ptr is a mapping of len bytes, and the mask is a node mask of maxnode - 1 bits.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            ptr,
+            len,
+            mode.mode(),
+            mask.0.as_ptr(),
+            mask.maxnode(),
+            0u32,
+        )
+    };
+    Errno::result(ret)
+        .map_err(describe)
+        .context("Failed to call mbind")?;
+
+    let mut counts = BTreeMap::new();
+    for offset in (0..len).step_by(page_size) {
+        let page = ptr as usize + offset;
+        // SAFETY: the page is in the writable mapping and not referenced by anything else.
+        unsafe { std::ptr::write_volatile(page as *mut u8, 1) };
+        // With MPOL_F_NODE | MPOL_F_ADDR, the node of the page is returned as the mode
+        let node = get_mempolicy(None, page, MPOL_F_NODE | MPOL_F_ADDR)
+            .map_err(describe)
+            .context("Failed to get the node of a page")?;
+        *counts.entry(node).or_insert(0) += 1;
+    }
+
+    Ok(counts)
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Nodes => handle_nodes(),
+        Commands::GetPolicy { pid, addr } => handle_get_policy(pid, addr),
+        Commands::SetPolicy { policy, command } => handle_set_policy(policy, command),
+        Commands::BindRange {
+            addr,
+            len,
+            mode,
+            nodes,
+        } => handle_bind_range(addr, len, mode, nodes),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+
+const OOM_SCORE_ADJ_MIN: i32 = -1000;
+const OOM_SCORE_ADJ_MAX: i32 = 1000;
+
+/// Bit of CAP_SYS_RESOURCE in the capability sets of /proc/PID/status
+const CAP_SYS_RESOURCE: u32 = 24;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the OOM score and its adjustment of a process
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        #[clap(flatten)]
+        target: Target,
+    },
+    /// Set the OOM score adjustment of a process
+    #[clap(display_order = 2, name = "set", allow_negative_numbers = true)]
+    #[clap(override_usage = "oom-score set <PID|--self> <SCORE>")]
+    Set {
+        #[clap(flatten)]
+        target: Target,
+        /// Adjustment from -1000 (never killed) to 1000 (killed first)
+        #[clap(value_parser = parse_score, value_name = "SCORE")]
+        score: Option<i32>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct Target {
+    /// Target process
+    #[clap(value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// Target this process instead of PID
+    #[clap(short, long = "self")]
+    self_: bool,
+}
+
+/// Parses an OOM score adjustment from -1000 to 1000.
+fn parse_score(s: &str) -> Result<i32, String> {
+    let score = s
+        .parse::<i32>()
+        .map_err(|_| format!("'{}' is not a number", s))?;
+    check_score(score)
+}
+
+fn check_score(score: i32) -> Result<i32, String> {
+    if !(OOM_SCORE_ADJ_MIN..=OOM_SCORE_ADJ_MAX).contains(&score) {
+        return Err(format!(
+            "{} is out of the range from {} to {}",
+            score, OOM_SCORE_ADJ_MIN, OOM_SCORE_ADJ_MAX
+        ));
+    }
+
+    Ok(score)
+}
+
+fn proc_dir(pid: Option<i32>) -> String {
+    match pid {
+        Some(pid) => format!("/proc/{}", pid),
+        None => "/proc/self".to_owned(),
+    }
+}
+
+fn read_value(pid: Option<i32>, name: &str) -> anyhow::Result<i32> {
+    let path = format!("{}/{}", proc_dir(pid), name);
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+    content
+        .trim()
+        .parse::<i32>()
+        .with_context(|| format!("Unexpected content '{}' in {}", content.trim(), path))
+}
+
+/// Returns whether this process has CAP_SYS_RESOURCE in its effective set.
+fn has_cap_sys_resource() -> anyhow::Result<bool> {
+    let status =
+        std::fs::read_to_string("/proc/self/status").context("Failed to read /proc/self/status")?;
+    let cap_eff = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .ok_or_else(|| anyhow!("CapEff is not found in /proc/self/status"))?;
+    let cap_eff = u64::from_str_radix(cap_eff.trim(), 16).with_context(|| {
+        format!(
+            "Unexpected CapEff '{}' in /proc/self/status",
+            cap_eff.trim()
+        )
+    })?;
+
+    Ok(cap_eff & (1 << CAP_SYS_RESOURCE) != 0)
+}
+
+fn handle_get(target: Target) -> anyhow::Result<()> {
+    let pid = match (target.self_, target.pid) {
+        (true, None) => None,
+        (false, Some(pid)) => Some(pid),
+        (true, Some(_)) => return Err(anyhow!("PID cannot be used with --self")),
+        (false, None) => return Err(anyhow!("PID or --self is required")),
+    };
+
+    println!("oom_score: {}", read_value(pid, "oom_score")?);
+    println!("oom_score_adj: {}", read_value(pid, "oom_score_adj")?);
+
+    Ok(())
+}
+
+fn handle_set(target: Target, score: Option<i32>) -> anyhow::Result<()> {
+    // PID and SCORE are both optional positionals for clap, so with --self,
+    // SCORE is parsed into PID and validated here.
+    let (pid, score) = match (target.self_, target.pid, score) {
+        (false, Some(pid), Some(score)) => (Some(pid), score),
+        (true, Some(score), None) => (None, check_score(score).map_err(|err| anyhow!(err))?),
+        (true, Some(_), Some(_)) => return Err(anyhow!("PID cannot be used with --self")),
+        _ => return Err(anyhow!("Usage: oom-score set <PID|--self> <SCORE>")),
+    };
+
+    let current = read_value(pid, "oom_score_adj")?;
+    // Lowering the adjustment, which includes disabling the OOM killer with -1000,
+    // requires CAP_SYS_RESOURCE
+    if score < current && !has_cap_sys_resource()? {
+        eprintln!(
+            "warning: setting {} under the current {} requires CAP_SYS_RESOURCE",
+            score, current
+        );
+    }
+
+    let path = format!("{}/oom_score_adj", proc_dir(pid));
+    std::fs::write(&path, score.to_string())
+        .with_context(|| format!("Failed to write {} to {}", score, path))?;
+    eprintln!("oom_score_adj: {} -> {}", current, score);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { target } => handle_get(target),
+        Commands::Set { target, score } => handle_set(target, score),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
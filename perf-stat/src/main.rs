@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_cpu_list;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::fs::File;
+use std::io::Read;
+use std::os::raw::c_ulong;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::time::Duration;
+
+// The expansion of the nix macros refers to libc::c_int, which is newer than the MSRV for clippy
+#[allow(clippy::incompatible_msrv)]
+mod ioctl {
+    nix::ioctl_none!(perf_event_ioc_enable, b'$', 0);
+    nix::ioctl_none!(perf_event_ioc_disable, b'$', 1);
+}
+
+/// PERF_TYPE_HARDWARE from linux/perf_event.h
+const PERF_TYPE_HARDWARE: u32 = 0;
+
+/// The first version of perf_event_attr, which has all the fields used here
+const PERF_ATTR_SIZE_VER0: u32 = 64;
+
+/// Flag of perf_event_open(2) to open the file descriptor with close-on-exec
+const PERF_FLAG_FD_CLOEXEC: c_ulong = 1 << 3;
+
+// Bits of the flags in perf_event_attr
+const ATTR_DISABLED: u64 = 1 << 0;
+const ATTR_INHERIT: u64 = 1 << 1;
+const ATTR_EXCLUDE_KERNEL: u64 = 1 << 5;
+const ATTR_EXCLUDE_HV: u64 = 1 << 6;
+
+// Values of read_format, which make read(2) also return the times to scale multiplexed counters
+const PERF_FORMAT_TOTAL_TIME_ENABLED: u64 = 1 << 0;
+const PERF_FORMAT_TOTAL_TIME_RUNNING: u64 = 1 << 1;
+
+const PERF_EVENT_PARANOID: &str = "/proc/sys/kernel/perf_event_paranoid";
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    stat: StatInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct StatInfo {
+    /// Process to count, or -1 for all processes on every CPU
+    #[clap(
+        short,
+        long,
+        default_value_t = -1,
+        allow_hyphen_values = true,
+        value_parser = clap::value_parser!(i32).range(-1..)
+    )]
+    pid: i32,
+    /// Events to count, separated by commas [default: all]
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        ignore_case = true,
+        use_value_delimiter = true,
+        value_name = "EVENTS"
+    )]
+    events: Vec<Event>,
+    /// Seconds to count for
+    #[clap(
+        short,
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "SECONDS"
+    )]
+    duration: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Event {
+    #[clap(name = "cpu-cycles")]
+    CpuCycles,
+    #[clap(name = "instructions")]
+    Instructions,
+    #[clap(name = "cache-references")]
+    CacheReferences,
+    #[clap(name = "cache-misses")]
+    CacheMisses,
+    #[clap(name = "branch-instructions")]
+    BranchInstructions,
+    #[clap(name = "branch-misses")]
+    BranchMisses,
+}
+
+impl Event {
+    /// Returns PERF_COUNT_HW_* of the event.
+    fn config(self) -> u64 {
+        match self {
+            Event::CpuCycles => 0,
+            Event::Instructions => 1,
+            Event::CacheReferences => 2,
+            Event::CacheMisses => 3,
+            Event::BranchInstructions => 4,
+            Event::BranchMisses => 5,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Event::CpuCycles => "cpu-cycles",
+            Event::Instructions => "instructions",
+            Event::CacheReferences => "cache-references",
+            Event::CacheMisses => "cache-misses",
+            Event::BranchInstructions => "branch-instructions",
+            Event::BranchMisses => "branch-misses",
+        }
+    }
+}
+
+/// The fields of perf_event_attr up to PERF_ATTR_SIZE_VER0.
+#[repr(C)]
+#[derive(Default)]
+struct PerfEventAttr {
+    type_: u32,
+    size: u32,
+    config: u64,
+    sample_period: u64,
+    sample_type: u64,
+    read_format: u64,
+    flags: u64,
+    wakeup_events: u32,
+    bp_type: u32,
+    config1: u64,
+}
+
+fn online_cpus() -> anyhow::Result<Vec<i32>> {
+    let path = "/sys/devices/system/cpu/online";
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    let cpus = parse_cpu_list(&content)
+        .map_err(|err| anyhow!(err))
+        .with_context(|| format!("Failed to parse {}", path))?;
+
+    Ok(cpus.into_iter().map(|cpu| cpu as i32).collect())
+}
+
+fn describe(errno: Errno, pid: i32) -> anyhow::Error {
+    match errno {
+        Errno::EACCES | Errno::EPERM => {
+            let paranoid = std::fs::read_to_string(PERF_EVENT_PARANOID)
+                .map(|value| value.trim().to_owned())
+                .unwrap_or_else(|_| "unknown".to_owned());
+            if pid == -1 {
+                anyhow!(
+                    "Counting all processes requires CAP_PERFMON or {} of 0 or lower, which is {}",
+                    PERF_EVENT_PARANOID,
+                    paranoid
+                )
+            } else {
+                anyhow!(
+                    "Counting process {} requires CAP_PERFMON, or the permission to ptrace it and {} of 2 or lower, which is {}",
+                    pid,
+                    PERF_EVENT_PARANOID,
+                    paranoid
+                )
+            }
+        }
+        Errno::ESRCH => anyhow!("Process {} does not exist", pid),
+        Errno::ENOSYS => anyhow!("The kernel does not support perf_event_open(2)"),
+        errno => anyhow!(errno),
+    }
+}
+
+fn perf_event_open(attr: &PerfEventAttr, pid: i32, cpu: i32) -> nix::Result<File> {
+    // SAFETY: attr is a valid perf_event_attr of the size stored in it.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_perf_event_open,
+            attr as *const PerfEventAttr,
+            pid,
+            cpu,
+            -1,
+            PERF_FLAG_FD_CLOEXEC,
+        )
+    };
+    let fd = Errno::result(ret)?;
+
+    // SAFETY: perf_event_open returns a new file descriptor owned by nobody else.
+    Ok(unsafe { File::from_raw_fd(fd as i32) })
+}
+
+/// The counters of an event, one for each CPU when counting all processes.
+struct Counter {
+    event: Event,
+    /// None if the hardware does not support the event
+    files: Option<Vec<File>>,
+    /// Whether the kernel is excluded since counting it is not permitted
+    user_only: bool,
+}
+
+impl Counter {
+    fn open(event: Event, pid: i32, cpus: &[i32]) -> anyhow::Result<Self> {
+        let mut attr = PerfEventAttr {
+            type_: PERF_TYPE_HARDWARE,
+            size: PERF_ATTR_SIZE_VER0,
+            config: event.config(),
+            read_format: PERF_FORMAT_TOTAL_TIME_ENABLED | PERF_FORMAT_TOTAL_TIME_RUNNING,
+            // Threads created while counting are counted as well
+            flags: ATTR_DISABLED | ATTR_INHERIT | ATTR_EXCLUDE_HV,
+            ..Default::default()
+        };
+
+        let mut files = Vec::with_capacity(cpus.len());
+        let mut user_only = false;
+        for &cpu in cpus {
+            let result = match perf_event_open(&attr, pid, cpu) {
+                // perf_event_paranoid of 2 or higher only permits counting user space
+                Err(Errno::EACCES) | Err(Errno::EPERM) if !user_only => {
+                    user_only = true;
+                    attr.flags |= ATTR_EXCLUDE_KERNEL;
+                    perf_event_open(&attr, pid, cpu)
+                }
+                result => result,
+            };
+            match result {
+                Ok(file) => files.push(file),
+                // No PMU, such as in many virtual machines, or no such event in it
+                Err(Errno::ENOENT) | Err(Errno::EOPNOTSUPP) => {
+                    return Ok(Counter {
+                        event,
+                        files: None,
+                        user_only,
+                    })
+                }
+                Err(errno) => {
+                    return Err(describe(errno, pid))
+                        .with_context(|| format!("Failed to open the counter of {}", event.name()))
+                }
+            }
+        }
+
+        Ok(Counter {
+            event,
+            files: Some(files),
+            user_only,
+        })
+    }
+
+    fn files(&self) -> &[File] {
+        self.files.as_deref().unwrap_or_default()
+    }
+
+    /// Returns the value scaled by the running time, and the ratio of the time it was counted.
+    fn read(&self) -> anyhow::Result<(u64, f64)> {
+        let (mut value, mut enabled, mut running) = (0u128, 0u128, 0u128);
+        for mut file in self.files() {
+            let mut buf = [0u8; 24];
+            file.read_exact(&mut buf)
+                .with_context(|| format!("Failed to read the counter of {}", self.event.name()))?;
+            let field = |index: usize| {
+                u64::from_ne_bytes(buf[index * 8..(index + 1) * 8].try_into().unwrap()) as u128
+            };
+            let (cpu_enabled, cpu_running) = (field(1), field(2));
+            // Counters are multiplexed when there are more events than the hardware counters,
+            // and one which was never running counts nothing
+            value += (field(0) * cpu_enabled)
+                .checked_div(cpu_running)
+                .unwrap_or(0);
+            enabled += cpu_enabled;
+            running += cpu_running;
+        }
+        let ratio = if enabled == 0 {
+            0.0
+        } else {
+            running as f64 / enabled as f64
+        };
+
+        Ok((u64::try_from(value).unwrap_or(u64::MAX), ratio))
+    }
+}
+
+fn handle_stat(info: StatInfo) -> anyhow::Result<()> {
+    let events = if info.events.is_empty() {
+        Event::value_variants().to_vec()
+    } else {
+        let mut events = info.events;
+        events.sort_unstable();
+        events.dedup();
+        events
+    };
+    // A counter of all processes must be bound to a CPU
+    let cpus = if info.pid == -1 {
+        online_cpus()?
+    } else {
+        vec![-1]
+    };
+
+    let counters = events
+        .iter()
+        .map(|&event| Counter::open(event, info.pid, &cpus))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if counters.iter().all(|counter| counter.files.is_none()) {
+        return Err(anyhow!(
+            "The hardware does not support any of the events, which virtual machines often lack"
+        ));
+    }
+
+    for file in counters.iter().flat_map(Counter::files) {
+        // SAFETY: the file descriptor is a perf event.
+        unsafe { ioctl::perf_event_ioc_enable(file.as_raw_fd()) }
+            .context("Failed to enable a counter")?;
+    }
+    std::thread::sleep(Duration::from_secs(info.duration));
+    for file in counters.iter().flat_map(Counter::files) {
+        // SAFETY: the file descriptor is a perf event.
+        unsafe { ioctl::perf_event_ioc_disable(file.as_raw_fd()) }
+            .context("Failed to disable a counter")?;
+    }
+
+    for counter in &counters {
+        if counter.files.is_none() {
+            println!("{}: not supported", counter.event.name());
+            continue;
+        }
+        let (value, ratio) = counter.read()?;
+        let mut notes = Vec::new();
+        if counter.user_only {
+            notes.push("user space only".to_owned());
+        }
+        if ratio > 0.0 && ratio < 1.0 {
+            notes.push(format!("scaled, counted {:.1}%", ratio * 100.0));
+        }
+        let value = if ratio == 0.0 {
+            "not counted".to_owned()
+        } else {
+            value.to_string()
+        };
+        if notes.is_empty() {
+            println!("{}: {}", counter.event.name(), value);
+        } else {
+            println!("{}: {} ({})", counter.event.name(), value, notes.join(", "));
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_stat(cli.stat),
+    }
+}
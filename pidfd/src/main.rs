@@ -0,0 +1,294 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::pidfd::{open_pidfd, pidfd_getfd};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::signal::Signal;
+use nix::unistd::dup2;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::ErrorKind;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Open a pidfd of a process, print its path, and keep it open until the process exits
+    #[clap(display_order = 1, name = "open")]
+    Open {
+        /// Process to open
+        #[clap(value_parser, value_name = "PID")]
+        pid: i32,
+    },
+    /// Wait for the process of a pidfd to exit
+    #[clap(display_order = 2, name = "wait")]
+    Wait {
+        /// Path of a pidfd, such as /proc/PID/fd/N printed by 'open'
+        #[clap(value_parser, value_name = "PIDFD_PATH")]
+        path: PathBuf,
+    },
+    /// Send a signal to the process of a pidfd
+    #[clap(display_order = 3, name = "send-signal")]
+    SendSignal {
+        /// Path of a pidfd, such as /proc/PID/fd/N printed by 'open'
+        #[clap(value_parser, value_name = "PIDFD_PATH")]
+        path: PathBuf,
+        /// Signal by name or number (e.g. TERM, SIGKILL, 10)
+        #[clap(value_parser = parse_signal, value_name = "SIGNAME")]
+        signal: Signal,
+    },
+    /// Duplicate a file descriptor of the process of a pidfd as TARGET_FD, and execute a command
+    /// with it
+    #[clap(display_order = 4, name = "get-fd", trailing_var_arg = true)]
+    GetFd {
+        /// Path of a pidfd, such as /proc/PID/fd/N printed by 'open'
+        #[clap(value_parser, value_name = "PIDFD_PATH")]
+        path: PathBuf,
+        /// File descriptor of the process to duplicate
+        #[clap(value_parser, value_name = "FD")]
+        fd: RawFd,
+        /// File descriptor number of the duplicate
+        #[clap(value_parser, value_name = "TARGET_FD")]
+        target_fd: RawFd,
+        /// Command to execute with the duplicate [default: print what the duplicate is]
+        #[clap(value_parser, value_name = "COMMAND", multiple_values = true)]
+        command: Vec<OsString>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 5, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// Parses a signal such as `SIGINT`, `int` or `2`.
+fn parse_signal(s: &str) -> Result<Signal, String> {
+    match s.parse::<i32>() {
+        Ok(number) => Signal::try_from(number)
+            .map_err(|_| format!("{} is not a signal supported on this platform", number)),
+        Err(_) => {
+            let name = s.to_ascii_uppercase();
+            let name = if name.starts_with("SIG") {
+                name
+            } else {
+                format!("SIG{}", name)
+            };
+            name.parse::<Signal>()
+                .map_err(|_| format!("unknown signal '{}'", s))
+        }
+    }
+}
+
+/// Reads the PID of the process of a pidfd from its fdinfo, which is -1 once the process has
+/// exited and been reaped, or None if the file descriptor is not a pidfd.
+fn pidfd_pid(pidfd: &File) -> anyhow::Result<Option<i32>> {
+    let path = format!("/proc/self/fdinfo/{}", pidfd.as_raw_fd());
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+    Ok(content
+        .lines()
+        .find_map(|line| line.strip_prefix("Pid:"))
+        .and_then(|pid| pid.trim().parse().ok()))
+}
+
+/// Reopens a pidfd of another process through its path in /proc.
+fn open_pidfd_path(path: &Path) -> anyhow::Result<(File, i32)> {
+    let pidfd = File::open(path).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!(
+            "'{}' does not exist; 'pidfd open' must be running to keep the pidfd open",
+            path.display()
+        ),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to open '{}'; a pidfd of a process of another user requires CAP_SYS_PTRACE",
+            path.display()
+        ),
+        // A pidfd is an anonymous inode, which cannot be reopened before pidfs of Linux 6.9
+        _ if err.raw_os_error() == Some(libc::ENXIO) => anyhow!(
+            "'{}' cannot be reopened; reopening a pidfd requires Linux 6.9 or later",
+            path.display()
+        ),
+        _ => anyhow!(err).context(format!("Failed to open '{}'", path.display())),
+    })?;
+    let pid = pidfd_pid(&pidfd)?.ok_or_else(|| anyhow!("'{}' is not a pidfd", path.display()))?;
+
+    Ok((pidfd, pid))
+}
+
+/// Waits until the process of a pidfd exits, which makes the pidfd readable.
+fn poll_exit(pidfd: &File) -> anyhow::Result<()> {
+    let mut fds = [PollFd::new(pidfd.as_raw_fd(), PollFlags::POLLIN)];
+    loop {
+        match poll(&mut fds, -1) {
+            Ok(_) => return Ok(()),
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to poll the pidfd"),
+        }
+    }
+}
+
+fn handle_open(pid: i32) -> anyhow::Result<()> {
+    let pidfd = File::from(open_pidfd(pid)?);
+    // Other processes can open the pidfd through /proc while this process is alive
+    println!("pid: {}", pid);
+    println!(
+        "pidfd: /proc/{}/fd/{}",
+        std::process::id(),
+        pidfd.as_raw_fd()
+    );
+    poll_exit(&pidfd)?;
+    println!("exited: true");
+
+    Ok(())
+}
+
+fn handle_wait(path: PathBuf) -> anyhow::Result<()> {
+    let (pidfd, pid) = open_pidfd_path(&path)?;
+    println!("pid: {}", pid);
+
+    // SAFETY: siginfo_t is a plain C struct, for which all zeros is valid.
+    let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+    loop {
+        // SAFETY: info is valid for the kernel to write a siginfo_t.
+        let ret = unsafe {
+            libc::waitid(
+                libc::P_PIDFD,
+                pidfd.as_raw_fd() as libc::id_t,
+                &mut info,
+                libc::WEXITED,
+            )
+        };
+        match Errno::result(ret) {
+            Ok(_) => break,
+            Err(Errno::EINTR) => continue,
+            // Only the parent can reap the process and get its status
+            Err(Errno::ECHILD) => {
+                eprintln!(
+                    "warning: process {} is not a child, so its exit status is unavailable",
+                    pid
+                );
+                poll_exit(&pidfd)?;
+                println!("exited: true");
+                return Ok(());
+            }
+            Err(Errno::EINVAL) => {
+                return Err(anyhow!(
+                    "waitid(2) with P_PIDFD requires Linux 5.4 or later"
+                ))
+            }
+            Err(errno) => return Err(errno).context("Failed to call waitid"),
+        }
+    }
+
+    // SAFETY: waitid filled info with the status of an exited child.
+    let status = unsafe { info.si_status() };
+    println!("exited: true");
+    match info.si_code {
+        libc::CLD_EXITED => println!("status: {}", status),
+        _ => match Signal::try_from(status) {
+            Ok(signal) => println!("signal: {}", signal),
+            Err(_) => println!("signal: {}", status),
+        },
+    }
+
+    Ok(())
+}
+
+fn handle_send_signal(path: PathBuf, signal: Signal) -> anyhow::Result<()> {
+    let (pidfd, pid) = open_pidfd_path(&path)?;
+    // SAFETY: pidfd_send_signal only takes integer arguments and a null siginfo.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            signal as i32,
+            std::ptr::null::<libc::siginfo_t>(),
+            0u32,
+        )
+    };
+    match Errno::result(ret) {
+        Ok(_) => {}
+        Err(Errno::ESRCH) => return Err(anyhow!("Process {} has already exited", pid)),
+        Err(Errno::EPERM) => {
+            return Err(anyhow!(
+                "Sending a signal to process {} of another user requires CAP_KILL",
+                pid
+            ))
+        }
+        Err(Errno::ENOSYS) => {
+            return Err(anyhow!("pidfd_send_signal(2) requires Linux 5.1 or later"))
+        }
+        Err(errno) => return Err(errno).context("Failed to call pidfd_send_signal"),
+    }
+    println!("pid: {}", pid);
+    println!("signal: {}", signal);
+
+    Ok(())
+}
+
+fn handle_get_fd(
+    path: PathBuf,
+    fd: RawFd,
+    target_fd: RawFd,
+    command: Vec<OsString>,
+) -> anyhow::Result<()> {
+    let (pidfd, pid) = open_pidfd_path(&path)?;
+    let duplicate = File::from(pidfd_getfd(&pidfd, pid, fd)?);
+    // dup2 leaves O_CLOEXEC unset, so COMMAND inherits TARGET_FD
+    if duplicate.as_raw_fd() != target_fd {
+        dup2(duplicate.as_raw_fd(), target_fd)
+            .with_context(|| format!("Failed to duplicate the file descriptor as {}", target_fd))?;
+        drop(duplicate);
+    } else {
+        // pidfd_getfd sets O_CLOEXEC, which is cleared by leaving the file descriptor open
+        nix::fcntl::fcntl(
+            target_fd,
+            nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::empty()),
+        )
+        .context("Failed to clear FD_CLOEXEC")?;
+        std::mem::forget(duplicate);
+    }
+
+    let link = std::fs::read_link(format!("/proc/self/fd/{}", target_fd))
+        .map(|link| link.display().to_string())
+        .unwrap_or_else(|_| "unknown".to_owned());
+    eprintln!("pid: {}", pid);
+    eprintln!("fd: {}", fd);
+    eprintln!("target_fd: {}", target_fd);
+    eprintln!("file: {}", link);
+    if command.is_empty() {
+        return Ok(());
+    }
+
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Open { pid } => handle_open(pid),
+        Commands::Wait { path } => handle_wait(path),
+        Commands::SendSignal { path, signal } => handle_send_signal(path, signal),
+        Commands::GetFd {
+            path,
+            fd,
+            target_fd,
+            command,
+        } => handle_get_fd(path, fd, target_fd, command),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
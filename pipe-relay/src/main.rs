@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::copy::throughput;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, splice, FcntlArg, OFlag, SpliceFFlags};
+use nix::unistd::pipe2;
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::time::Instant;
+
+const STDIN_FD: RawFd = 0;
+const STDOUT_FD: RawFd = 1;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    relay: RelayInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct RelayInfo {
+    /// Capacity of the pipe, which is also the bytes moved by a single splice (accepts suffixes
+    /// such as K, MiB, GB) [default: the default capacity of a pipe]
+    #[clap(short, long, value_parser = parse_size, value_name = "SIZE")]
+    pipe_size: Option<i64>,
+}
+
+/// The pipe between stdin and stdout.
+struct Pipe {
+    reader: File,
+    writer: File,
+}
+
+impl Pipe {
+    fn new() -> nix::Result<Self> {
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        // SAFETY: pipe2 returns new file descriptors owned by nobody else.
+        let (reader, writer) = unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) };
+
+        Ok(Pipe { reader, writer })
+    }
+
+    fn size(&self) -> nix::Result<i32> {
+        fcntl(self.writer.as_raw_fd(), FcntlArg::F_GETPIPE_SZ)
+    }
+
+    /// Resizes the pipe to hold at least `size` bytes and returns the new size, or None when
+    /// the kernel does not support F_SETPIPE_SZ.
+    fn resize(&self, size: i32) -> anyhow::Result<Option<i32>> {
+        match fcntl(self.writer.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(size)) {
+            Ok(size) => Ok(Some(size)),
+            // F_SETPIPE_SZ requires Linux 2.6.35
+            Err(Errno::EINVAL) => Ok(None),
+            Err(Errno::EPERM) => Err(anyhow!(
+                "The pipe size {} exceeds /proc/sys/fs/pipe-max-size; raising the size of the pipe over it \
+                requires CAP_SYS_RESOURCE",
+                size
+            )),
+            Err(Errno::EBUSY) => Err(anyhow!(
+                "The pipe size {} is too small for the data in the pipe",
+                size
+            )),
+            Err(errno) => Err(errno).context("Failed to set the pipe size"),
+        }
+    }
+}
+
+/// Relays stdin to stdout with a splice(2) into the pipe and another out of it, adding the bytes
+/// relayed to `total`, and returns false when stdin or stdout does not support splice(2) before
+/// anything is relayed.
+fn relay_with_splice(pipe: &Pipe, chunk_size: usize, total: &mut u64) -> anyhow::Result<bool> {
+    let flags = SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_MORE;
+    loop {
+        let len = match splice(
+            STDIN_FD,
+            None,
+            pipe.writer.as_raw_fd(),
+            None,
+            chunk_size,
+            flags,
+        ) {
+            Ok(0) => break,
+            Ok(len) => len,
+            // e.g. stdin is a terminal
+            Err(Errno::EINVAL) if *total == 0 => return Ok(false),
+            Err(errno) => return Err(errno).context("Failed to call splice for stdin"),
+        };
+
+        // stdout may take less than the pipe has
+        let mut pending = len;
+        while pending > 0 {
+            match splice(
+                pipe.reader.as_raw_fd(),
+                None,
+                STDOUT_FD,
+                None,
+                pending,
+                flags,
+            ) {
+                Ok(written) => pending -= written,
+                // e.g. stdout is a file opened in append mode, so the data already in the pipe
+                // is written from a copy
+                Err(Errno::EINVAL) if *total == 0 && pending == len => {
+                    let mut buffer = vec![0; len];
+                    (&pipe.reader)
+                        .read_exact(&mut buffer)
+                        .context("Failed to read the pipe")?;
+                    write_stdout(&buffer)?;
+                    *total += len as u64;
+                    return Ok(false);
+                }
+                Err(errno) => return Err(errno).context("Failed to call splice for stdout"),
+            }
+        }
+        *total += len as u64;
+    }
+
+    Ok(true)
+}
+
+fn write_stdout(data: &[u8]) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    stdout
+        .write_all(data)
+        .and_then(|_| stdout.flush())
+        .context("Failed to write to stdout")
+}
+
+fn relay_with_buffer(buffer_size: usize, total: &mut u64) -> anyhow::Result<()> {
+    let mut stdin = std::io::stdin().lock();
+    let mut buffer = vec![0; buffer_size];
+    loop {
+        let len = match stdin.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err).context("Failed to read stdin"),
+        };
+        write_stdout(&buffer[..len])?;
+        *total += len as u64;
+    }
+
+    Ok(())
+}
+
+fn handle_relay(info: RelayInfo) -> anyhow::Result<()> {
+    let requested = info
+        .pipe_size
+        .map(|size| {
+            i32::try_from(size)
+                .ok()
+                .filter(|&size| size > 0)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The pipe size {} is out of the range of 1 to {}",
+                        size,
+                        i32::MAX
+                    )
+                })
+        })
+        .transpose()?;
+
+    let pipe = Pipe::new().context("Failed to create a pipe")?;
+    let mut splice_supported = true;
+    // The kernel rounds the size up to a power of two pages
+    let pipe_size = match requested {
+        Some(size) => match pipe.resize(size)? {
+            Some(size) => size,
+            None => {
+                eprintln!("warning: F_SETPIPE_SZ is not supported; falling back to read and write");
+                splice_supported = false;
+                size
+            }
+        },
+        None => pipe.size().context("Failed to get the pipe size")?,
+    };
+    let chunk_size = pipe_size as usize;
+
+    let start = Instant::now();
+    let mut total = 0;
+    let mut method = "splice";
+    if !splice_supported || !relay_with_splice(&pipe, chunk_size, &mut total)? {
+        if splice_supported {
+            eprintln!(
+                "warning: splice is not supported for stdin or stdout; falling back to read and write"
+            );
+        }
+        method = "buffer";
+        relay_with_buffer(chunk_size, &mut total)?;
+    }
+    let elapsed = start.elapsed();
+
+    eprintln!("method: {}", method);
+    eprintln!("pipe_size: {}", pipe_size);
+    eprintln!("bytes: {}", total);
+    eprintln!("elapsed: {:.3}s", elapsed.as_secs_f64());
+    eprintln!(
+        "throughput: {:.0} bytes/sec",
+        throughput(total as i64, elapsed)
+    );
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_relay(cli.relay),
+    }
+}
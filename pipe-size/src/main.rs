@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the buffer size of a pipe
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        #[clap(flatten)]
+        target: PipeTarget,
+    },
+    /// Set the buffer size of a pipe
+    #[clap(display_order = 2, name = "set")]
+    #[clap(override_usage = "pipe-size set <PIPE|--pid <PID> --fd <FD>> <SIZE>")]
+    Set {
+        #[clap(flatten)]
+        target: PipeTarget,
+        /// New buffer size, rounded up to a power of two pages (accepts suffixes such as K, MiB)
+        #[clap(value_parser = parse_size, value_name = "SIZE")]
+        size: Option<i64>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct PipeTarget {
+    /// Named pipe
+    #[clap(value_parser, value_name = "PIPE")]
+    path: Option<PathBuf>,
+    /// Process owning an anonymous pipe as the file descriptor given with --fd
+    #[clap(short, long, value_parser, requires = "fd")]
+    pid: Option<i32>,
+    /// File descriptor of the pipe in the process given with --pid
+    #[clap(short, long, value_parser, requires = "pid")]
+    fd: Option<i32>,
+}
+
+fn proc_fd_path(pid: i32, fd: i32) -> PathBuf {
+    PathBuf::from(format!("/proc/{}/fd/{}", pid, fd))
+}
+
+fn open_pipe(path: &Path) -> anyhow::Result<File> {
+    let metadata =
+        std::fs::metadata(path).with_context(|| format!("Failed to stat '{}'", path.display()))?;
+    if !metadata.file_type().is_fifo() {
+        return Err(anyhow!("'{}' is not a pipe", path.display()));
+    }
+
+    // O_NONBLOCK avoids waiting for a writer of a named pipe
+    OpenOptions::new()
+        .read(true)
+        .custom_flags(OFlag::O_NONBLOCK.bits())
+        .open(path)
+        .with_context(|| format!("Failed to open '{}'", path.display()))
+}
+
+fn get_pipe_size(pipe: &File) -> anyhow::Result<i32> {
+    fcntl(pipe.as_raw_fd(), FcntlArg::F_GETPIPE_SZ).context("Failed to get the pipe size")
+}
+
+fn read_pipe_max_size() -> anyhow::Result<String> {
+    let path = "/proc/sys/fs/pipe-max-size";
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))?;
+    Ok(content.trim().to_owned())
+}
+
+fn handle_get(target: PipeTarget) -> anyhow::Result<()> {
+    let path = match (target.pid.zip(target.fd), target.path) {
+        (Some((pid, fd)), None) => proc_fd_path(pid, fd),
+        (None, Some(path)) => path,
+        (Some(_), Some(_)) => return Err(anyhow!("PIPE cannot be used with --pid and --fd")),
+        (None, None) => return Err(anyhow!("PIPE or --pid and --fd are required")),
+    };
+    let pipe = open_pipe(&path)?;
+    println!("size: {}", get_pipe_size(&pipe)?);
+
+    Ok(())
+}
+
+/// Parses SIZE given in the position of PIPE.
+fn parse_size_arg(arg: &Path) -> anyhow::Result<i64> {
+    let arg = arg.to_string_lossy();
+    parse_size(&arg).map_err(|err| anyhow!("Invalid value '{}' for SIZE: {}", arg, err))
+}
+
+fn handle_set(target: PipeTarget, size: Option<i64>) -> anyhow::Result<()> {
+    // PIPE and SIZE are both optional positionals for clap, so with --pid and --fd,
+    // SIZE is parsed into PIPE and parsed here.
+    let (path, size) = match (target.pid.zip(target.fd), target.path, size) {
+        (Some((pid, fd)), Some(size), None) => (proc_fd_path(pid, fd), parse_size_arg(&size)?),
+        (None, Some(path), Some(size)) => (path, size),
+        (Some(_), Some(_), Some(_)) => {
+            return Err(anyhow!("PIPE cannot be used with --pid and --fd"))
+        }
+        _ => {
+            return Err(anyhow!(
+                "Usage: pipe-size set <PIPE|--pid <PID> --fd <FD>> <SIZE>"
+            ))
+        }
+    };
+    let size = i32::try_from(size)
+        .map_err(|_| anyhow!("The size {} exceeds the maximum of {}", size, i32::MAX))?;
+
+    let pipe = open_pipe(&path)?;
+    let old = get_pipe_size(&pipe)?;
+    let new = match fcntl(pipe.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(size)) {
+        Ok(new) => new,
+        Err(Errno::EPERM) => {
+            return Err(anyhow!(
+                "The size {} exceeds /proc/sys/fs/pipe-max-size ({} bytes) or the pipe buffers of the user \
+                exceed /proc/sys/fs/pipe-user-pages-hard; raising the size over them requires CAP_SYS_RESOURCE",
+                size,
+                read_pipe_max_size()?
+            ))
+        }
+        Err(Errno::EBUSY) => {
+            return Err(anyhow!(
+                "The size {} is smaller than the data currently in the pipe",
+                size
+            ))
+        }
+        Err(errno) => return Err(errno).context("Failed to set the pipe size"),
+    };
+    eprintln!("size: {} -> {}", old, new);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { target } => handle_get(target),
+        Commands::Set { target, size } => handle_set(target, size),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,224 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::ffi::OsString;
+use std::os::unix::process::CommandExt;
+use std::process::Command;
+
+/// Maximum length of a thread name, excluding the terminating NUL
+const TASK_COMM_LEN: usize = 15;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the name of the calling thread (PR_GET_NAME)
+    #[clap(display_order = 1, name = "get-name")]
+    GetName,
+    /// Set the name of the calling thread (PR_SET_NAME)
+    #[clap(display_order = 2, name = "set-name")]
+    SetName {
+        /// New name, truncated to 15 bytes
+        #[clap(value_parser, value_name = "NAME")]
+        name: String,
+    },
+    /// Print the dumpable attribute (PR_GET_DUMPABLE)
+    #[clap(display_order = 3, name = "get-dumpable")]
+    GetDumpable,
+    /// Set the dumpable attribute (PR_SET_DUMPABLE)
+    #[clap(display_order = 4, name = "set-dumpable")]
+    SetDumpable {
+        /// 0 (not dumpable), 1 (dumpable) or 2 (dumpable and readable only by root)
+        #[clap(value_parser = clap::value_parser!(u64).range(0..=2), value_name = "VALUE")]
+        value: u64,
+    },
+    /// Set the no_new_privs attribute, which cannot be unset (PR_SET_NO_NEW_PRIVS)
+    #[clap(display_order = 5, name = "no-new-privs")]
+    NoNewPrivs {
+        /// Command to execute with the attribute
+        #[clap(
+            value_parser,
+            value_name = "COMMAND",
+            multiple_values = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<OsString>,
+    },
+    /// Print the no_new_privs attribute (PR_GET_NO_NEW_PRIVS)
+    #[clap(display_order = 6, name = "get-no-new-privs")]
+    GetNoNewPrivs,
+    /// Set the child subreaper attribute (PR_SET_CHILD_SUBREAPER)
+    #[clap(display_order = 7, name = "set-child-subreaper")]
+    SetChildSubreaper {
+        /// 0 (disabled) or 1 (enabled)
+        #[clap(value_parser = clap::value_parser!(u64).range(0..=1), value_name = "VALUE")]
+        value: u64,
+        /// Command to execute with the attribute
+        #[clap(
+            value_parser,
+            value_name = "COMMAND",
+            multiple_values = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<OsString>,
+    },
+    /// Print the child subreaper attribute (PR_GET_CHILD_SUBREAPER)
+    #[clap(display_order = 8, name = "get-child-subreaper")]
+    GetChildSubreaper,
+    /// Generate code for completion
+    #[clap(display_order = 9, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+fn prctl(option: i32, arg2: u64) -> nix::Result<i32> {
+    // SAFETY: the options used in this file take integer arguments, or a pointer
+    // to a buffer of 16 bytes for PR_GET_NAME and PR_SET_NAME.
+    let ret = unsafe { libc::prctl(option, arg2, 0u64, 0u64, 0u64) };
+    Errno::result(ret)
+}
+
+fn dumpable_name(value: i32) -> &'static str {
+    match value {
+        0 => "SUID_DUMP_DISABLE",
+        1 => "SUID_DUMP_USER",
+        2 => "SUID_DUMP_ROOT",
+        _ => "unknown",
+    }
+}
+
+fn flag_name(value: i32) -> &'static str {
+    match value {
+        0 => "disabled",
+        _ => "enabled",
+    }
+}
+
+/// Replaces this process with COMMAND so that it runs with the attribute.
+fn exec_command(command: &[OsString]) -> anyhow::Result<()> {
+    if command.is_empty() {
+        return Ok(());
+    }
+
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn handle_get_name() -> anyhow::Result<()> {
+    let mut buffer = [0u8; TASK_COMM_LEN + 1];
+    prctl(libc::PR_GET_NAME, buffer.as_mut_ptr() as u64).context("Failed to get the name")?;
+    let len = buffer
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(buffer.len());
+
+    println!("name: {}", String::from_utf8_lossy(&buffer[..len]));
+
+    Ok(())
+}
+
+fn handle_set_name(name: String) -> anyhow::Result<()> {
+    let mut bytes = name.into_bytes();
+    if bytes.contains(&0) {
+        return Err(anyhow!("NAME must not contain a NUL byte"));
+    }
+    if bytes.len() > TASK_COMM_LEN {
+        bytes.truncate(TASK_COMM_LEN);
+        eprintln!(
+            "warning: the name is truncated to {} bytes: {}",
+            TASK_COMM_LEN,
+            String::from_utf8_lossy(&bytes)
+        );
+    }
+    let mut buffer = [0u8; TASK_COMM_LEN + 1];
+    buffer[..bytes.len()].copy_from_slice(&bytes);
+
+    prctl(libc::PR_SET_NAME, buffer.as_ptr() as u64).context("Failed to set the name")?;
+
+    Ok(())
+}
+
+fn handle_get_dumpable() -> anyhow::Result<()> {
+    let value = prctl(libc::PR_GET_DUMPABLE, 0).context("Failed to get the dumpable attribute")?;
+
+    println!("dumpable: {} ({})", dumpable_name(value), value);
+
+    Ok(())
+}
+
+fn handle_set_dumpable(value: u64) -> anyhow::Result<()> {
+    prctl(libc::PR_SET_DUMPABLE, value)
+        .map_err(|errno| match errno {
+            Errno::EINVAL if value == 2 => {
+                anyhow!(
+                    "The kernel does not allow setting {} with prctl",
+                    dumpable_name(2)
+                )
+            }
+            errno => anyhow!(errno),
+        })
+        .context("Failed to set the dumpable attribute")?;
+
+    Ok(())
+}
+
+fn handle_no_new_privs(command: Vec<OsString>) -> anyhow::Result<()> {
+    prctl(libc::PR_SET_NO_NEW_PRIVS, 1).context("Failed to set the no_new_privs attribute")?;
+
+    exec_command(&command)
+}
+
+fn handle_get_no_new_privs() -> anyhow::Result<()> {
+    let value =
+        prctl(libc::PR_GET_NO_NEW_PRIVS, 0).context("Failed to get the no_new_privs attribute")?;
+
+    println!("no_new_privs: {} ({})", flag_name(value), value);
+
+    Ok(())
+}
+
+fn handle_set_child_subreaper(value: u64, command: Vec<OsString>) -> anyhow::Result<()> {
+    prctl(libc::PR_SET_CHILD_SUBREAPER, value)
+        .context("Failed to set the child subreaper attribute")?;
+
+    exec_command(&command)
+}
+
+fn handle_get_child_subreaper() -> anyhow::Result<()> {
+    let mut value: i32 = 0;
+    // PR_GET_CHILD_SUBREAPER stores the value to the pointer instead of returning it
+    prctl(libc::PR_GET_CHILD_SUBREAPER, &mut value as *mut i32 as u64)
+        .context("Failed to get the child subreaper attribute")?;
+
+    println!("child_subreaper: {} ({})", flag_name(value), value);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::GetName => handle_get_name(),
+        Commands::SetName { name } => handle_set_name(name),
+        Commands::GetDumpable => handle_get_dumpable(),
+        Commands::SetDumpable { value } => handle_set_dumpable(value),
+        Commands::NoNewPrivs { command } => handle_no_new_privs(command),
+        Commands::GetNoNewPrivs => handle_get_no_new_privs(),
+        Commands::SetChildSubreaper { value, command } => {
+            handle_set_child_subreaper(value, command)
+        }
+        Commands::GetChildSubreaper => handle_get_child_subreaper(),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,343 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_duration;
+use linux_tools_core::output::format_duration;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::os::unix::io::AsRawFd;
+use std::time::Duration;
+
+/// Limits of the window of a trigger in the kernel
+const MIN_WINDOW_US: u64 = 500_000;
+const MAX_WINDOW_US: u64 = 10_000_000;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    pressure: PressureInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct PressureInfo {
+    /// Print the pressure every number of seconds until interrupted
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        value_name = "SECONDS"
+    )]
+    watch: Option<u64>,
+    /// Wait until tasks stall for THRESHOLD (e.g. 150ms or 10%) within a window, then exit
+    #[clap(
+        short,
+        long,
+        number_of_values = 3,
+        value_names = &["THRESHOLD", "RESOURCE", "METRIC"],
+        conflicts_with = "watch"
+    )]
+    poll: Vec<String>,
+    /// Window of --poll, between 500ms and 10s, which must be a multiple of 2s without CAP_SYS_RESOURCE
+    #[clap(
+        long,
+        default_value = "2s",
+        value_parser = parse_duration,
+        value_name = "DURATION",
+        requires = "poll"
+    )]
+    window: u64,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Resource {
+    #[clap(name = "cpu")]
+    Cpu,
+    #[clap(name = "memory")]
+    Memory,
+    #[clap(name = "io")]
+    Io,
+}
+
+impl Resource {
+    fn name(self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Memory => "memory",
+            Resource::Io => "io",
+        }
+    }
+
+    fn path(self) -> String {
+        format!("/proc/pressure/{}", self.name())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Metric {
+    /// Some tasks are stalled
+    #[clap(name = "some")]
+    Some,
+    /// All non-idle tasks are stalled at the same time
+    #[clap(name = "full")]
+    Full,
+}
+
+impl Metric {
+    fn name(self) -> &'static str {
+        match self {
+            Metric::Some => "some",
+            Metric::Full => "full",
+        }
+    }
+}
+
+/// A threshold of stall time in a window.
+enum Threshold {
+    Microseconds(u64),
+    Percent(u64),
+}
+
+impl Threshold {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s.strip_suffix('%') {
+            Some(percent) => match percent.parse::<u64>() {
+                Ok(percent) if (1..=100).contains(&percent) => Ok(Threshold::Percent(percent)),
+                _ => Err(format!("'{}' is not a percentage between 1% and 100%", s)),
+            },
+            None => parse_duration(s).map(|ns| Threshold::Microseconds(ns / 1000)),
+        }
+    }
+
+    fn microseconds(&self, window_us: u64) -> u64 {
+        match *self {
+            Threshold::Microseconds(us) => us,
+            Threshold::Percent(percent) => window_us * percent / 100,
+        }
+    }
+}
+
+/// The arguments of --poll.
+struct Trigger {
+    threshold: Threshold,
+    resource: Resource,
+    metric: Metric,
+}
+
+fn parse_trigger(values: &[String]) -> anyhow::Result<Trigger> {
+    // clap gives the three values of --poll
+    let threshold = Threshold::parse(&values[0])
+        .map_err(|err| anyhow!("Invalid value '{}' for THRESHOLD: {}", values[0], err))?;
+    let resource = Resource::from_str(&values[1], true).map_err(|_| {
+        anyhow!(
+            "Invalid value '{}' for RESOURCE: expected cpu, memory or io",
+            values[1]
+        )
+    })?;
+    let metric = Metric::from_str(&values[2], true).map_err(|_| {
+        anyhow!(
+            "Invalid value '{}' for METRIC: expected some or full",
+            values[2]
+        )
+    })?;
+
+    Ok(Trigger {
+        threshold,
+        resource,
+        metric,
+    })
+}
+
+/// A line of a PSI file such as `some avg10=0.00 avg60=0.00 avg300=0.00 total=0`.
+struct Pressure {
+    metric: String,
+    avg10: f64,
+    avg60: f64,
+    avg300: f64,
+    /// Total stall time in microseconds
+    total: u64,
+}
+
+fn read_pressure(resource: Resource) -> anyhow::Result<Vec<Pressure>> {
+    let path = resource.path();
+    let content = std::fs::read_to_string(&path).map_err(|err| match err.raw_os_error() {
+        // The kernel is built with CONFIG_PSI but booted with psi=0
+        Some(code) if code == Errno::EOPNOTSUPP as i32 => {
+            anyhow!("PSI is disabled; enable it with the psi=1 boot parameter")
+        }
+        _ if err.kind() == std::io::ErrorKind::NotFound => {
+            anyhow!(
+                "{} does not exist; PSI requires Linux 4.20 or later built with CONFIG_PSI",
+                path
+            )
+        }
+        _ => anyhow!(err).context(format!("Failed to read {}", path)),
+    })?;
+
+    content
+        .lines()
+        .map(|line| {
+            let unexpected = || anyhow!("Unexpected line in {}: {}", path, line);
+            let mut fields = line.split_whitespace();
+            let metric = fields.next().ok_or_else(unexpected)?;
+            let mut pressure = Pressure {
+                metric: metric.to_owned(),
+                avg10: 0.0,
+                avg60: 0.0,
+                avg300: 0.0,
+                total: 0,
+            };
+            for field in fields {
+                let (key, value) = field.split_once('=').ok_or_else(unexpected)?;
+                match key {
+                    "avg10" => pressure.avg10 = value.parse().map_err(|_| unexpected())?,
+                    "avg60" => pressure.avg60 = value.parse().map_err(|_| unexpected())?,
+                    "avg300" => pressure.avg300 = value.parse().map_err(|_| unexpected())?,
+                    "total" => pressure.total = value.parse().map_err(|_| unexpected())?,
+                    _ => {}
+                }
+            }
+            Ok(pressure)
+        })
+        .collect()
+}
+
+fn print_pressure() -> anyhow::Result<()> {
+    println!(
+        "{:<8} {:<6} {:>7} {:>7} {:>7} {:>16}",
+        "RESOURCE", "METRIC", "AVG10", "AVG60", "AVG300", "TOTAL(us)"
+    );
+    for resource in Resource::value_variants() {
+        for pressure in read_pressure(*resource)? {
+            println!(
+                "{:<8} {:<6} {:>7.2} {:>7.2} {:>7.2} {:>16}",
+                resource.name(),
+                pressure.metric,
+                pressure.avg10,
+                pressure.avg60,
+                pressure.avg300,
+                pressure.total
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn describe(errno: Errno, window_us: u64) -> anyhow::Error {
+    match errno {
+        // Checked beforehand except the one for unprivileged users
+        Errno::EINVAL if window_us % 2_000_000 != 0 => {
+            anyhow!("Without CAP_SYS_RESOURCE, the window must be a multiple of 2s")
+        }
+        Errno::EINVAL => anyhow!("The kernel does not accept the trigger"),
+        Errno::EPERM | Errno::EACCES => {
+            anyhow!("Triggers require CAP_SYS_RESOURCE before Linux 6.5")
+        }
+        Errno::EBUSY => anyhow!("The file already has a trigger"),
+        errno => anyhow!(errno),
+    }
+}
+
+/// Creates a trigger in the PSI file, which notifies poll(2) with POLLPRI.
+fn create_trigger(trigger: &Trigger, threshold_us: u64, window_us: u64) -> anyhow::Result<File> {
+    let path = trigger.resource.path();
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path))?;
+    // The kernel takes the string including the terminating null byte
+    let command = format!("{} {} {}\0", trigger.metric.name(), threshold_us, window_us);
+    file.write_all(command.as_bytes())
+        .map_err(|err| describe(Errno::from_i32(err.raw_os_error().unwrap_or(0)), window_us))
+        .with_context(|| format!("Failed to create a trigger in {}", path))?;
+
+    Ok(file)
+}
+
+fn handle_poll(trigger: Trigger, window_ns: u64) -> anyhow::Result<()> {
+    let window_us = window_ns / 1000;
+    if !(MIN_WINDOW_US..=MAX_WINDOW_US).contains(&window_us) {
+        return Err(anyhow!("--window must be between 500ms and 10s"));
+    }
+    let threshold_us = trigger.threshold.microseconds(window_us);
+    if threshold_us == 0 || threshold_us > window_us {
+        return Err(anyhow!(
+            "THRESHOLD must be between 1us and the window of {}",
+            format_duration(window_ns)
+        ));
+    }
+
+    let file = create_trigger(&trigger, threshold_us, window_us)?;
+    eprintln!(
+        "waiting for {} {} stalls of {} within {}",
+        trigger.resource.name(),
+        trigger.metric.name(),
+        format_duration(threshold_us * 1000),
+        format_duration(window_ns)
+    );
+    loop {
+        let mut fds = [PollFd::new(file.as_raw_fd(), PollFlags::POLLPRI)];
+        match poll(&mut fds, -1) {
+            Ok(_) => {}
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to wait for the trigger"),
+        }
+        let revents = fds[0].revents().unwrap_or_else(PollFlags::empty);
+        if revents.contains(PollFlags::POLLERR) {
+            return Err(anyhow!("The trigger is no longer available"));
+        }
+        if revents.contains(PollFlags::POLLPRI) {
+            println!(
+                "{} {}: threshold crossed",
+                trigger.resource.name(),
+                trigger.metric.name()
+            );
+            return Ok(());
+        }
+    }
+}
+
+fn handle_pressure(info: PressureInfo) -> anyhow::Result<()> {
+    if !info.poll.is_empty() {
+        return handle_poll(parse_trigger(&info.poll)?, info.window);
+    }
+
+    print_pressure()?;
+    if let Some(watch) = info.watch {
+        loop {
+            std::thread::sleep(Duration::from_secs(watch));
+            println!();
+            print_pressure()?;
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_pressure(cli.pressure),
+    }
+}
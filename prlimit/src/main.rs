@@ -0,0 +1,256 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+
+const RLIM_INFINITY: u64 = u64::MAX;
+
+/// Bit of CAP_SYS_RESOURCE in the capability sets of /proc/PID/status
+const CAP_SYS_RESOURCE: u32 = 24;
+
+/// Resources with the names printed for them and the units of their limits
+const RESOURCES: &[(i32, &str, &str)] = &[
+    (libc::RLIMIT_AS as i32, "RLIMIT_AS", "bytes"),
+    (libc::RLIMIT_CORE as i32, "RLIMIT_CORE", "bytes"),
+    (libc::RLIMIT_CPU as i32, "RLIMIT_CPU", "seconds"),
+    (libc::RLIMIT_DATA as i32, "RLIMIT_DATA", "bytes"),
+    (libc::RLIMIT_FSIZE as i32, "RLIMIT_FSIZE", "bytes"),
+    (libc::RLIMIT_LOCKS as i32, "RLIMIT_LOCKS", "locks"),
+    (libc::RLIMIT_MEMLOCK as i32, "RLIMIT_MEMLOCK", "bytes"),
+    (libc::RLIMIT_MSGQUEUE as i32, "RLIMIT_MSGQUEUE", "bytes"),
+    (libc::RLIMIT_NICE as i32, "RLIMIT_NICE", "priority"),
+    (libc::RLIMIT_NOFILE as i32, "RLIMIT_NOFILE", "files"),
+    (libc::RLIMIT_NPROC as i32, "RLIMIT_NPROC", "processes"),
+    (libc::RLIMIT_RSS as i32, "RLIMIT_RSS", "bytes"),
+    (libc::RLIMIT_RTPRIO as i32, "RLIMIT_RTPRIO", "priority"),
+    (libc::RLIMIT_RTTIME as i32, "RLIMIT_RTTIME", "microseconds"),
+    (
+        libc::RLIMIT_SIGPENDING as i32,
+        "RLIMIT_SIGPENDING",
+        "signals",
+    ),
+    (libc::RLIMIT_STACK as i32, "RLIMIT_STACK", "bytes"),
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print resource limits of a process
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// Target process [default: this process]
+        #[clap(value_parser)]
+        pid: Option<i32>,
+        /// Only print the limit of the resource (e.g. RLIMIT_NOFILE or nofile)
+        #[clap(short, long, value_parser = parse_resource, value_name = "RESOURCE")]
+        resource: Option<Resource>,
+    },
+    /// Set a resource limit of a process
+    #[clap(display_order = 2, name = "set")]
+    Set {
+        /// Target process
+        #[clap(value_parser)]
+        pid: i32,
+        /// Resource to limit (e.g. RLIMIT_NOFILE or nofile)
+        #[clap(value_parser = parse_resource, value_name = "RESOURCE")]
+        resource: Resource,
+        /// New soft limit, a number or 'unlimited' [default: unchanged]
+        #[clap(short, long, value_parser = parse_limit, required_unless_present = "hard")]
+        soft: Option<u64>,
+        /// New hard limit, a number or 'unlimited' [default: unchanged]
+        #[clap(short = 'H', long, value_parser = parse_limit)]
+        hard: Option<u64>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Resource(i32);
+
+impl Resource {
+    fn name(self) -> Option<&'static str> {
+        RESOURCES
+            .iter()
+            .find(|&&(raw, _, _)| raw == self.0)
+            .map(|&(_, name, _)| name)
+    }
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "resource {}", self.0),
+        }
+    }
+}
+
+/// Parses a resource name such as `RLIMIT_NOFILE` or `nofile`, case-insensitively.
+fn parse_resource(s: &str) -> Result<Resource, String> {
+    let upper = s.to_ascii_uppercase();
+    let name = upper.strip_prefix("RLIMIT_").unwrap_or(&upper);
+    RESOURCES
+        .iter()
+        .find(|(_, full_name, _)| full_name.strip_prefix("RLIMIT_") == Some(name))
+        .map(|&(raw, _, _)| Resource(raw))
+        .ok_or_else(|| {
+            format!(
+                "unknown resource '{}'; supported resources are {}",
+                s,
+                RESOURCES
+                    .iter()
+                    .map(|(_, name, _)| *name)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        })
+}
+
+/// Parses a limit, which is a number or `unlimited`.
+fn parse_limit(s: &str) -> Result<u64, String> {
+    match s {
+        "unlimited" | "infinity" => Ok(RLIM_INFINITY),
+        _ => s
+            .parse::<u64>()
+            .map_err(|_| format!("'{}' is not a number or 'unlimited'", s)),
+    }
+}
+
+fn format_limit(limit: u64) -> String {
+    if limit == RLIM_INFINITY {
+        "unlimited".to_owned()
+    } else {
+        limit.to_string()
+    }
+}
+
+/// Calls prlimit(2), setting the limit to `new` if given, and returns the old limit.
+fn prlimit(pid: i32, resource: Resource, new: Option<(u64, u64)>) -> nix::Result<(u64, u64)> {
+    let new = new.map(|(soft, hard)| libc::rlimit64 {
+        rlim_cur: soft,
+        rlim_max: hard,
+    });
+    let mut old = libc::rlimit64 {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    let new_ptr = new
+        .as_ref()
+        .map_or(std::ptr::null(), |new| new as *const libc::rlimit64);
+    // The raw syscall is used since glibc and musl disagree on the type of the resource.
+    // SAFETY: new_ptr is null or points to a valid rlimit64, and old is a valid rlimit64.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_prlimit64,
+            pid,
+            resource.0,
+            new_ptr,
+            &mut old as *mut libc::rlimit64,
+        )
+    };
+    Errno::result(ret).map(|_| (old.rlim_cur, old.rlim_max))
+}
+
+/// Returns whether this process has CAP_SYS_RESOURCE in its effective set.
+fn has_cap_sys_resource() -> anyhow::Result<bool> {
+    let status =
+        std::fs::read_to_string("/proc/self/status").context("Failed to read /proc/self/status")?;
+    let cap_eff = status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .ok_or_else(|| anyhow!("CapEff is not found in /proc/self/status"))?;
+    let cap_eff = u64::from_str_radix(cap_eff.trim(), 16).with_context(|| {
+        format!(
+            "Unexpected CapEff '{}' in /proc/self/status",
+            cap_eff.trim()
+        )
+    })?;
+
+    Ok(cap_eff & (1 << CAP_SYS_RESOURCE) != 0)
+}
+
+fn handle_get(pid: Option<i32>, resource: Option<Resource>) -> anyhow::Result<()> {
+    // 0 means the calling process
+    let pid = pid.unwrap_or(0);
+    let resources = RESOURCES
+        .iter()
+        .map(|&(raw, _, unit)| (Resource(raw), unit))
+        .filter(|&(r, _)| resource.map_or(true, |resource| r == resource));
+
+    println!("{:<18} {:>20} {:>20}  UNIT", "RESOURCE", "SOFT", "HARD");
+    for (resource, unit) in resources {
+        let (soft, hard) = prlimit(pid, resource, None)
+            .with_context(|| format!("Failed to get {} of process {}", resource, pid))?;
+        println!(
+            "{:<18} {:>20} {:>20}  {}",
+            resource.to_string(),
+            format_limit(soft),
+            format_limit(hard),
+            unit
+        );
+    }
+
+    Ok(())
+}
+
+fn handle_set(
+    pid: i32,
+    resource: Resource,
+    soft: Option<u64>,
+    hard: Option<u64>,
+) -> anyhow::Result<()> {
+    let (old_soft, old_hard) = prlimit(pid, resource, None)
+        .with_context(|| format!("Failed to get {} of process {}", resource, pid))?;
+    let (soft, hard) = (soft.unwrap_or(old_soft), hard.unwrap_or(old_hard));
+
+    if soft > hard {
+        return Err(anyhow!(
+            "The soft limit {} exceeds the hard limit {}",
+            format_limit(soft),
+            format_limit(hard)
+        ));
+    }
+    if hard > old_hard && !has_cap_sys_resource()? {
+        return Err(anyhow!(
+            "Raising the hard limit of {} from {} to {} requires CAP_SYS_RESOURCE",
+            resource,
+            format_limit(old_hard),
+            format_limit(hard)
+        ));
+    }
+
+    prlimit(pid, resource, Some((soft, hard)))
+        .with_context(|| format!("Failed to set {} of process {}", resource, pid))?;
+    eprintln!("soft: {} -> {}", format_limit(old_soft), format_limit(soft));
+    eprintln!("hard: {} -> {}", format_limit(old_hard), format_limit(hard));
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { pid, resource } => handle_get(pid, resource),
+        Commands::Set {
+            pid,
+            resource,
+            soft,
+            hard,
+        } => handle_set(pid, resource, soft, hard),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,331 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::{parse_address, parse_size};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::uio::{process_vm_readv, process_vm_writev, RemoteIoVec};
+use nix::unistd::Pid;
+use std::io::{IoSlice, IoSliceMut};
+use std::path::{Path, PathBuf};
+
+/// Bytes printed in a line of a dump
+const DUMP_WIDTH: usize = 16;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print memory of a process as a hex dump
+    #[clap(display_order = 1, name = "read")]
+    #[clap(override_usage = "process-vm read <PID> <ADDR|--file <FILE>> <LEN>")]
+    Read {
+        /// Target process
+        #[clap(value_parser)]
+        pid: i32,
+        /// Address to read from, in hex with 0x or in decimal
+        #[clap(value_name = "ADDR")]
+        addr: Option<String>,
+        /// Number of bytes to read (accepts suffixes such as K, MiB)
+        #[clap(value_parser = parse_size, value_name = "LEN")]
+        len: Option<i64>,
+        /// File listing addresses in hex, one per line, to read from instead of ADDR
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Write bytes to memory of a process
+    #[clap(display_order = 2, name = "write")]
+    #[clap(override_usage = "process-vm write <PID> <ADDR|--file <FILE>> <DATA>")]
+    Write {
+        /// Target process
+        #[clap(value_parser)]
+        pid: i32,
+        /// Address to write to, in hex with 0x or in decimal
+        #[clap(value_name = "ADDR")]
+        addr: Option<String>,
+        /// Bytes to write in hex (e.g. 48656c6c6f)
+        #[clap(value_parser = parse_hex, value_name = "DATA")]
+        data: Option<Bytes>,
+        /// File listing addresses in hex, one per line, to write to instead of ADDR
+        #[clap(short, long, value_parser, value_name = "FILE")]
+        file: Option<PathBuf>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// Bytes given in hex
+#[derive(Debug, Clone)]
+struct Bytes(Vec<u8>);
+
+/// Parses bytes written in hex such as `48656c6c6f`.
+fn parse_hex(s: &str) -> Result<Bytes, String> {
+    let hex = s.strip_prefix("0x").unwrap_or(s);
+    if hex.is_empty() || hex.len() % 2 != 0 {
+        return Err(format!("'{}' is not an even number of hex digits", s));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| format!("'{}' contains a non-hex digit", s))
+        })
+        .collect::<Result<_, _>>()
+        .map(Bytes)
+}
+
+/// Reads addresses in hex, one per line, ignoring empty lines and comments starting with '#'.
+fn read_addresses(path: &Path) -> anyhow::Result<Vec<u64>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+
+    content
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number, line.split('#').next().unwrap_or("").trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(number, line)| {
+            let hex = line.strip_prefix("0x").unwrap_or(line);
+            u64::from_str_radix(hex, 16).map_err(|_| {
+                anyhow!(
+                    "'{}' at line {} of '{}' is not a hex address",
+                    line,
+                    number + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+/// Resolves the addresses and the argument after them.
+///
+/// ADDR and the argument after it are both optional positionals for clap,
+/// so with --file, the argument is parsed into ADDR and parsed here.
+fn resolve<T>(
+    file: Option<PathBuf>,
+    addr: Option<String>,
+    arg: Option<T>,
+    parse_arg: fn(&str) -> Result<T, String>,
+    arg_name: &str,
+) -> anyhow::Result<(Vec<u64>, T)> {
+    match (file, addr, arg) {
+        (Some(file), Some(arg), None) => {
+            let arg = parse_arg(&arg)
+                .map_err(|err| anyhow!("Invalid value '{}' for {}: {}", arg, arg_name, err))?;
+            Ok((read_addresses(&file)?, arg))
+        }
+        (None, Some(addr), Some(arg)) => {
+            let addr = parse_address(&addr)
+                .map_err(|err| anyhow!("Invalid value '{}' for ADDR: {}", addr, err))?;
+            Ok((vec![addr], arg))
+        }
+        (Some(_), Some(_), Some(_)) => Err(anyhow!("ADDR cannot be used with --file")),
+        _ => Err(anyhow!("ADDR or --file, and {} are required", arg_name)),
+    }
+}
+
+/// A mapping of /proc/PID/maps
+struct Region {
+    start: u64,
+    end: u64,
+    perms: String,
+}
+
+fn read_maps(pid: i32) -> anyhow::Result<Vec<Region>> {
+    let path = format!("/proc/{}/maps", pid);
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+
+    // Each line is like "7ffd5a3a1000-7ffd5a3c2000 rw-p 00000000 00:00 0  [stack]"
+    content
+        .lines()
+        .map(|line| {
+            let mut fields = line.split_whitespace();
+            let range = fields.next().unwrap_or("");
+            let perms = fields.next().unwrap_or("");
+            let (start, end) = range
+                .split_once('-')
+                .ok_or_else(|| anyhow!("Unexpected line in {}: {}", path, line))?;
+            Ok(Region {
+                start: u64::from_str_radix(start, 16)
+                    .with_context(|| format!("Unexpected line in {}: {}", path, line))?,
+                end: u64::from_str_radix(end, 16)
+                    .with_context(|| format!("Unexpected line in {}: {}", path, line))?,
+                perms: perms.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// Returns whether contiguous mappings with the permission cover the range.
+fn is_covered(regions: &[Region], addr: u64, len: u64, perm: char) -> bool {
+    let end = addr.saturating_add(len);
+    let mut covered = addr;
+    // The mappings are sorted by their addresses
+    for region in regions {
+        if region.start <= covered && covered < region.end && region.perms.contains(perm) {
+            covered = region.end;
+        }
+        if covered >= end {
+            return true;
+        }
+    }
+    false
+}
+
+/// Warns about the ranges outside of the mappings with the permission, without failing.
+fn check_maps(pid: i32, addrs: &[u64], len: usize, perm: char, kind: &str) {
+    let regions = match read_maps(pid) {
+        Ok(regions) => regions,
+        Err(err) => {
+            eprintln!("warning: {:#}; the addresses are not checked", err);
+            return;
+        }
+    };
+    for &addr in addrs {
+        if !is_covered(&regions, addr, len as u64, perm) {
+            eprintln!(
+                "warning: {:#x}-{:#x} is not within a {} mapping of process {}",
+                addr,
+                addr.saturating_add(len as u64),
+                kind,
+                pid
+            );
+        }
+    }
+}
+
+fn describe(errno: Errno, pid: i32, addr: u64) -> anyhow::Error {
+    match errno {
+        Errno::EFAULT => anyhow!("{:#x} is not accessible in process {}", addr, pid),
+        Errno::EPERM => anyhow!(
+            "Accessing memory of process {} requires the permission to ptrace it",
+            pid
+        ),
+        Errno::ESRCH => anyhow!("Process {} does not exist", pid),
+        errno => anyhow!(errno),
+    }
+}
+
+fn print_dump(addr: u64, data: &[u8]) {
+    for (i, line) in data.chunks(DUMP_WIDTH).enumerate() {
+        let hex = line
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<_>>()
+            .join(" ");
+        let ascii: String = line
+            .iter()
+            .map(|&byte| {
+                if byte.is_ascii_graphic() || byte == b' ' {
+                    byte as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        println!(
+            "{:016x}  {:<width$}  |{}|",
+            addr + (i * DUMP_WIDTH) as u64,
+            hex,
+            ascii,
+            width = DUMP_WIDTH * 3 - 1
+        );
+    }
+}
+
+fn handle_read(
+    pid: i32,
+    file: Option<PathBuf>,
+    addr: Option<String>,
+    len: Option<i64>,
+) -> anyhow::Result<()> {
+    let (addrs, len) = resolve(file, addr, len, parse_size, "LEN")?;
+    let len = len as usize;
+    check_maps(pid, &addrs, len, 'r', "readable");
+
+    for (i, &addr) in addrs.iter().enumerate() {
+        let mut buf = vec![0u8; len];
+        let remote = [RemoteIoVec {
+            base: addr as usize,
+            len,
+        }];
+        let read = process_vm_readv(
+            Pid::from_raw(pid),
+            &mut [IoSliceMut::new(&mut buf)],
+            &remote,
+        )
+        .map_err(|errno| describe(errno, pid, addr))
+        .with_context(|| format!("Failed to read {:#x}", addr))?;
+        // The read stops at the end of a mapping
+        if read < len {
+            eprintln!(
+                "warning: only {} of {} bytes at {:#x} were read",
+                read, len, addr
+            );
+        }
+
+        if i > 0 {
+            println!();
+        }
+        print_dump(addr, &buf[..read]);
+    }
+
+    Ok(())
+}
+
+fn handle_write(
+    pid: i32,
+    file: Option<PathBuf>,
+    addr: Option<String>,
+    data: Option<Bytes>,
+) -> anyhow::Result<()> {
+    let (addrs, Bytes(data)) = resolve(file, addr, data, parse_hex, "DATA")?;
+    check_maps(pid, &addrs, data.len(), 'w', "writable");
+
+    for &addr in &addrs {
+        let remote = [RemoteIoVec {
+            base: addr as usize,
+            len: data.len(),
+        }];
+        let written = process_vm_writev(Pid::from_raw(pid), &[IoSlice::new(&data)], &remote)
+            .map_err(|errno| describe(errno, pid, addr))
+            .with_context(|| format!("Failed to write {:#x}", addr))?;
+        eprintln!("{:#x}: wrote {} of {} bytes", addr, written, data.len());
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Read {
+            pid,
+            addr,
+            len,
+            file,
+        } => handle_read(pid, file, addr, len),
+        Commands::Write {
+            pid,
+            addr,
+            data,
+            file,
+        } => handle_write(pid, file, addr, data),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
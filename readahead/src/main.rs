@@ -0,0 +1,144 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::mem::{page_size, query_residency};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::fs::File;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Bytes read ahead by a single call
+///
+/// The kernel silently truncates each call to the larger of the readahead size
+/// and the maximum I/O size of the device, and the default readahead size is 128 KiB.
+const CHUNK_SIZE: i64 = 128 * 1024;
+
+/// Maximum time --verify waits for the I/O submitted by readahead
+const VERIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval of the checks of --verify
+const VERIFY_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    readahead: ReadaheadInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct ReadaheadInfo {
+    /// File to read into the page cache
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Offset in FILE to start from (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Number of bytes to read [default: up to the end of FILE]
+    #[clap(short = 'n', long, value_parser = parse_size)]
+    count: Option<i64>,
+    /// Check with mincore that the pages of the range are in the page cache afterwards
+    #[clap(long)]
+    verify: bool,
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::EINVAL => {
+            anyhow!("The file type does not support readahead, such as a pipe or a socket")
+        }
+        errno => anyhow!(errno),
+    }
+}
+
+/// Waits for the pages of the range to enter the page cache and prints how many of them did.
+fn verify(file: &File, offset: i64, end: i64) -> anyhow::Result<()> {
+    let page_size = page_size()?;
+    // mmap requires the offset to be page aligned
+    let start = offset / page_size * page_size;
+    // readahead only submits the I/O, so the pages appear in the page cache while it completes
+    let deadline = Instant::now() + VERIFY_TIMEOUT;
+    let (residency, resident) = loop {
+        let residency = query_residency(file, start, end, page_size)?;
+        let resident = residency.iter().filter(|&&resident| resident).count();
+        if resident == residency.len() || Instant::now() >= deadline {
+            break (residency, resident);
+        }
+        std::thread::sleep(VERIFY_INTERVAL);
+    };
+
+    println!("pages: {}", residency.len());
+    println!("resident: {}", resident);
+    println!(
+        "percentage: {:.2}%",
+        resident as f64 * 100.0 / residency.len().max(1) as f64
+    );
+    if resident < residency.len() {
+        eprintln!(
+            "warning: {} pages are not in the page cache; they may be evicted under memory pressure",
+            residency.len() - resident
+        );
+    }
+
+    Ok(())
+}
+
+fn handle_readahead(info: ReadaheadInfo) -> anyhow::Result<()> {
+    // clap requires FILE unless a subcommand is given
+    let filename = info.filename.unwrap();
+    let file = File::open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+    let file_len = file
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?
+        .len() as i64;
+
+    let offset = info.offset.min(file_len);
+    let end = match info.count {
+        Some(count) => offset.saturating_add(count).min(file_len),
+        None => file_len,
+    };
+
+    let mut chunk_start = offset;
+    while chunk_start < end {
+        let len = (end - chunk_start).min(CHUNK_SIZE);
+        // SAFETY: readahead only takes integer arguments.
+        let ret = unsafe { libc::readahead(file.as_raw_fd(), chunk_start, len as usize) };
+        Errno::result(ret)
+            .map_err(describe)
+            .with_context(|| format!("Failed to read ahead '{}'", filename.display()))?;
+        chunk_start += len;
+    }
+
+    if info.verify {
+        verify(&file, offset, end)?;
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_readahead(cli.readahead),
+    }
+}
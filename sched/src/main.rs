@@ -0,0 +1,301 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_duration;
+use linux_tools_core::output::format_duration;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+
+/// Size of the first version of struct sched_attr, which has all the fields used here
+const SCHED_ATTR_SIZE_VER0: u32 = 48;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the scheduling policy and parameters of a process
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// Target process [default: this process]
+        #[clap(value_parser)]
+        pid: Option<i32>,
+    },
+    /// Set the scheduling policy and parameters of a process
+    #[clap(display_order = 2, name = "set")]
+    Set {
+        /// Target process
+        #[clap(value_parser)]
+        pid: i32,
+        /// Scheduling policy
+        #[clap(short, long, arg_enum)]
+        policy: Policy,
+        /// Static priority, from 1 to 99 for fifo and rr [default: 0 for the other policies]
+        #[clap(short = 'P', long, value_parser)]
+        priority: Option<i32>,
+        /// CPU time given in each period for deadline (accepts units such as us and ms)
+        #[clap(short, long, value_parser = parse_duration, required_if_eq("policy", "deadline"))]
+        runtime: Option<u64>,
+        /// Time from the beginning of each period to finish the runtime by for deadline
+        #[clap(short, long, value_parser = parse_duration, required_if_eq("policy", "deadline"))]
+        deadline: Option<u64>,
+        /// Length of each period for deadline [default: same as --deadline]
+        #[clap(long, value_parser = parse_duration)]
+        period: Option<u64>,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Policy {
+    Other,
+    Fifo,
+    Rr,
+    Batch,
+    Idle,
+    Deadline,
+}
+
+impl std::fmt::Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Policy::Other => write!(f, "SCHED_OTHER"),
+            Policy::Fifo => write!(f, "SCHED_FIFO"),
+            Policy::Rr => write!(f, "SCHED_RR"),
+            Policy::Batch => write!(f, "SCHED_BATCH"),
+            Policy::Idle => write!(f, "SCHED_IDLE"),
+            Policy::Deadline => write!(f, "SCHED_DEADLINE"),
+        }
+    }
+}
+
+impl Policy {
+    fn raw(self) -> i32 {
+        match self {
+            Policy::Other => libc::SCHED_OTHER,
+            Policy::Fifo => libc::SCHED_FIFO,
+            Policy::Rr => libc::SCHED_RR,
+            Policy::Batch => libc::SCHED_BATCH,
+            Policy::Idle => libc::SCHED_IDLE,
+            Policy::Deadline => libc::SCHED_DEADLINE,
+        }
+    }
+
+    fn from_raw(policy: i32) -> Option<Self> {
+        match policy {
+            libc::SCHED_OTHER => Some(Policy::Other),
+            libc::SCHED_FIFO => Some(Policy::Fifo),
+            libc::SCHED_RR => Some(Policy::Rr),
+            libc::SCHED_BATCH => Some(Policy::Batch),
+            libc::SCHED_IDLE => Some(Policy::Idle),
+            libc::SCHED_DEADLINE => Some(Policy::Deadline),
+            _ => None,
+        }
+    }
+}
+
+/// struct sched_attr of sched_setattr(2)
+#[repr(C)]
+#[derive(Default)]
+struct SchedAttr {
+    size: u32,
+    sched_policy: u32,
+    sched_flags: u64,
+    sched_nice: i32,
+    sched_priority: u32,
+    sched_runtime: u64,
+    sched_deadline: u64,
+    sched_period: u64,
+}
+
+fn sched_getscheduler(pid: i32) -> nix::Result<i32> {
+    // SAFETY: sched_getscheduler only takes an integer argument.
+    Errno::result(unsafe { libc::sched_getscheduler(pid) })
+}
+
+fn sched_getparam(pid: i32) -> nix::Result<i32> {
+    let mut param = libc::sched_param { sched_priority: 0 };
+    // SAFETY: param is a valid sched_param.
+    Errno::result(unsafe { libc::sched_getparam(pid, &mut param) })?;
+    Ok(param.sched_priority)
+}
+
+fn sched_setscheduler(pid: i32, policy: Policy, priority: i32) -> nix::Result<()> {
+    let param = libc::sched_param {
+        sched_priority: priority,
+    };
+    // SAFETY: param is a valid sched_param.
+    Errno::result(unsafe { libc::sched_setscheduler(pid, policy.raw(), &param) }).map(drop)
+}
+
+fn sched_getattr(pid: i32) -> nix::Result<SchedAttr> {
+    let mut attr = SchedAttr::default();
+    // SAFETY: attr is a valid sched_attr of SCHED_ATTR_SIZE_VER0 bytes.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_sched_getattr,
+            pid,
+            &mut attr as *mut SchedAttr,
+            SCHED_ATTR_SIZE_VER0,
+            0,
+        )
+    };
+    Errno::result(ret).map(|_| attr)
+}
+
+fn sched_setattr(pid: i32, attr: &SchedAttr) -> nix::Result<()> {
+    // SAFETY: attr is a valid sched_attr, and its size field tells the kernel its size.
+    let ret = unsafe { libc::syscall(libc::SYS_sched_setattr, pid, attr as *const SchedAttr, 0) };
+    Errno::result(ret).map(drop)
+}
+
+fn priority_range(policy: Policy) -> nix::Result<(i32, i32)> {
+    // SAFETY: both functions only take an integer argument.
+    let min = Errno::result(unsafe { libc::sched_get_priority_min(policy.raw()) })?;
+    let max = Errno::result(unsafe { libc::sched_get_priority_max(policy.raw()) })?;
+    Ok((min, max))
+}
+
+fn handle_get(pid: Option<i32>) -> anyhow::Result<()> {
+    // 0 means the calling process
+    let pid = pid.unwrap_or(0);
+    let raw = sched_getscheduler(pid)
+        .with_context(|| format!("Failed to get the scheduling policy of process {}", pid))?;
+    let reset_on_fork = raw & libc::SCHED_RESET_ON_FORK != 0;
+    let raw = raw & !libc::SCHED_RESET_ON_FORK;
+    let policy =
+        Policy::from_raw(raw).ok_or_else(|| anyhow!("Unknown scheduling policy {}", raw))?;
+
+    println!("policy: {} ({})", policy, raw);
+    match policy {
+        Policy::Deadline => {
+            let attr = sched_getattr(pid).with_context(|| {
+                format!("Failed to get the deadline parameters of process {}", pid)
+            })?;
+            println!("runtime: {}", format_duration(attr.sched_runtime));
+            println!("deadline: {}", format_duration(attr.sched_deadline));
+            println!("period: {}", format_duration(attr.sched_period));
+        }
+        Policy::Fifo | Policy::Rr => {
+            let priority = sched_getparam(pid).with_context(|| {
+                format!("Failed to get the scheduling priority of process {}", pid)
+            })?;
+            println!("priority: {}", priority);
+        }
+        Policy::Other | Policy::Batch | Policy::Idle => {
+            // These policies are only weighted by the nice value
+            let attr = sched_getattr(pid)
+                .with_context(|| format!("Failed to get the nice value of process {}", pid))?;
+            println!("nice: {}", attr.sched_nice);
+        }
+    }
+    println!("reset_on_fork: {}", reset_on_fork);
+
+    Ok(())
+}
+
+fn set_deadline(pid: i32, runtime: u64, deadline: u64, period: Option<u64>) -> anyhow::Result<()> {
+    let period = period.unwrap_or(deadline);
+    if !(runtime <= deadline && deadline <= period) {
+        return Err(anyhow!(
+            "SCHED_DEADLINE requires runtime <= deadline <= period, but they are {}, {} and {}",
+            format_duration(runtime),
+            format_duration(deadline),
+            format_duration(period)
+        ));
+    }
+
+    let attr = SchedAttr {
+        size: SCHED_ATTR_SIZE_VER0,
+        sched_policy: libc::SCHED_DEADLINE as u32,
+        sched_runtime: runtime,
+        sched_deadline: deadline,
+        sched_period: period,
+        ..SchedAttr::default()
+    };
+    sched_setattr(pid, &attr)
+        .map_err(|errno| match errno {
+            Errno::EBUSY => anyhow!("The CPUs do not have enough bandwidth left for the runtime"),
+            Errno::EINVAL => anyhow!(
+                "The kernel rejected the parameters; runtime must be at least 1 us and fit in the period"
+            ),
+            errno => anyhow!(errno),
+        })
+        .with_context(|| format!("Failed to set SCHED_DEADLINE to process {}", pid))
+}
+
+fn handle_set(
+    pid: i32,
+    policy: Policy,
+    priority: Option<i32>,
+    runtime: Option<u64>,
+    deadline: Option<u64>,
+    period: Option<u64>,
+) -> anyhow::Result<()> {
+    if policy == Policy::Deadline {
+        if priority.is_some() {
+            eprintln!("warning: --priority is ignored for {}", policy);
+        }
+        // clap requires --runtime and --deadline for deadline
+        return set_deadline(pid, runtime.unwrap(), deadline.unwrap(), period);
+    }
+    if runtime.is_some() || deadline.is_some() || period.is_some() {
+        eprintln!(
+            "warning: --runtime, --deadline and --period are ignored for {}",
+            policy
+        );
+    }
+
+    let (min, max) = priority_range(policy)
+        .with_context(|| format!("Failed to get the priority range of {}", policy))?;
+    let priority = match (policy, priority) {
+        (Policy::Fifo | Policy::Rr, None) => {
+            return Err(anyhow!(
+                "{} requires --priority from {} to {}",
+                policy,
+                min,
+                max
+            ))
+        }
+        (_, priority) => priority.unwrap_or(min),
+    };
+    if !(min..=max).contains(&priority) {
+        return Err(anyhow!(
+            "The priority of {} must be from {} to {}, but it is {}",
+            policy,
+            min,
+            max,
+            priority
+        ));
+    }
+
+    sched_setscheduler(pid, policy, priority)
+        .with_context(|| format!("Failed to set {} to process {}", policy, pid))
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { pid } => handle_get(pid),
+        Commands::Set {
+            pid,
+            policy,
+            priority,
+            runtime,
+            deadline,
+            period,
+        } => handle_set(pid, policy, priority, runtime, deadline, period),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
@@ -0,0 +1,253 @@
+#[cfg(target_arch = "x86_64")]
+mod syscalls;
+
+/// No system call numbers are known on the other architectures, where seccomp fails at run time
+#[cfg(not(target_arch = "x86_64"))]
+mod syscalls {
+    pub const SYSCALLS: &[(&str, i64)] = &[];
+}
+
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::default_command;
+use nix::errno::Errno;
+use nix::unistd::execvp;
+use std::ffi::{CString, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Whether the system call numbers of this architecture are known, which are only the ones of x86_64
+const SUPPORTED_ARCH: bool = cfg!(target_arch = "x86_64");
+
+const UNSUPPORTED_ARCH: &str =
+    "Unsupported architecture; seccomp only knows the system call numbers of x86_64";
+
+/// AUDIT_ARCH_X86_64 from linux/audit.h, checked so that the numbers of another ABI are not allowed
+const AUDIT_ARCH_X86_64: u32 = 0xc000_003e;
+
+/// System calls of the x32 ABI have this bit in their numbers
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// Offsets of the fields of seccomp_data, which the filter loads
+const SECCOMP_DATA_NR: u32 = 0;
+const SECCOMP_DATA_ARCH: u32 = 4;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    filter: FilterInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+#[clap(group(ArgGroup::new("allow-list").required(true).multiple(true).args(&["allow-syscalls", "file"])))]
+struct FilterInfo {
+    /// System calls to allow, by name or number, separated by commas (e.g. read,write,exit_group)
+    #[clap(
+        short,
+        long,
+        use_value_delimiter = true,
+        value_parser = parse_syscall,
+        value_name = "SYS,..."
+    )]
+    allow_syscalls: Vec<i64>,
+    /// File listing system calls to allow, separated by whitespace, with comments after '#'
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    file: Option<PathBuf>,
+    /// Make the other system calls fail with ENOSYS, or stop for a tracer, instead of killing
+    #[clap(long)]
+    audit: bool,
+    /// Command to run with the filter [default: $SHELL or /bin/sh]
+    #[clap(value_parser, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+/// Parses a system call such as `read` or `0`.
+fn parse_syscall(s: &str) -> Result<i64, String> {
+    if !SUPPORTED_ARCH {
+        return Err(UNSUPPORTED_ARCH.to_owned());
+    }
+    let s = s.trim();
+    match s.parse::<i64>() {
+        Ok(number) => syscalls::SYSCALLS
+            .iter()
+            .find(|&&(_, nr)| nr == number)
+            .map(|&(_, nr)| nr)
+            .ok_or_else(|| format!("{} is not a known system call number", number)),
+        Err(_) => syscalls::SYSCALLS
+            .iter()
+            .find(|&&(name, _)| name == s)
+            .map(|&(_, nr)| nr)
+            .ok_or_else(|| format!("unknown system call '{}'", s)),
+    }
+}
+
+fn read_syscalls(path: &Path) -> anyhow::Result<Vec<i64>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read '{}'", path.display()))?;
+    let mut numbers = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        for name in line.split_whitespace() {
+            let number = parse_syscall(name)
+                .map_err(|err| anyhow!("{}:{}: {}", path.display(), index + 1, err))?;
+            numbers.push(number);
+        }
+    }
+
+    Ok(numbers)
+}
+
+fn stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+/// Builds a filter allowing `numbers` and returning `default` for the other system calls.
+fn build_filter(numbers: &[i64], default: u32) -> Vec<libc::sock_filter> {
+    let mut filter = vec![
+        // Numbers of another architecture mean different system calls, so they are always killed
+        stmt(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            SECCOMP_DATA_ARCH,
+        ),
+        jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            AUDIT_ARCH_X86_64,
+            1,
+            0,
+        ),
+        stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_KILL_PROCESS),
+        stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, SECCOMP_DATA_NR),
+        jump(
+            libc::BPF_JMP | libc::BPF_JGE | libc::BPF_K,
+            X32_SYSCALL_BIT,
+            0,
+            1,
+        ),
+        stmt(libc::BPF_RET | libc::BPF_K, default),
+    ];
+    for &number in numbers {
+        filter.push(jump(
+            libc::BPF_JMP | libc::BPF_JEQ | libc::BPF_K,
+            number as u32,
+            0,
+            1,
+        ));
+        filter.push(stmt(libc::BPF_RET | libc::BPF_K, libc::SECCOMP_RET_ALLOW));
+    }
+    filter.push(stmt(libc::BPF_RET | libc::BPF_K, default));
+
+    filter
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::EINVAL => {
+            anyhow!("The kernel does not support seccomp filters (CONFIG_SECCOMP_FILTER)")
+        }
+        Errno::ENOMEM => anyhow!("The filter is too large"),
+        errno => anyhow!(errno),
+    }
+}
+
+fn install_filter(filter: &mut [libc::sock_filter]) -> anyhow::Result<()> {
+    // Required to install a filter without CAP_SYS_ADMIN, and inherited by COMMAND
+    // SAFETY: PR_SET_NO_NEW_PRIVS only takes integer arguments.
+    let ret = unsafe { libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) };
+    Errno::result(ret).context("Failed to set no_new_privs")?;
+
+    let prog = libc::sock_fprog {
+        len: u16::try_from(filter.len()).map_err(|_| anyhow!("The filter is too large"))?,
+        filter: filter.as_mut_ptr(),
+    };
+    // SAFETY: prog refers to the instructions in filter, which the kernel copies.
+    let ret = unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &prog as *const libc::sock_fprog,
+        )
+    };
+    Errno::result(ret)
+        .map(drop)
+        .map_err(describe)
+        .context("Failed to install the seccomp filter")
+}
+
+fn handle_filter(info: FilterInfo) -> anyhow::Result<()> {
+    if !SUPPORTED_ARCH {
+        return Err(anyhow!(UNSUPPORTED_ARCH));
+    }
+    let mut numbers = info.allow_syscalls;
+    if let Some(path) = &info.file {
+        numbers.extend(read_syscalls(path)?);
+    }
+    // The filter is installed before executing COMMAND
+    numbers.push(libc::SYS_execve);
+    numbers.sort_unstable();
+    numbers.dedup();
+
+    let default = if info.audit {
+        libc::SECCOMP_RET_TRACE
+    } else {
+        libc::SECCOMP_RET_KILL_PROCESS
+    };
+    let command = if info.command.is_empty() {
+        default_command()
+    } else {
+        info.command
+    };
+
+    // Prepared beforehand, since only execve is surely allowed once the filter is installed
+    let args = command
+        .iter()
+        .map(|arg| CString::new(arg.as_bytes()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("The command contains a null byte")?;
+
+    let mut filter = build_filter(&numbers, default);
+    install_filter(&mut filter)?;
+
+    let errno = execvp(&args[0], &args).unwrap_err();
+    Err(errno).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_filter(cli.filter),
+    }
+}
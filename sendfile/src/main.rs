@@ -0,0 +1,134 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::copy::{copy_with_sendfile, throughput};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    copy: CopyInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CopyInfo {
+    /// File to copy from
+    #[clap(required = true, value_parser, value_name = "SOURCE")]
+    source: Option<PathBuf>,
+    /// File to copy to
+    #[clap(required = true, value_parser, value_name = "DEST")]
+    destination: Option<PathBuf>,
+    /// Offset in SOURCE to start copying from (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Number of bytes to copy [default: up to the end of SOURCE]
+    #[clap(short = 'n', long, value_parser = parse_size)]
+    count: Option<i64>,
+    /// Create DEST if it does not exist
+    #[clap(short, long)]
+    create: bool,
+}
+
+fn copy_with_buffer(
+    mut source: &File,
+    mut destination: &File,
+    offset: i64,
+    count: i64,
+) -> std::io::Result<i64> {
+    source.seek(SeekFrom::Start(offset as u64))?;
+    let copied = std::io::copy(&mut source.take(count as u64), &mut destination)?;
+
+    Ok(copied as i64)
+}
+
+fn handle_copy(info: CopyInfo) -> anyhow::Result<()> {
+    // clap requires SOURCE and DEST unless a subcommand is given
+    let source_path = info.source.unwrap();
+    let destination_path = info.destination.unwrap();
+
+    let source = File::open(&source_path)
+        .with_context(|| format!("Failed to open '{}'", source_path.display()))?;
+    let source_len = source
+        .metadata()
+        .context("Failed to retrieve metadata of the source")?
+        .len() as i64;
+    let count = info
+        .count
+        .unwrap_or(source_len - info.offset.min(source_len));
+    if info.offset.checked_add(count).is_none() {
+        return Err(anyhow!(
+            "The range of offset {} and count {} exceeds the maximum file offset",
+            info.offset,
+            count
+        ));
+    }
+    let destination = OpenOptions::new()
+        .write(true)
+        .create(info.create)
+        .truncate(true)
+        .open(&destination_path)
+        .with_context(|| format!("Failed to open '{}'", destination_path.display()))?;
+
+    eprintln!("source: {}", source_path.display());
+    eprintln!("destination: {}", destination_path.display());
+    eprintln!("offset: {}", info.offset);
+    eprintln!("count: {}", count);
+
+    let start = Instant::now();
+    let mut offset = info.offset;
+    let copied = match copy_with_sendfile(&source, &destination, &mut offset, count) {
+        Ok(copied) => {
+            eprintln!("method: sendfile");
+            copied
+        }
+        Err(errno @ (Errno::EINVAL | Errno::EXDEV)) => {
+            eprintln!(
+                "warning: sendfile failed with {}; falling back to buffered copy",
+                errno
+            );
+            eprintln!("method: buffered");
+            let sent = offset - info.offset;
+            let rest = copy_with_buffer(&source, &destination, offset, count - sent)
+                .context("Failed to copy the file")?;
+            sent + rest
+        }
+        Err(errno) => return Err(errno).context("Failed to call sendfile"),
+    };
+    let elapsed = start.elapsed();
+
+    eprintln!("copied: {}", copied);
+    eprintln!("elapsed: {:.3}s", elapsed.as_secs_f64());
+    eprintln!("throughput: {:.0} bytes/sec", throughput(copied, elapsed));
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_copy(cli.copy),
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::exit_code;
+use nix::errno::Errno;
+use nix::sys::signal::{kill, SigSet, Signal};
+use nix::sys::signalfd::SignalFd;
+use nix::unistd::Pid;
+use std::ffi::OsString;
+use std::process::{Command, ExitCode};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[clap(trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    receive: ReceiveInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct ReceiveInfo {
+    /// Signals to receive, by name or number (e.g. INT,SIGTERM,10)
+    #[clap(
+        short,
+        long,
+        required = true,
+        use_value_delimiter = true,
+        value_parser = parse_signal,
+        value_name = "SIG,..."
+    )]
+    signals: Vec<Signal>,
+    /// Exit after receiving the number of signals [default: unlimited]
+    #[clap(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..))]
+    count: Option<u64>,
+    /// Command to run and forward the signals to, exiting when it exits
+    #[clap(value_parser, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+/// Parses a signal such as `SIGINT`, `int` or `2`.
+fn parse_signal(s: &str) -> Result<Signal, String> {
+    let signal = match s.parse::<i32>() {
+        Ok(number) => Signal::try_from(number)
+            .map_err(|_| format!("{} is not a signal supported on this platform", number))?,
+        Err(_) => {
+            let name = s.to_ascii_uppercase();
+            let name = if name.starts_with("SIG") {
+                name
+            } else {
+                format!("SIG{}", name)
+            };
+            name.parse::<Signal>()
+                .map_err(|_| format!("unknown signal '{}'", s))?
+        }
+    };
+
+    match signal {
+        Signal::SIGKILL | Signal::SIGSTOP => Err(format!("{} cannot be blocked", signal)),
+        signal => Ok(signal),
+    }
+}
+
+fn signal_name(signo: u32) -> String {
+    match Signal::try_from(signo as i32) {
+        Ok(signal) => format!("{} ({})", signal, signo),
+        Err(_) => signo.to_string(),
+    }
+}
+
+/// Blocks the signals and creates a signalfd receiving them.
+///
+/// The signals must be blocked so that they are queued for the signalfd
+/// instead of running their default actions.
+fn create_signalfd(signals: &[Signal]) -> anyhow::Result<SignalFd> {
+    let mut mask = SigSet::empty();
+    for &signal in signals {
+        mask.add(signal);
+    }
+    mask.thread_block().context("Failed to block the signals")?;
+
+    SignalFd::new(&mask).context("Failed to create a signalfd")
+}
+
+fn handle_receive(info: ReceiveInfo) -> anyhow::Result<ExitCode> {
+    let forward = !info.command.is_empty();
+    let mut signals = info.signals.clone();
+    // SIGCHLD tells when the command exits
+    if forward && !signals.contains(&Signal::SIGCHLD) {
+        signals.push(Signal::SIGCHLD);
+    }
+    // Blocked before spawning the command so that no signal is missed,
+    // while the command starts with an empty signal mask.
+    let mut signalfd = create_signalfd(&signals)?;
+
+    let mut child = if forward {
+        let child = Command::new(&info.command[0])
+            .args(&info.command[1..])
+            .spawn()
+            .with_context(|| {
+                format!("Failed to execute '{}'", info.command[0].to_string_lossy())
+            })?;
+        Some(child)
+    } else {
+        None
+    };
+
+    eprintln!(
+        "pid: {}",
+        match &child {
+            Some(child) => child.id(),
+            None => std::process::id(),
+        }
+    );
+    eprintln!(
+        "signals: {}",
+        info.signals
+            .iter()
+            .map(|signal| signal.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let count = info.count.unwrap_or(u64::MAX);
+    let mut received = 0u64;
+    while received < count {
+        let siginfo = match signalfd.read_signal() {
+            Ok(Some(siginfo)) => siginfo,
+            // The signalfd is blocking, so it always returns a signal
+            Ok(None) => continue,
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to read the signalfd"),
+        };
+
+        let signo = siginfo.ssi_signo;
+        // Only the signals in the mask of the signalfd are received
+        let signal = Signal::try_from(signo as i32)
+            .map_err(|_| anyhow!("Received an unknown signal {}", signo))?;
+        if info.signals.contains(&signal) {
+            let line = format!(
+                "signal: {}, pid: {}, uid: {}, status: {}",
+                signal_name(signo),
+                siginfo.ssi_pid,
+                siginfo.ssi_uid,
+                siginfo.ssi_status
+            );
+            // Keep stdout for the command
+            if forward {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+            received += 1;
+        }
+
+        let child = match &mut child {
+            Some(child) => child,
+            None => continue,
+        };
+        if signal == Signal::SIGCHLD {
+            let status = child.try_wait().context("Failed to wait for the command")?;
+            if let Some(status) = status {
+                return Ok(exit_code(status));
+            }
+            continue;
+        }
+        let pid = Pid::from_raw(child.id() as i32);
+        match kill(pid, signal) {
+            // The command has exited but SIGCHLD is not read yet
+            Ok(()) | Err(Errno::ESRCH) => {}
+            Err(errno) => {
+                return Err(errno).with_context(|| {
+                    format!("Failed to forward {} to the command", signal_name(signo))
+                })
+            }
+        }
+    }
+
+    // The command keeps running after --count signals until it exits by itself
+    if let Some(mut child) = child {
+        let status = child.wait().context("Failed to wait for the command")?;
+        return Ok(exit_code(status));
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_receive(cli.receive),
+    }
+}
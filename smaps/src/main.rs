@@ -0,0 +1,476 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::OutputFormat;
+use linux_tools_core::output::new_reporter;
+use linux_tools_core::print_completer;
+use serde::Serialize;
+use serde_json::Value;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    smaps: SmapsInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct SmapsInfo {
+    /// Process to analyze
+    #[clap(required = true, value_parser)]
+    pid: Option<i32>,
+    /// Sort the mappings in descending order of the field, or by address
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        ignore_case = true,
+        default_value = "address",
+        conflicts_with = "rollup"
+    )]
+    sort_by: SortKey,
+    /// Only print anonymous mappings or ones backed by files
+    #[clap(short, long, arg_enum, conflicts_with = "rollup")]
+    filter: Option<Filter>,
+    /// Print the totals of all the mappings from /proc/PID/smaps_rollup instead
+    #[clap(short, long)]
+    rollup: bool,
+    /// Format of the result
+    #[clap(short = 'O', long, arg_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Print the result in JSON, the same as --output-format json
+    #[clap(short, long, conflicts_with = "output-format")]
+    json: bool,
+}
+
+impl SmapsInfo {
+    fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output_format
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum SortKey {
+    #[clap(name = "address")]
+    Address,
+    #[clap(name = "size")]
+    Size,
+    #[clap(name = "rss")]
+    Rss,
+    #[clap(name = "pss")]
+    Pss,
+    #[clap(name = "private-dirty")]
+    PrivateDirty,
+    #[clap(name = "swap-pss")]
+    SwapPss,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Filter {
+    /// Mappings without a file, including [heap] and [stack]
+    #[clap(name = "anonymous")]
+    Anonymous,
+    /// Mappings of files
+    #[clap(name = "file")]
+    File,
+}
+
+/// Memory usage in kB, which both smaps and smaps_rollup report.
+#[derive(Default, Clone, Copy, Serialize)]
+struct Usage {
+    rss: u64,
+    pss: u64,
+    shared_clean: u64,
+    shared_dirty: u64,
+    private_clean: u64,
+    private_dirty: u64,
+    referenced: u64,
+    anonymous: u64,
+    swap_pss: u64,
+}
+
+impl Usage {
+    /// Stores the value of a field line, ignoring the other fields.
+    fn set(&mut self, key: &str, value: u64) {
+        let field = match key {
+            "Rss" => &mut self.rss,
+            "Pss" => &mut self.pss,
+            "Shared_Clean" => &mut self.shared_clean,
+            "Shared_Dirty" => &mut self.shared_dirty,
+            "Private_Clean" => &mut self.private_clean,
+            "Private_Dirty" => &mut self.private_dirty,
+            "Referenced" => &mut self.referenced,
+            "Anonymous" => &mut self.anonymous,
+            "SwapPss" => &mut self.swap_pss,
+            _ => return,
+        };
+        *field = value;
+    }
+
+    fn add(&mut self, other: &Usage) {
+        self.rss += other.rss;
+        self.pss += other.pss;
+        self.shared_clean += other.shared_clean;
+        self.shared_dirty += other.shared_dirty;
+        self.private_clean += other.private_clean;
+        self.private_dirty += other.private_dirty;
+        self.referenced += other.referenced;
+        self.anonymous += other.anonymous;
+        self.swap_pss += other.swap_pss;
+    }
+}
+
+/// A mapping of /proc/PID/smaps, whose sizes are in kB.
+#[derive(Serialize)]
+struct Region {
+    start: u64,
+    end: u64,
+    perms: String,
+    offset: u64,
+    dev: String,
+    inode: u64,
+    pathname: String,
+    size: u64,
+    kernel_page_size: u64,
+    mmu_page_size: u64,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+impl Region {
+    /// Parses a header line such as
+    /// `7f0c2a400000-7f0c2a428000 r--p 00000000 08:01 1234   /usr/lib/libc.so.6`.
+    fn parse_header(line: &str) -> Option<Region> {
+        let mut fields = line.splitn(6, ' ');
+        let (start, end) = fields.next()?.split_once('-')?;
+        let perms = fields.next()?;
+        let offset = fields.next()?;
+        let dev = fields.next()?;
+        let inode = fields.next()?;
+        let pathname = fields.next().unwrap_or_default().trim_start();
+
+        Some(Region {
+            start: u64::from_str_radix(start, 16).ok()?,
+            end: u64::from_str_radix(end, 16).ok()?,
+            perms: perms.to_owned(),
+            offset: u64::from_str_radix(offset, 16).ok()?,
+            dev: dev.to_owned(),
+            inode: inode.parse().ok()?,
+            pathname: pathname.to_owned(),
+            size: 0,
+            kernel_page_size: 0,
+            mmu_page_size: 0,
+            usage: Usage::default(),
+        })
+    }
+
+    fn is_anonymous(&self) -> bool {
+        self.inode == 0
+    }
+
+    fn sort_value(&self, key: SortKey) -> u64 {
+        match key {
+            SortKey::Address => self.start,
+            SortKey::Size => self.size,
+            SortKey::Rss => self.usage.rss,
+            SortKey::Pss => self.usage.pss,
+            SortKey::PrivateDirty => self.usage.private_dirty,
+            SortKey::SwapPss => self.usage.swap_pss,
+        }
+    }
+}
+
+/// Parses a field line such as `Rss:   8 kB` into the key and the value.
+fn parse_field(line: &str) -> Option<(&str, u64)> {
+    let (key, value) = line.split_once(':')?;
+    let value = value.trim();
+    let value = value.strip_suffix(" kB").unwrap_or(value);
+    Some((key, value.parse().ok()?))
+}
+
+fn read_smaps(pid: i32) -> anyhow::Result<Vec<Region>> {
+    let path = format!("/proc/{}/smaps", pid);
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+
+    parse_smaps(&content).with_context(|| format!("Failed to parse {}", path))
+}
+
+fn parse_smaps(content: &str) -> anyhow::Result<Vec<Region>> {
+    let mut regions: Vec<Region> = Vec::new();
+    for line in content.lines() {
+        // Field names never contain a space before the colon, unlike header lines
+        let is_field = line
+            .split_once(':')
+            .map_or(false, |(key, _)| !key.contains(' ') && !key.contains('-'));
+        if !is_field {
+            let region =
+                Region::parse_header(line).ok_or_else(|| anyhow!("Unexpected line: {}", line))?;
+            regions.push(region);
+            continue;
+        }
+        // Fields without a numeric value such as VmFlags are skipped
+        let (key, value) = match parse_field(line) {
+            Some(field) => field,
+            None => continue,
+        };
+        let region = regions
+            .last_mut()
+            .ok_or_else(|| anyhow!("Unexpected line: {}", line))?;
+        match key {
+            "Size" => region.size = value,
+            "KernelPageSize" => region.kernel_page_size = value,
+            "MMUPageSize" => region.mmu_page_size = value,
+            key => region.usage.set(key, value),
+        }
+    }
+
+    Ok(regions)
+}
+
+fn read_rollup(pid: i32) -> anyhow::Result<Usage> {
+    let path = format!("/proc/{}/smaps_rollup", pid);
+    let content =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path))?;
+
+    Ok(parse_rollup(&content))
+}
+
+fn parse_rollup(content: &str) -> Usage {
+    let mut usage = Usage::default();
+    // The first line is the header of the range covering all the mappings
+    for line in content.lines().skip(1) {
+        if let Some((key, value)) = parse_field(line) {
+            usage.set(key, value);
+        }
+    }
+
+    usage
+}
+
+/// The totals of the printed mappings.
+#[derive(Default, Serialize)]
+struct Total {
+    size: u64,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+#[derive(Serialize)]
+struct SmapsReport<'a> {
+    pid: i32,
+    regions: &'a [Region],
+    total: Total,
+}
+
+fn print_header() {
+    println!(
+        "{:<27} {:<5} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>10} {:>9} {:>9}  PATHNAME",
+        "ADDRESS",
+        "PERMS",
+        "SIZE",
+        "RSS",
+        "PSS",
+        "SHR_CLEAN",
+        "SHR_DIRTY",
+        "PRV_CLEAN",
+        "PRV_DIRTY",
+        "REFERENCED",
+        "ANONYMOUS",
+        "SWAP_PSS"
+    );
+}
+
+fn print_row(address: &str, perms: &str, size: u64, usage: &Usage, pathname: &str) {
+    println!(
+        "{:<27} {:<5} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>9} {:>10} {:>9} {:>9}  {}",
+        address,
+        perms,
+        size,
+        usage.rss,
+        usage.pss,
+        usage.shared_clean,
+        usage.shared_dirty,
+        usage.private_clean,
+        usage.private_dirty,
+        usage.referenced,
+        usage.anonymous,
+        usage.swap_pss,
+        pathname
+    );
+}
+
+fn handle_rollup(pid: i32, format: OutputFormat) -> anyhow::Result<()> {
+    let usage = read_rollup(pid)?;
+    let mut reporter = new_reporter(format, std::io::stdout());
+    let fields = [
+        ("rss", usage.rss),
+        ("pss", usage.pss),
+        ("shared_clean", usage.shared_clean),
+        ("shared_dirty", usage.shared_dirty),
+        ("private_clean", usage.private_clean),
+        ("private_dirty", usage.private_dirty),
+        ("referenced", usage.referenced),
+        ("anonymous", usage.anonymous),
+        ("swap_pss", usage.swap_pss),
+    ];
+    for (key, kb) in fields {
+        reporter.report_value(key, &format!("{} kB", kb), Value::from(kb));
+    }
+
+    reporter.finish()
+}
+
+fn handle_smaps(info: SmapsInfo) -> anyhow::Result<()> {
+    // clap requires PID unless a subcommand is given
+    let pid = info.pid.unwrap();
+    if info.rollup {
+        return handle_rollup(pid, info.format());
+    }
+
+    let mut regions = read_smaps(pid)?;
+    match info.filter {
+        Some(Filter::Anonymous) => regions.retain(Region::is_anonymous),
+        Some(Filter::File) => regions.retain(|region| !region.is_anonymous()),
+        None => {}
+    }
+    match info.sort_by {
+        SortKey::Address => regions.sort_by_key(|region| region.start),
+        key => regions.sort_by_key(|region| std::cmp::Reverse(region.sort_value(key))),
+    }
+
+    let mut total = Total::default();
+    for region in &regions {
+        total.size += region.size;
+        total.usage.add(&region.usage);
+    }
+
+    // The mappings are nested in an array, which is not a field of a Reporter
+    if info.format() == OutputFormat::Json {
+        let report = SmapsReport {
+            pid,
+            regions: &regions,
+            total,
+        };
+        let json = serde_json::to_string(&report).context("Failed to serialize the result")?;
+        println!("{}", json);
+        return Ok(());
+    }
+
+    print_header();
+    for region in &regions {
+        print_row(
+            &format!("{:x}-{:x}", region.start, region.end),
+            &region.perms,
+            region.size,
+            &region.usage,
+            &region.pathname,
+        );
+    }
+    print_row("total", "", total.size, &total.usage, "");
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_smaps(cli.smaps),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SMAPS: &str = "\
+55d0c8a00000-55d0c8a21000 rw-p 00000000 00:00 0                          [heap]
+Size:                132 kB
+KernelPageSize:        4 kB
+MMUPageSize:           4 kB
+Rss:                  12 kB
+Pss:                  12 kB
+Private_Dirty:        12 kB
+Anonymous:            12 kB
+VmFlags: rd wr mr mw me ac sd
+7f0c2a400000-7f0c2a428000 r--p 00001000 08:01 1234                       /usr/lib/my lib.so
+Size:                160 kB
+Rss:                 160 kB
+Pss:                  40 kB
+Shared_Clean:        160 kB
+SwapPss:               0 kB
+7ffd5a3c1000-7ffd5a3c3000 r-xp 00000000 00:00 0
+Size:                  8 kB
+";
+
+    #[test]
+    fn smaps_is_parsed_into_regions() {
+        let regions = parse_smaps(SMAPS).unwrap();
+        assert_eq!(regions.len(), 3);
+
+        let heap = &regions[0];
+        assert_eq!((heap.start, heap.end), (0x55d0_c8a0_0000, 0x55d0_c8a2_1000));
+        assert_eq!(heap.perms, "rw-p");
+        assert_eq!(heap.pathname, "[heap]");
+        assert!(heap.is_anonymous());
+        assert_eq!(
+            (heap.size, heap.kernel_page_size, heap.mmu_page_size),
+            (132, 4, 4)
+        );
+        assert_eq!((heap.usage.rss, heap.usage.private_dirty), (12, 12));
+
+        let library = &regions[1];
+        assert_eq!((library.offset, library.dev.as_str()), (0x1000, "08:01"));
+        assert_eq!(library.inode, 1234);
+        assert_eq!(library.pathname, "/usr/lib/my lib.so");
+        assert!(!library.is_anonymous());
+        assert_eq!((library.usage.pss, library.usage.shared_clean), (40, 160));
+
+        assert_eq!(regions[2].pathname, "");
+        assert_eq!(regions[2].size, 8);
+    }
+
+    #[test]
+    fn malformed_smaps_is_rejected() {
+        assert!(parse_smaps("Rss:  4 kB\n").is_err());
+        assert!(parse_smaps("55d0c8a00000 rw-p 00000000 00:00 0\n").is_err());
+    }
+
+    #[test]
+    fn field_line_is_parsed() {
+        assert_eq!(
+            parse_field("Rss:                  12 kB"),
+            Some(("Rss", 12))
+        );
+        assert_eq!(parse_field("THPeligible:    0"), Some(("THPeligible", 0)));
+        assert_eq!(parse_field("VmFlags: rd wr mr"), None);
+    }
+
+    #[test]
+    fn rollup_skips_the_header() {
+        let usage = parse_rollup(
+            "55d0c8a00000-7ffd5a3e3000 ---p 00000000 00:00 0   [rollup]\nRss: 172 kB\nPss: 52 kB\n",
+        );
+        assert_eq!((usage.rss, usage.pss, usage.swap_pss), (172, 52, 0));
+    }
+}
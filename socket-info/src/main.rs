@@ -0,0 +1,352 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::pidfd::{open_pidfd, pidfd_getfd};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::sys::socket::sockopt::{KeepAlive, RcvBuf, ReuseAddr, SndBuf, SockType, TcpNoDelay};
+use nix::sys::socket::{getsockopt, GetSockOpt};
+use std::ffi::c_void;
+use std::io::ErrorKind;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::raw::c_int;
+use std::os::unix::io::{AsRawFd, OwnedFd, RawFd};
+use std::path::PathBuf;
+
+/// Names of the states of TCP in /proc/net/tcp from include/net/tcp_states.h
+const TCP_STATES: [&str; 12] = [
+    "ESTABLISHED",
+    "SYN_SENT",
+    "SYN_RECV",
+    "FIN_WAIT1",
+    "FIN_WAIT2",
+    "TIME_WAIT",
+    "CLOSE",
+    "CLOSE_WAIT",
+    "LAST_ACK",
+    "LISTEN",
+    "CLOSING",
+    "NEW_SYN_RECV",
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    socket: SocketInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct SocketInfo {
+    /// Process whose sockets are printed
+    #[clap(required = true, value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// File descriptors of the sockets [default: every socket of the process]
+    #[clap(value_parser, value_name = "FD")]
+    fds: Vec<RawFd>,
+}
+
+/// An integer option of SOL_SOCKET that nix does not provide.
+#[derive(Copy, Clone)]
+struct IntOption(c_int);
+
+impl GetSockOpt for IntOption {
+    type Val = c_int;
+
+    fn get(&self, fd: RawFd) -> nix::Result<c_int> {
+        let mut value: c_int = 0;
+        let mut len = std::mem::size_of::<c_int>() as libc::socklen_t;
+        // SAFETY: value and len are valid for the kernel to write an int and its size.
+        let ret = unsafe {
+            libc::getsockopt(
+                fd,
+                libc::SOL_SOCKET,
+                self.0,
+                &mut value as *mut c_int as *mut c_void,
+                &mut len,
+            )
+        };
+        Errno::result(ret).map(|_| value)
+    }
+}
+
+/// Returns the inode of the socket of a file descriptor, from the link such as `socket:[1234]`.
+fn socket_inode(pid: i32, fd: RawFd) -> anyhow::Result<u64> {
+    let link = PathBuf::from(format!("/proc/{}/fd/{}", pid, fd));
+    let target = std::fs::read_link(&link).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} has no file descriptor {}", pid, fd),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to read '{}'; the file descriptors of a process of another user require CAP_SYS_PTRACE",
+            link.display()
+        ),
+        _ => anyhow!(err).context(format!("Failed to read '{}'", link.display())),
+    })?;
+    let target = target.to_string_lossy();
+
+    target
+        .strip_prefix("socket:[")
+        .and_then(|inode| inode.strip_suffix(']'))
+        .and_then(|inode| inode.parse().ok())
+        .ok_or_else(|| {
+            anyhow!(
+                "File descriptor {} of process {} is '{}', not a socket",
+                fd,
+                pid,
+                target
+            )
+        })
+}
+
+/// Lists the file descriptors of the sockets of a process.
+fn list_sockets(pid: i32) -> anyhow::Result<Vec<RawFd>> {
+    let dir = PathBuf::from(format!("/proc/{}/fd", pid));
+    let entries = std::fs::read_dir(&dir).map_err(|err| match err.kind() {
+        ErrorKind::NotFound => anyhow!("Process {} does not exist", pid),
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to read '{}'; the file descriptors of a process of another user require CAP_SYS_PTRACE",
+            dir.display()
+        ),
+        _ => anyhow!(err).context(format!("Failed to read '{}'", dir.display())),
+    })?;
+
+    let mut fds = Vec::new();
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read '{}'", dir.display()))?;
+        // A file descriptor closed in the meantime has no link
+        let is_socket = std::fs::read_link(entry.path())
+            .map(|target| target.to_string_lossy().starts_with("socket:"))
+            .unwrap_or(false);
+        if let Some(fd) = entry.file_name().to_str().and_then(|fd| fd.parse().ok()) {
+            if is_socket {
+                fds.push(fd);
+            }
+        }
+    }
+    fds.sort_unstable();
+
+    Ok(fds)
+}
+
+fn domain_name(domain: c_int) -> String {
+    match domain {
+        libc::AF_UNIX => "unix".to_owned(),
+        libc::AF_INET => "inet".to_owned(),
+        libc::AF_INET6 => "inet6".to_owned(),
+        libc::AF_NETLINK => "netlink".to_owned(),
+        libc::AF_PACKET => "packet".to_owned(),
+        libc::AF_VSOCK => "vsock".to_owned(),
+        domain => domain.to_string(),
+    }
+}
+
+fn type_name(socket_type: nix::sys::socket::SockType) -> &'static str {
+    use nix::sys::socket::SockType;
+    match socket_type {
+        SockType::Stream => "stream",
+        SockType::Datagram => "dgram",
+        SockType::SeqPacket => "seqpacket",
+        SockType::Raw => "raw",
+        SockType::Rdm => "rdm",
+        _ => "unknown",
+    }
+}
+
+/// Names the protocols of inet sockets, whose tables in /proc/net have the same names.
+fn protocol_name(domain: c_int, protocol: c_int) -> Option<&'static str> {
+    if domain != libc::AF_INET && domain != libc::AF_INET6 {
+        return None;
+    }
+    match protocol {
+        libc::IPPROTO_TCP => Some("tcp"),
+        libc::IPPROTO_UDP => Some("udp"),
+        libc::IPPROTO_UDPLITE => Some("udplite"),
+        libc::IPPROTO_ICMP | libc::IPPROTO_ICMPV6 => Some("icmp"),
+        libc::IPPROTO_SCTP => Some("sctp"),
+        libc::IPPROTO_RAW => Some("raw"),
+        _ => None,
+    }
+}
+
+/// Parses an address of /proc/net/tcp such as `0100007F:1F90`, whose words are in host order.
+fn parse_address(s: &str) -> Option<SocketAddr> {
+    let (address, port) = s.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut bytes = Vec::with_capacity(16);
+    for i in (0..address.len()).step_by(8) {
+        let word = u32::from_str_radix(address.get(i..i + 8)?, 16).ok()?;
+        bytes.extend_from_slice(&word.to_ne_bytes());
+    }
+    let address = match bytes.len() {
+        4 => Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).into(),
+        16 => Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).into(),
+        _ => return None,
+    };
+
+    Some(SocketAddr::new(address, port))
+}
+
+/// Finds the line of the socket in a table of /proc/PID/net, which is of the network namespace
+/// of the process, and returns its fields.
+fn find_in_table(
+    pid: i32,
+    table: &str,
+    inode: u64,
+    column: usize,
+) -> anyhow::Result<Option<Vec<String>>> {
+    let path = format!("/proc/{}/net/{}", pid, table);
+    let content = match std::fs::read_to_string(&path) {
+        Ok(content) => content,
+        // The protocol is not loaded, such as sctp
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).with_context(|| format!("Failed to read {}", path)),
+    };
+    let inode = inode.to_string();
+
+    // The first line is the header
+    Ok(content.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        (fields.get(column) == Some(&inode.as_str()))
+            .then(|| fields.into_iter().map(str::to_owned).collect())
+    }))
+}
+
+fn print_inet_addresses(pid: i32, domain: c_int, protocol: &str, inode: u64) -> anyhow::Result<()> {
+    let table = if domain == libc::AF_INET6 {
+        format!("{}6", protocol)
+    } else {
+        protocol.to_owned()
+    };
+    // sl local_address rem_address st ... inode
+    let fields = match find_in_table(pid, &table, inode, 9)? {
+        Some(fields) => fields,
+        None => return Ok(()),
+    };
+    let address = |index: usize| {
+        fields
+            .get(index)
+            .and_then(|address| parse_address(address))
+            .map_or_else(|| "unknown".to_owned(), |address| address.to_string())
+    };
+    println!("local_address: {}", address(1));
+    println!("remote_address: {}", address(2));
+    if protocol == "tcp" {
+        let state = fields
+            .get(3)
+            .and_then(|state| usize::from_str_radix(state, 16).ok())
+            .and_then(|state| TCP_STATES.get(state.wrapping_sub(1)))
+            .unwrap_or(&"unknown");
+        println!("state: {}", state);
+    }
+
+    Ok(())
+}
+
+fn print_unix_path(pid: i32, inode: u64) -> anyhow::Result<()> {
+    // Num RefCount Protocol Flags Type St Inode Path
+    if let Some(fields) = find_in_table(pid, "unix", inode, 6)? {
+        // An unnamed socket has no path, and an abstract one starts with @
+        let path = fields.get(7).map_or("none", String::as_str);
+        println!("path: {}", path);
+    }
+
+    Ok(())
+}
+
+fn print_socket(pidfd: &OwnedFd, pid: i32, fd: RawFd) -> anyhow::Result<()> {
+    let inode = socket_inode(pid, fd)?;
+    let socket = pidfd_getfd(pidfd, pid, fd)?;
+    let socket = socket.as_raw_fd();
+    let domain =
+        getsockopt(socket, IntOption(libc::SO_DOMAIN)).context("Failed to get SO_DOMAIN")?;
+    let protocol =
+        getsockopt(socket, IntOption(libc::SO_PROTOCOL)).context("Failed to get SO_PROTOCOL")?;
+    let protocol_name = protocol_name(domain, protocol);
+
+    println!("fd: {}", fd);
+    println!("inode: {}", inode);
+    println!("domain: {}", domain_name(domain));
+    println!(
+        "type: {}",
+        type_name(getsockopt(socket, SockType).context("Failed to get SO_TYPE")?)
+    );
+    match protocol_name {
+        Some(name) => println!("protocol: {}", name),
+        None => println!("protocol: {}", protocol),
+    }
+    println!(
+        "rcvbuf: {}",
+        getsockopt(socket, RcvBuf).context("Failed to get SO_RCVBUF")?
+    );
+    println!(
+        "sndbuf: {}",
+        getsockopt(socket, SndBuf).context("Failed to get SO_SNDBUF")?
+    );
+    println!(
+        "keepalive: {}",
+        getsockopt(socket, KeepAlive).context("Failed to get SO_KEEPALIVE")?
+    );
+    println!(
+        "reuseaddr: {}",
+        getsockopt(socket, ReuseAddr).context("Failed to get SO_REUSEADDR")?
+    );
+    if protocol_name == Some("tcp") {
+        println!(
+            "tcp_nodelay: {}",
+            getsockopt(socket, TcpNoDelay).context("Failed to get TCP_NODELAY")?
+        );
+    }
+
+    match (domain, protocol_name) {
+        (libc::AF_INET | libc::AF_INET6, Some(protocol)) => {
+            print_inet_addresses(pid, domain, protocol, inode)
+        }
+        (libc::AF_UNIX, _) => print_unix_path(pid, inode),
+        _ => Ok(()),
+    }
+}
+
+fn handle_socket(info: SocketInfo) -> anyhow::Result<()> {
+    // clap requires PID unless a subcommand is given
+    let pid = info.pid.unwrap();
+    let pidfd = open_pidfd(pid)?;
+    let fds = if info.fds.is_empty() {
+        list_sockets(pid)?
+    } else {
+        info.fds
+    };
+    if fds.is_empty() {
+        eprintln!("Process {} has no sockets", pid);
+    }
+
+    for (i, &fd) in fds.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_socket(&pidfd, pid, fd)?;
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_socket(cli.socket),
+    }
+}
@@ -0,0 +1,385 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::output::format_duration;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{copy_file_range, fcntl, splice, FcntlArg, OFlag, SpliceFFlags};
+use nix::sys::sendfile::sendfile;
+use nix::sys::uio::pread;
+use nix::unistd::{lseek, pipe2, write, Whence};
+use std::fs::{File, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// Bytes a single sendfile(2) or copy_file_range(2) is asked for, which is their maximum.
+const MAX_COUNT: usize = 0x7fff_f000;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    benchmark: BenchmarkInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct BenchmarkInfo {
+    /// File or block device to copy from
+    #[clap(required = true, value_parser, value_name = "SOURCE")]
+    source: Option<PathBuf>,
+    /// File or device to copy to, which is created if it does not exist
+    #[clap(required = true, value_parser, value_name = "DEST")]
+    destination: Option<PathBuf>,
+    /// Seconds to repeat copying SOURCE with each method
+    #[clap(
+        short,
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        default_value_t = 3,
+        value_name = "N"
+    )]
+    seconds: u64,
+    /// Write SOURCE as a regular file of this size before the benchmark, replacing its content
+    /// (accepts suffixes such as K, MiB, GB)
+    #[clap(long, value_parser = parse_size, value_name = "SIZE")]
+    size: Option<i64>,
+    /// Bytes moved by a single splice or read, which is also the capacity of the pipe (accepts
+    /// suffixes such as K, MiB, GB)
+    #[clap(short, long, value_parser = parse_size, default_value = "64K", value_name = "SIZE")]
+    chunk: i64,
+    /// Methods to measure
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        value_delimiter = ',',
+        default_value = "splice,sendfile,copy-file-range,read-write",
+        value_name = "METHOD,..."
+    )]
+    methods: Vec<Method>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Method {
+    /// splice(2) from SOURCE into a pipe and from the pipe to DEST
+    Splice,
+    /// sendfile(2) from SOURCE to DEST
+    Sendfile,
+    /// copy_file_range(2) from SOURCE to DEST
+    CopyFileRange,
+    /// pread(2) from SOURCE into a buffer and write(2) to DEST
+    ReadWrite,
+}
+
+impl Method {
+    fn name(self) -> &'static str {
+        match self {
+            Method::Splice => "splice",
+            Method::Sendfile => "sendfile",
+            Method::CopyFileRange => "copy_file_range",
+            Method::ReadWrite => "read/write",
+        }
+    }
+}
+
+/// The pipe that splice moves the data through.
+struct Pipe {
+    reader: File,
+    writer: File,
+}
+
+impl Pipe {
+    fn new(size: usize) -> anyhow::Result<Self> {
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC).context("Failed to create a pipe")?;
+        // SAFETY: pipe2 returns new file descriptors owned by nobody else.
+        let (reader, writer) = unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) };
+        let size = i32::try_from(size).unwrap_or(i32::MAX);
+        fcntl(writer.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(size)).map_err(|errno| match errno {
+            Errno::EPERM => anyhow!(
+                "The chunk size {} exceeds /proc/sys/fs/pipe-max-size; raising the size of the \
+                pipe over it requires CAP_SYS_RESOURCE",
+                size
+            ),
+            errno => anyhow!(errno).context("Failed to set the pipe size"),
+        })?;
+
+        Ok(Pipe { reader, writer })
+    }
+}
+
+/// Durations of the copies of SOURCE with a method, or the error that stopped it.
+struct Measurement {
+    method: Method,
+    durations: Vec<Duration>,
+    error: Option<Errno>,
+}
+
+struct Benchmark {
+    source: File,
+    destination: File,
+    /// Whether DEST is rewound before each copy, which a character device or a pipe is not.
+    seekable: bool,
+    len: usize,
+    chunk: usize,
+}
+
+impl Benchmark {
+    /// Copies the whole of SOURCE to the beginning of DEST.
+    fn copy(&self, method: Method, pipe: &Pipe, buffer: &mut [u8]) -> nix::Result<()> {
+        if self.seekable {
+            lseek(self.destination.as_raw_fd(), 0, Whence::SeekSet)?;
+        }
+        let (src, dst) = (self.source.as_raw_fd(), self.destination.as_raw_fd());
+        let flags = SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_MORE;
+        let mut offset = 0;
+        while (offset as usize) < self.len {
+            let remaining = self.len - offset as usize;
+            // Each method advances the offset by the bytes it copied
+            let copied = match method {
+                Method::Splice => {
+                    let len = splice(
+                        src,
+                        Some(&mut offset),
+                        pipe.writer.as_raw_fd(),
+                        None,
+                        remaining.min(self.chunk),
+                        flags,
+                    )?;
+                    // DEST may take less than the pipe has
+                    let mut pending = len;
+                    while pending > 0 {
+                        pending -=
+                            splice(pipe.reader.as_raw_fd(), None, dst, None, pending, flags)?;
+                    }
+                    len
+                }
+                Method::Sendfile => {
+                    sendfile(dst, src, Some(&mut offset), remaining.min(MAX_COUNT))?
+                }
+                Method::CopyFileRange => {
+                    copy_file_range(src, Some(&mut offset), dst, None, remaining.min(MAX_COUNT))?
+                }
+                Method::ReadWrite => {
+                    let len = remaining.min(buffer.len());
+                    let read = pread(src, &mut buffer[..len], offset)?;
+                    let mut written = 0;
+                    while written < read {
+                        written += write(dst, &buffer[written..read])?;
+                    }
+                    offset += read as i64;
+                    read
+                }
+            };
+            // SOURCE is shorter than it was
+            if copied == 0 {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn measure(&self, method: Method, seconds: u64) -> anyhow::Result<Measurement> {
+        let pipe = Pipe::new(self.chunk)?;
+        let mut buffer = vec![0; self.chunk];
+        let mut measurement = Measurement {
+            method,
+            durations: Vec::new(),
+            error: None,
+        };
+
+        let begin = Instant::now();
+        // Copies at least once, and finishes the copy in progress at the end of the period
+        while measurement.durations.is_empty() || begin.elapsed() < Duration::from_secs(seconds) {
+            let start = Instant::now();
+            if let Err(errno) = self.copy(method, &pipe, &mut buffer) {
+                // e.g. copy_file_range does not support a device, or splice a file opened with
+                // O_APPEND
+                measurement.error = Some(errno);
+                break;
+            }
+            measurement.durations.push(start.elapsed());
+        }
+
+        Ok(measurement)
+    }
+}
+
+fn write_source(path: &Path, size: i64, chunk: usize) -> anyhow::Result<()> {
+    if size <= 0 {
+        return Err(anyhow!("SIZE must be larger than 0"));
+    }
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if !metadata.is_file() {
+            return Err(anyhow!(
+                "'{}' is not a regular file, which --size would overwrite",
+                path.display()
+            ));
+        }
+    }
+
+    let mut file =
+        File::create(path).with_context(|| format!("Failed to create '{}'", path.display()))?;
+    // Data that is not zero keeps the file from being stored sparsely or compressed away
+    let pattern: Vec<u8> = (0..chunk).map(|index| (index % 251) as u8 + 1).collect();
+    let mut written = 0;
+    while written < size as u64 {
+        let len = (size as u64 - written).min(chunk as u64) as usize;
+        file.write_all(&pattern[..len])
+            .with_context(|| format!("Failed to write '{}'", path.display()))?;
+        written += len as u64;
+    }
+    file.sync_all()
+        .with_context(|| format!("Failed to sync '{}'", path.display()))
+}
+
+fn open_files(
+    source_path: &Path,
+    destination_path: &Path,
+    chunk: usize,
+) -> anyhow::Result<Benchmark> {
+    let mut source = File::open(source_path)
+        .with_context(|| format!("Failed to open '{}'", source_path.display()))?;
+    // The size of a block device is only known from its end
+    let len = source
+        .seek(SeekFrom::End(0))
+        .with_context(|| format!("Failed to get the size of '{}'", source_path.display()))?;
+    if len == 0 {
+        return Err(anyhow!(
+            "'{}' is empty; give the size to write with --size",
+            source_path.display()
+        ));
+    }
+    let destination = OpenOptions::new()
+        .write(true)
+        .create(true)
+        // Each copy overwrites DEST from its beginning
+        .truncate(false)
+        .open(destination_path)
+        .with_context(|| format!("Failed to open '{}'", destination_path.display()))?;
+
+    let (src_meta, dst_meta) = (
+        source.metadata().context("Failed to stat SOURCE")?,
+        destination.metadata().context("Failed to stat DEST")?,
+    );
+    if (src_meta.dev(), src_meta.ino()) == (dst_meta.dev(), dst_meta.ino()) {
+        return Err(anyhow!("SOURCE and DEST must be different files"));
+    }
+    let seekable = match lseek(destination.as_raw_fd(), 0, Whence::SeekCur) {
+        Ok(_) => true,
+        Err(Errno::ESPIPE) => false,
+        Err(errno) => return Err(errno).context("Failed to seek DEST"),
+    };
+
+    Ok(Benchmark {
+        source,
+        destination,
+        seekable,
+        len: usize::try_from(len).context("SOURCE is too large")?,
+        chunk,
+    })
+}
+
+/// Returns the duration that 99% of the copies finished within.
+fn percentile_99(sorted: &[Duration]) -> Duration {
+    let index = (sorted.len() * 99 + 99) / 100;
+    sorted[index.clamp(1, sorted.len()) - 1]
+}
+
+fn print_table(measurements: &[Measurement], len: usize) {
+    println!(
+        "{:<16} {:>10} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "METHOD", "COPIES", "MB/S", "MEAN", "MIN", "MAX", "P99"
+    );
+    for measurement in measurements {
+        let name = measurement.method.name();
+        let mut sorted = measurement.durations.clone();
+        sorted.sort_unstable();
+        if sorted.is_empty() {
+            // An error stopped the first copy
+            let errno = measurement.error.unwrap_or(Errno::UnknownErrno);
+            println!("{:<16} unsupported: {:?} ({})", name, errno, errno.desc());
+            continue;
+        }
+
+        let total: Duration = sorted.iter().sum();
+        let mean = total / sorted.len() as u32;
+        let throughput =
+            (len * sorted.len()) as f64 / total.as_secs_f64().max(f64::MIN_POSITIVE) / 1e6;
+        println!(
+            "{:<16} {:>10} {:>12.1} {:>12} {:>12} {:>12} {:>12}",
+            name,
+            sorted.len(),
+            throughput,
+            format_duration(mean.as_nanos() as u64),
+            format_duration(sorted[0].as_nanos() as u64),
+            format_duration(sorted[sorted.len() - 1].as_nanos() as u64),
+            format_duration(percentile_99(&sorted).as_nanos() as u64),
+        );
+        if let Some(errno) = measurement.error {
+            eprintln!(
+                "warning: {} stopped after {} copies with {:?}",
+                name,
+                sorted.len(),
+                errno
+            );
+        }
+    }
+}
+
+fn handle_benchmark(info: BenchmarkInfo) -> anyhow::Result<()> {
+    // clap requires SOURCE and DEST unless a subcommand is given
+    let source_path = info.source.unwrap();
+    let destination_path = info.destination.unwrap();
+    let chunk = usize::try_from(info.chunk)
+        .ok()
+        .filter(|&chunk| chunk > 0)
+        .ok_or_else(|| anyhow!("The chunk size must be larger than 0"))?;
+
+    if let Some(size) = info.size {
+        write_source(&source_path, size, chunk)?;
+    }
+    let benchmark = open_files(&source_path, &destination_path, chunk)?;
+
+    println!("source: {}", source_path.display());
+    println!("destination: {}", destination_path.display());
+    println!("size: {}", benchmark.len);
+    println!("chunk: {}", chunk);
+    println!("seconds: {}", info.seconds);
+    println!();
+
+    let mut measurements = Vec::new();
+    for &method in &info.methods {
+        measurements.push(benchmark.measure(method, info.seconds)?);
+    }
+    print_table(&measurements, benchmark.len);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_benchmark(cli.benchmark),
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::copy::{copy_with_sendfile, throughput};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{fcntl, splice, FcntlArg, OFlag, SpliceFFlags};
+use nix::unistd::pipe2;
+use std::fs::{File, OpenOptions};
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::PathBuf;
+use std::time::Instant;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    copy: CopyInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct CopyInfo {
+    /// File to copy from
+    #[clap(required = true, value_parser, value_name = "SOURCE")]
+    source: Option<PathBuf>,
+    /// File to copy to
+    #[clap(required = true, value_parser, value_name = "DEST")]
+    destination: Option<PathBuf>,
+    /// Bytes moved by a single splice, which also sets the size of the pipe (accepts suffixes
+    /// such as K, MiB, GB)
+    #[clap(short = 's', long, default_value = "64K", value_parser = parse_size)]
+    chunk_size: i64,
+    /// Create DEST if it does not exist
+    #[clap(short, long)]
+    create: bool,
+    /// Copy SOURCE again with sendfile(2) and compare the throughput
+    #[clap(long)]
+    compare_sendfile: bool,
+}
+
+/// The intermediate pipe between SOURCE and DEST.
+struct Pipe {
+    reader: File,
+    writer: File,
+}
+
+impl Pipe {
+    fn new() -> nix::Result<Self> {
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        // SAFETY: pipe2 returns new file descriptors owned by nobody else.
+        let (reader, writer) = unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) };
+
+        Ok(Pipe { reader, writer })
+    }
+
+    /// Resizes the pipe to hold at least `size` bytes and returns the new size.
+    fn resize(&self, size: i32) -> anyhow::Result<i32> {
+        match fcntl(self.writer.as_raw_fd(), FcntlArg::F_SETPIPE_SZ(size)) {
+            Ok(size) => Ok(size),
+            Err(Errno::EPERM) => Err(anyhow!(
+                "The chunk size {} exceeds /proc/sys/fs/pipe-max-size; raising the size of the pipe over it \
+                requires CAP_SYS_RESOURCE",
+                size
+            )),
+            Err(errno) => Err(errno).context("Failed to set the pipe size"),
+        }
+    }
+}
+
+/// Copies with a splice(2) from SOURCE into the pipe and another from the pipe into DEST, and
+/// returns the number of bytes copied.
+fn copy_with_splice(
+    source: &File,
+    destination: &File,
+    pipe: &Pipe,
+    count: i64,
+    chunk_size: usize,
+) -> nix::Result<i64> {
+    let flags = SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_MORE;
+    let mut offset = 0;
+    let mut written = 0;
+    while offset < count {
+        let len = usize::try_from(count - offset).map_or(chunk_size, |len| len.min(chunk_size));
+        let spliced = splice(
+            source.as_raw_fd(),
+            Some(&mut offset),
+            pipe.writer.as_raw_fd(),
+            None,
+            len,
+            flags,
+        )?;
+        if spliced == 0 {
+            // SOURCE is shorter than expected
+            break;
+        }
+
+        // DEST may take less than the pipe has
+        let mut pending = spliced;
+        while pending > 0 {
+            pending -= splice(
+                pipe.reader.as_raw_fd(),
+                None,
+                destination.as_raw_fd(),
+                Some(&mut written),
+                pending,
+                flags,
+            )?;
+        }
+    }
+
+    Ok(written)
+}
+
+fn handle_copy(info: CopyInfo) -> anyhow::Result<()> {
+    // clap requires SOURCE and DEST unless a subcommand is given
+    let source_path = info.source.unwrap();
+    let destination_path = info.destination.unwrap();
+    let chunk_size = i32::try_from(info.chunk_size)
+        .ok()
+        .filter(|&size| size > 0)
+        .ok_or_else(|| {
+            anyhow!(
+                "The chunk size {} is out of the range of 1 to {}",
+                info.chunk_size,
+                i32::MAX
+            )
+        })?;
+
+    let source = File::open(&source_path)
+        .with_context(|| format!("Failed to open '{}'", source_path.display()))?;
+    let count = source
+        .metadata()
+        .context("Failed to retrieve metadata of the source")?
+        .len() as i64;
+    let destination = OpenOptions::new()
+        .write(true)
+        .create(info.create)
+        .truncate(true)
+        .open(&destination_path)
+        .with_context(|| format!("Failed to open '{}'", destination_path.display()))?;
+
+    let pipe = Pipe::new().context("Failed to create a pipe")?;
+    // The kernel rounds the size up to a power of two pages
+    let pipe_size = pipe.resize(chunk_size)?;
+
+    eprintln!("source: {}", source_path.display());
+    eprintln!("destination: {}", destination_path.display());
+    eprintln!("count: {}", count);
+    eprintln!("chunk_size: {}", chunk_size);
+    eprintln!("pipe_size: {}", pipe_size);
+
+    let start = Instant::now();
+    let copied = copy_with_splice(&source, &destination, &pipe, count, chunk_size as usize)
+        .map_err(|errno| match errno {
+            Errno::EINVAL => anyhow!(
+                "SOURCE or DEST does not support splice, such as a file opened in append mode"
+            ),
+            errno => anyhow!(errno),
+        })
+        .context("Failed to copy the file with splice")?;
+    let elapsed = start.elapsed();
+    let splice_throughput = throughput(copied, elapsed);
+
+    eprintln!("copied: {}", copied);
+    eprintln!("elapsed: {:.3}s", elapsed.as_secs_f64());
+    eprintln!("throughput: {:.0} bytes/sec", splice_throughput);
+
+    if info.compare_sendfile {
+        destination
+            .set_len(0)
+            .context("Failed to truncate the destination")?;
+        let start = Instant::now();
+        let copied = copy_with_sendfile(&source, &destination, &mut 0, count)
+            .context("Failed to copy the file with sendfile")?;
+        let elapsed = start.elapsed();
+        let sendfile_throughput = throughput(copied, elapsed);
+
+        eprintln!("sendfile_elapsed: {:.3}s", elapsed.as_secs_f64());
+        eprintln!("sendfile_throughput: {:.0} bytes/sec", sendfile_throughput);
+        eprintln!(
+            "splice_to_sendfile: {:.2}",
+            splice_throughput / sendfile_throughput.max(f64::MIN_POSITIVE)
+        );
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_copy(cli.copy),
+    }
+}
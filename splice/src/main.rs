@@ -0,0 +1,194 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{splice, OFlag, SpliceFFlags};
+use nix::sys::sendfile::sendfile;
+use nix::unistd::pipe2;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::PathBuf;
+
+/// Bytes moved by a single splice, which matches the default pipe capacity
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const STDOUT_FD: RawFd = 1;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    splice: SpliceInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct SpliceInfo {
+    /// File to write to stdout
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Offset in FILE to start from (accepts suffixes such as K, MiB, GB)
+    #[clap(short, long, default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Number of bytes to write [default: up to the end of FILE]
+    #[clap(short = 'n', long, value_parser = parse_size)]
+    count: Option<i64>,
+}
+
+/// The intermediate pipe between FILE and stdout.
+struct Pipe {
+    reader: File,
+    writer: File,
+    /// Bytes spliced into the pipe but not yet out of it
+    pending: usize,
+}
+
+impl Pipe {
+    fn new() -> nix::Result<Self> {
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        // SAFETY: pipe2 returns new file descriptors owned by nobody else.
+        let (reader, writer) = unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) };
+
+        Ok(Pipe {
+            reader,
+            writer,
+            pending: 0,
+        })
+    }
+
+    /// Writes the data left in the pipe to stdout with read(2) and write(2).
+    fn drain(&mut self) -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        let mut buffer = vec![0; self.pending];
+        self.reader.read_exact(&mut buffer)?;
+        stdout.write_all(&buffer)?;
+        stdout.flush()?;
+        self.pending = 0;
+
+        Ok(())
+    }
+}
+
+/// Writes with two splice(2) calls, file to pipe and pipe to stdout.
+///
+/// `offset` is advanced as bytes are written so that a fallback can resume from it.
+fn write_with_splice(file: &File, pipe: &mut Pipe, offset: &mut i64, end: i64) -> nix::Result<()> {
+    let flags = SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_MORE;
+    while *offset < end {
+        let len = usize::try_from(end - *offset).map_or(CHUNK_SIZE, |len| len.min(CHUNK_SIZE));
+        let spliced = splice(
+            file.as_raw_fd(),
+            Some(offset),
+            pipe.writer.as_raw_fd(),
+            None,
+            len,
+            flags,
+        )?;
+        if spliced == 0 {
+            // FILE is shorter than expected
+            break;
+        }
+        pipe.pending = spliced;
+
+        while pipe.pending > 0 {
+            let written = splice(
+                pipe.reader.as_raw_fd(),
+                None,
+                STDOUT_FD,
+                None,
+                pipe.pending,
+                flags,
+            )?;
+            pipe.pending -= written;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_with_sendfile(file: &File, offset: &mut i64, end: i64) -> nix::Result<()> {
+    while *offset < end {
+        let len = usize::try_from(end - *offset).unwrap_or(usize::MAX);
+        if sendfile(STDOUT_FD, file.as_raw_fd(), Some(offset), len)? == 0 {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_with_buffer(mut file: &File, offset: i64, end: i64) -> std::io::Result<()> {
+    file.seek(SeekFrom::Start(offset as u64))?;
+    let mut stdout = std::io::stdout().lock();
+    std::io::copy(&mut file.take((end - offset) as u64), &mut stdout)?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+fn handle_splice(info: SpliceInfo) -> anyhow::Result<()> {
+    // clap requires FILE unless a subcommand is given
+    let filename = info.filename.unwrap();
+    let file = File::open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+    let len = file
+        .metadata()
+        .context("Failed to retrieve metadata of the file")?
+        .len() as i64;
+    let count = info.count.unwrap_or(len - info.offset.min(len));
+    let end = info.offset.checked_add(count).ok_or_else(|| {
+        anyhow!(
+            "The range of offset {} and count {} exceeds the maximum file offset",
+            info.offset,
+            count
+        )
+    })?;
+
+    let mut pipe = Pipe::new().context("Failed to create a pipe")?;
+    let mut offset = info.offset;
+    match write_with_splice(&file, &mut pipe, &mut offset, end) {
+        Ok(()) => {}
+        // FILE or stdout does not support splice
+        Err(Errno::EINVAL) => {
+            eprintln!("warning: splice is not supported; falling back to sendfile");
+            pipe.drain().context("Failed to write to stdout")?;
+            match write_with_sendfile(&file, &mut offset, end) {
+                Ok(()) => {}
+                // e.g. stdout is opened in append mode
+                Err(Errno::EINVAL) => {
+                    eprintln!("warning: sendfile is not supported; falling back to buffered copy");
+                    write_with_buffer(&file, offset, end).context("Failed to write to stdout")?;
+                }
+                Err(errno) => return Err(errno).context("Failed to call sendfile"),
+            }
+        }
+        Err(errno) => return Err(errno).context("Failed to call splice"),
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_splice(cli.splice),
+    }
+}
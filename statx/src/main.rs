@@ -0,0 +1,428 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::OutputFormat;
+use linux_tools_core::output::{new_reporter, Reporter};
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use serde_json::{json, Value};
+use std::ffi::CString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+/// Attributes in stx_attributes with their names
+const ATTRIBUTES: &[(&str, u64)] = &[
+    ("compressed", 0x0000_0004),
+    ("immutable", 0x0000_0010),
+    ("append", 0x0000_0020),
+    ("nodump", 0x0000_0040),
+    ("encrypted", 0x0000_0800),
+    ("automount", 0x0000_1000),
+    ("mount_root", 0x0000_2000),
+    ("verity", 0x0010_0000),
+    ("dax", 0x0020_0000),
+];
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    stat: StatInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct StatInfo {
+    /// Files to print the status of
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filenames: Vec<PathBuf>,
+    /// Field groups to request
+    #[clap(
+        short,
+        long,
+        arg_enum,
+        use_value_delimiter = true,
+        default_value = "all"
+    )]
+    mask: Vec<Field>,
+    /// Follow symbolic links
+    #[clap(short = 'L', long)]
+    dereference: bool,
+    /// Format of the result
+    #[clap(short = 'O', long, arg_enum, default_value_t = OutputFormat::Text)]
+    output_format: OutputFormat,
+    /// Print the result in JSON, the same as --output-format json
+    #[clap(short, long, conflicts_with = "output-format")]
+    json: bool,
+}
+
+impl StatInfo {
+    fn format(&self) -> OutputFormat {
+        if self.json {
+            OutputFormat::Json
+        } else {
+            self.output_format
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Field {
+    Type,
+    Mode,
+    Nlink,
+    Uid,
+    Gid,
+    Atime,
+    Mtime,
+    Ctime,
+    Ino,
+    Size,
+    Blocks,
+    /// All the fields of stat(2)
+    Basic,
+    Btime,
+    MntId,
+    Dioalign,
+    /// All the fields above
+    All,
+}
+
+impl Field {
+    fn raw(self) -> u32 {
+        match self {
+            Field::Type => 0x0001,
+            Field::Mode => 0x0002,
+            Field::Nlink => 0x0004,
+            Field::Uid => 0x0008,
+            Field::Gid => 0x0010,
+            Field::Atime => 0x0020,
+            Field::Mtime => 0x0040,
+            Field::Ctime => 0x0080,
+            Field::Ino => 0x0100,
+            Field::Size => 0x0200,
+            Field::Blocks => 0x0400,
+            Field::Basic => 0x07ff,
+            Field::Btime => 0x0800,
+            Field::MntId => 0x1000,
+            Field::Dioalign => 0x2000,
+            Field::All => 0x3fff,
+        }
+    }
+}
+
+/// struct statx_timestamp of statx(2)
+#[repr(C)]
+#[derive(Default, Copy, Clone)]
+struct StatxTimestamp {
+    tv_sec: i64,
+    tv_nsec: u32,
+    reserved: i32,
+}
+
+/// struct statx of statx(2), which is defined here since older libc lacks the newer fields
+#[repr(C)]
+#[derive(Default)]
+struct Statx {
+    stx_mask: u32,
+    stx_blksize: u32,
+    stx_attributes: u64,
+    stx_nlink: u32,
+    stx_uid: u32,
+    stx_gid: u32,
+    stx_mode: u16,
+    spare0: u16,
+    stx_ino: u64,
+    stx_size: u64,
+    stx_blocks: u64,
+    stx_attributes_mask: u64,
+    stx_atime: StatxTimestamp,
+    stx_btime: StatxTimestamp,
+    stx_ctime: StatxTimestamp,
+    stx_mtime: StatxTimestamp,
+    stx_rdev_major: u32,
+    stx_rdev_minor: u32,
+    stx_dev_major: u32,
+    stx_dev_minor: u32,
+    stx_mnt_id: u64,
+    stx_dio_mem_align: u32,
+    stx_dio_offset_align: u32,
+    spare3: [u64; 12],
+}
+
+struct Timestamp {
+    sec: i64,
+    nsec: u32,
+}
+
+impl std::fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Converts days since the epoch to the civil date in UTC
+        let days = self.sec.div_euclid(86400);
+        let secs = self.sec.rem_euclid(86400);
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = doy - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = yoe + era * 400 + i64::from(month <= 2);
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}.{:09} UTC",
+            year,
+            month,
+            day,
+            secs / 3600,
+            secs / 60 % 60,
+            secs % 60,
+            self.nsec
+        )
+    }
+}
+
+/// The status of a file, where `None` is a field the kernel did not return.
+struct StatxReport {
+    filename: String,
+    mask: u32,
+    file_type: Option<&'static str>,
+    mode: Option<String>,
+    nlink: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    ino: Option<u64>,
+    size: Option<u64>,
+    blocks: Option<u64>,
+    blksize: u32,
+    atime: Option<Timestamp>,
+    btime: Option<Timestamp>,
+    ctime: Option<Timestamp>,
+    mtime: Option<Timestamp>,
+    dev: String,
+    rdev: String,
+    attributes: Vec<&'static str>,
+    attributes_mask: Vec<&'static str>,
+    mnt_id: Option<u64>,
+    dio_mem_align: Option<u32>,
+    dio_offset_align: Option<u32>,
+}
+
+impl StatxReport {
+    fn new(filename: &Path, stx: &Statx, requested: u32) -> Self {
+        // The kernel may return more fields than requested
+        let mask = stx.stx_mask & requested;
+        let has = |field: Field| mask & field.raw() != 0;
+        let timestamp = |field: Field, ts: &StatxTimestamp| {
+            has(field).then_some(Timestamp {
+                sec: ts.tv_sec,
+                nsec: ts.tv_nsec,
+            })
+        };
+
+        StatxReport {
+            filename: filename.display().to_string(),
+            mask,
+            file_type: has(Field::Type).then_some(file_type(stx.stx_mode)),
+            mode: has(Field::Mode).then(|| format!("{:04o}", stx.stx_mode & 0o7777)),
+            nlink: has(Field::Nlink).then_some(stx.stx_nlink),
+            uid: has(Field::Uid).then_some(stx.stx_uid),
+            gid: has(Field::Gid).then_some(stx.stx_gid),
+            ino: has(Field::Ino).then_some(stx.stx_ino),
+            size: has(Field::Size).then_some(stx.stx_size),
+            blocks: has(Field::Blocks).then_some(stx.stx_blocks),
+            blksize: stx.stx_blksize,
+            atime: timestamp(Field::Atime, &stx.stx_atime),
+            btime: timestamp(Field::Btime, &stx.stx_btime),
+            ctime: timestamp(Field::Ctime, &stx.stx_ctime),
+            mtime: timestamp(Field::Mtime, &stx.stx_mtime),
+            dev: format!("{}:{}", stx.stx_dev_major, stx.stx_dev_minor),
+            rdev: format!("{}:{}", stx.stx_rdev_major, stx.stx_rdev_minor),
+            attributes: attribute_names(stx.stx_attributes & stx.stx_attributes_mask),
+            attributes_mask: attribute_names(stx.stx_attributes_mask),
+            mnt_id: has(Field::MntId).then_some(stx.stx_mnt_id),
+            dio_mem_align: has(Field::Dioalign).then_some(stx.stx_dio_mem_align),
+            dio_offset_align: has(Field::Dioalign).then_some(stx.stx_dio_offset_align),
+        }
+    }
+
+    /// Reports the fields, where a field the kernel did not return is `none`, or `null` in JSON.
+    fn report(&self, reporter: &mut dyn Reporter) {
+        fn report_option<T: std::fmt::Display>(
+            reporter: &mut dyn Reporter,
+            key: &str,
+            value: &Option<T>,
+            json: impl FnOnce(&T) -> Value,
+        ) {
+            match value {
+                Some(value) => reporter.report_value(key, value, json(value)),
+                None => reporter.report_value(key, &"none", Value::Null),
+            }
+        }
+        fn report_names(reporter: &mut dyn Reporter, key: &str, names: &[&str]) {
+            let text = if names.is_empty() {
+                "none".to_owned()
+            } else {
+                names.join(" | ")
+            };
+            reporter.report_value(key, &text, json!(names));
+        }
+        let timestamp = |ts: &Timestamp| json!({"sec": ts.sec, "nsec": ts.nsec});
+
+        reporter.report_str("filename", &self.filename);
+        reporter.report_value("mask", &format!("{:#06x}", self.mask), json!(self.mask));
+        report_option(reporter, "type", &self.file_type, |t| json!(t));
+        report_option(reporter, "mode", &self.mode, |mode| json!(mode));
+        report_option(reporter, "nlink", &self.nlink, |n| json!(n));
+        report_option(reporter, "uid", &self.uid, |uid| json!(uid));
+        report_option(reporter, "gid", &self.gid, |gid| json!(gid));
+        report_option(reporter, "ino", &self.ino, |ino| json!(ino));
+        report_option(reporter, "size", &self.size, |size| json!(size));
+        report_option(reporter, "blocks", &self.blocks, |blocks| json!(blocks));
+        reporter.report_value("blksize", &self.blksize, json!(self.blksize));
+        report_option(reporter, "atime", &self.atime, timestamp);
+        report_option(reporter, "btime", &self.btime, timestamp);
+        report_option(reporter, "ctime", &self.ctime, timestamp);
+        report_option(reporter, "mtime", &self.mtime, timestamp);
+        reporter.report_str("dev", &self.dev);
+        reporter.report_str("rdev", &self.rdev);
+        report_names(reporter, "attributes", &self.attributes);
+        report_names(reporter, "attributes_mask", &self.attributes_mask);
+        report_option(reporter, "mnt_id", &self.mnt_id, |id| json!(id));
+        report_option(reporter, "dio_mem_align", &self.dio_mem_align, |a| json!(a));
+        report_option(reporter, "dio_offset_align", &self.dio_offset_align, |a| {
+            json!(a)
+        });
+    }
+}
+
+/// Prints the reports as `key: value` lines separated by an empty line, or in JSON as an object
+/// for a single file and as an array for several files.
+fn print_reports(reports: &[StatxReport], format: OutputFormat, array: bool) -> anyhow::Result<()> {
+    let mut stdout = std::io::stdout();
+    if format == OutputFormat::Text {
+        for (i, report) in reports.iter().enumerate() {
+            if i > 0 {
+                writeln!(stdout).context("Failed to write the result")?;
+            }
+            let mut reporter = new_reporter(format, &mut stdout);
+            report.report(&mut *reporter);
+            reporter.finish()?;
+        }
+        return Ok(());
+    }
+
+    // Each reporter prints an object in a line, which are joined into an array for several files
+    let mut objects = Vec::new();
+    for report in reports {
+        let mut object = Vec::new();
+        let mut reporter = new_reporter(format, &mut object);
+        report.report(&mut *reporter);
+        reporter.finish()?;
+        drop(reporter);
+        objects.push(String::from_utf8_lossy(&object).trim_end().to_owned());
+    }
+    let json = match objects.as_slice() {
+        [object] if !array => object.clone(),
+        _ => format!("[{}]", objects.join(",")),
+    };
+    writeln!(stdout, "{}", json).context("Failed to write the result")
+}
+
+fn file_type(mode: u16) -> &'static str {
+    match u32::from(mode) & libc::S_IFMT {
+        libc::S_IFREG => "regular file",
+        libc::S_IFDIR => "directory",
+        libc::S_IFLNK => "symbolic link",
+        libc::S_IFCHR => "character device",
+        libc::S_IFBLK => "block device",
+        libc::S_IFIFO => "fifo",
+        libc::S_IFSOCK => "socket",
+        _ => "unknown",
+    }
+}
+
+fn attribute_names(attributes: u64) -> Vec<&'static str> {
+    ATTRIBUTES
+        .iter()
+        .filter(|&&(_, attribute)| attributes & attribute != 0)
+        .map(|&(name, _)| name)
+        .collect()
+}
+
+fn statx(filename: &Path, mask: u32, dereference: bool) -> anyhow::Result<Statx> {
+    let path = CString::new(filename.as_os_str().as_bytes())
+        .with_context(|| format!("'{}' contains a NUL byte", filename.display()))?;
+    let flags = if dereference {
+        0
+    } else {
+        libc::AT_SYMLINK_NOFOLLOW
+    };
+    let mut stx = Statx::default();
+    // SAFETY: path is a NUL-terminated string, and stx is a valid statx,
+    // which is as large as the kernel writes into.
+    let ret = unsafe {
+        libc::syscall(
+            libc::SYS_statx,
+            libc::AT_FDCWD,
+            path.as_ptr(),
+            flags,
+            mask,
+            &mut stx as *mut Statx,
+        )
+    };
+    Errno::result(ret).map_err(|errno| match errno {
+        Errno::ENOSYS => anyhow!("statx(2) requires Linux 4.11 or later"),
+        errno => anyhow!(errno),
+    })?;
+
+    Ok(stx)
+}
+
+fn handle_stat(info: StatInfo) -> anyhow::Result<ExitCode> {
+    let mask = info.mask.iter().fold(0, |mask, field| mask | field.raw());
+
+    let mut reports = Vec::new();
+    let mut failures = 0usize;
+    for filename in &info.filenames {
+        match statx(filename, mask, info.dereference) {
+            Ok(stx) => reports.push(StatxReport::new(filename, &stx, mask)),
+            Err(err) => {
+                eprintln!("statx: {}: {:#}", filename.display(), err);
+                failures += 1;
+            }
+        }
+    }
+
+    print_reports(&reports, info.format(), info.filenames.len() > 1)?;
+
+    Ok(ExitCode::from(failures.min(125) as u8))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_stat(cli.stat),
+    }
+}
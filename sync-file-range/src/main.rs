@@ -0,0 +1,124 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::AsRawFd;
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    sync: SyncInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct SyncInfo {
+    /// Filename a range is synchronized
+    #[clap(required = true, value_parser, value_name = "FILE")]
+    filename: Option<PathBuf>,
+    /// Offset of a range to synchronize (accepts suffixes such as K, MiB, GB)
+    #[clap(default_value_t = 0, value_parser = parse_size)]
+    offset: i64,
+    /// Length of a range to synchronize (accepts suffixes such as K, MiB, GB) [default: 0, up to the end of FILE]
+    #[clap(default_value_t = 0, value_parser = parse_size, hide_default_value = true)]
+    len: i64,
+    /// Operations to perform (can be specified multiple times)
+    #[clap(
+        long,
+        short,
+        arg_enum,
+        use_value_delimiter = true,
+        default_value = "write"
+    )]
+    flags: Vec<SyncFlag>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum SyncFlag {
+    WaitBefore,
+    Write,
+    WaitAfter,
+}
+
+impl std::fmt::Display for SyncFlag {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            SyncFlag::WaitBefore => write!(f, "SYNC_FILE_RANGE_WAIT_BEFORE"),
+            SyncFlag::Write => write!(f, "SYNC_FILE_RANGE_WRITE"),
+            SyncFlag::WaitAfter => write!(f, "SYNC_FILE_RANGE_WAIT_AFTER"),
+        }
+    }
+}
+
+impl SyncFlag {
+    fn bits(self) -> u32 {
+        match self {
+            SyncFlag::WaitBefore => libc::SYNC_FILE_RANGE_WAIT_BEFORE,
+            SyncFlag::Write => libc::SYNC_FILE_RANGE_WRITE,
+            SyncFlag::WaitAfter => libc::SYNC_FILE_RANGE_WAIT_AFTER,
+        }
+    }
+}
+
+fn handle_sync(info: SyncInfo) -> anyhow::Result<()> {
+    // clap requires FILE unless a subcommand is given
+    let filename = info.filename.unwrap();
+    let offset = info.offset;
+    let len = info.len;
+    let mut flags = info.flags;
+    flags.sort();
+    flags.dedup();
+
+    // O_NONBLOCK keeps opening a FIFO from blocking so that ESPIPE is reported
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&filename)
+        .with_context(|| format!("Failed to open '{}'", filename.display()))?;
+
+    let flag_names: Vec<String> = flags.iter().map(|flag| flag.to_string()).collect();
+    eprintln!("filename: {}", filename.display());
+    eprintln!("flags: {}", flag_names.join(" | "));
+    eprintln!("offset: {}", offset);
+    eprintln!("len: {}", len);
+
+    let bits = flags.iter().fold(0, |bits, &flag| bits | flag.bits());
+    // SAFETY: sync_file_range only reads its integer arguments.
+    let ret = unsafe { libc::sync_file_range(file.as_raw_fd(), offset, len, bits) };
+    match Errno::result(ret) {
+        Ok(_) => Ok(()),
+        Err(Errno::ESPIPE) => Err(anyhow!(
+            "'{}' is a pipe or socket; sync_file_range(2) only works on regular files and block devices",
+            filename.display()
+        )),
+        Err(errno) => Err(errno).context("Failed to call sync_file_range"),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_sync(cli.sync),
+    }
+}
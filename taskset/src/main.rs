@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Context};
+use clap::{Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_cpu_list;
+use linux_tools_core::print_completer;
+use nix::sched::{sched_getaffinity, sched_setaffinity, CpuSet};
+use nix::unistd::Pid;
+
+const ONLINE_CPUS_PATH: &str = "/sys/devices/system/cpu/online";
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the CPU affinity of a process
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        /// Target process [default: this process]
+        #[clap(value_parser)]
+        pid: Option<i32>,
+    },
+    /// Set the CPU affinity of a process
+    #[clap(display_order = 2, name = "set")]
+    Set {
+        /// Target process
+        #[clap(value_parser)]
+        pid: i32,
+        /// CPUs the process is allowed to run on (e.g. 0-3,5,7)
+        #[clap(value_parser = parse_cpus, value_name = "CPU_LIST")]
+        cpus: CpuList,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 3, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+/// Sorted CPU indices without duplicates.
+#[derive(Clone)]
+struct CpuList(Vec<usize>);
+
+/// Parses a CPU list argument such as `0-3,5,7`.
+fn parse_cpus(s: &str) -> Result<CpuList, String> {
+    parse_cpu_list(s).map(CpuList)
+}
+
+/// Formats sorted CPU indices as a CPU list such as `0-3,5,7`.
+fn format_cpu_list(cpus: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &cpu in cpus {
+        match ranges.last_mut() {
+            Some((_, last)) if *last + 1 == cpu => *last = cpu,
+            _ => ranges.push((cpu, cpu)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|&(first, last)| {
+            if first == last {
+                first.to_string()
+            } else {
+                format!("{}-{}", first, last)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Formats CPU indices as a hex mask such as `af`.
+fn format_cpu_mask(cpus: &[usize]) -> String {
+    let max = match cpus.iter().max() {
+        Some(&max) => max,
+        None => return "0".to_owned(),
+    };
+
+    let mut nibbles = vec![0u32; max / 4 + 1];
+    for &cpu in cpus {
+        nibbles[cpu / 4] |= 1 << (cpu % 4);
+    }
+    nibbles
+        .iter()
+        .rev()
+        .map(|&nibble| std::char::from_digit(nibble, 16).unwrap())
+        .collect()
+}
+
+fn read_online_cpus() -> anyhow::Result<CpuList> {
+    let online = std::fs::read_to_string(ONLINE_CPUS_PATH)
+        .with_context(|| format!("Failed to read {}", ONLINE_CPUS_PATH))?;
+    parse_cpus(&online)
+        .map_err(|err| anyhow!(err))
+        .with_context(|| format!("Unexpected content in {}", ONLINE_CPUS_PATH))
+}
+
+fn handle_get(pid: Option<i32>) -> anyhow::Result<()> {
+    // 0 means the calling process
+    let cpu_set = sched_getaffinity(Pid::from_raw(pid.unwrap_or(0)))
+        .context("Failed to get the CPU affinity")?;
+    let cpus: Vec<usize> = (0..CpuSet::count())
+        .filter(|&cpu| cpu_set.is_set(cpu).unwrap_or(false))
+        .collect();
+
+    println!("list: {}", format_cpu_list(&cpus));
+    println!("mask: {}", format_cpu_mask(&cpus));
+
+    Ok(())
+}
+
+fn handle_set(pid: i32, cpus: CpuList) -> anyhow::Result<()> {
+    let CpuList(online) = read_online_cpus()?;
+    let CpuList(cpus) = cpus;
+    let offline: Vec<usize> = cpus
+        .iter()
+        .copied()
+        .filter(|cpu| !online.contains(cpu))
+        .collect();
+    if !offline.is_empty() {
+        return Err(anyhow!(
+            "CPU {} is not online; online CPUs are {}",
+            format_cpu_list(&offline),
+            format_cpu_list(&online)
+        ));
+    }
+
+    let mut cpu_set = CpuSet::new();
+    for &cpu in &cpus {
+        cpu_set
+            .set(cpu)
+            .with_context(|| format!("CPU {} exceeds the supported number of CPUs", cpu))?;
+    }
+    sched_setaffinity(Pid::from_raw(pid), &cpu_set)
+        .with_context(|| format!("Failed to set the CPU affinity of process {}", pid))?;
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { pid } => handle_get(pid),
+        Commands::Set { pid, cpus } => handle_set(pid, cpus),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cpus(s: &str) -> Vec<usize> {
+        parse_cpu_list(s).unwrap()
+    }
+
+    #[test]
+    fn cpu_list_round_trips() {
+        assert_eq!(format_cpu_list(&cpus("0-3,5,7-8")), "0-3,5,7-8");
+        assert_eq!(format_cpu_list(&[]), "");
+    }
+
+    #[test]
+    fn cpu_mask_has_a_bit_per_cpu() {
+        assert_eq!(format_cpu_mask(&cpus("0-3,5,7")), "af");
+        assert_eq!(format_cpu_mask(&cpus("8")), "100");
+        assert_eq!(format_cpu_mask(&[]), "0");
+    }
+}
@@ -0,0 +1,297 @@
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{splice, tee, OFlag, SpliceFFlags};
+use nix::sys::stat::{fstat, SFlag};
+use nix::unistd::{dup, pipe2};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+use std::path::PathBuf;
+
+/// Bytes duplicated by a single round, which matches the default pipe capacity
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const STDIN_FD: RawFd = 0;
+const STDOUT_FD: RawFd = 1;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    tee: TeeInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct TeeInfo {
+    /// Files or FIFOs to write stdin to in addition to stdout
+    #[clap(value_parser, value_name = "FILE")]
+    outputs: Vec<PathBuf>,
+    /// Append to FILE instead of truncating it
+    #[clap(short, long)]
+    append: bool,
+}
+
+/// A pipe of this process.
+struct Pipe {
+    reader: File,
+    writer: File,
+}
+
+impl Pipe {
+    fn new() -> nix::Result<Self> {
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        // SAFETY: pipe2 returns new file descriptors owned by nobody else.
+        let (reader, writer) = unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) };
+
+        Ok(Pipe { reader, writer })
+    }
+}
+
+/// How the data reaches an output.
+enum Sink {
+    /// The output is a pipe, which tee(2) duplicates the data into directly
+    Tee,
+    /// The data is duplicated into an intermediate pipe and spliced from it into the output
+    Splice(Pipe),
+    /// The output does not support splice(2), so the data is read out of the intermediate pipe
+    /// and written to it
+    Write(Pipe),
+}
+
+struct Output {
+    name: String,
+    file: File,
+    sink: Sink,
+}
+
+impl Output {
+    fn new(name: String, file: File) -> anyhow::Result<Self> {
+        let sink =
+            if is_pipe(file.as_raw_fd()).with_context(|| format!("Failed to stat {}", name))? {
+                Sink::Tee
+            } else {
+                Sink::Splice(Pipe::new().context("Failed to create a pipe")?)
+            };
+
+        Ok(Output { name, file, sink })
+    }
+
+    /// Duplicates up to `len` bytes at the head of `source` into the output without consuming
+    /// them, and returns the number of bytes duplicated.
+    fn duplicate(&mut self, source: &Pipe, len: usize) -> anyhow::Result<usize> {
+        let target = match &self.sink {
+            Sink::Tee => self.file.as_raw_fd(),
+            Sink::Splice(pipe) | Sink::Write(pipe) => pipe.writer.as_raw_fd(),
+        };
+        let duplicated = tee(
+            source.reader.as_raw_fd(),
+            target,
+            len,
+            SpliceFFlags::empty(),
+        )
+        .with_context(|| format!("Failed to call tee for {}", self.name))?;
+        if !matches!(self.sink, Sink::Tee) {
+            self.flush(duplicated)?;
+        }
+
+        Ok(duplicated)
+    }
+
+    /// Moves `pending` bytes out of the intermediate pipe into the output.
+    fn flush(&mut self, mut pending: usize) -> anyhow::Result<()> {
+        if let Sink::Splice(pipe) = &self.sink {
+            while pending > 0 {
+                match splice(
+                    pipe.reader.as_raw_fd(),
+                    None,
+                    self.file.as_raw_fd(),
+                    None,
+                    pending,
+                    SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_MORE,
+                ) {
+                    Ok(written) => pending -= written,
+                    // e.g. a file opened in append mode or a terminal
+                    Err(Errno::EINVAL) => {
+                        eprintln!(
+                            "warning: splice is not supported for {}; falling back to read and write",
+                            self.name
+                        );
+                        let pipe = match std::mem::replace(&mut self.sink, Sink::Tee) {
+                            Sink::Splice(pipe) => pipe,
+                            _ => unreachable!(),
+                        };
+                        self.sink = Sink::Write(pipe);
+                        break;
+                    }
+                    Err(errno) => {
+                        return Err(errno)
+                            .with_context(|| format!("Failed to call splice for {}", self.name))
+                    }
+                }
+            }
+        }
+        if let Sink::Write(pipe) = &mut self.sink {
+            let mut buffer = vec![0; pending];
+            pipe.reader
+                .read_exact(&mut buffer)
+                .context("Failed to read the intermediate pipe")?;
+            self.write(&buffer)?;
+        }
+
+        Ok(())
+    }
+
+    fn write(&mut self, data: &[u8]) -> anyhow::Result<()> {
+        self.file
+            .write_all(data)
+            .and_then(|_| self.file.flush())
+            .with_context(|| format!("Failed to write to {}", self.name))
+    }
+}
+
+fn is_pipe(fd: RawFd) -> nix::Result<bool> {
+    let stat = fstat(fd)?;
+    Ok(SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFIFO)
+}
+
+/// Duplicates stdin to the outputs with tee(2) and splice(2) and returns the bytes duplicated,
+/// or None when stdin does not support splice(2) before anything is read from it.
+fn tee_with_splice(outputs: &mut [Output]) -> anyhow::Result<Option<u64>> {
+    // stdin is moved into a pipe of this process first, so that it is consumed only after it is
+    // duplicated to every output, and so that it does not need to be a pipe
+    let source = Pipe::new().context("Failed to create a pipe")?;
+    let null = File::options()
+        .write(true)
+        .open("/dev/null")
+        .context("Failed to open /dev/null")?;
+
+    let mut total = 0;
+    loop {
+        let len = match splice(
+            STDIN_FD,
+            None,
+            source.writer.as_raw_fd(),
+            None,
+            CHUNK_SIZE,
+            SpliceFFlags::SPLICE_F_MOVE,
+        ) {
+            Ok(0) => break,
+            Ok(len) => len,
+            // e.g. stdin is a terminal
+            Err(Errno::EINVAL) if total == 0 => return Ok(None),
+            Err(errno) => return Err(errno).context("Failed to call splice for stdin"),
+        };
+
+        let mut shortages = Vec::new();
+        for (i, output) in outputs.iter_mut().enumerate() {
+            let duplicated = output.duplicate(&source, len)?;
+            if duplicated < len {
+                shortages.push((i, duplicated));
+            }
+        }
+
+        if shortages.is_empty() {
+            // The data has reached every output, so it is discarded without copying
+            let mut pending = len;
+            while pending > 0 {
+                pending -= splice(
+                    source.reader.as_raw_fd(),
+                    None,
+                    null.as_raw_fd(),
+                    None,
+                    pending,
+                    SpliceFFlags::SPLICE_F_MOVE,
+                )
+                .context("Failed to call splice for /dev/null")?;
+            }
+        } else {
+            // tee(2) always duplicates from the head of the pipe, so the rest for an output pipe
+            // that was nearly full is written from a copy
+            let mut buffer = vec![0; len];
+            (&source.reader)
+                .read_exact(&mut buffer)
+                .context("Failed to read the intermediate pipe")?;
+            for (i, duplicated) in shortages {
+                outputs[i].write(&buffer[duplicated..])?;
+            }
+        }
+        total += len as u64;
+    }
+
+    Ok(Some(total))
+}
+
+fn tee_with_buffer(outputs: &mut [Output]) -> anyhow::Result<u64> {
+    let mut stdin = std::io::stdin().lock();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut total = 0;
+    loop {
+        let len = match stdin.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(len) => len,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err).context("Failed to read stdin"),
+        };
+        for output in outputs.iter_mut() {
+            output.write(&buffer[..len])?;
+        }
+        total += len as u64;
+    }
+
+    Ok(total)
+}
+
+fn handle_tee(info: TeeInfo) -> anyhow::Result<()> {
+    // stdout is duplicated so that it is owned like the other outputs
+    let stdout = dup(STDOUT_FD).context("Failed to duplicate stdout")?;
+    // SAFETY: dup returns a new file descriptor owned by nobody else.
+    let stdout = unsafe { File::from_raw_fd(stdout) };
+    let mut outputs = vec![Output::new("stdout".to_owned(), stdout)?];
+    for path in &info.outputs {
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .append(info.append)
+            .truncate(!info.append)
+            .open(path)
+            .with_context(|| format!("Failed to open '{}'", path.display()))?;
+        outputs.push(Output::new(format!("'{}'", path.display()), file)?);
+    }
+
+    let total = match tee_with_splice(&mut outputs)? {
+        Some(total) => total,
+        None => {
+            eprintln!("warning: splice is not supported for stdin; falling back to read and write");
+            tee_with_buffer(&mut outputs)?
+        }
+    };
+    eprintln!("bytes: {}", total);
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_tee(cli.tee),
+    }
+}
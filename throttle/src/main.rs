@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Context};
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::cgroup::{cgroup_dir, read_mounts, Membership};
+use linux_tools_core::print_completer;
+use nix::sys::stat::{major, minor, stat, SFlag};
+use std::fmt::Display;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    throttle: ThrottleInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+#[clap(group(
+    ArgGroup::new("limits")
+        .required(true)
+        .multiple(true)
+        .args(&["read-bps", "write-bps", "read-iops", "write-iops"])
+))]
+struct ThrottleInfo {
+    /// Process whose cgroup is throttled
+    #[clap(required = true, value_parser, value_name = "PID")]
+    pid: Option<i32>,
+    /// Block device, or any file on the device, to throttle
+    #[clap(required = true, value_parser, value_name = "PATH")]
+    path: Option<PathBuf>,
+    /// Bytes read per second (accepts suffixes such as K, MiB, GB, or 'max' for no limit)
+    #[clap(long, value_parser = parse_bps, value_name = "BYTES")]
+    read_bps: Option<Limit>,
+    /// Bytes written per second (accepts suffixes such as K, MiB, GB, or 'max' for no limit)
+    #[clap(long, value_parser = parse_bps, value_name = "BYTES")]
+    write_bps: Option<Limit>,
+    /// Read operations per second ('max' for no limit)
+    #[clap(long, value_parser = parse_iops, value_name = "IOPS")]
+    read_iops: Option<Limit>,
+    /// Write operations per second ('max' for no limit)
+    #[clap(long, value_parser = parse_iops, value_name = "IOPS")]
+    write_iops: Option<Limit>,
+}
+
+/// A limit of io.max, where None is `max`, which removes the limit.
+#[derive(Copy, Clone)]
+struct Limit(Option<u64>);
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0 {
+            Some(limit) => write!(f, "{}", limit),
+            None => write!(f, "max"),
+        }
+    }
+}
+
+fn parse_bps(s: &str) -> Result<Limit, String> {
+    if s == "max" {
+        return Ok(Limit(None));
+    }
+    let bytes = parse_size(s)?;
+    match u64::try_from(bytes) {
+        Ok(bytes) if bytes > 0 => Ok(Limit(Some(bytes))),
+        _ => Err(format!("'{}' is not a positive number of bytes", s)),
+    }
+}
+
+fn parse_iops(s: &str) -> Result<Limit, String> {
+    if s == "max" {
+        return Ok(Limit(None));
+    }
+    match s.parse() {
+        Ok(iops) if iops > 0 => Ok(Limit(Some(iops))),
+        _ => Err(format!("'{}' is not a positive number of operations", s)),
+    }
+}
+
+/// Returns the major and minor numbers of the whole disk of a block device or the device of
+/// the filesystem a file is on, since io.max rejects partitions.
+fn disk_of(path: &Path) -> anyhow::Result<(u64, u64)> {
+    let stat = stat(path).with_context(|| format!("Failed to stat '{}'", path.display()))?;
+    let device = if SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFBLK {
+        stat.st_rdev
+    } else {
+        stat.st_dev
+    };
+    let (major, minor) = (major(device), minor(device));
+
+    let sysfs = PathBuf::from(format!("/sys/dev/block/{}:{}", major, minor));
+    if !sysfs.exists() {
+        return Err(anyhow!(
+            "'{}' is not on a block device; the device {}:{} is of a filesystem such as tmpfs, overlay or NFS",
+            path.display(),
+            major,
+            minor
+        ));
+    }
+    // The directory of a partition is in the directory of its disk
+    if !sysfs.join("partition").exists() {
+        return Ok((major, minor));
+    }
+    let dev = std::fs::read_to_string(sysfs.join("../dev")).with_context(|| {
+        format!(
+            "Failed to read the disk of the partition {}:{}",
+            major, minor
+        )
+    })?;
+    let (major, minor) = dev
+        .trim()
+        .split_once(':')
+        .and_then(|(major, minor)| Some((major.parse().ok()?, minor.parse().ok()?)))
+        .ok_or_else(|| anyhow!("Unexpected device number of the disk: {}", dev.trim()))?;
+
+    Ok((major, minor))
+}
+
+/// Finds the directory of the cgroup v2 of a process and checks that it has the io controller.
+fn io_cgroup_dir(pid: i32) -> anyhow::Result<PathBuf> {
+    let membership = Membership::read(pid)?;
+    let cgroup = membership.unified.as_deref().ok_or_else(|| {
+        anyhow!(
+            "Process {} is not in any cgroup v2; io.max requires cgroup v2",
+            pid
+        )
+    })?;
+    let mounts = read_mounts()?;
+    let dir = cgroup_dir(&mounts, None, cgroup)
+        .ok_or_else(|| anyhow!("The cgroup v2 filesystem of '{}' is not mounted", cgroup))?;
+
+    let controllers_path = dir.join("cgroup.controllers");
+    let controllers = std::fs::read_to_string(&controllers_path)
+        .with_context(|| format!("Failed to read {}", controllers_path.display()))?;
+    if !controllers.split_whitespace().any(|name| name == "io") {
+        if membership.hierarchy("blkio").is_some() {
+            return Err(anyhow!(
+                "The io controller of '{}' is taken by the blkio hierarchy of cgroup v1",
+                cgroup
+            ));
+        }
+        return Err(anyhow!(
+            "The io controller is not enabled for '{}'; enable it with '+io' in cgroup.subtree_control of its parent",
+            cgroup
+        ));
+    }
+    // The root cgroup has the controller but cannot be limited
+    if !dir.join("io.max").exists() {
+        return Err(anyhow!(
+            "'{}' has no io.max; the root cgroup cannot be throttled",
+            cgroup
+        ));
+    }
+
+    Ok(dir)
+}
+
+fn handle_throttle(info: ThrottleInfo) -> anyhow::Result<()> {
+    // clap requires PID and PATH unless a subcommand is given
+    let pid = info.pid.unwrap();
+    let path = info.path.unwrap();
+    let (major, minor) = disk_of(&path)?;
+    let dir = io_cgroup_dir(pid)?;
+
+    let mut line = format!("{}:{}", major, minor);
+    let limits = [
+        ("rbps", info.read_bps),
+        ("wbps", info.write_bps),
+        ("riops", info.read_iops),
+        ("wiops", info.write_iops),
+    ];
+    for (key, limit) in limits {
+        if let Some(limit) = limit {
+            line.push_str(&format!(" {}={}", key, limit));
+        }
+    }
+
+    let io_max = dir.join("io.max");
+    std::fs::write(&io_max, &line).map_err(|err| match err.kind() {
+        ErrorKind::PermissionDenied => anyhow!(
+            "Permission denied to write {}; it requires root or the ownership of the parent cgroup",
+            io_max.display()
+        ),
+        _ => anyhow!(err).context(format!(
+            "Failed to write '{}' to {}",
+            line,
+            io_max.display()
+        )),
+    })?;
+
+    // A device without any limits is not listed
+    let content = std::fs::read_to_string(&io_max)
+        .with_context(|| format!("Failed to read {}", io_max.display()))?;
+    let device = format!("{}:{} ", major, minor);
+    let limits = content
+        .lines()
+        .find_map(|line| line.strip_prefix(&device))
+        .unwrap_or("rbps=max wbps=max riops=max wiops=max");
+    println!("cgroup: {}", dir.display());
+    println!("device: {}:{}", major, minor);
+    for pair in limits.split_whitespace() {
+        if let Some((key, value)) = pair.split_once('=') {
+            println!("{}: {}", key, value);
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_throttle(cli.throttle),
+    }
+}
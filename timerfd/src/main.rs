@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_duration;
+use linux_tools_core::output::format_duration;
+use linux_tools_core::print_completer;
+use nix::sys::time::{TimeSpec, TimeValLike};
+use nix::sys::timerfd::{ClockId, Expiration, TimerFd, TimerFlags, TimerSetTimeFlags};
+use nix::time::clock_gettime;
+use std::os::unix::io::AsRawFd;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    timer: TimerInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct TimerInfo {
+    /// Clock to measure the time with
+    #[clap(short, long, arg_enum, default_value_t = Clock::Monotonic)]
+    clock: Clock,
+    /// Time until the first expiration (e.g. 1.5s, 500ms) [default: same as --interval]
+    #[clap(
+        short,
+        long,
+        value_parser = parse_duration,
+        value_name = "DURATION",
+        required_unless_present = "interval"
+    )]
+    initial: Option<u64>,
+    /// Time between expirations after the first one [default: expire only once]
+    #[clap(short = 'I', long, value_parser = parse_duration, value_name = "DURATION")]
+    interval: Option<u64>,
+    /// Exit after the number of ticks [default: unlimited with --interval]
+    #[clap(short = 'n', long, value_parser = clap::value_parser!(u64).range(1..))]
+    count: Option<u64>,
+    /// Take --initial as an absolute time of the clock, such as seconds since the epoch for realtime
+    #[clap(short, long, requires = "initial")]
+    abstime: bool,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Clock {
+    Realtime,
+    Monotonic,
+    Boottime,
+}
+
+impl std::fmt::Display for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Clock::Realtime => write!(f, "CLOCK_REALTIME"),
+            Clock::Monotonic => write!(f, "CLOCK_MONOTONIC"),
+            Clock::Boottime => write!(f, "CLOCK_BOOTTIME"),
+        }
+    }
+}
+
+impl Clock {
+    fn timerfd_clock(self) -> ClockId {
+        match self {
+            Clock::Realtime => ClockId::CLOCK_REALTIME,
+            Clock::Monotonic => ClockId::CLOCK_MONOTONIC,
+            Clock::Boottime => ClockId::CLOCK_BOOTTIME,
+        }
+    }
+
+    fn time_clock(self) -> nix::time::ClockId {
+        match self {
+            Clock::Realtime => nix::time::ClockId::CLOCK_REALTIME,
+            Clock::Monotonic => nix::time::ClockId::CLOCK_MONOTONIC,
+            Clock::Boottime => nix::time::ClockId::CLOCK_BOOTTIME,
+        }
+    }
+}
+
+/// Reads the number of expirations since the last read, blocking until the next one.
+fn read_expirations(timer: &TimerFd) -> nix::Result<u64> {
+    let mut buf = [0u8; 8];
+    nix::unistd::read(timer.as_raw_fd(), &mut buf)?;
+    Ok(u64::from_ne_bytes(buf))
+}
+
+fn handle_timer(info: TimerInfo) -> anyhow::Result<()> {
+    // clap requires --initial unless --interval is given
+    let initial = info.initial.or(info.interval).unwrap();
+    if initial == 0 && !info.abstime {
+        return Err(anyhow!(
+            "--initial must be longer than 0, which disarms the timer"
+        ));
+    }
+    let expiration = match info.interval {
+        Some(0) => return Err(anyhow!("--interval must be longer than 0")),
+        Some(interval) => Expiration::IntervalDelayed(
+            TimeSpec::nanoseconds(initial as i64),
+            TimeSpec::nanoseconds(interval as i64),
+        ),
+        None => Expiration::OneShot(TimeSpec::nanoseconds(initial as i64)),
+    };
+    let flags = if info.abstime {
+        TimerSetTimeFlags::TFD_TIMER_ABSTIME
+    } else {
+        TimerSetTimeFlags::empty()
+    };
+    // A one-shot timer only ticks once
+    let count = match info.interval {
+        Some(_) => info.count.unwrap_or(u64::MAX),
+        None => 1,
+    };
+
+    let timer = TimerFd::new(info.clock.timerfd_clock(), TimerFlags::TFD_CLOEXEC)
+        .context("Failed to create a timerfd")?;
+    timer
+        .set(expiration, flags)
+        .context("Failed to set the timer")?;
+
+    eprintln!("clock: {}", info.clock);
+    if info.abstime {
+        eprintln!(
+            "initial: {}.{:09} (absolute)",
+            initial / 1_000_000_000,
+            initial % 1_000_000_000
+        );
+    } else {
+        eprintln!("initial: {}", format_duration(initial));
+    }
+    if let Some(interval) = info.interval {
+        eprintln!("interval: {}", format_duration(interval));
+    }
+
+    let mut ticks = 0u64;
+    while ticks < count {
+        let expirations = read_expirations(&timer).context("Failed to read the timerfd")?;
+        let now = clock_gettime(info.clock.time_clock()).context("Failed to get the time")?;
+        ticks = ticks.saturating_add(expirations);
+        if expirations > 1 {
+            eprintln!("warning: {} expirations were missed", expirations - 1);
+        }
+        println!("{}\t{}.{:09}", ticks, now.tv_sec(), now.tv_nsec());
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_timer(cli.timer),
+    }
+}
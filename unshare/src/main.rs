@@ -0,0 +1,188 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use linux_tools_core::process::{default_command, run_command};
+use nix::errno::Errno;
+use nix::mount::{mount, MsFlags};
+use nix::sched::{unshare, CloneFlags};
+use nix::unistd::{getgid, getuid};
+use std::ffi::OsString;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitCode};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, trailing_var_arg = true)]
+struct Cli {
+    #[clap(flatten)]
+    unshare: UnshareInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct UnshareInfo {
+    /// Create a user namespace
+    #[clap(short = 'U', long)]
+    user: bool,
+    /// Create a mount namespace, where all mounts are made private
+    #[clap(short, long)]
+    mount: bool,
+    /// Create a PID namespace, where COMMAND runs as PID 1
+    #[clap(short, long)]
+    pid: bool,
+    /// Create a network namespace
+    #[clap(short, long)]
+    net: bool,
+    /// Create an IPC namespace
+    #[clap(short, long)]
+    ipc: bool,
+    /// Create a UTS namespace
+    #[clap(short, long)]
+    uts: bool,
+    /// Create a cgroup namespace
+    #[clap(short = 'C', long)]
+    cgroup: bool,
+    /// Map the current user and group to root in the user namespace (implies --user)
+    #[clap(short = 'r', long, conflicts_with_all = &["map-user", "map-group"])]
+    map_root: bool,
+    /// Map the current user to UID in the user namespace (implies --user) [default: the same UID]
+    #[clap(long, value_parser, value_name = "UID")]
+    map_user: Option<u32>,
+    /// Map the current group to GID in the user namespace (implies --user) [default: the same GID]
+    #[clap(long, value_parser, value_name = "GID")]
+    map_group: Option<u32>,
+    /// Command to run in the namespaces [default: $SHELL or /bin/sh]
+    #[clap(value_parser, value_name = "COMMAND")]
+    command: Vec<OsString>,
+}
+
+impl UnshareInfo {
+    fn user_namespace(&self) -> bool {
+        self.user || self.map_root || self.map_user.is_some() || self.map_group.is_some()
+    }
+
+    fn clone_flags(&self) -> CloneFlags {
+        let mut flags = CloneFlags::empty();
+        for (enabled, flag) in [
+            (self.user_namespace(), CloneFlags::CLONE_NEWUSER),
+            (self.mount, CloneFlags::CLONE_NEWNS),
+            (self.pid, CloneFlags::CLONE_NEWPID),
+            (self.net, CloneFlags::CLONE_NEWNET),
+            (self.ipc, CloneFlags::CLONE_NEWIPC),
+            (self.uts, CloneFlags::CLONE_NEWUTS),
+            (self.cgroup, CloneFlags::CLONE_NEWCGROUP),
+        ] {
+            if enabled {
+                flags |= flag;
+            }
+        }
+        flags
+    }
+}
+
+fn describe(errno: Errno) -> anyhow::Error {
+    match errno {
+        Errno::EPERM => anyhow!(
+            "Creating namespaces requires CAP_SYS_ADMIN unless a user namespace is also created with --user"
+        ),
+        Errno::ENOSPC | Errno::EUSERS => anyhow!(
+            "The limit on the number of namespaces is reached; see /proc/sys/user/max_*_namespaces"
+        ),
+        Errno::EINVAL => anyhow!("The kernel does not support one of the namespaces"),
+        errno => anyhow!(errno),
+    }
+}
+
+fn write_proc_file(name: &str, content: &str) -> anyhow::Result<()> {
+    let path = format!("/proc/self/{}", name);
+    std::fs::write(&path, content)
+        .with_context(|| format!("Failed to write '{}' to {}", content.trim_end(), path))
+}
+
+/// Maps the user and group outside the user namespace to `uid` and `gid` inside it.
+fn write_id_maps(outer_uid: u32, outer_gid: u32, uid: u32, gid: u32) -> anyhow::Result<()> {
+    write_proc_file("uid_map", &format!("{} {} 1\n", uid, outer_uid))?;
+    // Unprivileged processes can only write gid_map after disabling setgroups(2)
+    match std::fs::write("/proc/self/setgroups", "deny") {
+        Ok(()) => {}
+        // Linux before 3.19 does not have this file
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err).context("Failed to write 'deny' to /proc/self/setgroups"),
+    }
+    write_proc_file("gid_map", &format!("{} {} 1\n", gid, outer_gid))
+}
+
+fn handle_unshare(info: UnshareInfo) -> anyhow::Result<ExitCode> {
+    let flags = info.clone_flags();
+    if flags.is_empty() {
+        return Err(anyhow!(
+            "No namespace is given; use --user, --mount, --pid, --net, --ipc, --uts or --cgroup"
+        ));
+    }
+
+    // The IDs must be taken before unsharing, after which they are the overflow IDs
+    let (outer_uid, outer_gid) = (getuid().as_raw(), getgid().as_raw());
+    unshare(flags)
+        .map_err(describe)
+        .context("Failed to unshare namespaces")?;
+
+    if info.user_namespace() {
+        let (uid, gid) = if info.map_root {
+            (0, 0)
+        } else {
+            (
+                info.map_user.unwrap_or(outer_uid),
+                info.map_group.unwrap_or(outer_gid),
+            )
+        };
+        write_id_maps(outer_uid, outer_gid, uid, gid)?;
+    }
+    if info.mount {
+        // Keep mounts in the new namespace from propagating back to the original one
+        mount(
+            None::<&str>,
+            "/",
+            None::<&str>,
+            MsFlags::MS_REC | MsFlags::MS_PRIVATE,
+            None::<&str>,
+        )
+        .context("Failed to make the mounts private")?;
+    }
+
+    let command = if info.command.is_empty() {
+        default_command()
+    } else {
+        info.command
+    };
+    if info.pid {
+        // Only children of this process join the new PID namespace
+        return run_command(&command);
+    }
+
+    let err = Command::new(&command[0]).args(&command[1..]).exec();
+    Err(err).with_context(|| format!("Failed to execute '{}'", command[0].to_string_lossy()))
+}
+
+fn main() -> anyhow::Result<ExitCode> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => {
+            print_completer::<Cli, _>(shell).map(|_| ExitCode::SUCCESS)
+        }
+        None => handle_unshare(cli.unshare),
+    }
+}
@@ -0,0 +1,439 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::args::parse_size;
+use linux_tools_core::mem::page_size;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::sys::uio::pread;
+use nix::unistd::read;
+use std::ffi::c_void;
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+/// Size of the region without --file.
+const DEFAULT_SIZE: usize = 64 * 1024;
+/// Milliseconds the handler waits for a fault before checking whether the region is done.
+const POLL_INTERVAL: i32 = 100;
+
+/// Definitions of linux/userfaultfd.h, which the libc crate does not have.
+mod uffd {
+    pub const UFFD_API: u64 = 0xaa;
+    pub const UFFD_USER_MODE_ONLY: i32 = 1;
+    pub const UFFD_EVENT_PAGEFAULT: u8 = 0x12;
+    pub const UFFD_PAGEFAULT_FLAG_WRITE: u64 = 1;
+    pub const UFFDIO_REGISTER_MODE_MISSING: u64 = 1;
+    /// The bit of UFFDIO_COPY in the ioctls a registration supports.
+    pub const UFFDIO_COPY_BIT: u64 = 1 << 0x03;
+    /// Size of struct uffd_msg, which is read whole.
+    pub const MSG_SIZE: usize = 32;
+
+    #[repr(C)]
+    pub struct UffdioApi {
+        pub api: u64,
+        pub features: u64,
+        pub ioctls: u64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioRange {
+        pub start: u64,
+        pub len: u64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioRegister {
+        pub range: UffdioRange,
+        pub mode: u64,
+        pub ioctls: u64,
+    }
+
+    #[repr(C)]
+    pub struct UffdioCopy {
+        pub dst: u64,
+        pub src: u64,
+        pub len: u64,
+        pub mode: u64,
+        pub copy: i64,
+    }
+
+    // The expansion of the nix macros refers to libc::c_int, which is newer than the MSRV for
+    // clippy
+    #[allow(clippy::incompatible_msrv)]
+    mod ioctl {
+        use super::{UffdioApi, UffdioCopy, UffdioRegister};
+
+        nix::ioctl_readwrite!(uffdio_register, 0xaa, 0x00, UffdioRegister);
+        nix::ioctl_readwrite!(uffdio_copy, 0xaa, 0x03, UffdioCopy);
+        nix::ioctl_readwrite!(uffdio_api, 0xaa, 0x3f, UffdioApi);
+    }
+
+    pub use ioctl::*;
+}
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[clap(flatten)]
+    demo: DemoInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct DemoInfo {
+    /// Supply the content of each page from this file at the same offset, instead of zeros
+    #[clap(short, long, value_parser, value_name = "FILE")]
+    file: Option<PathBuf>,
+    /// Size of the anonymous region, rounded up to the page size (accepts suffixes such as K,
+    /// MiB, GB) [default: the size of FILE, or 64KiB]
+    #[clap(short, long, value_parser = parse_size, value_name = "SIZE")]
+    size: Option<i64>,
+    /// Do not print a line for each fault
+    #[clap(short, long)]
+    quiet: bool,
+}
+
+/// An anonymous private mapping, unmapped on drop.
+struct Mapping {
+    start: usize,
+    len: usize,
+}
+
+impl Mapping {
+    fn new(len: usize) -> nix::Result<Self> {
+        // SAFETY: a new mapping does not alias any memory.
+        let start = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        }? as usize;
+
+        Ok(Mapping { start, len })
+    }
+
+    fn page(&self, index: usize, page_size: usize) -> &[u8] {
+        // SAFETY: the page is within the mapping, which is readable until it is unmapped on drop.
+        unsafe {
+            std::slice::from_raw_parts((self.start + index * page_size) as *const u8, page_size)
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: nothing refers to the mapping after the drop.
+        let _ = unsafe { munmap(self.start as *mut c_void, self.len) };
+    }
+}
+
+/// Counts of the faults the handler resolved.
+#[derive(Default)]
+struct Faults {
+    total: u64,
+    from_file: u64,
+    zero: u64,
+}
+
+/// Opens a userfaultfd and returns it with whether it only handles faults in user mode.
+fn open_userfaultfd() -> anyhow::Result<(File, bool)> {
+    let flags = libc::O_CLOEXEC | libc::O_NONBLOCK;
+    // SAFETY: userfaultfd takes only the flags and returns a new file descriptor.
+    let ret = unsafe { libc::syscall(libc::SYS_userfaultfd, flags) };
+    let (fd, user_mode_only) = match Errno::result(ret) {
+        Ok(fd) => (fd, false),
+        // With vm.unprivileged_userfaultfd = 0, an unprivileged process may only handle faults
+        // in user mode, which is all that the demo causes
+        Err(Errno::EPERM) => {
+            // SAFETY: same as above.
+            let ret =
+                unsafe { libc::syscall(libc::SYS_userfaultfd, flags | uffd::UFFD_USER_MODE_ONLY) };
+            match Errno::result(ret) {
+                Ok(fd) => (fd, true),
+                // UFFD_USER_MODE_ONLY requires Linux 5.11
+                Err(Errno::EPERM | Errno::EINVAL) => {
+                    return Err(anyhow!(
+                        "Permission denied; userfaultfd requires CAP_SYS_PTRACE, or \
+                        /proc/sys/vm/unprivileged_userfaultfd set to 1"
+                    ))
+                }
+                Err(errno) => return Err(errno).context("Failed to call userfaultfd"),
+            }
+        }
+        Err(Errno::ENOSYS) => {
+            return Err(anyhow!(
+                "userfaultfd is not supported; it requires Linux 4.3 with CONFIG_USERFAULTFD"
+            ))
+        }
+        Err(errno) => return Err(errno).context("Failed to call userfaultfd"),
+    };
+    // SAFETY: userfaultfd returns a new file descriptor owned by nobody else.
+    let file = unsafe { File::from_raw_fd(fd as i32) };
+
+    let mut api = uffd::UffdioApi {
+        api: uffd::UFFD_API,
+        features: 0,
+        ioctls: 0,
+    };
+    // SAFETY: api is a valid struct uffdio_api.
+    unsafe { uffd::uffdio_api(file.as_raw_fd(), &mut api) }.context("Failed to call UFFDIO_API")?;
+
+    Ok((file, user_mode_only))
+}
+
+fn register(uffd: &File, mapping: &Mapping) -> anyhow::Result<()> {
+    let mut register = uffd::UffdioRegister {
+        range: uffd::UffdioRange {
+            start: mapping.start as u64,
+            len: mapping.len as u64,
+        },
+        mode: uffd::UFFDIO_REGISTER_MODE_MISSING,
+        ioctls: 0,
+    };
+    // SAFETY: register is a valid struct uffdio_register for a range of this process.
+    unsafe { uffd::uffdio_register(uffd.as_raw_fd(), &mut register) }
+        .context("Failed to call UFFDIO_REGISTER")?;
+    if register.ioctls & uffd::UFFDIO_COPY_BIT == 0 {
+        return Err(anyhow!("UFFDIO_COPY is not supported for the region"));
+    }
+    Ok(())
+}
+
+/// Fills `buffer` with the content of `file` at `offset`, leaving zeros past its end, and
+/// returns whether anything was read.
+fn read_page(file: &File, offset: usize, buffer: &mut [u8]) -> anyhow::Result<bool> {
+    buffer.fill(0);
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match pread(
+            file.as_raw_fd(),
+            &mut buffer[filled..],
+            (offset + filled) as i64,
+        ) {
+            Ok(0) => break,
+            Ok(len) => filled += len,
+            Err(Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to read the file"),
+        }
+    }
+    Ok(filled > 0)
+}
+
+/// Resolves the faults of the region until `done` is set, and closes the userfaultfd on return,
+/// which wakes up any faulting thread even on an error.
+fn handle_faults(
+    uffd: File,
+    file: Option<File>,
+    start: usize,
+    page_size: usize,
+    quiet: bool,
+    done: &AtomicBool,
+) -> anyhow::Result<Faults> {
+    let mut faults = Faults::default();
+    let mut page = vec![0u8; page_size];
+    let begin = Instant::now();
+    loop {
+        let mut fds = [PollFd::new(uffd.as_raw_fd(), PollFlags::POLLIN)];
+        match poll(&mut fds, POLL_INTERVAL) {
+            Ok(0) if done.load(Ordering::SeqCst) => return Ok(faults),
+            Ok(_) | Err(Errno::EINTR) => {}
+            Err(errno) => return Err(errno).context("Failed to poll the userfaultfd"),
+        }
+
+        let mut msg = [0u8; uffd::MSG_SIZE];
+        match read(uffd.as_raw_fd(), &mut msg) {
+            Ok(uffd::MSG_SIZE) => {}
+            Ok(len) => return Err(anyhow!("Read a message of {} bytes", len)),
+            Err(Errno::EAGAIN | Errno::EINTR) => continue,
+            Err(errno) => return Err(errno).context("Failed to read the userfaultfd"),
+        }
+        if msg[0] != uffd::UFFD_EVENT_PAGEFAULT {
+            continue;
+        }
+        // struct uffd_msg has the flags and the address of a page fault after 8 bytes of header
+        let flags = u64::from_ne_bytes(msg[8..16].try_into().unwrap());
+        let address = u64::from_ne_bytes(msg[16..24].try_into().unwrap()) as usize;
+        let address = address / page_size * page_size;
+        let index = (address - start) / page_size;
+
+        let source = match &file {
+            Some(file) if read_page(file, index * page_size, &mut page)? => {
+                faults.from_file += 1;
+                "file"
+            }
+            _ => {
+                page.fill(0);
+                faults.zero += 1;
+                "zero"
+            }
+        };
+        let mut copy = uffd::UffdioCopy {
+            dst: address as u64,
+            src: page.as_ptr() as u64,
+            len: page_size as u64,
+            mode: 0,
+            copy: 0,
+        };
+        // SAFETY: copy is a valid struct uffdio_copy, and src is a buffer of a page.
+        match unsafe { uffd::uffdio_copy(uffd.as_raw_fd(), &mut copy) } {
+            // Another fault of the same page already supplied it
+            Ok(_) | Err(Errno::EEXIST) => {}
+            Err(errno) => {
+                return Err(errno)
+                    .with_context(|| format!("Failed to call UFFDIO_COPY for page {}", index))
+            }
+        }
+        faults.total += 1;
+
+        if !quiet {
+            let access = if flags & uffd::UFFD_PAGEFAULT_FLAG_WRITE != 0 {
+                "write"
+            } else {
+                "read"
+            };
+            println!(
+                "{:.6}s fault: page {} at {:#x}, {}, supplied from {}",
+                begin.elapsed().as_secs_f64(),
+                index,
+                address,
+                access,
+                source
+            );
+        }
+    }
+}
+
+/// Compares each page of the region with what the handler should have supplied.
+fn verify(mapping: &Mapping, file: Option<&File>, page_size: usize) -> anyhow::Result<()> {
+    let mut expected = vec![0u8; page_size];
+    for index in 0..mapping.len / page_size {
+        match file {
+            Some(file) => {
+                read_page(file, index * page_size, &mut expected)?;
+            }
+            None => expected.fill(0),
+        }
+        if mapping.page(index, page_size) != expected.as_slice() {
+            return Err(anyhow!(
+                "The content of page {} differs from the source",
+                index
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn region_size(info: &DemoInfo, file: Option<&File>, path: Option<&Path>) -> anyhow::Result<usize> {
+    match (info.size, file, path) {
+        (Some(size), _, _) => usize::try_from(size)
+            .ok()
+            .filter(|&size| size > 0)
+            .ok_or_else(|| anyhow!("SIZE must be positive")),
+        (None, Some(file), Some(path)) => {
+            let len = file
+                .metadata()
+                .with_context(|| format!("Failed to stat '{}'", path.display()))?
+                .len();
+            match len {
+                0 => Err(anyhow!(
+                    "'{}' is empty; give the size with --size",
+                    path.display()
+                )),
+                len => Ok(len as usize),
+            }
+        }
+        _ => Ok(DEFAULT_SIZE),
+    }
+}
+
+fn handle_demo(info: DemoInfo) -> anyhow::Result<()> {
+    let file = info
+        .file
+        .as_ref()
+        .map(|path| {
+            File::open(path).with_context(|| format!("Failed to open '{}'", path.display()))
+        })
+        .transpose()?;
+    let page_size = page_size()? as usize;
+    let size = region_size(&info, file.as_ref(), info.file.as_deref())?;
+    let len = (size + page_size - 1) / page_size * page_size;
+
+    let (uffd, user_mode_only) = open_userfaultfd()?;
+    let mapping = Mapping::new(len).context("Failed to map an anonymous region")?;
+    register(&uffd, &mapping)?;
+
+    let done = Arc::new(AtomicBool::new(false));
+    let handler = {
+        let file = file
+            .as_ref()
+            .map(File::try_clone)
+            .transpose()
+            .context("Failed to duplicate the file")?;
+        let done = Arc::clone(&done);
+        let start = mapping.start;
+        let quiet = info.quiet;
+        thread::spawn(move || handle_faults(uffd, file, start, page_size, quiet, &done))
+    };
+
+    // Each first access of a page blocks until the handler supplies it
+    let begin = Instant::now();
+    for index in 0..len / page_size {
+        let ptr = (mapping.start + index * page_size) as *const u8;
+        // SAFETY: the page is within the mapping, and the volatile read is not optimized out.
+        unsafe { std::ptr::read_volatile(ptr) };
+    }
+    let elapsed = begin.elapsed();
+    done.store(true, Ordering::SeqCst);
+    let faults = handler
+        .join()
+        .map_err(|_| anyhow!("The fault handler panicked"))??;
+    verify(&mapping, file.as_ref(), page_size)?;
+
+    println!("mode: {}", if user_mode_only { "user-only" } else { "all" });
+    println!("size: {}", len);
+    println!("pages: {}", len / page_size);
+    println!("faults: {}", faults.total);
+    match &info.file {
+        Some(path) => println!("source: {}", path.display()),
+        None => println!("source: zero"),
+    }
+    println!("from_file: {}", faults.from_file);
+    println!("zero: {}", faults.zero);
+    println!("elapsed: {:.6}s", elapsed.as_secs_f64());
+    println!("verified: true");
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_demo(cli.demo),
+    }
+}
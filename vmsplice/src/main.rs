@@ -0,0 +1,232 @@
+use anyhow::Context;
+use clap::{Args, Parser, Subcommand};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use nix::fcntl::{splice, vmsplice, OFlag, SpliceFFlags};
+use nix::sys::mman::{mmap, munmap, MapFlags, ProtFlags};
+use nix::sys::stat::{fstat, SFlag};
+use nix::unistd::pipe2;
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::{IoSlice, Read, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+/// Bytes moved by a single vmsplice, which matches the default pipe capacity
+const CHUNK_SIZE: usize = 64 * 1024;
+
+const STDOUT_FD: RawFd = 1;
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+#[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct Cli {
+    #[clap(flatten)]
+    vmsplice: VmspliceInfo,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Generate code for completion
+    #[clap(display_order = 1, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct VmspliceInfo {
+    /// Gift the pages to the kernel with SPLICE_F_GIFT
+    #[clap(short, long)]
+    gift: bool,
+}
+
+/// A page-aligned anonymous buffer, unmapped on drop.
+///
+/// A buffer is never reused after its pages are spliced, since the pipe keeps referring to them
+/// until they are read out of it.
+struct Chunk {
+    addr: *mut c_void,
+    len: usize,
+}
+
+impl Chunk {
+    fn new() -> nix::Result<Self> {
+        // SAFETY: a new anonymous mapping does not alias any memory.
+        let addr = unsafe {
+            mmap(
+                std::ptr::null_mut(),
+                CHUNK_SIZE,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_PRIVATE | MapFlags::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        }?;
+
+        Ok(Chunk { addr, len: 0 })
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        // SAFETY: the mapping is CHUNK_SIZE bytes, of which len bytes are filled.
+        unsafe { std::slice::from_raw_parts(self.addr as *const u8, self.len) }
+    }
+
+    /// Reads from `reader` until the chunk is full or the end of it.
+    fn fill(&mut self, reader: &mut impl Read) -> std::io::Result<usize> {
+        // SAFETY: the mapping is CHUNK_SIZE bytes and only accessed through this slice.
+        let buf = unsafe { std::slice::from_raw_parts_mut(self.addr as *mut u8, CHUNK_SIZE) };
+        while self.len < CHUNK_SIZE {
+            match reader.read(&mut buf[self.len..]) {
+                Ok(0) => break,
+                Ok(n) => self.len += n,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+
+        Ok(self.len)
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        // SAFETY: the mapping is no longer used, while the pipe keeps its own references of the pages.
+        let _ = unsafe { munmap(self.addr, CHUNK_SIZE) };
+    }
+}
+
+/// The intermediate pipe between the chunks and stdout.
+struct Pipe {
+    reader: File,
+    writer: File,
+    /// Bytes spliced into the pipe but not yet out of it
+    pending: usize,
+}
+
+impl Pipe {
+    fn new() -> nix::Result<Self> {
+        let (reader, writer) = pipe2(OFlag::O_CLOEXEC)?;
+        // SAFETY: pipe2 returns new file descriptors owned by nobody else.
+        let (reader, writer) = unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) };
+
+        Ok(Pipe {
+            reader,
+            writer,
+            pending: 0,
+        })
+    }
+
+    /// Moves the data in the pipe to stdout with splice(2).
+    fn splice_out(&mut self) -> nix::Result<()> {
+        let flags = SpliceFFlags::SPLICE_F_MOVE | SpliceFFlags::SPLICE_F_MORE;
+        while self.pending > 0 {
+            let written = splice(
+                self.reader.as_raw_fd(),
+                None,
+                STDOUT_FD,
+                None,
+                self.pending,
+                flags,
+            )?;
+            self.pending -= written;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the data left in the pipe to stdout with read(2) and write(2).
+    fn drain(&mut self) -> std::io::Result<()> {
+        let mut stdout = std::io::stdout().lock();
+        let mut buffer = vec![0; self.pending];
+        self.reader.read_exact(&mut buffer)?;
+        stdout.write_all(&buffer)?;
+        stdout.flush()?;
+        self.pending = 0;
+
+        Ok(())
+    }
+}
+
+/// Moves the whole of `data` into the pipe `fd`, which may take several vmsplice(2) calls.
+fn vmsplice_all(fd: RawFd, data: &[u8], flags: SpliceFFlags) -> nix::Result<()> {
+    let mut offset = 0;
+    while offset < data.len() {
+        offset += vmsplice(fd, &[IoSlice::new(&data[offset..])], flags)?;
+    }
+
+    Ok(())
+}
+
+fn is_pipe(fd: RawFd) -> nix::Result<bool> {
+    let stat = fstat(fd)?;
+    Ok(SFlag::from_bits_truncate(stat.st_mode) & SFlag::S_IFMT == SFlag::S_IFIFO)
+}
+
+fn handle_vmsplice(info: VmspliceInfo) -> anyhow::Result<()> {
+    let flags = if info.gift {
+        SpliceFFlags::SPLICE_F_GIFT
+    } else {
+        SpliceFFlags::empty()
+    };
+    // When stdout is a pipe, the pages are moved into it directly
+    let mut pipe = if is_pipe(STDOUT_FD).context("Failed to stat stdout")? {
+        None
+    } else {
+        Some(Pipe::new().context("Failed to create a pipe")?)
+    };
+    let mut fallback = false;
+
+    let mut stdin = std::io::stdin().lock();
+    loop {
+        let mut chunk = Chunk::new().context("Failed to allocate a buffer")?;
+        if chunk.fill(&mut stdin).context("Failed to read stdin")? == 0 {
+            break;
+        }
+
+        if fallback {
+            let mut stdout = std::io::stdout().lock();
+            stdout
+                .write_all(chunk.as_slice())
+                .and_then(|_| stdout.flush())
+                .context("Failed to write to stdout")?;
+            continue;
+        }
+        let pipe = match &mut pipe {
+            Some(pipe) => pipe,
+            None => {
+                vmsplice_all(STDOUT_FD, chunk.as_slice(), flags)
+                    .context("Failed to call vmsplice")?;
+                continue;
+            }
+        };
+        vmsplice_all(pipe.writer.as_raw_fd(), chunk.as_slice(), flags)
+            .context("Failed to call vmsplice")?;
+        pipe.pending = chunk.len;
+        match pipe.splice_out() {
+            Ok(()) => {}
+            // stdout does not support splice, such as a terminal or a file opened in append mode
+            Err(Errno::EINVAL) => {
+                eprintln!("warning: splice to stdout is not supported; falling back to write");
+                pipe.drain().context("Failed to write to stdout")?;
+                fallback = true;
+            }
+            Err(errno) => return Err(errno).context("Failed to call splice"),
+        }
+    }
+
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Some(Commands::Completion { shell }) => print_completer::<Cli, _>(shell),
+        None => handle_vmsplice(cli.vmsplice),
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{anyhow, Context};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use linux_tools_core::print_completer;
+use nix::errno::Errno;
+use std::ffi::CString;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+#[derive(Parser)]
+#[clap(author, version, about, long_about = None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Print the value of an extended attribute
+    #[clap(display_order = 1, name = "get")]
+    Get {
+        #[clap(flatten)]
+        attr: AttrInfo,
+        /// Print the value as hex digits instead of raw bytes
+        #[clap(long)]
+        hex: bool,
+    },
+    /// Set the value of an extended attribute
+    #[clap(display_order = 2, name = "set")]
+    Set {
+        #[clap(flatten)]
+        attr: AttrInfo,
+        /// Value of the attribute
+        #[clap(value_parser, value_name = "VALUE")]
+        value: String,
+        /// Interpret VALUE as hex digits such as 00ff10
+        #[clap(long)]
+        hex: bool,
+    },
+    /// List the names of extended attributes, one per line
+    #[clap(display_order = 3, name = "list")]
+    List {
+        /// Target file
+        #[clap(value_parser, value_name = "FILE")]
+        filename: PathBuf,
+        /// Only list attributes in the namespace
+        #[clap(short, long, arg_enum)]
+        namespace: Option<Namespace>,
+    },
+    /// Remove an extended attribute
+    #[clap(display_order = 4, name = "remove")]
+    Remove {
+        #[clap(flatten)]
+        attr: AttrInfo,
+    },
+    /// Generate code for completion
+    #[clap(display_order = 5, name = "completion")]
+    Completion {
+        /// Target shell to create completion code
+        #[clap(long, short, arg_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(Args)]
+struct AttrInfo {
+    /// Target file
+    #[clap(value_parser, value_name = "FILE")]
+    filename: PathBuf,
+    /// Name of the attribute (e.g. user.comment)
+    #[clap(value_parser, value_name = "NAME")]
+    name: String,
+    /// Prepend the namespace prefix to NAME
+    #[clap(short, long, arg_enum)]
+    namespace: Option<Namespace>,
+}
+
+impl AttrInfo {
+    fn full_name(&self) -> String {
+        match self.namespace {
+            Some(namespace) => format!("{}.{}", namespace, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+enum Namespace {
+    User,
+    Trusted,
+    Security,
+    System,
+}
+
+impl std::fmt::Display for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            Namespace::User => write!(f, "user"),
+            Namespace::Trusted => write!(f, "trusted"),
+            Namespace::Security => write!(f, "security"),
+            Namespace::System => write!(f, "system"),
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> anyhow::Result<Vec<u8>> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(anyhow!("'{}' is not a hex value", s));
+    }
+    if digits.len() % 2 != 0 {
+        return Err(anyhow!("'{}' has an odd number of hex digits", s));
+    }
+
+    // Every pair is two ASCII hex digits, so neither slicing nor parsing can fail
+    Ok((0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap())
+        .collect())
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn to_cstring(bytes: &[u8]) -> anyhow::Result<CString> {
+    CString::new(bytes)
+        .map_err(|_| anyhow!("'{}' contains a NUL byte", String::from_utf8_lossy(bytes)))
+}
+
+/// Adds a hint to the errors that only mean the attribute or the support is missing.
+fn describe(errno: Errno, filename: &Path, name: &str) -> anyhow::Error {
+    match errno {
+        Errno::ENODATA => anyhow!("'{}' has no attribute '{}'", filename.display(), name),
+        Errno::EOPNOTSUPP => anyhow!(
+            "The file system of '{}' does not support extended attributes, or the namespace of '{}'",
+            filename.display(),
+            name
+        ),
+        errno => anyhow!(errno),
+    }
+}
+
+/// Calls a getxattr-like function until the buffer is large enough for the result.
+fn read_with_retry<F>(mut call: F) -> nix::Result<Vec<u8>>
+where
+    F: FnMut(*mut u8, usize) -> libc::ssize_t,
+{
+    loop {
+        // Size 0 asks for the current size of the value
+        let size = Errno::result(call(std::ptr::null_mut(), 0))? as usize;
+        let mut buffer = vec![0u8; size];
+        match Errno::result(call(buffer.as_mut_ptr(), buffer.len())) {
+            Ok(len) => {
+                buffer.truncate(len as usize);
+                return Ok(buffer);
+            }
+            // The value grew between the two calls
+            Err(Errno::ERANGE) => continue,
+            Err(errno) => return Err(errno),
+        }
+    }
+}
+
+fn getxattr(filename: &Path, name: &str) -> anyhow::Result<Vec<u8>> {
+    let path = to_cstring(filename.as_os_str().as_bytes())?;
+    let c_name = to_cstring(name.as_bytes())?;
+    read_with_retry(|buffer, size| {
+        // SAFETY: buffer is valid for size bytes, and path and c_name are NUL-terminated.
+        unsafe { libc::getxattr(path.as_ptr(), c_name.as_ptr(), buffer.cast(), size) }
+    })
+    .map_err(|errno| describe(errno, filename, name))
+    .with_context(|| format!("Failed to get '{}' of '{}'", name, filename.display()))
+}
+
+fn setxattr(filename: &Path, name: &str, value: &[u8]) -> anyhow::Result<()> {
+    let path = to_cstring(filename.as_os_str().as_bytes())?;
+    let c_name = to_cstring(name.as_bytes())?;
+    // SAFETY: value is valid for its length, and path and c_name are NUL-terminated.
+    let ret = unsafe {
+        libc::setxattr(
+            path.as_ptr(),
+            c_name.as_ptr(),
+            value.as_ptr().cast(),
+            value.len(),
+            0,
+        )
+    };
+    Errno::result(ret)
+        .map_err(|errno| describe(errno, filename, name))
+        .with_context(|| format!("Failed to set '{}' of '{}'", name, filename.display()))?;
+
+    Ok(())
+}
+
+fn listxattr(filename: &Path) -> anyhow::Result<Vec<String>> {
+    let path = to_cstring(filename.as_os_str().as_bytes())?;
+    let list = read_with_retry(|buffer, size| {
+        // SAFETY: buffer is valid for size bytes, and path is NUL-terminated.
+        unsafe { libc::listxattr(path.as_ptr(), buffer.cast(), size) }
+    })
+    .with_context(|| format!("Failed to list attributes of '{}'", filename.display()))?;
+
+    // The list is a sequence of NUL-terminated names
+    Ok(list
+        .split(|&byte| byte == 0)
+        .filter(|name| !name.is_empty())
+        .map(|name| String::from_utf8_lossy(name).into_owned())
+        .collect())
+}
+
+fn removexattr(filename: &Path, name: &str) -> anyhow::Result<()> {
+    let path = to_cstring(filename.as_os_str().as_bytes())?;
+    let c_name = to_cstring(name.as_bytes())?;
+    // SAFETY: path and c_name are NUL-terminated.
+    let ret = unsafe { libc::removexattr(path.as_ptr(), c_name.as_ptr()) };
+    Errno::result(ret)
+        .map_err(|errno| describe(errno, filename, name))
+        .with_context(|| format!("Failed to remove '{}' of '{}'", name, filename.display()))?;
+
+    Ok(())
+}
+
+fn handle_get(attr: AttrInfo, hex: bool) -> anyhow::Result<()> {
+    let value = getxattr(&attr.filename, &attr.full_name())?;
+
+    let mut stdout = std::io::stdout().lock();
+    if hex {
+        writeln!(stdout, "{}", format_hex(&value))
+    } else {
+        stdout.write_all(&value)
+    }
+    .context("Failed to write to stdout")?;
+
+    Ok(())
+}
+
+fn handle_set(attr: AttrInfo, value: String, hex: bool) -> anyhow::Result<()> {
+    let value = if hex {
+        parse_hex(&value)?
+    } else {
+        value.into_bytes()
+    };
+
+    setxattr(&attr.filename, &attr.full_name(), &value)
+}
+
+fn handle_list(filename: PathBuf, namespace: Option<Namespace>) -> anyhow::Result<()> {
+    let prefix = namespace.map(|namespace| format!("{}.", namespace));
+    for name in listxattr(&filename)? {
+        if prefix
+            .as_ref()
+            .map_or(true, |prefix| name.starts_with(prefix))
+        {
+            println!("{}", name);
+        }
+    }
+
+    Ok(())
+}
+
+fn handle_remove(attr: AttrInfo) -> anyhow::Result<()> {
+    removexattr(&attr.filename, &attr.full_name())
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Get { attr, hex } => handle_get(attr, hex),
+        Commands::Set { attr, value, hex } => handle_set(attr, value, hex),
+        Commands::List {
+            filename,
+            namespace,
+        } => handle_list(filename, namespace),
+        Commands::Remove { attr } => handle_remove(attr),
+        Commands::Completion { shell } => print_completer::<Cli, _>(shell),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_value_is_parsed() {
+        assert_eq!(parse_hex("0x00ff10").unwrap(), vec![0x00, 0xff, 0x10]);
+        assert_eq!(parse_hex("aB").unwrap(), vec![0xab]);
+        assert_eq!(parse_hex("").unwrap(), Vec::<u8>::new());
+    }
+
+    #[test]
+    fn invalid_hex_value_is_rejected() {
+        assert!(parse_hex("0xabc").is_err());
+        assert!(parse_hex("zz").is_err());
+        assert!(parse_hex("+1").is_err());
+        assert!(parse_hex("é0").is_err());
+    }
+
+    #[test]
+    fn hex_value_round_trips() {
+        assert_eq!(format_hex(&parse_hex("0x0a1b2c").unwrap()), "0a1b2c");
+    }
+}